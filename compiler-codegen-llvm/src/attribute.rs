@@ -0,0 +1,35 @@
+//! Attributes on items, as the type checker made sense of them
+
+use compiler_typeck::{Attributes, Inline};
+use inkwell::{
+    attributes::{Attribute as LlvmAttribute, AttributeLoc},
+    values::FunctionValue,
+};
+
+use crate::Codegen;
+
+impl<'ctx> Codegen<'ctx, '_>
+{
+    /// Adds the LLVM attributes matching `attributes` to `function`
+    pub fn apply_attributes(&self, function: FunctionValue<'ctx>, attributes: &Attributes)
+    {
+        let inline = attributes.inline.map(|i| match i
+        {
+            Inline::Hint => "inlinehint",
+            Inline::Always => "alwaysinline",
+            Inline::Never => "noinline",
+        });
+
+        for name in [inline, attributes.cold.then_some("cold")]
+            .into_iter()
+            .flatten()
+        {
+            let kind = LlvmAttribute::get_named_enum_kind_id(name);
+
+            function.add_attribute(
+                AttributeLoc::Function,
+                self.ctx.create_enum_attribute(kind, 0),
+            );
+        }
+    }
+}
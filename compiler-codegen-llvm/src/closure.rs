@@ -0,0 +1,161 @@
+//! Closures, lowered to a pointer to their code along with one to an environment holding what they capture. Environments live in
+//! the frame of the function creating the closure, so those capturing anything must not outlive it, which the type checker sees
+//! to.
+
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{Result, bail};
+use compiler_typeck::{Block, Capture, LocalId, Type};
+use inkwell::{module::Linkage, values::BasicValue};
+
+use crate::{Codegen, Function, LlvmType, Value};
+
+impl<'ctx> Codegen<'ctx, '_>
+{
+    /// The closure taking `parameters` and running `body`, of type `r#type`
+    pub fn gen_closure(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        captures: &[Capture],
+        parameters: &[LocalId],
+        body: &Block,
+        r#type: &Type,
+    ) -> Result<Value<'ctx>>
+    {
+        let Some(parent) = parent_func
+        else
+        {
+            bail!("Closures may only be created within functions");
+        };
+
+        let Type::Closure {
+            arguments,
+            box return_type,
+        } = r#type
+        else
+        {
+            bail!("Closures are of closure types, got `{type}`");
+        };
+
+        // Values are copied in, references point to the local's slot, or to a temporary if it has none
+        let fields = captures
+            .iter()
+            .map(|capture| self.gen_non_void_expression(parent_func, &capture.value))
+            .collect::<Result<Vec<_>>>()?;
+
+        let r#struct = self.ctx.struct_type(
+            &fields
+                .iter()
+                .map(|f| f.r#type.as_llvm_basic_type(self.ctx))
+                .collect::<Result<Vec<_>>>()?,
+            false,
+        );
+
+        let environment = if fields.is_empty()
+        {
+            self.ctx.ptr_type(Default::default()).const_null()
+        }
+        else
+        {
+            let environment = self.builder.build_alloca(r#struct, "environment")?;
+
+            for (i, field) in fields.iter().enumerate()
+            {
+                let ptr =
+                    self.builder
+                        .build_struct_gep(r#struct, environment, i as u32, "capture")?;
+
+                self.builder.build_store(ptr, field.inner)?;
+            }
+
+            environment
+        };
+
+        let symbol = {
+            let mut runtime = self.runtime.borrow_mut();
+            runtime.closures += 1;
+
+            format!(
+                "{}.{{closure#{}}}",
+                parent.borrow().inner.get_name().to_string_lossy(),
+                runtime.closures - 1
+            )
+        };
+
+        let inner = {
+            // The environment comes first, as an opaque pointer
+            let types = [Type::Ref(Box::new(Type::Void))]
+                .into_iter()
+                .chain(arguments.iter().cloned())
+                .collect::<Vec<_>>();
+
+            self.module.add_function(
+                &symbol,
+                return_type.as_llvm_fn_type(self.ctx, &types, false)?,
+                Some(Linkage::Internal),
+            )
+        };
+
+        let function = Rc::new(RefCell::new(Function::new(inner, return_type.clone())));
+
+        // Generate the code apart, then get back to where the closure is created
+        let block = self.builder.get_insert_block();
+
+        function.borrow_mut().init_block(self);
+
+        if !fields.is_empty()
+        {
+            let environment = inner.get_first_param().unwrap().into_pointer_value();
+
+            for (i, (capture, field)) in captures.iter().zip(&fields).enumerate()
+            {
+                let name = &self.thir[capture.inner].name;
+
+                let ptr = self
+                    .builder
+                    .build_struct_gep(r#struct, environment, i as u32, name)?;
+                let value = self.builder.build_load(
+                    field.r#type.as_llvm_basic_type(self.ctx)?,
+                    ptr,
+                    name,
+                )?;
+
+                function.borrow_mut().stack.insert(
+                    capture.inner,
+                    Value {
+                        r#type: field.r#type.clone(),
+                        inner: value,
+                    },
+                );
+            }
+        }
+
+        function.borrow_mut().init_args_stack(self, parameters)?;
+
+        let function = Some(function);
+
+        self.gen_block(&function, body)?;
+        self.gen_fallthrough(&function, false)?;
+
+        if let Some(block) = block
+        {
+            self.builder.position_at_end(block);
+        }
+
+        let closure = r#type.as_llvm_basic_type(self.ctx)?.into_struct_type();
+        let closure = self.builder.build_insert_value(
+            closure.get_undef(),
+            inner.as_global_value().as_pointer_value(),
+            0,
+            "closure",
+        )?;
+        let closure = self
+            .builder
+            .build_insert_value(closure, environment, 1, "closure")?;
+
+        Ok(Value {
+            r#type: r#type.clone(),
+            inner: closure.into_struct_value().as_basic_value_enum(),
+        })
+    }
+}
@@ -0,0 +1,108 @@
+//! Globals of `const` items, initialised with the values the type checker folds them to
+
+use anyhow::{Result, bail, ensure};
+use compiler_hir::ItemId;
+use compiler_typeck::{Constant, ItemKind};
+use inkwell::{context::Context, types::BasicTypeEnum, values::BasicValueEnum};
+
+use crate::{Codegen, LlvmType, Value};
+
+/// The LLVM constant holding `constant`
+fn lower<'ctx>(constant: &Constant, ctx: &'ctx Context) -> Result<BasicValueEnum<'ctx>>
+{
+    let r#type = constant.r#type().as_llvm_basic_type(ctx)?;
+
+    Ok(match constant
+    {
+        // Two's complement, LLVM truncates it to the type's width
+        Constant::Integer { value, .. } =>
+        {
+            let bits = *value as u128;
+
+            r#type
+                .into_int_type()
+                .const_int_arbitrary_precision(&[bits as u64, (bits >> 64) as u64])
+                .into()
+        }
+        Constant::Float { value, .. } => r#type.into_float_type().const_float(*value).into(),
+        Constant::Array { scalar, items } =>
+        {
+            let items = items
+                .iter()
+                .map(|i| lower(i, ctx))
+                .collect::<Result<Vec<_>>>()?;
+
+            match scalar.as_llvm_basic_type(ctx)?
+            {
+                BasicTypeEnum::IntType(t) => t
+                    .const_array(&items.iter().map(|i| i.into_int_value()).collect::<Vec<_>>())
+                    .into(),
+                BasicTypeEnum::FloatType(t) => t
+                    .const_array(
+                        &items
+                            .iter()
+                            .map(|i| i.into_float_value())
+                            .collect::<Vec<_>>(),
+                    )
+                    .into(),
+                BasicTypeEnum::ArrayType(t) => t
+                    .const_array(
+                        &items
+                            .iter()
+                            .map(|i| i.into_array_value())
+                            .collect::<Vec<_>>(),
+                    )
+                    .into(),
+                _ => bail!("Constant arrays of `{scalar}` aren't supported"),
+            }
+        }
+    })
+}
+
+impl Codegen<'_, '_>
+{
+    /// Defines the global of the constant `item`
+    pub fn gen_constant(&self, item: ItemId) -> Result<()>
+    {
+        let declaration = &self.thir[item];
+
+        self.runtime.borrow_mut().module = declaration.module.clone();
+
+        let qualified = self.runtime.borrow().qualify(&declaration.name);
+        let symbol = declaration
+            .attributes
+            .symbol(&declaration.name)
+            .unwrap_or_else(|| qualified.clone());
+
+        let ItemKind::Const { r#type, folded, .. } = &declaration.kind
+        else
+        {
+            bail!("`{qualified}` isn't a constant");
+        };
+
+        let inner = lower(folded, self.ctx)?;
+
+        ensure!(
+            self.module.get_global(&symbol).is_none(),
+            "Symbol `{symbol}` of constant `{qualified}` is already defined"
+        );
+
+        let global =
+            self.module
+                .add_global(r#type.as_llvm_basic_type(self.ctx)?, None, symbol.as_str());
+
+        global.set_constant(true);
+        global.set_initializer(&inner);
+
+        // Uses read the value itself, there's no need to go through the global
+        self.runtime.borrow_mut().constants.insert(
+            item,
+            Value {
+                r#type: r#type.clone(),
+                inner,
+            },
+        );
+
+        Ok(())
+    }
+}
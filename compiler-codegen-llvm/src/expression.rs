@@ -1,15 +1,17 @@
 use std::{cell::RefCell, rc::Rc};
 
 use anyhow::{Result, anyhow, bail, ensure};
-use compiler_lexer::definitions::LiteralType;
-use compiler_parser::{Expression, Operator};
-use inkwell::values::BasicValue;
+use compiler_typeck::{Block, Expression, ExpressionKind, FunctionId, Literal, Type, cast_hint};
+use inkwell::values::{BasicValue, FunctionValue};
 
-use crate::{Codegen, Function, Type, Value};
+use crate::{Codegen, Function, LlvmType, Value};
 
+mod array;
 mod binary;
+mod call;
+pub(crate) mod cast;
 
-impl<'ctx> Codegen<'ctx>
+impl<'ctx> Codegen<'ctx, '_>
 {
     #[inline]
     pub fn ref_cast(&self, from_value: Value<'ctx>, to: Type) -> Result<Value<'ctx>>
@@ -25,8 +27,37 @@ impl<'ctx> Codegen<'ctx>
 
                 Ok(Value { r#type: to.clone(), inner: from_value.inner })
             }
+            // `&mut` coerces to `&`
+            [Type::MutRef(box from), Type::Ref(box to)] => {
+                ensure!(from == to, "Cast asks for `&{}`, got `&mut {}`", to, from);
+
+                Ok(Value { r#type: Type::Ref(Box::new(to)), inner: from_value.inner })
+            }
+            // Arrays are borrowed as slices of all their items
+            [Type::MutRef(box Type::Array { box scalar, size }), Type::Slice(box to)]
+            | [Type::Ref(box Type::Array { box scalar, size }), Type::Slice(box to)] => {
+                ensure!(scalar == to, "Cast asks for `[]{}`, got `[{}]{}`", to, size, scalar);
+
+                self.build_slice(
+                    from_value.inner.into_pointer_value(),
+                    self.ctx.i64_type().const_int(size.into(), false),
+                    to,
+                )
+            }
+            [from @ Type::Array { .. }, to @ Type::Slice(_)] => {
+                let ptr = self
+                    .builder
+                    .build_alloca(from.as_llvm_basic_type(self.ctx)?, "cast")?;
+
+                self.builder.build_store(ptr, from_value.inner)?;
+
+                self.ref_cast(
+                    Value { r#type: Type::Ref(Box::new(from)), inner: ptr.as_basic_value_enum() },
+                    to,
+                )
+            }
             [from, Type::MutRef(box to)] | [from, Type::Ref(box to)] => {
-                ensure!(from == to, "Cast asks for `{}`, got `{}`", from, to);
+                ensure!(from == to, "Cast asks for `{}`, got `{}`{}", to, from, cast_hint(&from, &to));
 
                 let ptr = self
                     .builder
@@ -37,7 +68,7 @@ impl<'ctx> Codegen<'ctx>
                 Ok(Value { r#type: to.clone(), inner: ptr.as_basic_value_enum() })
             }
             [Type::MutRef(box from), to] | [Type::Ref(box from), to] => {
-                ensure!(from == to, "Cast asks for `{}`, got `{}`", from, to);
+                ensure!(from == to, "Cast asks for `{}`, got `{}`{}", to, from, cast_hint(&from, &to));
 
                 let load = self.builder.build_load(
                     to.as_llvm_basic_type(self.ctx)?,
@@ -47,16 +78,98 @@ impl<'ctx> Codegen<'ctx>
 
                 Ok(Value { r#type: to.clone(), inner: load })
             }
-            _ => unreachable!(),
+            [from, to] => bail!("Cast asks for `{}`, got `{}`{}", to, from, cast_hint(&from, &to)),
+        }
+    }
+
+    /// The function `id` as declared, unless it's left out of this build, as tests are unless testing
+    pub fn declared(&self, id: FunctionId) -> Result<FunctionValue<'ctx>>
+    {
+        match self.runtime.borrow().functions.get(&id)
+        {
+            Some(&function) => Ok(function),
+            None => bail!(
+                "Function `{}` is left out of this build",
+                self.function_name(id)
+            ),
+        }
+    }
+
+    /// Pointer to the function `id`
+    fn function_value(&self, id: FunctionId) -> Result<Value<'ctx>>
+    {
+        Ok(Value {
+            r#type: self.thir[id].pointer_type(),
+            inner: self
+                .declared(id)?
+                .as_global_value()
+                .as_pointer_value()
+                .into(),
+        })
+    }
+
+    /// Reads the value behind a reference, values are returned as they are
+    #[inline]
+    pub fn load(&self, value: Value<'ctx>) -> Result<Value<'ctx>>
+    {
+        match value.r#type.clone()
+        {
+            Type::Ref(box r#type) | Type::MutRef(box r#type) => self.ref_cast(value, r#type),
+            _ => Ok(value),
         }
     }
 
-    // TODO allow making a cast here directly
+    /// The literal `literal`, of type `r#type`
+    fn gen_literal(&self, literal: &Literal, r#type: &Type) -> Result<Value<'ctx>>
+    {
+        let inner = match literal
+        {
+            // Two's complement, LLVM truncates it to the type's width
+            &Literal::Integer(bits) => r#type
+                .as_llvm_basic_type(self.ctx)?
+                .into_int_type()
+                .const_int_arbitrary_precision(&[bits as u64, (bits >> 64) as u64])
+                .into(),
+            &Literal::Float(value) => r#type
+                .as_llvm_basic_type(self.ctx)?
+                .into_float_type()
+                .const_float(value)
+                .into(),
+            Literal::String(contents) => self.ctx.const_string(contents.as_bytes(), false).into(),
+        };
+
+        Ok(Value {
+            inner,
+            r#type: r#type.clone(),
+        })
+    }
+
+    fn gen_negation(&self, value: Value<'ctx>) -> Result<Value<'ctx>>
+    {
+        let value = self.load(value)?;
+
+        Ok(Value {
+            inner: match value.r#type
+            {
+                Type::Integer { .. } => self
+                    .builder
+                    .build_int_neg(value.inner.into_int_value(), "neg")?
+                    .into(),
+                Type::Float(_) => self
+                    .builder
+                    .build_float_neg(value.inner.into_float_value(), "neg")?
+                    .into(),
+                ref r#type => bail!("Can't negate a value of type `{type}`"),
+            },
+            r#type: value.r#type,
+        })
+    }
+
     #[inline]
     pub fn gen_non_void_expression(
         &self,
         parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
-        expression: Expression,
+        expression: &Expression,
     ) -> Result<Value<'ctx>>
     {
         self.gen_expression(parent_func, expression)
@@ -66,271 +179,154 @@ impl<'ctx> Codegen<'ctx>
     pub fn gen_expression(
         &self,
         parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
-        expression: Expression,
+        expression: &Expression,
     ) -> Result<Option<Value<'ctx>>>
     {
-        Ok(match expression
+        Ok(match &expression.kind
         {
-            Expression::Literal { value, r#type } => Some(match r#type
+            ExpressionKind::Literal(literal) =>
             {
-                LiteralType::String =>
-                {
-                    let bytes = value[1..value.len() - 1].as_bytes();
-
-                    Value {
-                        inner: self.ctx.const_string(bytes, false).into(),
-                        r#type: Type::Array {
-                            scalar: Box::new(Type::Integer {
-                                width: 8,
-                                signed: true,
-                            }),
-                            size: bytes.len().try_into()?,
-                        },
-                    }
-                }
-                LiteralType::Rune => Value {
-                    inner: self
-                        .ctx
-                        .i8_type()
-                        .const_int(value.as_bytes()[1].into(), false)
-                        .into(),
-                    r#type: Type::Integer {
-                        width: 8,
-                        signed: true,
-                    },
-                },
-                LiteralType::Int => Value {
-                    inner: self.ctx.i32_type().const_int(value.parse()?, false).into(),
-                    r#type: Type::Integer {
-                        width: 32,
-                        signed: true,
-                    },
-                },
-                LiteralType::Float => Value {
-                    inner: self.ctx.f64_type().const_float(value.parse()?).into(),
-                    r#type: Type::Float(64),
-                },
-            }),
-            Expression::Path(path) =>
-            {
-                let name = path.last().unwrap();
-
-                if let Some(global) = self.runtime.borrow().constants.get(path[0].as_str())
-                {
-                    Some(Value {
-                        r#type: global.r#type.clone(),
-                        inner: self
-                            .module
-                            .get_global(name)
-                            .unwrap()
-                            .get_initializer()
-                            .unwrap(),
-                    })
-                }
-                else
-                {
-                    let lookup = parent_func
-                        .as_ref()
-                        .unwrap()
-                        .borrow()
-                        .stack
-                        .get(name)
-                        .ok_or_else(|| anyhow!("Identifier `{}` not found", name))?
-                        .clone();
-
-                    Some(lookup)
-                }
+                Some(self.gen_literal(literal, &expression.r#type)?)
             }
-            Expression::Binary(box n) => Some(self.gen_binary(parent_func, n)?),
-            Expression::Unary(op, box e) =>
+            ExpressionKind::Local(local) =>
             {
-                let value = self.gen_non_void_expression(parent_func, e)?;
-                // TODO cast should be immediate
-                let value = self.ref_cast(
-                    value,
-                    parent_func.as_ref().unwrap().borrow().return_type.clone(),
-                )?;
-
-                if op == Operator::Minus && value.inner.is_int_value()
-                {
-                    Some(Value {
-                        r#type: value.r#type,
-                        inner: self
-                            .builder
-                            .build_int_neg(value.inner.into_int_value(), "neg")?
-                            .into(),
-                    })
-                }
-                else if op == Operator::Minus && value.inner.is_float_value()
-                {
-                    Some(Value {
-                        r#type: value.r#type,
-                        inner: self
-                            .builder
-                            .build_float_neg(value.inner.into_float_value(), "neg")?
-                            .into(),
-                    })
-                }
+                let Some(value) = parent_func
+                    .as_ref()
+                    .and_then(|f| f.borrow().stack.get(local).cloned())
                 else
                 {
-                    unreachable!()
-                }
+                    bail!("Local `{}` isn't in scope", self.thir[*local].name);
+                };
+
+                Some(value)
             }
-            Expression::Call { path, args } =>
+            ExpressionKind::Constant(item) =>
             {
-                let name = path.last().unwrap();
-
-                let runtime = self.runtime.borrow();
-
-                let Some(function) = runtime.functions.get(name)
+                let Some(value) = self.runtime.borrow().constants.get(item).cloned()
                 else
                 {
-                    bail!("Function `{}` not found", name);
+                    bail!("Constant `{}` isn't generated", self.thir[*item].name);
                 };
 
-                let ret = self.builder.build_call(
-                    function.borrow().inner,
-                    args.into_iter()
-                        .enumerate()
-                        .map(|(i, e)| {
-                            let function = function.borrow();
-                            let Some(decl_type) = function.arguments.get(i)
-                            else
-                            {
-                                bail!(
-                                    "Function `{}` expects {} arguments",
-                                    name,
-                                    function.arguments.len()
-                                );
-                            };
-
-                            let value = self
-                                .gen_non_void_expression(&parent_func.as_ref().map(Rc::clone), e)?;
-
-                            // TODO we might need the cast everywhere else.. test
-                            Ok(self.ref_cast(value, decl_type.1.clone())?.inner.into())
-                        })
-                        .collect::<Result<Vec<_>>>()?
-                        .as_slice(),
-                    "call",
-                )?;
+                Some(value)
+            }
+            &ExpressionKind::Function(id) => Some(self.function_value(id)?),
+            ExpressionKind::Binary(l, op, r) =>
+            {
+                Some(self.gen_binary(parent_func, l, *op, r, &expression.r#type)?)
+            }
+            ExpressionKind::Negate(e) =>
+            {
+                let value = self.gen_non_void_expression(parent_func, e)?;
+
+                Some(self.gen_negation(value)?)
+            }
+            ExpressionKind::Cast(target) =>
+            {
+                Some(self.gen_cast(parent_func, target, &expression.r#type)?)
+            }
+            ExpressionKind::Call { callee, args } =>
+            {
+                self.gen_call(parent_func, callee, args, expression)?
+            }
+            ExpressionKind::Assert { condition, message } =>
+            {
+                self.gen_assert(parent_func, condition, message.as_ref(), expression.span)?;
 
-                ret.try_as_basic_value().left().map(|r| Value {
-                    inner: r,
-                    r#type: function.borrow().return_type.clone(),
-                })
+                None
+            }
+            ExpressionKind::Len(target) => Some(self.gen_len(parent_func, target)?),
+            ExpressionKind::Index { target, index } =>
+            {
+                Some(self.gen_index(parent_func, target, index, expression)?)
             }
-            Expression::If {
+            ExpressionKind::Slice { target, start, end } => Some(self.gen_slice(
+                parent_func,
+                target,
+                (start.as_deref(), end.as_deref()),
+                expression.span,
+            )?),
+            ExpressionKind::If {
                 condition,
                 block,
                 else_block,
             } =>
             {
-                let then = self
-                    .ctx
-                    .append_basic_block(parent_func.as_ref().unwrap().borrow().inner, "then");
-                let r#else = self
-                    .ctx
-                    .append_basic_block(parent_func.as_ref().unwrap().borrow().inner, "else");
-
-                let r#continue = if else_block.is_none()
-                {
-                    Some(self.ctx.append_basic_block(
-                        parent_func.as_ref().unwrap().borrow().inner,
-                        "continue",
-                    ))
-                }
-                else
-                {
-                    None
-                };
-
-                self.builder.position_at_end(then);
-
-                for statement in block
-                {
-                    self.gen_statement(&parent_func.as_ref().map(Rc::clone), statement)?;
-                }
+                self.gen_if(parent_func, condition, block, else_block.as_ref())?;
 
-                if let Some(r#continue) = r#continue
-                {
-                    self.builder.build_unconditional_branch(r#continue)?;
-                }
+                None
+            }
+            ExpressionKind::Array(items) =>
+            {
+                Some(self.gen_array(parent_func, items, &expression.r#type)?)
+            }
+            ExpressionKind::Closure {
+                captures,
+                parameters,
+                body,
+            } => Some(self.gen_closure(
+                parent_func,
+                captures,
+                parameters,
+                body,
+                &expression.r#type,
+            )?),
+            ExpressionKind::Coerce(value) =>
+            {
+                let value = self.gen_non_void_expression(parent_func, value)?;
 
-                self.builder.position_at_end(r#else);
+                Some(self.ref_cast(value, expression.r#type.clone())?)
+            }
+        })
+    }
 
-                if let Some(r#continue) = r#continue
-                {
-                    self.builder.build_unconditional_branch(r#continue)?;
-                }
-                else
-                {
-                    else_block.unwrap().into_iter().try_for_each(|s| {
-                        self.gen_statement(&parent_func.as_ref().map(Rc::clone), s)
-                    })?;
-                }
-
-                self.builder.position_at_end(
-                    parent_func
-                        .as_ref()
-                        .unwrap()
-                        .borrow()
-                        .inner
-                        .get_first_basic_block()
-                        .unwrap(),
-                );
-
-                let gen_condition = self.gen_non_void_expression(parent_func, *condition)?;
-
-                if !gen_condition.inner.is_int_value()
-                {
-                    bail!("Expected condition, got {:?}", gen_condition.r#type);
-                }
+    /// `if condition { block } else { else_block }`. The condition is generated where the `if` is, and either branch only goes on
+    /// past it unless it returns
+    fn gen_if(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        condition: &Expression,
+        block: &Block,
+        else_block: Option<&Block>,
+    ) -> Result<()>
+    {
+        let Some(function) = parent_func.as_ref().map(|f| f.borrow().inner)
+        else
+        {
+            bail!("`if` may only be used within functions");
+        };
 
-                self.builder.build_conditional_branch(
-                    gen_condition.inner.into_int_value(),
-                    then,
-                    r#else,
-                )?;
+        let condition = self
+            .gen_non_void_expression(parent_func, condition)?
+            .inner
+            .into_int_value();
 
-                if let Some(r#continue) = r#continue
-                {
-                    self.builder.position_at_end(r#continue);
-                }
+        let then = self.ctx.append_basic_block(function, "then");
+        let r#else = else_block.map(|_| self.ctx.append_basic_block(function, "else"));
+        let r#continue = self.ctx.append_basic_block(function, "continue");
 
-                None // TODO this is incorrect
-            }
-            Expression::Parenthesis(box e) =>
-            {
-                let value = self.gen_non_void_expression(parent_func, e)?;
+        self.builder
+            .build_conditional_branch(condition, then, r#else.unwrap_or(r#continue))?;
 
-                Some(value)
-            }
-            Expression::Tuple(_) =>
+        for (basic_block, block) in [(Some(then), Some(block)), (r#else, else_block)]
+        {
+            let (Some(basic_block), Some(block)) = (basic_block, block)
+            else
             {
-                // let mapped = eco_vec
-                //     .into_iter()
-                //     .map(|e| Ok(self.gen_non_void_expression(parent_func, e)?.inner))
-                //     .collect::<Result<Vec<_>, _>>()?;
+                continue;
+            };
 
-                // let llvm_value = self.ctx.const_struct(mapped.as_slice(), false);
+            self.builder.position_at_end(basic_block);
+            self.gen_block(parent_func, block)?;
 
-                // let value = Value {
-                //     r#type: Type::Tuple(mapped.iter().map(|v| v.get_type()).collect()),
-                //     inner: llvm_value.into(),
-                // };
-
-                // Some(value)
-                todo!()
-            }
-            Expression::Array(_) =>
+            if !self.terminated()
             {
-                // let size = eco_vec.len();
+                self.builder.build_unconditional_branch(r#continue)?;
+            }
+        }
 
-                // self.builder.build_array_alloca();
+        self.builder.position_at_end(r#continue);
 
-                todo!()
-            }
-        })
+        Ok(())
     }
 }
@@ -0,0 +1,320 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{Result, bail};
+use compiler_lexer::definitions::Span;
+use compiler_typeck::{Expression, LENGTH, Type};
+use inkwell::{
+    IntPredicate,
+    values::{BasicMetadataValueEnum, FunctionValue, IntValue, PointerValue},
+};
+
+use crate::{Codegen, Function, LlvmType, Value};
+
+impl<'ctx> Codegen<'ctx, '_>
+{
+    /// `[a, b, c]`, an array of type `r#type`, whose items are already of its scalar type
+    pub fn gen_array(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        items: &[Expression],
+        r#type: &Type,
+    ) -> Result<Value<'ctx>>
+    {
+        let values = items
+            .iter()
+            .map(|item| {
+                self.gen_non_void_expression(parent_func, item)
+                    .map(|v| v.inner)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let array = values.into_iter().enumerate().try_fold(
+            r#type
+                .as_llvm_basic_type(self.ctx)?
+                .into_array_type()
+                .get_undef(),
+            |array, (i, value)| {
+                anyhow::Ok(
+                    self.builder
+                        .build_insert_value(array, value, i as u32, "array")?
+                        .into_array_value(),
+                )
+            },
+        )?;
+
+        Ok(Value {
+            r#type: r#type.clone(),
+            inner: array.into(),
+        })
+    }
+
+    /// A `[]scalar` of the `len` items starting at `items`
+    pub fn build_slice(
+        &self,
+        items: PointerValue<'ctx>,
+        len: IntValue<'ctx>,
+        scalar: Type,
+    ) -> Result<Value<'ctx>>
+    {
+        let r#type = Type::Slice(Box::new(scalar));
+
+        let slice = r#type
+            .as_llvm_basic_type(self.ctx)?
+            .into_struct_type()
+            .get_undef();
+        let slice = self.builder.build_insert_value(slice, items, 0, "slice")?;
+        let slice = self.builder.build_insert_value(slice, len, 1, "slice")?;
+
+        Ok(Value {
+            r#type,
+            inner: slice.into_struct_value().into(),
+        })
+    }
+
+    /// Pointer to the first item of what `target` evaluates to, along with the type of its items and how many there are. Arrays
+    /// held as values are spilled, so there's something to point into
+    fn gen_items(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        target: &Expression,
+    ) -> Result<(PointerValue<'ctx>, Type, IntValue<'ctx>)>
+    {
+        let target = self.gen_non_void_expression(parent_func, target)?;
+        let i64 = self.ctx.i64_type();
+
+        Ok(match target.r#type.clone()
+        {
+            Type::MutRef(box Type::Array { box scalar, size })
+            | Type::Ref(box Type::Array { box scalar, size }) => (
+                target.inner.into_pointer_value(),
+                scalar,
+                i64.const_int(size.into(), false),
+            ),
+            Type::Array { box scalar, size } =>
+            {
+                let array = self
+                    .builder
+                    .build_alloca(target.r#type.as_llvm_basic_type(self.ctx)?, "array")?;
+
+                self.builder.build_store(array, target.inner)?;
+
+                (array, scalar, i64.const_int(size.into(), false))
+            }
+            Type::Slice(box scalar)
+            | Type::Ref(box Type::Slice(box scalar))
+            | Type::MutRef(box Type::Slice(box scalar)) =>
+            {
+                let slice = self.load(target)?.inner.into_struct_value();
+
+                (
+                    self.builder
+                        .build_extract_value(slice, 0, "items")?
+                        .into_pointer_value(),
+                    scalar,
+                    self.builder
+                        .build_extract_value(slice, 1, "len")?
+                        .into_int_value(),
+                )
+            }
+            r#type => bail!("Can't index into a value of type `{type}`"),
+        })
+    }
+
+    /// Generates an index or a slice bound, an integer of up to 64 bits, as an `i64`, along with whether it was signed
+    fn gen_offset(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        expression: &Expression,
+    ) -> Result<(IntValue<'ctx>, bool)>
+    {
+        let offset = self.gen_non_void_expression(parent_func, expression)?;
+
+        let Type::Integer { signed, .. } = offset.r#type
+        else
+        {
+            bail!("Arrays are indexed by integers, got `{}`", offset.r#type);
+        };
+
+        Ok((
+            self.builder.build_int_cast_sign_flag(
+                offset.inner.into_int_value(),
+                self.ctx.i64_type(),
+                signed,
+                "offset",
+            )?,
+            signed,
+        ))
+    }
+
+    /// Panics with `format`, formatted with `args`, unless `holds`. Only when bounds checks are enabled
+    fn gen_bounds_check(
+        &self,
+        function: FunctionValue<'ctx>,
+        holds: IntValue<'ctx>,
+        format: &str,
+        args: &[BasicMetadataValueEnum<'ctx>],
+    ) -> Result<()>
+    {
+        let out = self.ctx.append_basic_block(function, "bounds.out");
+        let within = self.ctx.append_basic_block(function, "bounds.within");
+
+        self.builder.build_conditional_branch(holds, within, out)?;
+
+        self.builder.position_at_end(out);
+        self.gen_panic(format, args)?;
+
+        self.builder.position_at_end(within);
+
+        Ok(())
+    }
+
+    /// `target[index]`, as a reference to the item of the type `expression` is. Bounds check failures point at it
+    pub fn gen_index(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        target: &Expression,
+        index: &Expression,
+        expression: &Expression,
+    ) -> Result<Value<'ctx>>
+    {
+        let Some(function) = parent_func.as_ref().map(|f| f.borrow().inner)
+        else
+        {
+            bail!("Arrays may only be indexed within functions");
+        };
+
+        let (items, scalar, len) = self.gen_items(parent_func, target)?;
+        let (index, signed) = self.gen_offset(parent_func, index)?;
+
+        if self.runtime.borrow().bounds_checks
+        {
+            // Negative indices wrap around, past any length
+            let within = self
+                .builder
+                .build_int_compare(IntPredicate::ULT, index, len, "within")?;
+
+            let location = self.runtime.borrow().location(expression.span);
+
+            self.gen_bounds_check(
+                function,
+                within,
+                &format!(
+                    "{}: index out of bounds: the length is %llu but the index is {}\n",
+                    location.replace("%", "%%"),
+                    if signed { "%lld" } else { "%llu" }
+                ),
+                &[len.into(), index.into()],
+            )?;
+        }
+
+        let item = unsafe {
+            self.builder.build_in_bounds_gep(
+                scalar.as_llvm_basic_type(self.ctx)?,
+                items,
+                &[index],
+                "item",
+            )?
+        };
+
+        Ok(Value {
+            r#type: expression.r#type.clone(),
+            inner: item.into(),
+        })
+    }
+
+    /// `target[start..end]`, as a slice. Bounds left out span from the first item, or up to the last one
+    pub fn gen_slice(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        target: &Expression,
+        (start, end): (Option<&Expression>, Option<&Expression>),
+        span: Span,
+    ) -> Result<Value<'ctx>>
+    {
+        let Some(function) = parent_func.as_ref().map(|f| f.borrow().inner)
+        else
+        {
+            bail!("Arrays may only be sliced within functions");
+        };
+
+        let (items, scalar, len) = self.gen_items(parent_func, target)?;
+
+        let (start, start_signed) = match start
+        {
+            Some(start) => self.gen_offset(parent_func, start)?,
+            None => (self.ctx.i64_type().const_zero(), false),
+        };
+        let (end, end_signed) = match end
+        {
+            Some(end) => self.gen_offset(parent_func, end)?,
+            None => (len, false),
+        };
+
+        if self.runtime.borrow().bounds_checks
+        {
+            // Negative bounds wrap around, past any length
+            let ordered =
+                self.builder
+                    .build_int_compare(IntPredicate::ULE, start, end, "ordered")?;
+            let within = self
+                .builder
+                .build_int_compare(IntPredicate::ULE, end, len, "within")?;
+            let holds = self.builder.build_and(ordered, within, "holds")?;
+
+            let location = self.runtime.borrow().location(span);
+            let specifier = |signed| if signed { "%lld" } else { "%llu" };
+
+            self.gen_bounds_check(
+                function,
+                holds,
+                &format!(
+                    "{}: slice out of bounds: the range is {}..{} but the length is %llu\n",
+                    location.replace("%", "%%"),
+                    specifier(start_signed),
+                    specifier(end_signed)
+                ),
+                &[start.into(), end.into(), len.into()],
+            )?;
+        }
+
+        let first = unsafe {
+            self.builder.build_in_bounds_gep(
+                scalar.as_llvm_basic_type(self.ctx)?,
+                items,
+                &[start],
+                "first",
+            )?
+        };
+        let len = self.builder.build_int_sub(end, start, "len")?;
+
+        self.build_slice(first, len, scalar)
+    }
+
+    /// `target.len`, how many items the array or slice `target` holds
+    pub fn gen_len(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        target: &Expression,
+    ) -> Result<Value<'ctx>>
+    {
+        let target = self.gen_non_void_expression(parent_func, target)?;
+
+        match target.r#type.referent()
+        {
+            &Type::Array { size, .. } => Ok(Value {
+                inner: self.ctx.i64_type().const_int(size.into(), false).into(),
+                r#type: LENGTH,
+            }),
+            Type::Slice(_) =>
+            {
+                let slice = self.load(target)?.inner.into_struct_value();
+
+                Ok(Value {
+                    inner: self.builder.build_extract_value(slice, 1, "len")?,
+                    r#type: LENGTH,
+                })
+            }
+            r#type => bail!("`{type}` has no length"),
+        }
+    }
+}
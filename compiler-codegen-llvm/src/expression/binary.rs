@@ -1,92 +1,119 @@
 use std::{cell::RefCell, rc::Rc};
 
-use anyhow::Result;
-use compiler_parser::{Node, Operator};
-use inkwell::{IntPredicate, values::BasicValue};
+use anyhow::{Result, bail};
+use compiler_parser::Operator;
+use compiler_typeck::{Expression, Type};
+use inkwell::{FloatPredicate, IntPredicate, values::BasicValue};
 
-use crate::{Codegen, Function, Type, Value};
+use crate::{Codegen, Function, Value};
 
-impl<'ctx> Codegen<'ctx>
+impl<'ctx> Codegen<'ctx, '_>
 {
+    /// Both operands are values of the same type, as typed. Comparisons give a `BOOL`, the rest a value of that type, as `result`
     pub fn gen_binary(
         &self,
         parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
-        node: Node,
+        l: &Expression,
+        op: Operator,
+        r: &Expression,
+        result: &Type,
     ) -> Result<Value<'ctx>>
     {
-        let Node::Compound(box (l, op, r)) = node
-        else
+        let l = self.gen_non_void_expression(parent_func, l)?;
+        let r = self.gen_non_void_expression(parent_func, r)?;
+
+        let r#type = l.r#type.clone();
+
+        let value = match r#type
         {
-            unreachable!()
-        };
+            Type::Integer { signed, .. } =>
+            {
+                let (l, r) = (l.inner.into_int_value(), r.inner.into_int_value());
 
-        let eval_side = |parent_func: &Option<Rc<RefCell<Function<'ctx>>>>, node| {
-            let parent_func = &parent_func.as_ref().map(Rc::clone);
-            anyhow::Ok(
-                match node
-                {
-                    Node::Scalar(node) => self.ref_cast(
-                        self.gen_non_void_expression(parent_func, node)?,
-                        Type::Integer {
-                            width: 32,
-                            signed: true,
+                let compare = |signed_predicate, unsigned_predicate, name| {
+                    self.builder.build_int_compare(
+                        if signed
+                        {
+                            signed_predicate
+                        }
+                        else
+                        {
+                            unsigned_predicate
                         },
-                    )?,
-                    node @ Node::Compound(..) => self.gen_binary(parent_func, node)?,
-                }
-                .inner
-                .into_int_value(),
-            )
-            // TODO ^ gotta do checking before, as on other places
-        };
+                        l,
+                        r,
+                        name,
+                    )
+                };
 
-        let l = eval_side(parent_func, l)?;
-        let r = eval_side(parent_func, r)?;
-
-        // TODO check for and generate according instructions for fp operands
-        // same thing for signed and unsigned predicate types
+                let value = match op
+                {
+                    Operator::Plus => self.builder.build_int_add(l, r, "sum"),
+                    Operator::Minus => self.builder.build_int_sub(l, r, "sub"),
+                    Operator::Star => self.builder.build_int_mul(l, r, "mul"),
+                    Operator::Div if signed => self.builder.build_int_signed_div(l, r, "div"),
+                    Operator::Div => self.builder.build_int_unsigned_div(l, r, "div"),
+                    // LLVM doesn't implement && or ||, rather they work like & and | to bools (i1)
+                    Operator::And | Operator::BitAnd => self.builder.build_and(l, r, "and"),
+                    Operator::Or | Operator::BitOr => self.builder.build_or(l, r, "or"),
+                    Operator::Shl => self.builder.build_left_shift(l, r, "shl"),
+                    // Arithmetic on signed values, logical on the rest
+                    Operator::Shr => self.builder.build_right_shift(l, r, signed, "shr"),
+                    Operator::Xor => self.builder.build_xor(l, r, "xor"),
+                    Operator::Lt => compare(IntPredicate::SLT, IntPredicate::ULT, "lt"),
+                    Operator::Gt => compare(IntPredicate::SGT, IntPredicate::UGT, "gt"),
+                    Operator::Le => compare(IntPredicate::SLE, IntPredicate::ULE, "le"),
+                    Operator::Ge => compare(IntPredicate::SGE, IntPredicate::UGE, "ge"),
+                    Operator::EqEq => compare(IntPredicate::EQ, IntPredicate::EQ, "eqeq"),
+                    Operator::Neq => compare(IntPredicate::NE, IntPredicate::NE, "neq"),
+                }?;
 
-        // TODO read abt difference on E/U abt NaN
+                value.as_basic_value_enum()
+            }
+            Type::Float(_) =>
+            {
+                let (l, r) = (l.inner.into_float_value(), r.inner.into_float_value());
 
-        let value = match op
-        {
-            Operator::Plus => self.builder.build_int_add(l, r, "sum"),
-            Operator::Minus => self.builder.build_int_sub(l, r, "sub"),
-            Operator::Star => self.builder.build_int_mul(l, r, "mul"),
-            Operator::Div => self.builder.build_int_signed_div(l, r, "div"),
-            // LLVM doesn't implement && or ||, rather they work like & and | to bools (i1)
-            Operator::And | Operator::BitAnd => self.builder.build_and(l, r, "and"),
-            Operator::Or | Operator::BitOr => self.builder.build_or(l, r, "or"),
-            Operator::Shl => self.builder.build_left_shift(l, r, "shl"),
-            Operator::Shr => self.builder.build_right_shift(l, r, true, "shr"),
-            Operator::Xor => self.builder.build_xor(l, r, "xor"),
-            Operator::Lt => self
-                .builder
-                .build_int_compare(IntPredicate::SLT, l, r, "lt"),
-            Operator::Gt => self
-                .builder
-                .build_int_compare(IntPredicate::SGT, l, r, "gt"),
-            Operator::Le => self
-                .builder
-                .build_int_compare(IntPredicate::SLE, l, r, "le"),
-            Operator::Ge => self
-                .builder
-                .build_int_compare(IntPredicate::SGE, l, r, "ge"),
-            Operator::EqEq => self
-                .builder
-                .build_int_compare(IntPredicate::EQ, l, r, "eqeq"),
-            Operator::Neq => self
-                .builder
-                .build_int_compare(IntPredicate::NE, l, r, "neq"),
-        }?;
+                match op
+                {
+                    Operator::Plus => self.builder.build_float_add(l, r, "sum")?.into(),
+                    Operator::Minus => self.builder.build_float_sub(l, r, "sub")?.into(),
+                    Operator::Star => self.builder.build_float_mul(l, r, "mul")?.into(),
+                    Operator::Div => self.builder.build_float_div(l, r, "div")?.into(),
+                    // Comparisons with NaN don't hold, except for `!=`
+                    Operator::Lt => self
+                        .builder
+                        .build_float_compare(FloatPredicate::OLT, l, r, "lt")?
+                        .into(),
+                    Operator::Gt => self
+                        .builder
+                        .build_float_compare(FloatPredicate::OGT, l, r, "gt")?
+                        .into(),
+                    Operator::Le => self
+                        .builder
+                        .build_float_compare(FloatPredicate::OLE, l, r, "le")?
+                        .into(),
+                    Operator::Ge => self
+                        .builder
+                        .build_float_compare(FloatPredicate::OGE, l, r, "ge")?
+                        .into(),
+                    Operator::EqEq => self
+                        .builder
+                        .build_float_compare(FloatPredicate::OEQ, l, r, "eqeq")?
+                        .into(),
+                    Operator::Neq => self
+                        .builder
+                        .build_float_compare(FloatPredicate::UNE, l, r, "neq")?
+                        .into(),
+                    op => bail!("Operator `{op}` can't be applied to `{type}`"),
+                }
+            }
+            _ => bail!("Operator `{op}` can't be applied to `{type}`"),
+        };
 
-        // TODO use correct type
         Ok(Value {
-            inner: value.as_basic_value_enum(),
-            r#type: Type::Integer {
-                width: 32,
-                signed: true,
-            },
+            inner: value,
+            r#type: result.clone(),
         })
     }
 }
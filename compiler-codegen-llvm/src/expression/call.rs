@@ -0,0 +1,80 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{Result, bail};
+use compiler_typeck::{Callee, Expression, Type};
+use inkwell::values::BasicMetadataValueEnum;
+
+use crate::{Codegen, Function, LlvmType, Value};
+
+impl<'ctx> Codegen<'ctx, '_>
+{
+    /// Calls `callee` with `args`, already converted to the types it takes. `call` is the whole call, of the type it returns
+    pub fn gen_call(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        callee: &Callee,
+        args: &[Expression],
+        call: &Expression,
+    ) -> Result<Option<Value<'ctx>>>
+    {
+        let args = args
+            .iter()
+            .map(|e| {
+                self.gen_non_void_expression(parent_func, e)
+                    .map(|v| v.inner.into())
+            })
+            .collect::<Result<Vec<BasicMetadataValueEnum>>>()?;
+
+        let ret = match callee
+        {
+            &Callee::Function(id) =>
+            {
+                self.builder
+                    .build_call(self.declared(id)?, args.as_slice(), "call")?
+            }
+            Callee::Pointer(pointer) =>
+            {
+                let value = self.gen_non_void_expression(parent_func, pointer)?;
+
+                // Closures hand their environment to their code
+                let (pointer, arguments, args) = match &value.r#type
+                {
+                    Type::Function { arguments, .. } =>
+                    {
+                        (value.inner.into_pointer_value(), arguments.clone(), args)
+                    }
+                    Type::Closure { arguments, .. } =>
+                    {
+                        let closure = value.inner.into_struct_value();
+                        let pointer = self.builder.build_extract_value(closure, 0, "code")?;
+                        let environment =
+                            self.builder
+                                .build_extract_value(closure, 1, "environment")?;
+
+                        (
+                            pointer.into_pointer_value(),
+                            [Type::Ref(Box::new(Type::Void))]
+                                .into_iter()
+                                .chain(arguments.iter().cloned())
+                                .collect(),
+                            [environment.into()].into_iter().chain(args).collect(),
+                        )
+                    }
+                    r#type => bail!("`{type}` isn't a function"),
+                };
+
+                self.builder.build_indirect_call(
+                    call.r#type.as_llvm_fn_type(self.ctx, &arguments, false)?,
+                    pointer,
+                    args.as_slice(),
+                    "call",
+                )?
+            }
+        };
+
+        Ok(ret.try_as_basic_value().basic().map(|r| Value {
+            inner: r,
+            r#type: call.r#type.clone(),
+        }))
+    }
+}
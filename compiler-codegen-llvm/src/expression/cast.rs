@@ -0,0 +1,134 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{Context, Result, bail};
+use compiler_typeck::{Expression, Type};
+use inkwell::{intrinsics::Intrinsic, values::BasicMetadataValueEnum};
+
+use crate::{Codegen, Function, LlvmType, Value};
+
+impl<'ctx> Codegen<'ctx, '_>
+{
+    /// `target as to`, between integers and floats of any width
+    pub fn gen_cast(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        target: &Expression,
+        to: &Type,
+    ) -> Result<Value<'ctx>>
+    {
+        let value = self.gen_non_void_expression(parent_func, target)?;
+
+        self.build_cast(value, to.clone())
+    }
+
+    /// Converts the number `value` to `to`, extending, truncating or rounding it as needed. Floats out of the range of the
+    /// integer type they're cast to saturate to its smallest or largest value, NaN to 0
+    pub fn build_cast(&self, value: Value<'ctx>, to: Type) -> Result<Value<'ctx>>
+    {
+        let inner = match (&value.r#type, &to)
+        {
+            (from, to) if from == to => value.inner,
+            (
+                &Type::Integer {
+                    width: from,
+                    signed,
+                },
+                &Type::Integer { width: into, .. },
+            ) =>
+            {
+                let int = value.inner.into_int_value();
+                let into_type = to.as_llvm_basic_type(self.ctx)?.into_int_type();
+
+                if into < from
+                {
+                    self.builder
+                        .build_int_truncate(int, into_type, "cast")?
+                        .into()
+                }
+                else if into > from && signed
+                {
+                    self.builder
+                        .build_int_s_extend(int, into_type, "cast")?
+                        .into()
+                }
+                else if into > from
+                {
+                    self.builder
+                        .build_int_z_extend(int, into_type, "cast")?
+                        .into()
+                }
+                else
+                {
+                    // Signedness is only in how the bits are used
+                    int.into()
+                }
+            }
+            (&Type::Integer { signed, .. }, Type::Float(_)) =>
+            {
+                let int = value.inner.into_int_value();
+                let float = to.as_llvm_basic_type(self.ctx)?.into_float_type();
+
+                if signed
+                {
+                    self.builder
+                        .build_signed_int_to_float(int, float, "cast")?
+                        .into()
+                }
+                else
+                {
+                    self.builder
+                        .build_unsigned_int_to_float(int, float, "cast")?
+                        .into()
+                }
+            }
+            (Type::Float(_), &Type::Integer { signed, .. }) =>
+            {
+                let float = value.inner.into_float_value();
+                let int = to.as_llvm_basic_type(self.ctx)?.into_int_type();
+
+                // Plain `fptosi` and `fptoui` give poison out of range
+                let name = if signed
+                {
+                    "llvm.fptosi.sat"
+                }
+                else
+                {
+                    "llvm.fptoui.sat"
+                };
+
+                let saturating = Intrinsic::find(name)
+                    .and_then(|i| {
+                        i.get_declaration(&self.module, &[int.into(), float.get_type().into()])
+                    })
+                    .with_context(|| format!("LLVM has no `{name}`"))?;
+
+                self.builder
+                    .build_call(saturating, &[BasicMetadataValueEnum::from(float)], "cast")?
+                    .try_as_basic_value()
+                    .basic()
+                    .context("Saturating casts return a value")?
+            }
+            (&Type::Float(from), &Type::Float(into)) =>
+            {
+                let float = value.inner.into_float_value();
+                let into_type = to.as_llvm_basic_type(self.ctx)?.into_float_type();
+
+                if into < from
+                {
+                    self.builder
+                        .build_float_trunc(float, into_type, "cast")?
+                        .into()
+                }
+                else
+                {
+                    self.builder
+                        .build_float_ext(float, into_type, "cast")?
+                        .into()
+                }
+            }
+            (from, to) => bail!("Only numbers are cast with `as`, can't cast `{from}` to `{to}`"),
+        };
+
+        Ok(Value { r#type: to, inner })
+    }
+}
@@ -1,142 +1,261 @@
 use std::{cell::RefCell, rc::Rc};
 
-use anyhow::{Result, bail};
-use compiler_parser::Item;
-use inkwell::{
-    types::{AnyTypeEnum, BasicType},
-    values::BasicValue,
-};
+use anyhow::{Result, bail, ensure};
+use compiler_hir::ItemId;
+use compiler_typeck::{FunctionId, ItemKind, Type};
+use ecow::{EcoString, eco_format};
+use inkwell::{module::Linkage, values::FunctionValue};
 
-use crate::{Codegen, Function, Type, Value};
+use crate::{Codegen, CodegenError, Function, LlvmType};
 
-impl Codegen<'_>
+impl<'ctx> Codegen<'ctx, '_>
 {
-    pub fn gen_item(&self, item: Item) -> Result<()>
+    fn declare_function(
+        &self,
+        id: FunctionId,
+        symbol: &str,
+        linkage: Option<Linkage>,
+    ) -> Result<FunctionValue<'ctx>>
     {
-        match item
+        let function = &self.thir[id];
+
+        let inner = {
+            let fn_type = function.return_type.as_llvm_fn_type(
+                self.ctx,
+                &function.arguments,
+                function.variadic,
+            )?;
+
+            self.module.add_function(symbol, fn_type, linkage)
+        };
+
+        self.runtime.borrow_mut().functions.insert(id, inner);
+
+        Ok(inner)
+    }
+
+    /// Generates the whole program. Constants come first, then every function is declared before any body is generated, so
+    /// they may refer to each other in any order
+    pub fn gen_program(&self) -> Result<()>
+    {
+        let items = self
+            .thir
+            .modules
+            .iter()
+            .flat_map(|m| m.items.iter().copied())
+            .collect::<Vec<_>>();
+
         {
-            Item::Const { name, value } =>
+            let mut runtime = self.runtime.borrow_mut();
+
+            for module in &self.thir.modules
             {
-                let r#type = Type::try_from(name.1.unwrap())?;
-                let global = self.module.add_global(
-                    r#type.as_llvm_basic_type(self.ctx)?,
-                    None,
-                    name.0.as_str(),
+                runtime.sources.insert(
+                    module.path.clone(),
+                    (module.file.clone(), module.source.clone()),
                 );
+            }
+        }
 
-                global.set_constant(true);
+        items
+            .iter()
+            .filter(|&&i| matches!(self.thir[i].kind, ItemKind::Const { .. }))
+            .try_for_each(|&i| self.gen_constant(i))?;
 
-                self.runtime.borrow_mut().constants.insert(
-                    name.0,
-                    Value {
-                        r#type,
-                        inner: global.as_basic_value_enum(),
-                    },
-                );
+        items.iter().try_for_each(|&i| self.declare_item(i))?;
 
-                global.set_initializer(
-                    (Box::new(self.gen_non_void_expression(&None, value)?.inner)
-                        as Box<dyn BasicValue>)
-                        .as_ref(),
-                );
+        (0..self.thir.functions.len()).try_for_each(|f| self.gen_function(FunctionId(f)))
+    }
+
+    fn declare_item(&self, item: ItemId) -> Result<()>
+    {
+        let declaration = &self.thir[item];
+
+        self.runtime.borrow_mut().module = declaration.module.clone();
+
+        match &declaration.kind
+        {
+            // Already generated
+            ItemKind::Const { .. } => Ok(()),
+            &ItemKind::Function(id) => self.declare_defined(item, &[id], false),
+            ItemKind::Generic { instances } => self.declare_defined(item, instances, true),
+            &ItemKind::Extern(id) =>
+            {
+                let attributes = &declaration.attributes;
+
+                // Extern symbols keep their name as is, though they're still namespaced by their module on our side
+                let symbol = attributes
+                    .link_name
+                    .clone()
+                    .unwrap_or_else(|| declaration.name.clone());
+
+                let inner = self.declare_extern(item, id, &symbol)?;
+
+                inner.set_call_conventions(0); // C calling convention
+                self.apply_attributes(inner, attributes);
 
                 Ok(())
             }
-            Item::Function { signature, body } =>
+        }
+    }
+
+    /// Declares the extern function `id`, as `symbol`. Several modules may declare the same one, which is a single symbol all the
+    /// same, as long as they agree on its type
+    fn declare_extern(
+        &self,
+        item: ItemId,
+        id: FunctionId,
+        symbol: &str,
+    ) -> Result<FunctionValue<'ctx>>
+    {
+        let Some(existing) = self.module.get_function(symbol)
+        else
+        {
+            return self.declare_function(id, symbol, Some(Linkage::External));
+        };
+
+        let function = &self.thir[id];
+        let fn_type = function.return_type.as_llvm_fn_type(
+            self.ctx,
+            &function.arguments,
+            function.variadic,
+        )?;
+
+        if existing.get_type() != fn_type
+        {
+            let declaration = &self.thir[item];
+            let other = self
+                .runtime
+                .borrow()
+                .functions
+                .iter()
+                .find(|&(_, &f)| f == existing)
+                .map(|(&other, _)| other);
+
+            return Err(CodegenError::ExternMismatch {
+                name: compiler_hir::qualify_in(&declaration.module, &declaration.name),
+                symbol: symbol.into(),
+                module: declaration.module.clone(),
+                span: declaration.span,
+                help: other.map(|other| {
+                    format!(
+                        "`{}` declares it as `{}`",
+                        self.function_name(other),
+                        self.thir[other].pointer_type()
+                    )
+                }),
+            }
+            .into());
+        }
+
+        self.runtime.borrow_mut().functions.insert(id, existing);
+
+        Ok(existing)
+    }
+
+    /// Declares the function `item`, or each of its `instances` if it's `generic`
+    fn declare_defined(&self, item: ItemId, instances: &[FunctionId], generic: bool) -> Result<()>
+    {
+        let declaration = &self.thir[item];
+        let name = &declaration.name;
+
+        let attributes = &declaration.attributes;
+        let is_main = declaration.module.is_empty() && name == "main";
+        let qualified = self.runtime.borrow().qualify(name);
+        let testing = self.runtime.borrow().testing;
+
+        // Tests only make it into the harness, which brings its own main
+        if attributes.test && !testing || is_main && testing
+        {
+            return Ok(());
+        }
+
+        if generic && is_main
+        {
+            bail!("main function can't be generic");
+        }
+
+        for &id in instances
+        {
+            // Instances are named after their generic arguments, as in `swap<i32>`
+            let symbol = if generic
             {
-                let return_type = if signature.name.0 == "main"
-                {
-                    Type::Integer {
-                        width: 32,
-                        signed: true,
-                    }
-                }
-                else
-                {
-                    signature
-                        .name
-                        .1
-                        .clone()
-                        .map(Type::try_from)
-                        .transpose()?
-                        .unwrap_or_default()
-                };
-
-                let arguments = signature
-                    .arguments
-                    .clone()
-                    .into_iter()
-                    .map(|a| Ok((a.0, Type::try_from(a.1)?)))
-                    .collect::<Result<Vec<_>>>()?;
-
-                let inner = {
-                    let arguments = arguments
-                        .iter()
-                        .map(|(_, t)| Ok(t.as_llvm_basic_type(self.ctx)?.into()))
-                        .collect::<Result<Vec<_>>>()?;
-
-                    let fn_type = if let Type::Void = return_type
-                    {
-                        return_type
-                            .as_llvm_any_type(self.ctx)
-                            .into_void_type()
-                            .fn_type(arguments.as_slice(), false)
-                    }
-                    else
-                    {
-                        return_type
-                            .as_llvm_basic_type(self.ctx)?
-                            .fn_type(arguments.as_slice(), false)
-                    };
-
-                    self.module
-                        .add_function(signature.name.0.as_str(), fn_type, None)
-                };
-
-                let function = Rc::new(RefCell::new(Function {
-                    arguments,
-                    return_type: return_type.clone(),
-                    stack: Default::default(),
-                    inner,
-                }));
-
-                function.borrow_mut().init_block(self);
-
-                function.borrow_mut().init_args_stack(self)?;
-
-                for statement in body
-                {
-                    self.gen_statement(&Some(Rc::clone(&function)), statement)?;
-                }
-
-                if signature.name.0 == "main"
-                {
-                    let ret = if let AnyTypeEnum::IntType(v) =
-                        return_type.as_llvm_any_type(self.ctx)
-                    {
-                        Some(Box::new(v.const_zero()) as Box<dyn BasicValue>)
-                    }
-                    else
-                    {
-                        bail!(
-                            "main function must return an integer type or void (returns {return_type:?})"
-                        )
-                    };
-
-                    self.builder.build_return(ret.as_deref())?;
-                }
-                else if signature.name.1.is_none()
-                {
-                    self.builder.build_return(None)?;
-                }
+                let generics = self.thir[id]
+                    .generics
+                    .iter()
+                    .map(Type::to_string)
+                    .collect::<Vec<_>>();
+
+                eco_format!("{qualified}<{}>", generics.join(", "))
+            }
+            else
+            {
+                attributes.symbol(name).unwrap_or_else(|| qualified.clone())
+            };
+
+            ensure!(
+                self.module.get_function(&symbol).is_none(),
+                "Symbol `{symbol}` of function `{qualified}` is already defined"
+            );
 
+            let inner = self.declare_function(id, &symbol, None)?;
+
+            self.apply_attributes(inner, attributes);
+
+            if attributes.test
+            {
                 self.runtime
                     .borrow_mut()
-                    .functions
-                    .insert(signature.name.0.clone(), function);
-
-                Ok(())
+                    .tests
+                    .push((qualified.clone(), inner));
             }
         }
+
+        Ok(())
+    }
+
+    /// Generates the body of the function `id`, unless it has none or it's left out of the build
+    fn gen_function(&self, id: FunctionId) -> Result<()>
+    {
+        let function = &self.thir[id];
+
+        let Some(body) = &function.body
+        else
+        {
+            return Ok(());
+        };
+
+        let Some(inner) = self.runtime.borrow().functions.get(&id).copied()
+        else
+        {
+            return Ok(());
+        };
+
+        let item = &self.thir[function.item];
+
+        self.runtime.borrow_mut().module = item.module.clone();
+
+        let frame = Rc::new(RefCell::new(Function::new(
+            inner,
+            function.return_type.clone(),
+        )));
+
+        frame.borrow_mut().init_block(self);
+        frame
+            .borrow_mut()
+            .init_args_stack(self, &function.parameters)?;
+
+        let frame = Some(frame);
+
+        self.gen_block(&frame, body)?;
+        self.gen_fallthrough(&frame, item.module.is_empty() && item.name == "main")
+    }
+
+    /// Qualified name of the function `id`, as in diagnostics
+    pub fn function_name(&self, id: FunctionId) -> EcoString
+    {
+        let item = &self.thir[self.thir[id].item];
+
+        compiler_hir::qualify_in(&item.module, &item.name)
     }
 }
@@ -1,196 +1,148 @@
 #![feature(box_patterns)]
 
-use std::{cell::RefCell, collections::HashMap, fmt, fs, path::PathBuf, process::Command, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fs, path::PathBuf, process::Command, rc::Rc};
 
 use anyhow::{Result, anyhow, bail};
-use compiler_parser::{AST, Type as ParserType};
-use ecow::EcoString;
+use compiler_hir::ItemId;
+use compiler_lexer::definitions::Span;
+use compiler_typeck::{FunctionId, LocalId, Thir, Type};
+use ecow::{EcoString, EcoVec, eco_format};
 use inkwell::{
     OptimizationLevel,
     builder::Builder,
     context::Context,
     module::Module,
     targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine},
-    types::{AnyType, AnyTypeEnum, BasicType, BasicTypeEnum},
+    types::{AnyType, AnyTypeEnum, BasicType, BasicTypeEnum, FunctionType},
     values::{BasicValueEnum, FunctionValue},
 };
+use miette::Diagnostic;
+use thiserror::Error;
 
+mod attribute;
+mod closure;
+mod constant;
 mod expression;
 mod item;
+pub mod panic;
 mod statement;
+pub mod test;
+
+/// Errors pointing at source code. `module` is the path of the module the spans refer to
+#[derive(Error, Debug, Diagnostic)]
+pub enum CodegenError
+{
+    #[error("Extern function `{name}` disagrees on the type of symbol `{symbol}`")]
+    ExternMismatch
+    {
+        name: EcoString,
+        symbol: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+}
+
+impl CodegenError
+{
+    #[inline]
+    pub fn module(&self) -> &EcoVec<EcoString>
+    {
+        match self
+        {
+            Self::ExternMismatch { module, .. } => module,
+        }
+    }
+}
 
 pub struct Settings
 {
     pub ir: bool,
     pub opt: u8,
     pub output: Option<PathBuf>,
+    /// Libraries to link against, as in `cc -l`
+    pub libraries: Vec<String>,
+    /// Library search paths, as in `cc -L`
+    pub library_paths: Vec<PathBuf>,
+    /// Build a test harness out of the `@test` functions instead of the program, see [`test`]
+    pub test: bool,
+    /// Check indices against the length of what's indexed, panicking if out of bounds
+    pub bounds_checks: bool,
+    /// Linker `cc` links with, as in `cc -fuse-ld`. `cc` picks its own if `None`
+    pub linker: Option<String>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub enum Type
+/// What [`r#gen`] produced
+pub struct Output
 {
-    Integer
-    {
-        width: u32,
-        signed: bool,
-    },
-    Float(u32),
-    Void,
-    Array
-    {
-        scalar: Box<Type>,
-        size: u32,
-    },
-    Ref(Box<Type>),
-    MutRef(Box<Type>),
-    Tuple(Vec<Type>),
+    /// Path of the linked binary
+    pub binary: PathBuf,
+    /// Qualified names of the tests, in the order the harness numbers them. Empty unless [`Settings::test`]
+    pub tests: Vec<EcoString>,
 }
 
-impl Default for Type
+/// The LLVM types [`Type`]s are lowered to
+pub trait LlvmType
 {
-    #[inline]
-    fn default() -> Self
-    {
-        Self::Void
-    }
+    fn as_llvm_any_type<'ctx>(&self, ctx: &'ctx Context) -> Result<AnyTypeEnum<'ctx>>;
+
+    /// Type of a function taking `arguments` and returning `self`
+    fn as_llvm_fn_type<'ctx>(
+        &self,
+        ctx: &'ctx Context,
+        arguments: &[Type],
+        variadic: bool,
+    ) -> Result<FunctionType<'ctx>>;
+
+    fn as_llvm_basic_type<'ctx>(&self, ctx: &'ctx Context) -> Result<BasicTypeEnum<'ctx>>;
 }
 
-impl fmt::Display for Type
+impl LlvmType for Type
 {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    #[inline]
+    fn as_llvm_any_type<'ctx>(&self, ctx: &'ctx Context) -> Result<AnyTypeEnum<'ctx>>
     {
-        match self
+        match (self.as_llvm_basic_type(ctx), self)
         {
-            Type::Integer { width, signed } =>
-            {
-                write!(f, "{}{width}", if *signed { "i" } else { "u" })
-            }
-            Type::Float(width) => write!(f, "f{width}"),
-            Type::Void => write!(f, "()"),
-            Type::Array { box scalar, size } => write!(f, "[{size}]{scalar}"),
-            Type::Ref(box v) => write!(f, "&{v}"),
-            Type::MutRef(box v) => write!(f, "&mut {v}"),
-            Type::Tuple(items) =>
-            {
-                write!(f, "(")?;
-                items.iter().enumerate().try_for_each(|(i, item)| {
-                    if i > 0
-                    {
-                        write!(f, ", ")?;
-                    }
-                    write!(f, "{item}")
-                })?;
-                write!(f, ")")
-            }
+            (Ok(t), _) => Ok(t.as_any_type_enum()),
+            (Err(_), Type::Void) => Ok(ctx.void_type().into()),
+            (Err(error), _) => Err(error),
         }
     }
-}
-
-impl TryFrom<ParserType> for Type
-{
-    type Error = anyhow::Error;
 
-    fn try_from(value: ParserType) -> Result<Self, Self::Error>
+    fn as_llvm_fn_type<'ctx>(
+        &self,
+        ctx: &'ctx Context,
+        arguments: &[Type],
+        variadic: bool,
+    ) -> Result<FunctionType<'ctx>>
     {
-        if let [a, b, c @ ..] = value.0.as_slice()
-            && let ["&", "mut"] = [a.as_str(), b.as_str()]
-        {
-            Ok(Self::MutRef(Box::new(ParserType(c.to_vec()).try_into()?)))
-        }
-        else if let [a, b @ ..] = value.0.as_slice()
-            && let ["&"] = [a.as_str()]
-        {
-            Ok(Self::Ref(Box::new(ParserType(b.to_vec()).try_into()?)))
-        }
-        else if let [a, .., c] = value.0.as_slice()
-            && let ["(", ")"] = [a.as_str(), c.as_str()]
-        {
-            todo!()
-            // values.iter().scan(Ok(EcoVec::new()), |it, v| {
-            //     if v == ","
-            //     // skip commas
-            //     {
-            //         None
-            //     }
-            //     else
-            //     {
-            //         it.push(ParserType(vec![v.clone()]).try_into()?);
-            //         Some(it)
-            //     }
-            // });
-
-            // let items = it
-            //     .map(|v| ParserType(vec![v.clone()]).try_into())
-            //     .collect::<Result<Vec<_>, _>>()?;
-
-            // Ok(Self::Tuple(items))
-        }
-        // else if let [a, b @ ..] = value.0.as_slice()
-        //     && let ["[", "]"] = [a.as_str(), b.last().unwrap().as_str()]
-        //     && let Some(size) = b.first().and_then(|s| s.parse::<u32>().ok())
-        // {
-        //     if b.len() != 2
-        //     {
-        //         return Err(anyhow!("Array type must have exactly one scalar type"));
-        //     }
-
-        //     Ok(Self::Array {
-        //         scalar: Box::new(ParserType(b[1].clone()).try_into()?),
-        //         size,
-        //     })
-        // }
-        else
-        {
-            let r#type = &value.0[0]; // TODO assume for now, modify when we have structs (and fields, thus)
+        let arguments = arguments
+            .iter()
+            .map(|t| Ok(t.as_llvm_basic_type(ctx)?.into()))
+            .collect::<Result<Vec<_>>>()?;
 
-            let (signedness, bits) = r#type.split_at(1);
-            if matches!(signedness, "u" | "i")
+        Ok(
+            if let Type::Void = self
             {
-                Ok(Self::Integer {
-                    width: bits
-                        .parse()
-                        .map_err(|_| anyhow!("Integer with invalid width"))?,
-                    signed: signedness == "i",
-                })
+                ctx.void_type().fn_type(arguments.as_slice(), variadic)
             }
             else
             {
-                match r#type.as_str()
-                {
-                    "f16" => Ok(Self::Float(16)),
-                    "f32" => Ok(Self::Float(32)),
-                    "f64" => Ok(Self::Float(64)),
-                    "f128" => Ok(Self::Float(128)),
-                    "void" => Ok(Self::Void),
-                    _ => Err(anyhow!("Unknown type")),
-                }
-            }
-        }
-    }
-}
-
-impl Type
-{
-    #[inline]
-    pub fn as_llvm_any_type<'ctx>(&self, ctx: &'ctx Context) -> AnyTypeEnum<'ctx>
-    {
-        match self.as_llvm_basic_type(ctx)
-        {
-            Ok(t) => t.as_any_type_enum(),
-            Err(_) => match self
-            {
-                Type::Void => ctx.void_type().into(),
-                _ => unreachable!(),
+                self.as_llvm_basic_type(ctx)?
+                    .fn_type(arguments.as_slice(), variadic)
             },
-        }
+        )
     }
 
     #[inline]
-    pub fn as_llvm_basic_type<'ctx>(&self, ctx: &'ctx Context) -> Result<BasicTypeEnum<'ctx>>
+    fn as_llvm_basic_type<'ctx>(&self, ctx: &'ctx Context) -> Result<BasicTypeEnum<'ctx>>
     {
         match self
         {
-            Self::Integer { width, .. } => Ok(match width
+            Type::Integer { width, .. } => Ok(match width
             {
                 8 => ctx.i8_type().into(),
                 16 => ctx.i16_type().into(),
@@ -199,7 +151,7 @@ impl Type
                 128 => ctx.i128_type().into(),
                 n => ctx.custom_width_int_type(*n).into(),
             }),
-            Self::Float(width) => Ok(match width
+            Type::Float(width) => Ok(match width
             {
                 16 => ctx.f16_type().into(),
                 32 => ctx.f32_type().into(),
@@ -207,11 +159,29 @@ impl Type
                 128 => ctx.f128_type().into(),
                 _ => unreachable!(),
             }),
-            Self::Array { box scalar, size } =>
+            Type::Array { box scalar, size } =>
             {
                 Ok(scalar.as_llvm_basic_type(ctx)?.array_type(*size).into())
             }
-            Self::Ref(_) | Self::MutRef(_) => Ok(ctx.ptr_type(Default::default()).into()),
+            Type::Ref(_) | Type::MutRef(_) | Type::Function { .. } =>
+            {
+                Ok(ctx.ptr_type(Default::default()).into())
+            }
+            Type::Closure { .. } =>
+            {
+                let ptr = ctx.ptr_type(Default::default());
+
+                Ok(ctx.struct_type(&[ptr.into(), ptr.into()], false).into())
+            }
+            Type::Slice(_) => Ok(ctx
+                .struct_type(
+                    &[
+                        ctx.ptr_type(Default::default()).into(),
+                        ctx.i64_type().into(),
+                    ],
+                    false,
+                )
+                .into()),
             _ => bail!("type {self:?} can't be converted to a basic type"),
         }
     }
@@ -224,46 +194,74 @@ pub struct Value<'ctx>
     pub inner: BasicValueEnum<'ctx>,
 }
 
-/*
-pub struct Local<'ctx> {
-    mutable: bool,
-    value: Value<'ctx>,
-}
-*/
-
+/// A function or a closure whose body is being generated
 #[derive(Clone, Debug)]
 pub struct Function<'ctx>
 {
-    pub arguments: Vec<(EcoString, Type)>,
     pub return_type: Type,
-    pub stack: HashMap<EcoString, Value<'ctx>>,
+    /// Values of the locals, parameters and captures declared so far, as their [`compiler_typeck::Local::place_type`]
+    pub stack: HashMap<LocalId, Value<'ctx>>,
     pub inner: FunctionValue<'ctx>,
 }
 
-impl Function<'_>
+impl<'ctx> Function<'ctx>
 {
     #[inline]
-    pub fn init_block(&mut self, codegen: &Codegen)
+    pub fn new(inner: FunctionValue<'ctx>, return_type: Type) -> Self
+    {
+        Self {
+            return_type,
+            stack: HashMap::new(),
+            inner,
+        }
+    }
+
+    #[inline]
+    pub fn init_block(&mut self, codegen: &Codegen<'ctx, '_>)
     {
         let block = codegen.ctx.append_basic_block(self.inner, "entry");
         codegen.builder.position_at_end(block);
     }
 
+    /// Binds the arguments to `parameters`. Only `mut` ones get a stack slot, so they may be assigned to
     #[inline]
-    pub fn init_args_stack(&mut self, codegen: &Codegen) -> Result<()>
+    pub fn init_args_stack(
+        &mut self,
+        codegen: &Codegen<'ctx, '_>,
+        parameters: &[LocalId],
+    ) -> Result<()>
     {
-        self.arguments
-            .clone()
-            .into_iter()
-            .zip(self.inner.get_param_iter())
-            .try_for_each(|((name, r#type), arg)| {
-                let ptr = codegen
-                    .builder
-                    .build_alloca(r#type.as_llvm_basic_type(codegen.ctx)?, &name)?;
+        // Closures take their captures before any argument
+        let hidden = self.inner.count_params() as usize - parameters.len();
 
-                codegen.builder.build_store(ptr, arg)?;
+        parameters
+            .iter()
+            .zip(self.inner.get_param_iter().skip(hidden))
+            .try_for_each(|(&id, arg)| {
+                let local = &codegen.thir[id];
 
-                self.stack.insert(name, Value { r#type, inner: arg });
+                let value = if local.mutable
+                {
+                    let ptr = codegen
+                        .builder
+                        .build_alloca(local.r#type.as_llvm_basic_type(codegen.ctx)?, &local.name)?;
+
+                    codegen.builder.build_store(ptr, arg)?;
+
+                    Value {
+                        r#type: local.place_type(),
+                        inner: ptr.into(),
+                    }
+                }
+                else
+                {
+                    Value {
+                        r#type: local.place_type(),
+                        inner: arg,
+                    }
+                };
+
+                self.stack.insert(id, value);
 
                 Ok(())
             })
@@ -273,35 +271,109 @@ impl Function<'_>
 #[derive(Default)]
 pub struct Runtime<'ctx>
 {
-    pub functions: HashMap<EcoString, Rc<RefCell<Function<'ctx>>>>,
-    pub constants: HashMap<EcoString, Value<'ctx>>,
+    /// Functions and instances declared so far. Those left out of the build, as tests unless [`Runtime::testing`], never are
+    pub functions: HashMap<FunctionId, FunctionValue<'ctx>>,
+    /// Values of the constants generated so far
+    pub constants: HashMap<ItemId, Value<'ctx>>,
+    /// Path of the module currently being generated
+    pub module: EcoVec<EcoString>,
+    /// Closures generated so far, which number their symbols
+    pub closures: usize,
+    /// File path and source of each module, by module path, for source locations
+    pub sources: HashMap<EcoVec<EcoString>, (EcoString, EcoString)>,
+    /// Whether `@test` functions are generated, rather than left out. The root `main` is left out instead, for the harness's
+    pub testing: bool,
+    /// Qualified names of the tests generated so far, along with their functions
+    pub tests: Vec<(EcoString, FunctionValue<'ctx>)>,
+    /// See [`Settings::bounds_checks`]
+    pub bounds_checks: bool,
+}
+
+impl Runtime<'_>
+{
+    /// Qualified name of an item declared in the current module
+    #[inline]
+    pub fn qualify(&self, name: &str) -> EcoString
+    {
+        compiler_hir::qualify_in(&self.module, name)
+    }
+
+    /// Where `span` is in the current module, as `file:line:column`
+    pub fn location(&self, span: Span) -> EcoString
+    {
+        match self.sources.get(&self.module)
+        {
+            Some((file, source)) =>
+            {
+                let (line, column) = span.location(source);
+
+                eco_format!("{file}:{line}:{column}")
+            }
+            None => eco_format!("{}:{}", self.module.join("."), span.start),
+        }
+    }
 }
 
-pub struct Codegen<'ctx>
+pub struct Codegen<'ctx, 't>
 {
     pub ctx: &'ctx Context,
     pub module: Module<'ctx>,
     pub builder: Builder<'ctx>,
+    /// The program being generated, as the type checker hands it over
+    pub thir: &'t Thir,
     pub runtime: Rc<RefCell<Runtime<'ctx>>>,
 }
 
-pub fn r#gen(module: &str, ast: AST, settings: Settings) -> Result<()>
+/// Generates `thir`, and the test harness if [`Settings::test`], into an LLVM module named `module`
+fn generate<'ctx, 't>(
+    ctx: &'ctx Context,
+    module: &str,
+    thir: &'t Thir,
+    settings: &Settings,
+) -> Result<Codegen<'ctx, 't>>
 {
-    let ctx = Context::create();
-
     let codegen = {
         let r#mod = ctx.create_module(module);
         let builder = ctx.create_builder();
-        let runtime = Runtime::default();
+        let runtime = Runtime {
+            testing: settings.test,
+            bounds_checks: settings.bounds_checks,
+            ..Default::default()
+        };
 
         Codegen {
-            ctx: &ctx,
+            ctx,
             module: r#mod,
             builder,
+            thir,
             runtime: Rc::new(RefCell::new(runtime)),
         }
     };
 
+    codegen.gen_program()?;
+
+    if settings.test
+    {
+        codegen.gen_harness()?;
+    }
+
+    Ok(codegen)
+}
+
+/// Generates `thir` as [`r#gen`] does, but builds nothing. Errors are those found before any code is emitted
+pub fn check(module: &str, thir: &Thir, settings: &Settings) -> Result<()>
+{
+    let ctx = Context::create();
+
+    generate(&ctx, module, thir, settings).map(|_| ())
+}
+
+/// Compiles and links `thir`
+pub fn r#gen(module: &str, thir: &Thir, settings: Settings) -> Result<Output>
+{
+    let ctx = Context::create();
+    let codegen = generate(&ctx, module, thir, &settings)?;
+
     // TODO extern used symbols
     // On build dump std as LLVM IR
     // Read LLVM IR
@@ -322,48 +394,25 @@ pub fn r#gen(module: &str, ast: AST, settings: Settings) -> Result<()>
     let target = Target::from_triple(&triple)
         .map_err(|m| anyhow!("Couldn't create target from triple `{m}`"))?;
 
+    let opt = match settings.opt
+    {
+        0 => OptimizationLevel::None,
+        1 => OptimizationLevel::Less,
+        2 => OptimizationLevel::Default,
+        3 => OptimizationLevel::Aggressive,
+        n => bail!("Optimisation levels go from 0 to 3, got {n}"),
+    };
+
     let machine = target
         .create_target_machine(
             &triple,
             "generic",
             "",
-            match settings.opt
-            {
-                0 => OptimizationLevel::None,
-                1 => OptimizationLevel::Less,
-                2 => OptimizationLevel::Default,
-                3 => OptimizationLevel::Aggressive,
-                _ => unreachable!(),
-            },
+            opt,
             RelocMode::Default,
             CodeModel::Default,
         )
-        .unwrap();
-
-    // codegen.runtime.borrow_mut().functions.insert(
-    //     "putd".into(),
-    //     Rc::new(RefCell::new(Function {
-    //         arguments: vec![(
-    //             "n".into(),
-    //             Type::Integer {
-    //                 width: 32,
-    //                 signed: true,
-    //             },
-    //         )],
-    //         return_type: Type::Void,
-    //         stack: Default::default(),
-    //         inner: codegen.module.add_function(
-    //             "putd",
-    //             codegen
-    //                 .ctx
-    //                 .void_type()
-    //                 .fn_type(&[codegen.ctx.i32_type().into()], false),
-    //             None,
-    //         ),
-    //     })),
-    // );
-
-    ast.0.into_iter().try_for_each(|i| codegen.gen_item(i))?;
+        .ok_or_else(|| anyhow!("Couldn't create a target machine for `{triple}`"))?;
 
     let output_path = {
         let base = settings.output.unwrap_or_default();
@@ -405,19 +454,24 @@ pub fn r#gen(module: &str, ast: AST, settings: Settings) -> Result<()>
     // on msvc is lld -> link.exe
     {
         let output = Command::new("cc")
-            .args([
-                "-fuse-ld=mold",
-                object_path.to_str().unwrap(),
-                "-Ltarget/debug",
-                "-lsundae_library",
-                "-o",
-                output_path.to_str().unwrap(),
-            ])
+            .args(settings.linker.iter().map(|l| format!("-fuse-ld={l}")))
+            .arg(&object_path)
+            .args(
+                settings
+                    .library_paths
+                    .iter()
+                    .map(|p| format!("-L{}", p.display())),
+            )
+            .args(settings.libraries.iter().map(|l| format!("-l{l}")))
+            .arg("-o")
+            .arg(&output_path)
             .output()?;
 
         if !output.stderr.is_empty()
         {
-            Err(anyhow!(String::from_utf8(output.stderr).unwrap()))
+            Err(anyhow!(
+                String::from_utf8_lossy(&output.stderr).into_owned()
+            ))
         }
         else
         {
@@ -426,7 +480,12 @@ pub fn r#gen(module: &str, ast: AST, settings: Settings) -> Result<()>
     }
     .map_err(|m| anyhow!("Error linking object file: {}", m))?;
 
-    Ok(())
+    let runtime = codegen.runtime.borrow();
+
+    Ok(Output {
+        binary: output_path,
+        tests: runtime.tests.iter().map(|(name, _)| name.clone()).collect(),
+    })
 }
 
 // take some concepts of iconic:
@@ -0,0 +1,79 @@
+//! Runtime panics, for failed assertions and out of bounds indexing. They print where they happened to stderr and exit with
+//! [`PANICKED`], there's no unwinding.
+
+use anyhow::{Result, ensure};
+use inkwell::{
+    AddressSpace,
+    module::Linkage,
+    types::FunctionType,
+    values::{BasicMetadataValueEnum, FunctionValue},
+};
+
+use crate::Codegen;
+
+/// Exit code of a program which panicked
+pub const PANICKED: i32 = 101;
+
+impl<'ctx> Codegen<'ctx, '_>
+{
+    /// Function `name` of the C library, declared as `r#type` unless the program already did. A function of the program's own
+    /// by that symbol would take its place, so that's an error, as is an extern one of another type
+    pub(crate) fn libc(&self, name: &str, r#type: FunctionType<'ctx>)
+    -> Result<FunctionValue<'ctx>>
+    {
+        let Some(existing) = self.module.get_function(name)
+        else
+        {
+            return Ok(self
+                .module
+                .add_function(name, r#type, Some(Linkage::External)));
+        };
+
+        let defined = self
+            .runtime
+            .borrow()
+            .functions
+            .iter()
+            .any(|(&id, &f)| f == existing && self.thir[id].body.is_some());
+
+        ensure!(
+            !defined && existing.get_type() == r#type,
+            "Symbol `{name}` is already defined by the program, but the C library's is needed"
+        );
+
+        Ok(existing)
+    }
+
+    /// Prints `format` to stderr, formatted with `args` as `printf` would, then exits with [`PANICKED`]. Nothing follows on the
+    /// current block, so the builder must be moved elsewhere afterwards
+    pub fn gen_panic(&self, format: &str, args: &[BasicMetadataValueEnum<'ctx>]) -> Result<()>
+    {
+        let i32 = self.ctx.i32_type();
+        let ptr = self.ctx.ptr_type(AddressSpace::default());
+
+        let dprintf = self.libc("dprintf", i32.fn_type(&[i32.into(), ptr.into()], true))?;
+        let exit = self.libc("exit", self.ctx.void_type().fn_type(&[i32.into()], false))?;
+
+        let format = self.builder.build_global_string_ptr(format, "panic")?;
+
+        self.builder.build_call(
+            dprintf,
+            &[
+                i32.const_int(2, false).into(),
+                format.as_pointer_value().into(),
+            ]
+            .into_iter()
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>(),
+            "dprintf",
+        )?;
+        self.builder.build_call(
+            exit,
+            &[i32.const_int(PANICKED as u64, false).into()],
+            "exit",
+        )?;
+        self.builder.build_unreachable()?;
+
+        Ok(())
+    }
+}
@@ -1,42 +1,97 @@
 use std::{cell::RefCell, rc::Rc};
 
 use anyhow::Result;
-use compiler_parser::Statement;
+use compiler_typeck::{Statement, Type};
 use inkwell::values::BasicValue;
 
-use crate::{Codegen, Function, Type, Value};
+use crate::{Codegen, Function, LlvmType, Value};
 
-impl<'ctx> Codegen<'ctx>
+impl<'ctx> Codegen<'ctx, '_>
 {
+    pub fn gen_block(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        block: &[Statement],
+    ) -> Result<()>
+    {
+        for statement in block
+        {
+            // Whatever follows a return is never reached, and LLVM takes nothing after it on the same block
+            if self.terminated()
+            {
+                break;
+            }
+
+            self.gen_statement(parent_func, statement)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the current block already ends, by returning or branching elsewhere
+    #[inline]
+    pub fn terminated(&self) -> bool
+    {
+        self.builder
+            .get_insert_block()
+            .is_some_and(|b| b.get_terminator().is_some())
+    }
+
+    /// Ends the function being generated if its body falls through. Functions returning nothing return, the root `main` returns
+    /// 0, and any other can't get there
+    pub fn gen_fallthrough(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        is_main: bool,
+    ) -> Result<()>
+    {
+        if self.terminated()
+        {
+            return Ok(());
+        }
+
+        match parent_func.as_ref().unwrap().borrow().return_type.clone()
+        {
+            Type::Void => self.builder.build_return(None)?,
+            r#type if is_main =>
+            {
+                let zero = r#type.as_llvm_basic_type(self.ctx)?.const_zero();
+
+                self.builder.build_return(Some(&zero))?
+            }
+            _ => self.builder.build_unreachable()?,
+        };
+
+        Ok(())
+    }
+
     pub fn gen_statement(
         &self,
         parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
-        statement: Statement,
+        statement: &Statement,
     ) -> Result<()>
     {
         match statement
         {
             Statement::Return(e) =>
             {
-                let ret = e
-                    .and_then(|e| self.gen_expression(parent_func, e).transpose())
-                    .transpose()?;
+                let return_type = parent_func.as_ref().unwrap().borrow().return_type.clone();
 
-                self.builder.build_return(
-                    ret.map(|v| {
-                        Ok::<Box<dyn BasicValue<'_>>, anyhow::Error>(Box::new(
-                            // this generic should be implicit?
-                            self.ref_cast(
-                                v,
-                                parent_func.as_ref().unwrap().borrow().return_type.clone(),
-                            )?
-                            .inner,
-                        )
-                            as Box<dyn BasicValue>)
-                    })
-                    .transpose()?
-                    .as_deref(),
-                )?;
+                let ret = match (e, &return_type)
+                {
+                    (Some(e), Type::Void) =>
+                    {
+                        self.gen_expression(parent_func, e)?;
+                        None
+                    }
+                    (Some(e), _) => Some(self.gen_non_void_expression(parent_func, e)?.inner),
+                    (None, Type::Void) => None,
+                    // Only the root `main` may leave its value out, returning 0
+                    (None, r#type) => Some(r#type.as_llvm_basic_type(self.ctx)?.const_zero()),
+                };
+
+                self.builder
+                    .build_return(ret.as_ref().map(|v| v as &dyn BasicValue))?;
 
                 Ok(())
             }
@@ -46,61 +101,42 @@ impl<'ctx> Codegen<'ctx>
                 source,
             } =>
             {
-                let destination = self
-                    .gen_non_void_expression(&parent_func.as_ref().map(Rc::clone), destination)?;
-
+                let destination = self.gen_non_void_expression(parent_func, destination)?;
                 let source = self.gen_non_void_expression(parent_func, source)?;
 
-                self.builder.build_store(
-                    destination.inner.into_pointer_value(),
-                    if let Type::Ref(box inner_type) | Type::MutRef(box inner_type) = source.r#type
-                    {
-                        let inner_ptr = source.inner.into_pointer_value();
-
-                        self.builder.build_load(
-                            inner_type.as_llvm_basic_type(self.ctx)?,
-                            inner_ptr,
-                            "load",
-                        )?
-                    }
-                    else
-                    {
-                        source.inner
-                    },
-                )?;
+                self.builder
+                    .build_store(destination.inner.into_pointer_value(), source.inner)?;
 
                 Ok(())
             }
-            Statement::Local {
-                mutable: _,
-                name,
-                init,
-            } =>
+            Statement::Local { local, init } =>
             {
-                // TODO impl mut
-                let r#type = Type::try_from(name.1.unwrap())?;
+                let declared = &self.thir[*local];
 
-                let alloc = self
-                    .builder
-                    .build_alloca(r#type.as_llvm_basic_type(self.ctx)?, &name.0)?;
+                // The initial value can't see the local itself, it may refer to one it shadows
+                let init = init
+                    .as_ref()
+                    .map(|e| self.gen_non_void_expression(parent_func, e))
+                    .transpose()?;
+
+                let alloc = self.builder.build_alloca(
+                    declared.r#type.as_llvm_basic_type(self.ctx)?,
+                    &declared.name,
+                )?;
+
+                if let Some(init) = init
+                {
+                    self.builder.build_store(alloc, init.inner)?;
+                }
 
                 parent_func.as_ref().unwrap().borrow_mut().stack.insert(
-                    name.0,
+                    *local,
                     Value {
                         inner: alloc.into(),
-                        r#type: Type::MutRef(Box::new(r#type.clone())),
+                        r#type: declared.place_type(),
                     },
                 );
 
-                if let Some(init) = init
-                {
-                    self.builder.build_store(
-                        alloc,
-                        self.ref_cast(self.gen_non_void_expression(parent_func, init)?, r#type)?
-                            .inner,
-                    )?;
-                }
-
                 Ok(())
             }
         }
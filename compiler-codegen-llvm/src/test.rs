@@ -0,0 +1,146 @@
+//! Tests, as `@test` functions, and the harness running them. The harness runs the test numbered by its first argument, exiting
+//! with 0 once it returns. A failing `assert` panics instead, exiting with [`ASSERT_FAILED`], so running each test in its own
+//! process keeps one failing from taking the rest down.
+
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{Result, bail};
+use compiler_lexer::definitions::Span;
+use compiler_typeck::Expression;
+use ecow::{EcoString, eco_format};
+use inkwell::{AddressSpace, IntPredicate, values::BasicMetadataValueEnum};
+
+use crate::{Codegen, Function, panic::PANICKED};
+
+/// Exit code of a test whose assertion failed, or which panicked otherwise
+pub const ASSERT_FAILED: i32 = PANICKED;
+
+/// Exit code of the harness when it isn't given the number of a test
+pub const NO_SUCH_TEST: i32 = 2;
+
+impl<'ctx> Codegen<'ctx, '_>
+{
+    /// `assert(condition)` or `assert(condition, "message")`, at `span`. Prints where it failed to stderr and exits with
+    /// [`ASSERT_FAILED`] if `condition` doesn't hold
+    pub fn gen_assert(
+        &self,
+        parent_func: &Option<Rc<RefCell<Function<'ctx>>>>,
+        condition: &Expression,
+        message: Option<&EcoString>,
+        span: Span,
+    ) -> Result<()>
+    {
+        let Some(function) = parent_func.as_ref().map(|f| f.borrow().inner)
+        else
+        {
+            bail!("`assert` may only be used within functions");
+        };
+
+        let condition = self
+            .gen_non_void_expression(parent_func, condition)?
+            .inner
+            .into_int_value();
+        let holds = self.builder.build_int_compare(
+            IntPredicate::NE,
+            condition,
+            condition.get_type().const_zero(),
+            "assert",
+        )?;
+
+        let failed = self.ctx.append_basic_block(function, "assert.failed");
+        let passed = self.ctx.append_basic_block(function, "assert.passed");
+
+        self.builder
+            .build_conditional_branch(holds, passed, failed)?;
+
+        self.builder.position_at_end(failed);
+
+        // The whole report is known by now
+        let report = {
+            let location = self.runtime.borrow().location(span);
+
+            match message
+            {
+                Some(message) => eco_format!("{location}: assertion failed: {message}\n"),
+                None => eco_format!("{location}: assertion failed\n"),
+            }
+        };
+
+        self.gen_panic(&report.replace("%", "%%"), &[])?;
+
+        self.builder.position_at_end(passed);
+
+        Ok(())
+    }
+
+    /// `main` of the harness, calling the test numbered by its first argument
+    pub fn gen_harness(&self) -> Result<()>
+    {
+        let i32 = self.ctx.i32_type();
+        let ptr = self.ctx.ptr_type(AddressSpace::default());
+
+        let main =
+            self.module
+                .add_function("main", i32.fn_type(&[i32.into(), ptr.into()], false), None);
+        let atoi = self.libc("atoi", i32.fn_type(&[ptr.into()], false))?;
+
+        let entry = self.ctx.append_basic_block(main, "entry");
+        let select = self.ctx.append_basic_block(main, "select");
+        let missing = self.ctx.append_basic_block(main, "missing");
+
+        self.builder.position_at_end(entry);
+
+        let argc = main.get_nth_param(0).unwrap().into_int_value();
+        let argv = main.get_nth_param(1).unwrap().into_pointer_value();
+
+        let given = self.builder.build_int_compare(
+            IntPredicate::SGT,
+            argc,
+            i32.const_int(1, false),
+            "given",
+        )?;
+        self.builder
+            .build_conditional_branch(given, select, missing)?;
+
+        self.builder.position_at_end(select);
+
+        let argument = unsafe {
+            self.builder
+                .build_in_bounds_gep(ptr, argv, &[i32.const_int(1, false)], "argument")?
+        };
+        let argument = self.builder.build_load(ptr, argument, "argument")?;
+        let number = self
+            .builder
+            .build_call(atoi, &[BasicMetadataValueEnum::from(argument)], "number")?
+            .try_as_basic_value()
+            .basic()
+            .unwrap()
+            .into_int_value();
+
+        let cases = self
+            .runtime
+            .borrow()
+            .tests
+            .iter()
+            .enumerate()
+            .map(|(i, (name, test))| {
+                let block = self.ctx.append_basic_block(main, name);
+
+                self.builder.position_at_end(block);
+                self.builder.build_call(*test, &[], "test")?;
+                self.builder.build_return(Some(&i32.const_zero()))?;
+
+                Ok((i32.const_int(i as u64, false), block))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.builder.position_at_end(select);
+        self.builder.build_switch(number, missing, &cases)?;
+
+        self.builder.position_at_end(missing);
+        self.builder
+            .build_return(Some(&i32.const_int(NO_SUCH_TEST as u64, false)))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+//! `--emit` dumps of what the front end built, to see into the lexer, the parser, name resolution and type checking without a
+//! debugger
+
+use std::path::Path;
+
+use compiler_hir::Hir;
+use compiler_lexer::definitions::{Span, Token, TokenType};
+use compiler_parser::AST;
+use compiler_typeck::Thir;
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Emit
+{
+    /// Tokens the parser takes, which leaves comments out
+    Tokens,
+    /// Syntax tree of each module
+    Ast,
+    /// The whole program with its names resolved, once every module is loaded
+    Hir,
+    /// The whole program typed, as handed to the code generator
+    Thir,
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum EmitFormat
+{
+    /// Human readable, the AST, HIR and THIR in their `Debug` form
+    Pretty,
+    /// One JSON object per line, for each dump of each file
+    Json,
+}
+
+pub struct Dumps
+{
+    pub emit: Vec<Emit>,
+    pub format: EmitFormat,
+}
+
+#[derive(Serialize)]
+struct TokenDump<'s>
+{
+    r#type: TokenType,
+    span: Span,
+    text: &'s str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Dump<'a>
+{
+    Tokens(Vec<TokenDump<'a>>),
+    Ast(&'a AST),
+    Hir(&'a Hir),
+    Thir(&'a Thir),
+}
+
+#[derive(Serialize)]
+struct Document<'a>
+{
+    file: &'a Path,
+    #[serde(flatten)]
+    dump: Dump<'a>,
+}
+
+impl Dumps
+{
+    pub fn tokens(&self, file: &Path, source: &str, tokens: &[Token]) -> Result<()>
+    {
+        if !self.emit.contains(&Emit::Tokens)
+        {
+            return Ok(());
+        }
+
+        match self.format
+        {
+            EmitFormat::Pretty =>
+            {
+                println!("Tokens of `{}`:", file.display());
+
+                for token in tokens
+                {
+                    println!(
+                        "{:?} {}..{} {:?}",
+                        token.r#type,
+                        token.span.start,
+                        token.span.end,
+                        token.span.source(source)
+                    );
+                }
+            }
+            EmitFormat::Json => Self::json(
+                file,
+                Dump::Tokens(
+                    tokens
+                        .iter()
+                        .map(|t| TokenDump {
+                            r#type: t.r#type,
+                            span: t.span,
+                            text: t.span.source(source),
+                        })
+                        .collect(),
+                ),
+            )?,
+        }
+
+        Ok(())
+    }
+
+    pub fn ast(&self, file: &Path, ast: &AST) -> Result<()>
+    {
+        if !self.emit.contains(&Emit::Ast)
+        {
+            return Ok(());
+        }
+
+        match self.format
+        {
+            EmitFormat::Pretty => println!("AST of `{}`:\n{ast:#?}", file.display()),
+            EmitFormat::Json => Self::json(file, Dump::Ast(ast))?,
+        }
+
+        Ok(())
+    }
+
+    /// `file` is the root of the program
+    pub fn hir(&self, file: &Path, hir: &Hir) -> Result<()>
+    {
+        if !self.emit.contains(&Emit::Hir)
+        {
+            return Ok(());
+        }
+
+        match self.format
+        {
+            EmitFormat::Pretty => println!("HIR of `{}`:\n{hir:#?}", file.display()),
+            EmitFormat::Json => Self::json(file, Dump::Hir(hir))?,
+        }
+
+        Ok(())
+    }
+
+    /// `file` is the root of the program
+    pub fn thir(&self, file: &Path, thir: &Thir) -> Result<()>
+    {
+        if !self.emit.contains(&Emit::Thir)
+        {
+            return Ok(());
+        }
+
+        match self.format
+        {
+            EmitFormat::Pretty => println!("THIR of `{}`:\n{thir:#?}", file.display()),
+            EmitFormat::Json => Self::json(file, Dump::Thir(thir))?,
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn json(file: &Path, dump: Dump) -> Result<()>
+    {
+        println!(
+            "{}",
+            serde_json::to_string(&Document { file, dump }).into_diagnostic()?
+        );
+
+        Ok(())
+    }
+}
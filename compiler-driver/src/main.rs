@@ -1,24 +1,60 @@
 use std::{
-    fs,
+    collections::HashMap,
+    env, fs,
+    io::{self, Write},
+    mem,
     path::{Path, PathBuf},
+    process,
+    time::Instant,
 };
 
 use clap::Parser;
-use compiler_codegen_llvm::Settings;
-use miette::{Context, IntoDiagnostic, Report, Result, bail};
+use compiler_codegen_llvm::{CodegenError, Output, Settings, test};
+use compiler_hir::Hir;
+use compiler_interpreter::repl::{self, Repl};
+use compiler_parser::Module;
+use compiler_typeck::Thir;
+use ecow::{EcoString, EcoVec};
+use emit::{Dumps, Emit, EmitFormat};
+use miette::{
+    Context, Diagnostic, IntoDiagnostic, JSONReportHandler, NamedSource, Report, Result, bail,
+    miette,
+};
 use mimalloc::MiMalloc;
 
+mod emit;
+
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 #[derive(clap::Parser)]
-#[command(version, about)]
+#[command(
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args
 {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Dump LLVM IR to a file
     #[arg(short, long)]
     ir: bool,
 
+    /// Print what the front end built, e.g. `--emit=tokens,ast`
+    #[arg(long, value_delimiter = ',')]
+    emit: Vec<Emit>,
+
+    /// How `--emit` dumps are printed
+    #[arg(long, value_enum, default_value_t = EmitFormat::Pretty)]
+    emit_format: EmitFormat,
+
+    /// How errors and warnings are printed
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Pretty)]
+    error_format: ErrorFormat,
+
     /// Optimisation level
     #[arg(short = 'O', long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=3))]
     opt: u8,
@@ -27,9 +63,144 @@ struct Args
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Source file path
-    #[arg(value_parser = path_is_valid_file)]
-    source: PathBuf,
+    /// Link against a library, e.g. `-l sundae_library`
+    #[arg(short = 'l', long = "link")]
+    libraries: Vec<String>,
+
+    /// Add a library search path
+    #[arg(short = 'L', long = "library-path")]
+    library_paths: Vec<PathBuf>,
+
+    /// Code generation option, e.g. `-C bounds-checks=off`. Bounds checks are only left out of optimised builds. `-C linker=lld`
+    /// has `cc` link with another linker than `mold`, `-C linker=default` with its own, as it does if `mold` isn't installed
+    #[arg(short = 'C', long = "codegen", value_parser = parse_codegen_option)]
+    codegen: Vec<CodegenOption>,
+
+    /// Source file path. Imports are resolved relative to its directory
+    #[arg(value_parser = path_is_valid_file, required = true)]
+    source: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+enum CodegenOption
+{
+    BoundsChecks(bool),
+    Linker(Option<String>),
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum ErrorFormat
+{
+    /// Rendered along with the source they point at
+    Pretty,
+    /// One JSON object per line, with the byte offsets of their labels
+    Json,
+}
+
+fn parse_codegen_option(s: &str) -> Result<CodegenOption>
+{
+    let Some((key, value)) = s.split_once('=')
+    else
+    {
+        bail!("Expected `key=value`");
+    };
+
+    let flag = || match value
+    {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(miette!("Expected `on` or `off` for `{key}`, got `{value}`")),
+    };
+
+    match key
+    {
+        "bounds-checks" => Ok(CodegenOption::BoundsChecks(flag()?)),
+        "linker" => Ok(CodegenOption::Linker(
+            (value != "default").then(|| value.to_owned()),
+        )),
+        _ => bail!("Unknown option `{key}`, expected `bounds-checks` or `linker`"),
+    }
+}
+
+/// Linker the last `-C linker` among `options` chooses. Otherwise `mold`, unless it isn't installed, then `cc` picks its own
+fn linker(options: &[CodegenOption]) -> Option<String>
+{
+    let chosen = options.iter().rev().find_map(|option| match option
+    {
+        CodegenOption::Linker(name) => Some(name.clone()),
+        CodegenOption::BoundsChecks(_) => None,
+    });
+
+    chosen.unwrap_or_else(|| {
+        let installed = env::var_os("PATH")
+            .is_some_and(|paths| env::split_paths(&paths).any(|p| p.join("mold").is_file()));
+
+        installed.then(|| "mold".into())
+    })
+}
+
+#[derive(clap::Subcommand)]
+enum Command
+{
+    /// Reprint source files in the canonical style
+    Fmt
+    {
+        /// Don't write anything, fail if any file isn't formatted
+        #[arg(long)]
+        check: bool,
+
+        /// Source file paths
+        #[arg(value_parser = path_is_valid_file, required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Build the `@test` functions into a harness and run each of them
+    Test
+    {
+        /// Source file path. Imports are resolved relative to its directory
+        #[arg(value_parser = path_is_valid_file)]
+        source: PathBuf,
+
+        /// Only run the tests whose qualified name contains this
+        filter: Option<String>,
+
+        /// Link against a library, e.g. `-l sundae_library`
+        #[arg(short = 'l', long = "link")]
+        libraries: Vec<String>,
+
+        /// Add a library search path
+        #[arg(short = 'L', long = "library-path")]
+        library_paths: Vec<PathBuf>,
+
+        /// Code generation option, as on builds. Only `linker` makes a difference, tests and programs run keep bounds checks
+        #[arg(short = 'C', long = "codegen", value_parser = parse_codegen_option)]
+        codegen: Vec<CodegenOption>,
+    },
+    /// Build the program and run it
+    Run
+    {
+        /// Source file path. Imports are resolved relative to its directory
+        #[arg(value_parser = path_is_valid_file)]
+        source: PathBuf,
+
+        /// Interpret the program rather than building it, which needs no LLVM nor linker. Only the `putd`, `puts` and `prints`
+        /// extern functions are available then
+        #[arg(long)]
+        interp: bool,
+
+        /// Link against a library, e.g. `-l sundae_library`
+        #[arg(short = 'l', long = "link")]
+        libraries: Vec<String>,
+
+        /// Add a library search path
+        #[arg(short = 'L', long = "library-path")]
+        library_paths: Vec<PathBuf>,
+
+        /// Code generation option, as on builds. Only `linker` makes a difference, tests and programs run keep bounds checks
+        #[arg(short = 'C', long = "codegen", value_parser = parse_codegen_option)]
+        codegen: Vec<CodegenOption>,
+    },
+    /// Evaluate items and statements as they're typed, printing the value and type of each expression
+    Repl,
 }
 
 fn path_is_valid_file(s: &str) -> Result<PathBuf>
@@ -45,47 +216,587 @@ fn path_is_valid_file(s: &str) -> Result<PathBuf>
     }
 }
 
+/// Source files by module path, for diagnostics
+type Sources = HashMap<EcoVec<EcoString>, NamedSource<String>>;
+
+fn parse_file(
+    source: &Path,
+    path: EcoVec<EcoString>,
+    sources: &mut Sources,
+    dumps: &Dumps,
+) -> Result<Module>
+{
+    let file = fs::read_to_string(source)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Couldn't read file from path `{}`", source.display()))?;
+
+    let tokens = match compiler_parser::lex(&file)
+    {
+        Ok(tokens) => tokens,
+        Err(error) => return Err(Report::new(error).with_source_code(file)),
+    };
+
+    dumps.tokens(source, &file, &tokens)?;
+
+    let ast = compiler_parser::parse(&file, tokens.into_iter())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Parser failed on `{}`", source.display()))?;
+
+    dumps.ast(source, &ast)?;
+
+    sources.insert(
+        path.clone(),
+        NamedSource::new(source.display().to_string(), file.clone()),
+    );
+
+    Ok(Module {
+        path,
+        file: source.display().to_string().into(),
+        source: file.into(),
+        ast,
+    })
+}
+
+/// Loads `path` and, recursively, everything it imports. Modules are pushed after their imports, so `modules` ends up in dependency order.
+fn load_module(
+    root: &Path,
+    source: &Path,
+    path: EcoVec<EcoString>,
+    visiting: &mut Vec<EcoVec<EcoString>>,
+    modules: &mut Vec<Module>,
+    sources: &mut Sources,
+    dumps: &Dumps,
+) -> Result<()>
+{
+    let module = parse_file(source, path.clone(), sources, dumps)?;
+
+    visiting.push(path);
+
+    for import in module.ast.imports()
+    {
+        if visiting.contains(import)
+        {
+            bail!("Import cycle on module `{}`", import.join("."));
+        }
+
+        if modules.iter().any(|m| m.path == *import)
+        {
+            continue;
+        }
+
+        let file = import
+            .iter()
+            .fold(root.to_owned(), |p, s| p.join(s.as_str()))
+            .with_extension("su");
+
+        if !file.is_file()
+        {
+            bail!(
+                "Couldn't find module `{}` (expected at `{}`)",
+                import.join("."),
+                file.display()
+            );
+        }
+
+        load_module(
+            root,
+            &file,
+            import.clone(),
+            visiting,
+            modules,
+            sources,
+            dumps,
+        )?;
+    }
+
+    visiting.pop();
+    modules.push(module);
+
+    Ok(())
+}
+
+/// Formats `files` in place. With `check` nothing is written, and unformatted files are reported instead
+fn format(files: &[PathBuf], check: bool) -> Result<()>
+{
+    let settings = compiler_fmt::Settings::default();
+    let mut unformatted = 0;
+
+    for file in files
+    {
+        let source = fs::read_to_string(file)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Couldn't read file from path `{}`", file.display()))?;
+
+        let formatted = compiler_fmt::format(&source, &settings)
+            .map_err(|error| {
+                Report::new(error)
+                    .with_source_code(NamedSource::new(file.display().to_string(), source.clone()))
+            })
+            .wrap_err_with(|| format!("Couldn't format `{}`", file.display()))?;
+
+        if formatted == source
+        {
+            continue;
+        }
+
+        if check
+        {
+            eprintln!("`{}` isn't formatted", file.display());
+            unformatted += 1;
+        }
+        else
+        {
+            fs::write(file, formatted)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Couldn't write file to path `{}`", file.display()))?;
+        }
+    }
+
+    if unformatted > 0
+    {
+        bail!("{unformatted} of {} files aren't formatted", files.len());
+    }
+
+    Ok(())
+}
+
+/// Loads the program rooted at `source`, returning its modules in dependency order along with their sources
+fn load(source: &Path, dumps: &Dumps) -> Result<(Vec<Module>, Sources)>
+{
+    let root = source.parent().unwrap_or(Path::new(""));
+
+    let mut modules = Vec::new();
+    let mut sources = HashMap::new();
+    load_module(
+        root,
+        source,
+        EcoVec::new(),
+        &mut Vec::new(),
+        &mut modules,
+        &mut sources,
+        dumps,
+    )?;
+
+    Ok((modules, sources))
+}
+
+/// Prints an error or a warning to stderr
+fn report(report: &Report, format: ErrorFormat)
+{
+    match format
+    {
+        ErrorFormat::Pretty => eprintln!("{report:?}"),
+        ErrorFormat::Json =>
+        {
+            let mut json = String::new();
+
+            // Only fails on writing to `json`, which doesn't
+            let _ = JSONReportHandler::new().render_report(&mut json, report.as_ref());
+
+            eprintln!("{json}");
+        }
+    }
+}
+
+/// Prints every error of a failed pass as `format` says but the last, which is returned. `module` tells which module each is in
+fn failed<E>(
+    errors: Vec<E>,
+    module: impl Fn(&E) -> &EcoVec<EcoString>,
+    sources: &Sources,
+    format: ErrorFormat,
+) -> Report
+where
+    E: Diagnostic + Send + Sync + 'static,
+{
+    let mut reports = errors
+        .into_iter()
+        .map(|error| match sources.get(module(&error))
+        {
+            Some(source) => Report::new(error).with_source_code(source.clone()),
+            None => Report::new(error),
+        });
+
+    let last = reports.next_back().expect("Passes only fail with errors");
+
+    for error in reports
+    {
+        report(&error, format);
+    }
+
+    last
+}
+
+/// Prints every warning of a pass as `format` says. `module` tells which module each is in
+fn warned<W>(
+    warnings: Vec<W>,
+    module: impl Fn(&W) -> &EcoVec<EcoString>,
+    sources: &Sources,
+    format: ErrorFormat,
+) where
+    W: Diagnostic + Send + Sync + 'static,
+{
+    for warning in warnings
+    {
+        let warning = match sources.get(module(&warning))
+        {
+            Some(source) => Report::new(warning).with_source_code(source.clone()),
+            None => Report::new(warning),
+        };
+
+        report(&warning, format);
+    }
+}
+
+/// Resolves the names of the program rooted at `source`. Every error is printed as `errors` says but the last, which is
+/// returned
+fn resolve(
+    source: &Path,
+    modules: &[Module],
+    sources: &Sources,
+    dumps: &Dumps,
+    errors: ErrorFormat,
+) -> Result<Hir>
+{
+    let hir = compiler_hir::resolve(modules)
+        .map_err(|failed| self::failed(failed, |e| e.module(), sources, errors))?;
+
+    dumps.hir(source, &hir)?;
+
+    Ok(hir)
+}
+
+/// Type checks the program rooted at `source`, once resolved. Errors are reported as by [`resolve`], warnings are printed
+fn check(
+    source: &Path,
+    hir: &Hir,
+    sources: &Sources,
+    dumps: &Dumps,
+    errors: ErrorFormat,
+) -> Result<Thir>
+{
+    let mut thir = compiler_typeck::check(hir)
+        .map_err(|failed| self::failed(failed, |e| e.module(), sources, errors))?;
+
+    warned(
+        mem::take(&mut thir.warnings),
+        |w| w.module(),
+        sources,
+        errors,
+    );
+
+    dumps.thir(source, &thir)?;
+
+    Ok(thir)
+}
+
+/// Compiles the program rooted at `source`, printing any warnings as `errors` says
+fn compile(source: &Path, settings: Settings, dumps: &Dumps, errors: ErrorFormat)
+-> Result<Output>
+{
+    let module = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .wrap_err("Incorrect file name")?;
+
+    let (modules, mut sources) = load(source, dumps)?;
+    let hir = resolve(source, &modules, &sources, dumps, errors)?;
+    let thir = check(source, &hir, &sources, dumps, errors)?;
+
+    compiler_codegen_llvm::r#gen(module, &thir, settings)
+        .map_err(|error| match error.downcast::<CodegenError>()
+        {
+            Ok(error) => match sources.remove(error.module())
+            {
+                Some(source) => Report::new(error).with_source_code(source),
+                None => Report::new(error),
+            },
+            Err(error) => miette!("{error:?}"),
+        })
+        .wrap_err("Code generator failed")
+}
+
+/// Builds the tests of the program rooted at `source` into a harness, then runs those whose name contains `filter`, each in its
+/// own process
+fn test(
+    source: &Path,
+    filter: Option<String>,
+    libraries: Vec<String>,
+    library_paths: Vec<PathBuf>,
+    linker: Option<String>,
+) -> Result<()>
+{
+    let directory = env::temp_dir().join(format!("sundae-test-{}", process::id()));
+
+    let Output { binary, tests, .. } = compile(
+        source,
+        Settings {
+            ir: false,
+            // Tests are rarely worth the wait for optimisations
+            opt: 0,
+            output: Some(directory.join("harness")),
+            libraries,
+            library_paths,
+            test: true,
+            bounds_checks: true,
+            linker,
+        },
+        &Dumps {
+            emit: Vec::new(),
+            format: EmitFormat::Pretty,
+        },
+        ErrorFormat::Pretty,
+    )?;
+
+    let selected = tests
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| filter.as_ref().is_none_or(|f| name.contains(f.as_str())))
+        .collect::<Vec<_>>();
+
+    println!("running {} of {} tests", selected.len(), tests.len());
+
+    let start = Instant::now();
+    let mut failures = Vec::new();
+
+    for (i, name) in &selected
+    {
+        let test_start = Instant::now();
+        let output = process::Command::new(&binary)
+            .arg(i.to_string())
+            .output()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Couldn't run test `{name}`"))?;
+        let elapsed = test_start.elapsed();
+
+        if output.status.success()
+        {
+            println!("test {name} ... ok ({elapsed:.2?})");
+        }
+        else
+        {
+            println!("test {name} ... FAILED ({elapsed:.2?})");
+            failures.push((name, output));
+        }
+    }
+
+    let _ = fs::remove_dir_all(&directory);
+
+    for (name, output) in &failures
+    {
+        println!("\n---- {name} ----");
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        print!("{}", String::from_utf8_lossy(&output.stderr));
+
+        // Failed assertions and other panics already said why
+        if output.status.code() != Some(test::ASSERT_FAILED)
+        {
+            println!("exited with {}", output.status);
+        }
+    }
+
+    println!(
+        "\n{} passed, {} failed in {:.2?}",
+        selected.len() - failures.len(),
+        failures.len(),
+        start.elapsed()
+    );
+
+    if !failures.is_empty()
+    {
+        bail!("{} of {} tests failed", failures.len(), selected.len());
+    }
+
+    Ok(())
+}
+
+/// Runs the program rooted at `source`, either interpreted or built into a temporary binary, exiting with its status
+fn run(
+    source: &Path,
+    interp: bool,
+    libraries: Vec<String>,
+    library_paths: Vec<PathBuf>,
+    linker: Option<String>,
+) -> Result<()>
+{
+    let dumps = Dumps {
+        emit: Vec::new(),
+        format: EmitFormat::Pretty,
+    };
+
+    if interp
+    {
+        let (modules, sources) = load(source, &dumps)?;
+        let hir = resolve(source, &modules, &sources, &dumps, ErrorFormat::Pretty)?;
+        let thir = check(source, &hir, &sources, &dumps, ErrorFormat::Pretty)?;
+
+        return compiler_interpreter::run(thir, io::stdout().lock())
+            .map_err(|error| miette!("{error:?}"))
+            .wrap_err("Interpreter failed");
+    }
+
+    let directory = env::temp_dir().join(format!("sundae-run-{}", process::id()));
+
+    let Output { binary, .. } = compile(
+        source,
+        Settings {
+            ir: false,
+            opt: 0,
+            output: Some(directory.join("program")),
+            libraries,
+            library_paths,
+            test: false,
+            bounds_checks: true,
+            linker,
+        },
+        &dumps,
+        ErrorFormat::Pretty,
+    )?;
+
+    let status = process::Command::new(&binary)
+        .status()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("Couldn't run `{}`", binary.display()));
+
+    let _ = fs::remove_dir_all(&directory);
+
+    let status = status?;
+    if !status.success()
+    {
+        process::exit(status.code().unwrap_or(1));
+    }
+
+    Ok(())
+}
+
+/// Reads input from stdin until its braces are balanced, then interprets it on a session kept between inputs
+fn repl() -> Result<()>
+{
+    let mut repl = Repl::new(io::stdout());
+    let mut input = String::new();
+
+    loop
+    {
+        print!("{}", if input.is_empty() { ">> " } else { ".. " });
+        io::stdout().flush().into_diagnostic()?;
+
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).into_diagnostic()? == 0
+        {
+            break;
+        }
+
+        input.push_str(&line);
+
+        if input.trim().is_empty()
+        {
+            input.clear();
+            continue;
+        }
+
+        if repl::depth(&input) > 0
+        {
+            continue;
+        }
+
+        match repl.eval(&input)
+        {
+            Ok(values) =>
+            {
+                for value in values
+                {
+                    println!("{value}: {}", value.r#type());
+                }
+            }
+            Err(error) => eprintln!("Error: {error:#}"),
+        }
+
+        input.clear();
+    }
+
+    println!();
+
+    Ok(())
+}
+
 fn main() -> Result<()>
 {
     let Args {
+        command,
         ir,
+        emit,
+        emit_format,
+        error_format,
         opt,
         output,
+        libraries,
+        library_paths,
+        codegen,
         source,
     } = Args::parse();
 
-    let file = fs::read_to_string(&source)
-        .into_diagnostic()
-        .wrap_err_with(|| format!("Couldn't read file from path `{}`", source.display()))?;
+    match command
+    {
+        Some(Command::Fmt { check, files }) => return format(&files, check),
+        Some(Command::Test {
+            source,
+            filter,
+            libraries,
+            library_paths,
+            codegen,
+        }) => return test(&source, filter, libraries, library_paths, linker(&codegen)),
+        Some(Command::Run {
+            source,
+            interp,
+            libraries,
+            library_paths,
+            codegen,
+        }) => return run(&source, interp, libraries, library_paths, linker(&codegen)),
+        Some(Command::Repl) => return repl(),
+        None => (),
+    }
 
-    let module = source
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .wrap_err("Incorrect file name")?;
+    let source = source.wrap_err("Missing source file path")?;
 
-    let tokens = compiler_lexer::tokenize(&file);
+    let linker = linker(&codegen);
+    let mut bounds_checks = true;
 
-    // TODO Use collect instead. I'd rather allocate more to the heap than run the whole lexer twice lmao
-    for event in tokens.clone()
+    for option in codegen
     {
-        if let compiler_lexer::LexerEvent::Error(error) = event
+        if let CodegenOption::BoundsChecks(on) = option
         {
-            return Err(Report::new(error).with_source_code(file.clone()));
+            bounds_checks = on;
         }
     }
 
-    let ast = compiler_parser::parse(
-        file.as_bytes(),
-        tokens
-            .flatten()
-            .filter(|t| t.r#type != compiler_lexer::definitions::TokenType::Comment),
-    )
-    .into_diagnostic()
-    .wrap_err("Parser failed")?;
+    let output = compile(
+        &source,
+        Settings {
+            ir,
+            opt,
+            output,
+            libraries,
+            library_paths,
+            test: false,
+            // Debug builds keep them regardless
+            bounds_checks: bounds_checks || opt == 0,
+            linker,
+        },
+        &Dumps {
+            emit,
+            format: emit_format,
+        },
+        error_format,
+    );
+
+    // Pretty errors are left to `main`'s own report
+    if let Err(error) = &output
+        && error_format == ErrorFormat::Json
+    {
+        report(error, error_format);
+        process::exit(1);
+    }
 
-    compiler_codegen_llvm::r#gen(module, ast, Settings { ir, opt, output }).unwrap();
-    // .into_diagnostic()
-    // .wrap_err("Code generator failed")?;
+    output?;
 
     Ok(())
 }
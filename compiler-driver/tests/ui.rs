@@ -0,0 +1,25 @@
+//! Runs the UI tests under `tests/ui` against the driver, see [`compiler_uitest`]. `cargo test -p sundae --test ui -- --bless`
+//! takes what failing ones got as expected
+
+use std::{path::Path, process::ExitCode};
+
+use compiler_uitest::Config;
+
+fn main() -> ExitCode
+{
+    let config = Config::from_args(
+        env!("CARGO_BIN_EXE_sundae").into(),
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/ui"),
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join("ui"),
+    );
+
+    match compiler_uitest::run_tests(&config)
+    {
+        Ok(code) => code,
+        Err(error) =>
+        {
+            eprintln!("Error: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
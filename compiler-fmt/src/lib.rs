@@ -0,0 +1,969 @@
+//! Reprints sundae sources in one canonical style. The [concrete syntax tree](compiler_parser::cst) gives the layout of items,
+//! statements, blocks and comments, each line is then respaced token by token and wrapped if it runs past the width limit or a comment
+//! ends it early. Both the source and the result go through the parser, and the result is only handed out if their ASTs agree.
+
+use std::{mem, slice};
+
+use compiler_lexer::{LexerError, definitions::Span};
+use compiler_parser::{
+    AST, ArgumentName, Capture, Expression, Item, MutVisitor, Name, ParserError,
+    cst::{self, SyntaxElement, SyntaxKind, SyntaxNode},
+    expression::{binary::priority, operator},
+    item::{Attribute, FunctionSignature},
+    visit::mut_visit::{
+        walk_argument, walk_attribute, walk_expression, walk_function_signature, walk_item,
+        walk_name,
+    },
+};
+use ecow::EcoString;
+use miette::Diagnostic;
+use thiserror::Error;
+
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+pub enum FormatError
+{
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Lexer(#[from] LexerError),
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Parser(#[from] ParserError),
+    #[error("Formatting would change the meaning of the source")]
+    #[diagnostic(help("This is a bug in the formatter, please report it along with the file"))]
+    Unstable,
+}
+
+pub struct Settings
+{
+    /// Lines longer than this get their lists wrapped, one element per line
+    pub max_width: usize,
+    /// Spaces per indentation level
+    pub tab_spaces: usize,
+}
+
+impl Default for Settings
+{
+    #[inline]
+    fn default() -> Self
+    {
+        Self {
+            max_width: 100,
+            tab_spaces: 4,
+        }
+    }
+}
+
+/// Formats `source`, which must parse
+pub fn format(source: &str, settings: &Settings) -> Result<String, FormatError>
+{
+    let ast = parse(source)?;
+
+    let mut printer = Printer {
+        settings,
+        out: String::new(),
+        indent: 0,
+    };
+    printer.list(cst::parse(source)?.children());
+
+    match parse(&printer.out)
+    {
+        Ok(formatted) if formatted == ast => Ok(printer.out),
+        _ => Err(FormatError::Unstable),
+    }
+}
+
+fn parse(source: &str) -> Result<AST, FormatError>
+{
+    let tokens = compiler_parser::lex(source)?;
+    let mut ast = compiler_parser::parse(source, tokens.into_iter())?;
+    ClearSpans.visit_ast(&mut ast);
+
+    Ok(ast)
+}
+
+/// Spans move along with the whitespace, so they're left out when comparing trees
+struct ClearSpans;
+
+impl MutVisitor for ClearSpans
+{
+    fn visit_item(&mut self, item: &mut Item)
+    {
+        if let Item::Import(_, span) = item
+        {
+            *span = Span::empty(0);
+        }
+
+        walk_item(self, item);
+    }
+
+    fn visit_argument(&mut self, argument: &mut ArgumentName)
+    {
+        argument.span = Span::empty(0);
+        walk_argument(self, argument);
+    }
+
+    fn visit_name(&mut self, name: &mut Name)
+    {
+        name.2 = Span::empty(0);
+        walk_name(self, name);
+    }
+
+    fn visit_capture(&mut self, capture: &mut Capture)
+    {
+        capture.span = Span::empty(0);
+    }
+
+    fn visit_attribute(&mut self, attribute: &mut Attribute)
+    {
+        attribute.span = Span::empty(0);
+        walk_attribute(self, attribute);
+    }
+
+    fn visit_function_signature(&mut self, signature: &mut FunctionSignature)
+    {
+        signature.span = Span::empty(0);
+        walk_function_signature(self, signature);
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression)
+    {
+        if let Expression::Literal { span, .. }
+        | Expression::Path { span, .. }
+        | Expression::Cast { span, .. }
+        | Expression::Call { span, .. }
+        | Expression::Method { span, .. }
+        | Expression::Field { span, .. }
+        | Expression::Index { span, .. }
+        | Expression::Slice { span, .. } = expression
+        {
+            *span = Span::empty(0);
+        }
+
+        walk_expression(self, expression);
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Piece
+{
+    kind: SyntaxKind,
+    text: EcoString,
+    /// Part of a type annotation, where `&mut T` and `[4]i32` are glued together
+    r#type: bool,
+    /// A comment starting its own line, rather than trailing the piece before it
+    own_line: bool,
+}
+
+impl Piece
+{
+    /// Whether an operator following this piece is binary
+    #[inline]
+    fn ends_operand(&self) -> bool
+    {
+        matches!(self.kind, SyntaxKind::Identifier | SyntaxKind::Literal(_))
+            || matches!(self.text.as_str(), ")" | "]")
+    }
+
+    #[inline]
+    fn comment(text: EcoString, own_line: bool) -> Self
+    {
+        Self {
+            kind: SyntaxKind::Comment,
+            text,
+            r#type: false,
+            own_line,
+        }
+    }
+
+    /// Whether this is a comment trailing the piece before it
+    #[inline]
+    fn trails(&self) -> bool
+    {
+        self.kind == SyntaxKind::Comment && !self.own_line
+    }
+}
+
+#[derive(Default)]
+struct Line
+{
+    /// Comments are pieces too, they end the line they're in
+    pieces: Vec<Piece>,
+}
+
+impl Line
+{
+    #[inline]
+    fn push(&mut self, kind: SyntaxKind, text: EcoString)
+    {
+        self.pieces.push(Piece {
+            kind,
+            text,
+            r#type: false,
+            own_line: false,
+        });
+    }
+
+    /// Flags the pieces of declared types, in signatures, locals and constants
+    fn mark_types(&mut self)
+    {
+        let texts = self
+            .pieces
+            .iter()
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>();
+
+        let declared = match texts.as_slice()
+        {
+            ["extern", "func", ..] | ["func", ..] =>
+            {
+                self.mark_signature(0);
+                None
+            }
+            ["let", "mut", _, ..] => Some((3, "=")),
+            ["let", _, ..] | ["const", _, ..] => Some((2, "=")),
+            _ => None,
+        };
+
+        if let Some((start, end)) = declared
+        {
+            self.pieces
+                .iter_mut()
+                .skip(start)
+                .filter(|p| p.kind != SyntaxKind::Comment)
+                .take_while(|p| p.text != end)
+                .for_each(|p| p.r#type = true);
+        }
+
+        // Closures declare their arguments anywhere, as in `func[&total](n i32) {`
+        for i in 0..self.pieces.len().saturating_sub(1)
+        {
+            if self.pieces[i].text == "func"
+                && self.pieces[i + 1].text == "["
+                && !self.pieces[i].r#type
+            {
+                self.mark_signature(i);
+            }
+        }
+    }
+
+    /// Flags the argument and return types of the signature starting at the piece `start`
+    fn mark_signature(&mut self, start: usize)
+    {
+        let Some(open) = self.pieces[start..]
+            .iter()
+            .position(|p| p.text == "(")
+            .map(|open| start + open)
+        else
+        {
+            return;
+        };
+
+        let mut depth = 0;
+        // Position within the current argument, the type starts after its name
+        let mut element = 0;
+        let mut returns = false;
+
+        for piece in &mut self.pieces[open..]
+        {
+            if piece.kind == SyntaxKind::Comment
+            {
+                continue;
+            }
+
+            // Everything up to the body is the return type, which may have parentheses of its own
+            if returns
+            {
+                if piece.text == "{"
+                {
+                    break;
+                }
+
+                piece.r#type = true;
+                continue;
+            }
+
+            match piece.text.as_str()
+            {
+                "(" if depth == 0 =>
+                {
+                    depth = 1;
+                    continue;
+                }
+                ")" if depth == 1 =>
+                {
+                    returns = true;
+                    continue;
+                }
+                "," if depth == 1 =>
+                {
+                    element = 0;
+                    continue;
+                }
+                _ => (),
+            }
+
+            match depth
+            {
+                _ if element == 0 && piece.text == "mut" => (),
+                // Variadic `...`
+                _ if element == 0 && piece.text == "..." => (),
+                _ =>
+                {
+                    piece.r#type = element > 0;
+                    element += 1;
+
+                    match piece.text.as_str()
+                    {
+                        "(" | "[" => depth += 1,
+                        ")" | "]" => depth -= 1,
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether `piece` is a prefix operator, given the piece before it
+#[inline]
+fn is_unary(before: Option<&Piece>, piece: &Piece) -> bool
+{
+    piece.kind == SyntaxKind::Operator && !before.is_some_and(Piece::ends_operand)
+}
+
+/// Whether a space goes between `a` and `b`, `before` being the piece preceding `a`
+fn space(before: Option<&Piece>, a: &Piece, b: &Piece) -> bool
+{
+    // Types are glued together, but for keywords and function pointers, `&mut func(a, b) c`
+    if b.r#type
+    {
+        return match (a.text.as_str(), b.text.as_str())
+        {
+            (_, "(" | ")" | "," | "[" | "]") if a.r#type => false,
+            ("(", _) => false,
+            ("," | ")", _) => true,
+            _ => !a.r#type || a.kind == SyntaxKind::Keyword,
+        };
+    }
+
+    match (a.text.as_str(), b.text.as_str())
+    {
+        (",", _) => true,
+        (_, "," | ")" | "]" | "." | "..")
+        | ("(" | "[" | "." | ".." | "@", _)
+        | ("{", "}")
+        | ("func", "[") => false,
+        (_, "(" | "[") if a.ends_operand() => false,
+        _ => !is_unary(before, a),
+    }
+}
+
+/// Joins `pieces` into a line, which may only end in a comment
+fn join(pieces: &[Piece]) -> String
+{
+    let mut text = String::new();
+
+    for (i, piece) in pieces.iter().enumerate()
+    {
+        if piece.kind == SyntaxKind::Comment
+        {
+            if i > 0
+            {
+                text.push(' ');
+            }
+
+            text.push_str(piece.text.trim_end());
+            continue;
+        }
+
+        if i > 0 && space(i.checked_sub(2).map(|i| &pieces[i]), &pieces[i - 1], piece)
+        {
+            text.push(' ');
+        }
+
+        text.push_str(&piece.text);
+    }
+
+    text
+}
+
+/// How many parentheses and brackets are left open in `pieces`
+#[inline]
+fn depth(pieces: &[Piece]) -> usize
+{
+    pieces.iter().fold(0usize, |depth, p| match p.text.as_str()
+    {
+        "(" | "[" => depth + 1,
+        ")" | "]" => depth.saturating_sub(1),
+        _ => depth,
+    })
+}
+
+/// The outermost groups of parentheses and brackets in `pieces`, as the positions of their opening and closing pieces. Those of types,
+/// as in `[4]i32`, are left out.
+fn groups(pieces: &[Piece]) -> Vec<(usize, usize)>
+{
+    let mut groups = Vec::new();
+    let mut depth = 0usize;
+    let mut open = None;
+
+    for (i, piece) in pieces.iter().enumerate()
+    {
+        match piece.text.as_str()
+        {
+            "(" | "[" =>
+            {
+                if depth == 0 && !piece.r#type
+                {
+                    open = Some(i);
+                }
+
+                depth += 1;
+            }
+            ")" | "]" =>
+            {
+                depth = depth.saturating_sub(1);
+
+                if depth == 0
+                    && let Some(open) = open.take()
+                {
+                    groups.push((open, i));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    groups
+}
+
+/// The binary operators in `pieces` outside of any group and followed by another operand, of those binding the loosest
+fn operators(pieces: &[Piece]) -> Vec<usize>
+{
+    let mut operators = Vec::new();
+    let mut depth = 0usize;
+    let mut before = None;
+
+    for (i, piece) in pieces.iter().enumerate()
+    {
+        match piece.text.as_str()
+        {
+            "(" | "[" => depth += 1,
+            ")" | "]" => depth = depth.saturating_sub(1),
+            text if depth == 0 && !piece.r#type && !is_unary(before, piece) =>
+            {
+                if let Some(operator) = operator::binary(text)
+                    && pieces[i + 1..]
+                        .iter()
+                        .any(|p| p.kind != SyntaxKind::Comment)
+                {
+                    operators.push((i, priority(operator)));
+                }
+            }
+            _ => (),
+        }
+
+        if piece.kind != SyntaxKind::Comment
+        {
+            before = Some(piece);
+        }
+    }
+
+    let loosest = operators.iter().map(|&(_, p)| p).min();
+
+    operators
+        .into_iter()
+        .filter(|&(_, p)| Some(p) == loosest)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+struct Printer<'s>
+{
+    settings: &'s Settings,
+    out: String,
+    indent: usize,
+}
+
+impl Printer<'_>
+{
+    /// Prints the items of the root or the statements of a block, keeping at most a blank line between them
+    fn list(&mut self, children: impl Iterator<Item = SyntaxElement>)
+    {
+        let mut newlines = 0;
+        let mut first = true;
+
+        for child in children
+        {
+            match child
+            {
+                SyntaxElement::Token(token) => match token.kind
+                {
+                    SyntaxKind::Newline => newlines += 1,
+                    SyntaxKind::Comment =>
+                    {
+                        self.separate(first, newlines);
+                        self.write(&[Piece::comment(token.text, true)], self.indent);
+                        (first, newlines) = (false, 0);
+                    }
+                    _ => (),
+                },
+                SyntaxElement::Node(node) =>
+                {
+                    self.separate(first, newlines);
+                    self.statement(&node);
+                    (first, newlines) = (false, 0);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn separate(&mut self, first: bool, newlines: usize)
+    {
+        if !first && newlines > 1
+        {
+            self.out.push('\n');
+        }
+    }
+
+    /// Prints an item or a statement, along with the comments attached to it
+    fn statement(&mut self, node: &SyntaxNode)
+    {
+        let mut line = Line::default();
+        let mut indent = self.indent;
+        // Whether the line in the source was broken since the last token
+        let mut broken = false;
+
+        for child in node.children()
+        {
+            match child
+            {
+                SyntaxElement::Token(token) => match token.kind
+                {
+                    SyntaxKind::Whitespace => (),
+                    SyntaxKind::Newline => broken = true,
+                    SyntaxKind::Comment if line.pieces.is_empty() =>
+                    {
+                        self.write(&[Piece::comment(token.text, true)], indent);
+                    }
+                    // The line is laid out around the comment once it's complete
+                    SyntaxKind::Comment =>
+                    {
+                        line.pieces.push(Piece::comment(token.text, broken));
+                    }
+                    kind =>
+                    {
+                        line.push(kind, token.text);
+                        broken = false;
+                    }
+                },
+                // Attributes go on their own lines
+                SyntaxElement::Node(attribute) if attribute.kind() == SyntaxKind::Attribute =>
+                {
+                    let mut line = Line::default();
+
+                    for token in attribute.tokens()
+                    {
+                        if token.kind != SyntaxKind::Whitespace
+                        {
+                            line.push(token.kind, token.text);
+                        }
+                    }
+
+                    self.emit(line, indent);
+                }
+                SyntaxElement::Node(block) =>
+                {
+                    self.block(&mut line, indent, &block);
+                    indent = self.indent;
+                }
+            }
+        }
+
+        if !line.pieces.is_empty()
+        {
+            self.emit(line, indent);
+        }
+    }
+
+    /// Prints a block, opening it on `line` and leaving its closing brace there
+    fn block(&mut self, line: &mut Line, indent: usize, block: &SyntaxNode)
+    {
+        let mut children = block
+            .children()
+            .skip(1)
+            .filter(|c| c.kind() != SyntaxKind::Whitespace)
+            .peekable();
+
+        line.push(SyntaxKind::Separator, "{".into());
+
+        let closed = block
+            .children()
+            .last()
+            .is_some_and(|c| matches!(c, SyntaxElement::Token(t) if t.text == "}"));
+
+        if block
+            .children()
+            .all(|c| !matches!(c.kind(), SyntaxKind::Comment | SyntaxKind::Statement))
+        {
+            if closed
+            {
+                line.push(SyntaxKind::Separator, "}".into());
+            }
+
+            return;
+        }
+
+        // A comment right after the brace stays on its line
+        if let Some(SyntaxElement::Token(token)) =
+            children.next_if(|c| c.kind() == SyntaxKind::Comment)
+        {
+            line.pieces.push(Piece::comment(token.text, false));
+        }
+
+        self.emit(mem::take(line), indent);
+
+        self.indent += 1;
+        self.list(children.filter(|c| !matches!(c, SyntaxElement::Token(t) if t.text == "}")));
+        self.indent -= 1;
+
+        if closed
+        {
+            line.push(SyntaxKind::Separator, "}".into());
+        }
+    }
+
+    #[inline]
+    fn emit(&mut self, mut line: Line, indent: usize)
+    {
+        line.mark_types();
+        self.wrap(&line.pieces, indent);
+    }
+
+    /// Writes `pieces`, breaking them into several lines if they don't fit into one or a comment is left within them. Lists are broken
+    /// into one element per line, and expressions after their loosest binary operators.
+    fn wrap(&mut self, pieces: &[Piece], indent: usize)
+    {
+        // Comments of their own go before whatever they document
+        let (comments, pieces) = pieces.split_at(
+            pieces
+                .iter()
+                .take_while(|p| p.kind == SyntaxKind::Comment)
+                .count(),
+        );
+
+        for comment in comments
+        {
+            self.write(slice::from_ref(comment), indent);
+        }
+
+        let Some((_, within)) = pieces.split_last()
+        else
+        {
+            return;
+        };
+
+        let comment = within.iter().position(|p| p.kind == SyntaxKind::Comment);
+
+        if comment.is_none()
+            && indent * self.settings.tab_spaces + join(pieces).len() <= self.settings.max_width
+        {
+            return self.write(pieces, indent);
+        }
+
+        let groups = groups(pieces);
+        let operators = operators(pieces);
+
+        let Some(comment) = comment
+        else
+        {
+            if !operators.is_empty()
+            {
+                return self.operands(pieces, &operators, indent);
+            }
+
+            return match groups.iter().find(|(open, close)| *close > open + 1)
+            {
+                Some(&group) => self.group(pieces, group, indent),
+                None => self.write(pieces, indent),
+            };
+        };
+
+        if let Some(&group) = groups
+            .iter()
+            .find(|(open, close)| (open + 1..*close).contains(&comment))
+        {
+            return self.group(pieces, group, indent);
+        }
+
+        if !operators.is_empty()
+        {
+            return self.operands(pieces, &operators, indent);
+        }
+
+        // Otherwise the line is left in the comment, and whatever follows it carries on in the next one
+        let end = comment + usize::from(pieces[comment].trails());
+
+        self.write(&pieces[..end], indent);
+        self.wrap(
+            &pieces[end..],
+            indent + usize::from(depth(&pieces[..end]) > 0),
+        );
+    }
+
+    /// Writes the list between the pieces `open` and `close`, one element per line
+    fn group(&mut self, pieces: &[Piece], (open, close): (usize, usize), indent: usize)
+    {
+        // A comment right after the opening stays on its line
+        let start = open + 1 + usize::from(pieces[open + 1].trails());
+
+        self.write(&pieces[..start], indent);
+
+        let mut depth = 0usize;
+        let mut elements = vec![Vec::new()];
+        let mut separated = false;
+
+        for piece in &pieces[start..close]
+        {
+            // As does one right after a comma
+            if separated && !piece.trails()
+            {
+                elements.push(Vec::new());
+                separated = false;
+            }
+
+            match piece.text.as_str()
+            {
+                "(" | "[" => depth += 1,
+                ")" | "]" => depth -= 1,
+                "," if depth == 0 => separated = true,
+                _ => (),
+            }
+
+            elements.last_mut().unwrap().push(piece.clone());
+        }
+
+        for element in elements
+        {
+            self.wrap(&element, indent + 1);
+        }
+
+        self.wrap(&pieces[close..], indent);
+    }
+
+    /// Writes the operands of the binary `operators`, each after the first one on a line of its own
+    fn operands(&mut self, pieces: &[Piece], operators: &[usize], indent: usize)
+    {
+        let mut start = 0;
+
+        for &operator in operators
+        {
+            // A comment right after the operator stays on its line
+            let end = operator + 1 + usize::from(pieces[operator + 1].trails());
+
+            self.wrap(&pieces[start..end], indent + usize::from(start > 0));
+            start = end;
+        }
+
+        self.wrap(&pieces[start..], indent + 1);
+    }
+
+    fn write(&mut self, pieces: &[Piece], indent: usize)
+    {
+        self.out
+            .extend(std::iter::repeat_n(' ', indent * self.settings.tab_spaces));
+        self.out.push_str(&join(pieces));
+        self.out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[inline]
+    fn fmt(source: &str) -> String
+    {
+        format(source, &Settings::default()).unwrap()
+    }
+
+    #[test]
+    fn respaces_tokens()
+    {
+        assert_eq!(
+            fmt(
+                "extern   func putd(d i32,...)\nconst MAGIC_STR   [11]rune=\"Hello world\"\nfunc op(a i32,b &mut i32)i32{\n    let mut c i32=(a*  b)\n  let d=-(-42+2)\n\tret op(c+d +a*b,-b)\n}\n"
+            ),
+            "extern func putd(d i32, ...)\nconst MAGIC_STR [11]rune = \"Hello world\"\nfunc op(a i32, b &mut i32) i32 {\n    let mut c i32 = (a * b)\n    let d = -(-42 + 2)\n    ret op(c + d + a * b, -b)\n}\n"
+        );
+    }
+
+    #[test]
+    fn respaces_imports()
+    {
+        assert_eq!(
+            fmt("import   math\n\nimport  std . io\nfunc main() {}\n"),
+            "import math\n\nimport std.io\nfunc main() {}\n"
+        );
+    }
+
+    #[test]
+    fn spaces_function_pointer_types()
+    {
+        assert_eq!(
+            fmt(
+                "func apply(f func  (i32,&mut i32)i32, v i32) func(i32) {\n    let g func( i32 )=show\n    ret g\n}\n"
+            ),
+            "func apply(f func(i32, &mut i32) i32, v i32) func(i32) {\n    let g func(i32) = show\n    ret g\n}\n"
+        );
+    }
+
+    #[test]
+    fn glues_ranges()
+    {
+        assert_eq!(
+            fmt("func f(s []i8) u64 {\n    ret s[1 .. s.len] .len + s[..2][0]\n}\n"),
+            "func f(s []i8) u64 {\n    ret s[1..s.len].len + s[..2][0]\n}\n"
+        );
+    }
+
+    #[test]
+    fn spaces_casts()
+    {
+        assert_eq!(
+            fmt("func f(a i32) i64 {\n    ret (a)as i64*2\n}\n"),
+            "func f(a i32) i64 {\n    ret (a) as i64 * 2\n}\n"
+        );
+    }
+
+    #[test]
+    fn spaces_closures()
+    {
+        assert_eq!(
+            fmt(
+                "func f(g func [ ](i32)i32) {\n    let h = func [ &mut total , n ]( x i32 )i32 { ret x }\n    each(0, 3, func[](n i32) { putd(n) })\n}\n"
+            ),
+            "func f(g func[](i32) i32) {\n    let h = func[&mut total, n](x i32) i32 {\n        ret x\n    }\n    each(0, 3, func[](n i32) {\n        putd(n)\n    })\n}\n"
+        );
+    }
+
+    #[test]
+    fn puts_attributes_on_their_own_lines()
+    {
+        assert_eq!(
+            fmt(
+                "@ cold @link_name( \"put\" ) extern func put(s &i8)\n\n@inline\n\n// Twice\nfunc double(a i32) i32 { ret a*2 }\n"
+            ),
+            "@cold\n@link_name(\"put\")\nextern func put(s &i8)\n\n@inline\n// Twice\nfunc double(a i32) i32 {\n    ret a * 2\n}\n"
+        );
+    }
+
+    #[test]
+    fn places_braces_and_blank_lines()
+    {
+        assert_eq!(
+            fmt(
+                "\n\nfunc f(a i32) i32 {\n\n\n    if a < 2 { ret -a } else { ret a }\n\n\n\n    ret 0\n\n}\nfunc g() {\n}\n\n\n"
+            ),
+            "func f(a i32) i32 {\n    if a < 2 {\n        ret -a\n    } else {\n        ret a\n    }\n\n    ret 0\n}\nfunc g() {}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_comments()
+    {
+        let source = "// Adds them\nfunc add(a i32, b i32) i32 { // Simple\n    // Sum\n    ret a + b // Here\n\n    // Unreachable\n}\n\n// The end\n";
+
+        assert_eq!(fmt(source), source);
+    }
+
+    #[test]
+    fn wraps_long_lists()
+    {
+        let settings = Settings {
+            max_width: 40,
+            ..Settings::default()
+        };
+
+        assert_eq!(
+            format(
+                "func f() {\n    let value = first(second_argument, third(fourth_argument, fifth_argument))\n}\n",
+                &settings
+            )
+            .unwrap(),
+            "func f() {\n    let value = first(\n        second_argument,\n        third(\n            fourth_argument,\n            fifth_argument\n        )\n    )\n}\n"
+        );
+    }
+
+    #[test]
+    fn breaks_lists_around_comments()
+    {
+        assert_eq!(
+            fmt(
+                "const HALVES [2]i32 = [4 / 2, 1 / 0 // Zero\n]\n\nfunc add(a i32, // First\n    b i32) i32 {\n    ret f( // Arguments\n        a,\n        // The second one\n        b)\n}\n"
+            ),
+            "const HALVES [2]i32 = [\n    4 / 2,\n    1 / 0 // Zero\n]\n\nfunc add(\n    a i32, // First\n    b i32\n) i32 {\n    ret f( // Arguments\n        a,\n        // The second one\n        b\n    )\n}\n"
+        );
+    }
+
+    #[test]
+    fn wraps_long_binary_expressions()
+    {
+        let settings = Settings {
+            max_width: 40,
+            ..Settings::default()
+        };
+
+        assert_eq!(
+            format(
+                "func f() {\n    let value = first_operand * 2 + second_operand - third\n    putd(first_operand + second_operand * third_operand)\n    ret a + // Carried\n        b\n}\n",
+                &settings
+            )
+            .unwrap(),
+            "func f() {\n    let value = first_operand * 2 +\n        second_operand -\n        third\n    putd(\n        first_operand +\n            second_operand *\n                third_operand\n    )\n    ret a + // Carried\n        b\n}\n"
+        );
+    }
+
+    /// The sources of the UI tests are all formatted, which also keeps their annotations on the lines they point at
+    #[test]
+    fn keeps_ui_tests()
+    {
+        fn visit(directory: &std::path::Path)
+        {
+            for entry in std::fs::read_dir(directory).unwrap()
+            {
+                let path = entry.unwrap().path();
+
+                if path.is_dir()
+                {
+                    visit(&path);
+                }
+                else if path.extension().is_some_and(|e| e == "su")
+                {
+                    let source = std::fs::read_to_string(&path).unwrap();
+
+                    // Some are meant not to parse
+                    if parse(&source).is_ok()
+                    {
+                        assert_eq!(fmt(&source), source, "{}", path.display());
+                    }
+                }
+            }
+        }
+
+        visit(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/ui"));
+    }
+
+    #[test]
+    fn is_idempotent()
+    {
+        let source = "func main() {\n    let mut a i32 = op(4, 10)\n    swap(a, b)\n    std.println(-42)\n}\n";
+
+        assert_eq!(fmt(source), source);
+        assert_eq!(fmt(&fmt(source)), fmt(source));
+    }
+}
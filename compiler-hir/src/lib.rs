@@ -0,0 +1,362 @@
+#![feature(box_patterns)]
+
+//! High-level IR, the AST with every name resolved to what it refers to by [`resolve`]. Items and the locals of every function
+//! get an id, so later passes look them up by [`DefId`] rather than by name, and syntax which means nothing past parsing, as
+//! parentheses, is gone.
+
+use std::ops::Index;
+
+use compiler_lexer::definitions::{LiteralType, Span};
+use compiler_parser::{
+    CaptureKind, Operator, Type,
+    item::{Attribute, FunctionSignature},
+};
+use ecow::{EcoString, EcoVec};
+use miette::Diagnostic;
+use serde::Serialize;
+use thiserror::Error;
+
+pub use crate::resolve::resolve;
+
+mod resolve;
+
+/// Qualified name of the item `name` declared in `module`, as `a.b.name`. Those of the root module are left as they are
+#[inline]
+pub fn qualify_in(module: &[EcoString], name: &str) -> EcoString
+{
+    module
+        .iter()
+        .map(EcoString::as_str)
+        .chain([name])
+        .collect::<Vec<_>>()
+        .join(".")
+        .into()
+}
+
+/// Index of an item in [`Hir::items`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+pub struct ItemId(pub usize);
+
+/// Index of a local or a parameter in [`Hir::locals`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+pub struct LocalId(pub usize);
+
+/// What a name refers to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+pub enum DefId
+{
+    Local(LocalId),
+    Parameter(LocalId),
+    Const(ItemId),
+    Function(ItemId),
+    Extern(ItemId),
+}
+
+/// Errors pointing at source code. `module` is the path of the module the spans refer to, as on the code generator's
+#[derive(Error, Debug, Diagnostic, PartialEq)]
+pub enum ResolveError
+{
+    #[error("Identifier `{name}` not found")]
+    Undefined
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Not found in this scope")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Module `{name}` isn't imported")]
+    NotImported
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Neither a local, an item nor an imported module")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Duplicate {kind} `{name}`")]
+    Duplicate
+    {
+        kind: &'static str,
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Defined again here")]
+        span: Span,
+        #[label("First defined here")]
+        previous: Span,
+    },
+    /// A module named by the last segment of several imports, as `math` is by `import a.math` and `import b.math`
+    #[error("Module name `{name}` is ambiguous, it could be `{first}` or `{second}`")]
+    #[diagnostic(help("Write out the whole path of the module meant instead"))]
+    AmbiguousImport
+    {
+        name: EcoString,
+        first: EcoString,
+        second: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+        #[label("`{first}` imported here")]
+        first_import: Span,
+        #[label("`{second}` imported here")]
+        second_import: Span,
+    },
+    #[error("Closure doesn't capture `{name}`")]
+    NotCaptured
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+        #[label("Declared here")]
+        declaration: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Can't capture {kind} `{name}`, only locals")]
+    CaptureOfItem
+    {
+        kind: &'static str,
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Captured here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+}
+
+impl ResolveError
+{
+    #[inline]
+    pub fn module(&self) -> &EcoVec<EcoString>
+    {
+        match self
+        {
+            Self::Undefined { module, .. }
+            | Self::NotImported { module, .. }
+            | Self::Duplicate { module, .. }
+            | Self::AmbiguousImport { module, .. }
+            | Self::NotCaptured { module, .. }
+            | Self::CaptureOfItem { module, .. } => module,
+        }
+    }
+}
+
+/// The whole program, every module of it
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Hir
+{
+    /// In dependency order, as given to [`resolve`]
+    pub modules: Vec<Module>,
+    pub items: Vec<Item>,
+    pub locals: Vec<Local>,
+}
+
+impl Index<ItemId> for Hir
+{
+    type Output = Item;
+
+    #[inline]
+    fn index(&self, index: ItemId) -> &Self::Output
+    {
+        &self.items[index.0]
+    }
+}
+
+impl Index<LocalId> for Hir
+{
+    type Output = Local;
+
+    #[inline]
+    fn index(&self, index: LocalId) -> &Self::Output
+    {
+        &self.locals[index.0]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Module
+{
+    pub path: EcoVec<EcoString>,
+    /// Path of the file it was read from, and its contents, for locations in panic messages
+    pub file: EcoString,
+    #[serde(skip)]
+    pub source: EcoString,
+    /// In the order they're declared
+    pub items: Vec<ItemId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Item
+{
+    pub name: EcoString,
+    /// Path of the module it's declared in
+    pub module: EcoVec<EcoString>,
+    /// Of the name
+    pub span: Span,
+    /// Left as parsed, it's up to the code generator what they mean
+    pub attributes: EcoVec<Attribute>,
+    pub kind: ItemKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ItemKind
+{
+    Const
+    {
+        r#type: Option<Type>,
+        value: Expression,
+    },
+    Function
+    {
+        signature: FunctionSignature,
+        /// One for each of the signature's arguments
+        parameters: EcoVec<LocalId>,
+        body: Block,
+    },
+    Extern
+    {
+        signature: FunctionSignature
+    },
+}
+
+/// A local or a parameter, of a function or a closure
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Local
+{
+    pub name: EcoString,
+    pub mutable: bool,
+    /// As declared, `None` if left to be inferred
+    pub r#type: Option<Type>,
+    /// Of the name where it's declared
+    pub span: Span,
+}
+
+pub type Block = EcoVec<Statement>;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Statement
+{
+    Return(Option<Expression>),
+    Expression(Expression),
+    Assign
+    {
+        destination: Expression,
+        source: Expression,
+    },
+    /// Declares `local`, which is only visible to the statements after it in its block
+    Local
+    {
+        local: LocalId,
+        init: Option<Expression>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Node
+{
+    Scalar(Expression),
+    Compound(Box<(Node, Operator, Node)>),
+}
+
+/// What a call goes to
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Callee
+{
+    /// A function, or a local or a constant holding one
+    Def(DefId),
+    /// `assert(condition)`, built in unless something named `assert` is in scope
+    Assert,
+    /// Whatever a callee other than a path evaluates to, as in `get()(3)`
+    Value(Box<Expression>),
+}
+
+/// A local captured by a closure. Within the closure, its name refers to the same [`DefId`] as outside
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Capture
+{
+    pub def: DefId,
+    pub kind: CaptureKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Expression
+{
+    Literal
+    {
+        value: EcoString,
+        r#type: LiteralType,
+        span: Span,
+    },
+    /// A path naming a value, whose span it keeps
+    Def
+    {
+        def: DefId,
+        span: Span,
+    },
+    Binary(Box<Node>),
+    Unary(Operator, Box<Expression>),
+    Cast
+    {
+        target: Box<Expression>,
+        r#type: Type,
+        span: Span,
+    },
+    Call
+    {
+        callee: Callee,
+        args: EcoVec<Expression>,
+        /// Of the path called, or of the `(` if the callee isn't one
+        span: Span,
+    },
+    /// `receiver.name(args)`, calling what `name` refers to with the receiver as its first argument. Paths like `a.b.c(args)`
+    /// end up here too when `a.b` is a value rather than a module
+    Method
+    {
+        receiver: Box<Expression>,
+        callee: DefId,
+        args: EcoVec<Expression>,
+        /// Of the name, or of the whole path
+        span: Span,
+    },
+    /// `target.name`, as on paths like `a.len` where `a` is a value
+    Field
+    {
+        target: Box<Expression>,
+        name: EcoString,
+        span: Span,
+    },
+    Index
+    {
+        target: Box<Expression>,
+        index: Box<Expression>,
+        span: Span,
+    },
+    Slice
+    {
+        target: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+        span: Span,
+    },
+    If
+    {
+        condition: Box<Expression>,
+        block: Block,
+        else_block: Option<Block>,
+    },
+    Tuple(EcoVec<Expression>),
+    Array(EcoVec<Expression>),
+    Closure
+    {
+        captures: EcoVec<Capture>,
+        signature: Box<FunctionSignature>,
+        /// One for each of the signature's arguments
+        parameters: EcoVec<LocalId>,
+        body: Block,
+    },
+}
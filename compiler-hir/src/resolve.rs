@@ -0,0 +1,1175 @@
+//! Name resolution. Items are collected from every module first, so they may be used before they're declared, then the bodies
+//! are lowered in one walk, keeping a stack of scopes: one for the parameters and body of each function or closure, and one
+//! for each block. Locals are only visible after their `let`, and shadow whatever came before them.
+
+use std::collections::HashMap;
+
+use compiler_lexer::definitions::Span;
+use compiler_parser::{
+    self as ast, ArgumentName, Name,
+    item::{FunctionSignature, Item as AstItem},
+};
+use ecow::{EcoString, EcoVec};
+
+use crate::{
+    Block, Callee, Capture, DefId, Expression, Hir, Item, ItemId, ItemKind, Local, LocalId, Module,
+    Node, ResolveError, Statement,
+};
+
+/// Names declared in a block, or in the parameters of a function or closure
+#[derive(Default)]
+struct Scope
+{
+    names: HashMap<EcoString, DefId>,
+    /// Of a closure, so it only sees the locals outside it that it captures
+    closure: bool,
+}
+
+struct Resolver
+{
+    /// Items of each module by name, with the spans of their names
+    namespaces: HashMap<EcoVec<EcoString>, HashMap<EcoString, (DefId, Span)>>,
+    /// Path of the module being lowered
+    module: EcoVec<EcoString>,
+    /// Modules it imports, by their whole path and by their last segment, along with the spans of the imports. Names several
+    /// go by are ambiguous
+    imports: HashMap<EcoString, Vec<(EcoVec<EcoString>, Span)>>,
+    scopes: Vec<Scope>,
+    locals: Vec<Local>,
+    errors: Vec<ResolveError>,
+}
+
+/// What an item of `def` is called in diagnostics
+fn kind_of(def: DefId) -> &'static str
+{
+    match def
+    {
+        DefId::Local(_) => "local",
+        DefId::Parameter(_) => "parameter",
+        DefId::Const(_) => "constant",
+        DefId::Function(_) => "function",
+        DefId::Extern(_) => "extern function",
+    }
+}
+
+/// Stands in for expressions which failed to resolve, never seen as the errors are returned instead
+#[inline]
+fn unresolved() -> Expression
+{
+    Expression::Tuple(EcoVec::new())
+}
+
+/// Resolves every name in `modules`, which must come in dependency order, as the code generator takes them. All the undefined
+/// and duplicate names are reported, rather than only the first
+pub fn resolve(modules: &[ast::Module]) -> Result<Hir, Vec<ResolveError>>
+{
+    let mut resolver = Resolver {
+        namespaces: HashMap::new(),
+        module: EcoVec::new(),
+        imports: HashMap::new(),
+        scopes: Vec::new(),
+        locals: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let mut count = 0;
+    let hir_modules = modules
+        .iter()
+        .map(|m| {
+            let module = resolver.declare_items(m, count);
+            count += module.items.len();
+
+            module
+        })
+        .collect::<Vec<_>>();
+
+    let mut items = Vec::with_capacity(count);
+
+    for module in modules
+    {
+        resolver.module = module.path.clone();
+        resolver.import(module);
+
+        items.extend(module.ast.0.iter().filter_map(|i| resolver.lower_item(i)));
+    }
+
+    if resolver.errors.is_empty()
+    {
+        Ok(Hir {
+            modules: hir_modules,
+            items,
+            locals: resolver.locals,
+        })
+    }
+    else
+    {
+        Err(resolver.errors)
+    }
+}
+
+impl Resolver
+{
+    /// Takes the imports of `module`. The same module imported twice is reported, names of several only once they're used
+    fn import(&mut self, module: &ast::Module)
+    {
+        self.imports.clear();
+
+        let mut imported = HashMap::<&EcoVec<EcoString>, Span>::new();
+
+        for item in &module.ast.0
+        {
+            let AstItem::Import(path, span) = item
+            else
+            {
+                continue;
+            };
+
+            if let Some(&previous) = imported.get(path)
+            {
+                self.errors.push(ResolveError::Duplicate {
+                    kind: "import",
+                    name: path.join(".").into(),
+                    module: self.module.clone(),
+                    span: *span,
+                    previous,
+                });
+
+                continue;
+            }
+
+            imported.insert(path, *span);
+
+            let whole = EcoString::from(path.join("."));
+            let last = path.last().unwrap().clone();
+
+            if last != whole
+            {
+                self.imports
+                    .entry(last)
+                    .or_default()
+                    .push((path.clone(), *span));
+            }
+
+            self.imports
+                .entry(whole)
+                .or_default()
+                .push((path.clone(), *span));
+        }
+    }
+
+    /// Gives an id to each item of `module`, counting up from `first` in the order they're lowered in afterwards
+    fn declare_items(&mut self, module: &ast::Module, first: usize) -> Module
+    {
+        let namespace = self.namespaces.entry(module.path.clone()).or_default();
+
+        let mut items = Vec::new();
+
+        for item in &module.ast.0
+        {
+            let id = ItemId(first + items.len());
+
+            let (def, name, span) = match item
+            {
+                AstItem::Import(..) => continue,
+                AstItem::Const {
+                    name: Name(name, _, span),
+                    ..
+                } => (DefId::Const(id), name, *span),
+                AstItem::Function { signature, .. } =>
+                {
+                    (DefId::Function(id), &signature.name.0, signature.span)
+                }
+                AstItem::Extern { signature, .. } =>
+                {
+                    (DefId::Extern(id), &signature.name.0, signature.span)
+                }
+            };
+
+            items.push(id);
+
+            if let Some(&(_, previous)) = namespace.get(name)
+            {
+                self.errors.push(ResolveError::Duplicate {
+                    kind: "item",
+                    name: name.clone(),
+                    module: module.path.clone(),
+                    span,
+                    previous,
+                });
+            }
+            else
+            {
+                namespace.insert(name.clone(), (def, span));
+            }
+        }
+
+        Module {
+            path: module.path.clone(),
+            file: module.file.clone(),
+            source: module.source.clone(),
+            items,
+        }
+    }
+
+    fn lower_item(&mut self, item: &AstItem) -> Option<Item>
+    {
+        let (name, span, attributes, kind) = match item
+        {
+            AstItem::Import(..) => return None,
+            AstItem::Const {
+                name: Name(name, r#type, span),
+                value,
+                attributes,
+            } => (
+                name,
+                *span,
+                attributes,
+                ItemKind::Const {
+                    r#type: r#type.clone(),
+                    value: self.lower_expression(value),
+                },
+            ),
+            AstItem::Function {
+                signature,
+                body,
+                attributes,
+            } =>
+            {
+                self.scopes.push(Scope::default());
+
+                let parameters = self.declare_parameters(&signature.arguments);
+                let body = self.lower_statements(body);
+
+                self.scopes.pop();
+
+                (
+                    &signature.name.0,
+                    signature.span,
+                    attributes,
+                    ItemKind::Function {
+                        signature: signature.clone(),
+                        parameters,
+                        body,
+                    },
+                )
+            }
+            AstItem::Extern {
+                signature,
+                attributes,
+            } => (
+                &signature.name.0,
+                signature.span,
+                attributes,
+                ItemKind::Extern {
+                    signature: signature.clone(),
+                },
+            ),
+        };
+
+        Some(Item {
+            name: name.clone(),
+            module: self.module.clone(),
+            span,
+            attributes: attributes.clone(),
+            kind,
+        })
+    }
+
+    /// Declares a new local, visible in the innermost scope from then on
+    fn declare(&mut self, local: Local, def: fn(LocalId) -> DefId) -> LocalId
+    {
+        let id = LocalId(self.locals.len());
+        let name = local.name.clone();
+
+        self.locals.push(local);
+        self.scopes
+            .last_mut()
+            .expect("Locals are only declared within a scope")
+            .names
+            .insert(name, def(id));
+
+        id
+    }
+
+    fn declare_parameters(&mut self, arguments: &[ArgumentName]) -> EcoVec<LocalId>
+    {
+        let mut parameters = EcoVec::<LocalId>::new();
+
+        for argument in arguments
+        {
+            if let Some(previous) = parameters
+                .iter()
+                .find(|&&p| self.locals[p.0].name == argument.name)
+            {
+                self.errors.push(ResolveError::Duplicate {
+                    kind: "parameter",
+                    name: argument.name.clone(),
+                    module: self.module.clone(),
+                    span: argument.span,
+                    previous: self.locals[previous.0].span,
+                });
+            }
+
+            parameters.push(self.declare(
+                Local {
+                    name: argument.name.clone(),
+                    mutable: argument.mutable,
+                    r#type: Some(argument.r#type.clone()),
+                    span: argument.span,
+                },
+                DefId::Parameter,
+            ));
+        }
+
+        parameters
+    }
+
+    /// Lowers the statements of a block in a scope of their own
+    fn lower_block(&mut self, block: &[ast::Statement]) -> Block
+    {
+        self.scopes.push(Scope::default());
+
+        let block = self.lower_statements(block);
+
+        self.scopes.pop();
+
+        block
+    }
+
+    fn lower_statements(&mut self, statements: &[ast::Statement]) -> Block
+    {
+        statements.iter().map(|s| self.lower_statement(s)).collect()
+    }
+
+    fn lower_statement(&mut self, statement: &ast::Statement) -> Statement
+    {
+        match statement
+        {
+            ast::Statement::Return(value) =>
+            {
+                Statement::Return(value.as_ref().map(|v| self.lower_expression(v)))
+            }
+            ast::Statement::Expression(expression) =>
+            {
+                Statement::Expression(self.lower_expression(expression))
+            }
+            ast::Statement::Assign {
+                destination,
+                source,
+            } => Statement::Assign {
+                destination: self.lower_expression(destination),
+                source: self.lower_expression(source),
+            },
+            ast::Statement::Local {
+                mutable,
+                name: Name(name, r#type, span),
+                init,
+            } =>
+            {
+                // Before it's declared, so `let a = a` reads whichever `a` came before
+                let init = init.as_ref().map(|i| self.lower_expression(i));
+
+                let local = self.declare(
+                    Local {
+                        name: name.clone(),
+                        mutable: *mutable,
+                        r#type: r#type.clone(),
+                        span: *span,
+                    },
+                    DefId::Local,
+                );
+
+                Statement::Local { local, init }
+            }
+        }
+    }
+
+    fn lower_node(&mut self, node: &ast::Node) -> Node
+    {
+        match node
+        {
+            ast::Node::Scalar(e) => Node::Scalar(self.lower_expression(e)),
+            ast::Node::Compound(box (lhs, op, rhs)) =>
+            {
+                Node::Compound(Box::new((self.lower_node(lhs), *op, self.lower_node(rhs))))
+            }
+        }
+    }
+
+    fn lower_expressions(&mut self, expressions: &[ast::Expression]) -> EcoVec<Expression>
+    {
+        expressions
+            .iter()
+            .map(|e| self.lower_expression(e))
+            .collect()
+    }
+
+    fn lower_expression(&mut self, expression: &ast::Expression) -> Expression
+    {
+        match expression
+        {
+            ast::Expression::Literal {
+                value,
+                r#type,
+                span,
+            } => Expression::Literal {
+                value: value.clone(),
+                r#type: *r#type,
+                span: *span,
+            },
+            ast::Expression::Path { path, span } => match self.find_path(path, *span)
+            {
+                Ok((def, used)) => Self::with_fields(def, &path[used..], *span),
+                Err(error) =>
+                {
+                    self.errors.push(error);
+                    unresolved()
+                }
+            },
+            ast::Expression::Binary(box node) =>
+            {
+                Expression::Binary(Box::new(self.lower_node(node)))
+            }
+            ast::Expression::Unary(op, box e) =>
+            {
+                Expression::Unary(*op, Box::new(self.lower_expression(e)))
+            }
+            ast::Expression::Cast {
+                box target,
+                r#type,
+                span,
+            } => Expression::Cast {
+                target: Box::new(self.lower_expression(target)),
+                r#type: r#type.clone(),
+                span: *span,
+            },
+            ast::Expression::Call {
+                box callee,
+                args,
+                span,
+            } => match callee
+            {
+                ast::Expression::Path { path, span } => self.lower_call(path, args, *span),
+                callee => Expression::Call {
+                    callee: Callee::Value(Box::new(self.lower_expression(callee))),
+                    args: self.lower_expressions(args),
+                    span: *span,
+                },
+            },
+            ast::Expression::Method {
+                box receiver,
+                name,
+                args,
+                span,
+            } =>
+            {
+                let receiver = self.lower_expression(receiver);
+                let args = self.lower_expressions(args);
+
+                match self.find_name(name, *span)
+                {
+                    Ok(callee) => Expression::Method {
+                        receiver: Box::new(receiver),
+                        callee,
+                        args,
+                        span: *span,
+                    },
+                    Err(error) =>
+                    {
+                        self.errors.push(error);
+                        unresolved()
+                    }
+                }
+            }
+            ast::Expression::Field {
+                box target,
+                name,
+                span,
+            } => Expression::Field {
+                target: Box::new(self.lower_expression(target)),
+                name: name.clone(),
+                span: *span,
+            },
+            ast::Expression::Index {
+                box target,
+                box index,
+                span,
+            } => Expression::Index {
+                target: Box::new(self.lower_expression(target)),
+                index: Box::new(self.lower_expression(index)),
+                span: *span,
+            },
+            ast::Expression::Slice {
+                box target,
+                start,
+                end,
+                span,
+            } => Expression::Slice {
+                target: Box::new(self.lower_expression(target)),
+                start: start.as_ref().map(|e| Box::new(self.lower_expression(e))),
+                end: end.as_ref().map(|e| Box::new(self.lower_expression(e))),
+                span: *span,
+            },
+            ast::Expression::If {
+                box condition,
+                block,
+                else_block,
+            } => Expression::If {
+                condition: Box::new(self.lower_expression(condition)),
+                block: self.lower_block(block),
+                else_block: else_block.as_ref().map(|b| self.lower_block(b)),
+            },
+            ast::Expression::Parenthesis(box e) => self.lower_expression(e),
+            ast::Expression::Tuple(elements) => Expression::Tuple(self.lower_expressions(elements)),
+            ast::Expression::Array(elements) => Expression::Array(self.lower_expressions(elements)),
+            ast::Expression::Closure {
+                captures,
+                signature,
+                body,
+            } => self.lower_closure(captures, signature, body),
+        }
+    }
+
+    /// `def` followed by reading each of `fields` in turn, as in `a.b.c` where `a` is a value
+    fn with_fields(def: DefId, fields: &[EcoString], span: Span) -> Expression
+    {
+        fields
+            .iter()
+            .fold(Expression::Def { def, span }, |target, name| {
+                Expression::Field {
+                    target: Box::new(target),
+                    name: name.clone(),
+                    span,
+                }
+            })
+    }
+
+    /// Calls of paths which name a value followed by fields, `a.b.c(args)`, are method calls on `a.b`
+    fn lower_call(&mut self, path: &[EcoString], args: &[ast::Expression], span: Span)
+    -> Expression
+    {
+        let args = self.lower_expressions(args);
+
+        let (def, used) = match self.find_path(path, span)
+        {
+            Ok(found) => found,
+            Err(_) if path == ["assert"] =>
+            {
+                return Expression::Call {
+                    callee: Callee::Assert,
+                    args,
+                    span,
+                };
+            }
+            Err(error) =>
+            {
+                self.errors.push(error);
+                return unresolved();
+            }
+        };
+
+        let Some((name, fields)) = path[used..].split_last()
+        else
+        {
+            return Expression::Call {
+                callee: Callee::Def(def),
+                args,
+                span,
+            };
+        };
+
+        match self.find_name(name, span)
+        {
+            Ok(callee) => Expression::Method {
+                receiver: Box::new(Self::with_fields(def, fields, span)),
+                callee,
+                args,
+                span,
+            },
+            Err(error) =>
+            {
+                self.errors.push(error);
+                unresolved()
+            }
+        }
+    }
+
+    fn lower_closure(
+        &mut self,
+        captures: &[ast::Capture],
+        signature: &FunctionSignature,
+        body: &[ast::Statement],
+    ) -> Expression
+    {
+        let mut scope = Scope {
+            names: HashMap::new(),
+            closure: true,
+        };
+        let mut resolved = EcoVec::<Capture>::new();
+
+        for capture in captures
+        {
+            if let Some(previous) = captures
+                .iter()
+                .take_while(|c| !std::ptr::eq(*c, capture))
+                .find(|c| c.name == capture.name)
+            {
+                self.errors.push(ResolveError::Duplicate {
+                    kind: "capture",
+                    name: capture.name.clone(),
+                    module: self.module.clone(),
+                    span: capture.span,
+                    previous: previous.span,
+                });
+                continue;
+            }
+
+            let def = match self.find_scoped(&capture.name)
+            {
+                Some(Ok(def)) => def,
+                Some(Err(local)) =>
+                {
+                    self.errors
+                        .push(self.not_captured(&capture.name, capture.span, local));
+                    continue;
+                }
+                None =>
+                {
+                    self.errors
+                        .push(match self.find_item(&self.module, &capture.name)
+                        {
+                            Some(def) => ResolveError::CaptureOfItem {
+                                kind: kind_of(def),
+                                name: capture.name.clone(),
+                                module: self.module.clone(),
+                                span: capture.span,
+                                help: Some(
+                                    "Items are visible within closures without capturing them"
+                                        .into(),
+                                ),
+                            },
+                            None => ResolveError::Undefined {
+                                name: capture.name.clone(),
+                                module: self.module.clone(),
+                                span: capture.span,
+                                help: None,
+                            },
+                        });
+                    continue;
+                }
+            };
+
+            scope.names.insert(capture.name.clone(), def);
+            resolved.push(Capture {
+                def,
+                kind: capture.kind,
+                span: capture.span,
+            });
+        }
+
+        self.scopes.push(scope);
+
+        let parameters = self.declare_parameters(&signature.arguments);
+        let body = self.lower_statements(body);
+
+        self.scopes.pop();
+
+        Expression::Closure {
+            captures: resolved,
+            signature: Box::new(signature.clone()),
+            parameters,
+            body,
+        }
+    }
+
+    /// Looks `name` up among the locals in scope, innermost first. Those outside a closure the lookup started in are `Err`, as
+    /// the closure didn't capture them
+    fn find_scoped(&self, name: &str) -> Option<Result<DefId, LocalId>>
+    {
+        let mut crossed = false;
+
+        for scope in self.scopes.iter().rev()
+        {
+            if let Some(&def) = scope.names.get(name)
+            {
+                return Some(match def
+                {
+                    DefId::Local(local) | DefId::Parameter(local) if crossed => Err(local),
+                    _ => Ok(def),
+                });
+            }
+
+            crossed |= scope.closure;
+        }
+
+        None
+    }
+
+    #[inline]
+    fn find_item(&self, module: &EcoVec<EcoString>, name: &str) -> Option<DefId>
+    {
+        self.namespaces
+            .get(module)
+            .and_then(|n| n.get(name))
+            .map(|&(def, _)| def)
+    }
+
+    fn not_captured(&self, name: &EcoString, span: Span, local: LocalId) -> ResolveError
+    {
+        ResolveError::NotCaptured {
+            name: name.clone(),
+            module: self.module.clone(),
+            span,
+            declaration: self.locals[local.0].span,
+            help: Some(format!("Capture it, as in `func[{name}]`")),
+        }
+    }
+
+    /// Resolves a single name, as methods are called by: a local in scope or an item of the current module
+    fn find_name(&self, name: &EcoString, span: Span) -> Result<DefId, ResolveError>
+    {
+        self.find_path(std::slice::from_ref(name), span)
+            .map(|(def, _)| def)
+    }
+
+    /// Resolves the start of `path`, returning what it names and how many segments that took. The rest are fields of it.
+    /// Locals come first, then imported modules, longest first, then items of the current module
+    fn find_path(&self, path: &[EcoString], span: Span) -> Result<(DefId, usize), ResolveError>
+    {
+        let Some(first) = path.first()
+        else
+        {
+            return Err(ResolveError::Undefined {
+                name: EcoString::new(),
+                module: self.module.clone(),
+                span,
+                help: None,
+            });
+        };
+
+        let scoped = self.find_scoped(first);
+
+        if let Some(Ok(def)) = scoped
+        {
+            return Ok((def, 1));
+        }
+
+        for used in (1..path.len()).rev()
+        {
+            let prefix = path[..used].join(".");
+
+            let Some(imported) = self.imports.get(prefix.as_str())
+            else
+            {
+                continue;
+            };
+
+            if let [(first, first_import), (second, second_import), ..] = imported.as_slice()
+            {
+                return Err(ResolveError::AmbiguousImport {
+                    name: prefix.as_str().into(),
+                    first: first.join(".").into(),
+                    second: second.join(".").into(),
+                    module: self.module.clone(),
+                    span,
+                    first_import: *first_import,
+                    second_import: *second_import,
+                });
+            }
+
+            let module = &imported[0].0;
+
+            return match self.find_item(module, &path[used])
+            {
+                Some(def) => Ok((def, used + 1)),
+                None => Err(ResolveError::Undefined {
+                    name: path[..=used].join(".").into(),
+                    module: self.module.clone(),
+                    span,
+                    help: Some(format!("Module `{prefix}` has no item `{}`", path[used])),
+                }),
+            };
+        }
+
+        if let Some(def) = self.find_item(&self.module, first)
+        {
+            return Ok((def, 1));
+        }
+
+        Err(match scoped
+        {
+            Some(Err(local)) => self.not_captured(first, span, local),
+            _ if path.len() == 1 => ResolveError::Undefined {
+                name: first.clone(),
+                module: self.module.clone(),
+                span,
+                help: None,
+            },
+            _ =>
+            {
+                let prefix = path[..path.len() - 1].join(".");
+
+                ResolveError::NotImported {
+                    help: Some(format!("Import it with `import {prefix}`")),
+                    name: prefix.into(),
+                    module: self.module.clone(),
+                    span,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn module(path: &[&str], source: &str) -> ast::Module
+    {
+        let tokens = compiler_parser::lex(source).unwrap();
+
+        ast::Module {
+            path: path.iter().map(|&s| s.into()).collect(),
+            file: "test.su".into(),
+            source: source.into(),
+            ast: compiler_parser::parse(source, tokens.into_iter()).unwrap(),
+        }
+    }
+
+    /// The body of the function `name`
+    fn body<'h>(hir: &'h Hir, name: &str) -> &'h Block
+    {
+        hir.items
+            .iter()
+            .find_map(|i| match &i.kind
+            {
+                ItemKind::Function { body, .. } if i.name == name => Some(body),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn errors(modules: &[ast::Module]) -> Vec<String>
+    {
+        resolve(modules)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn resolves_locals_parameters_and_items()
+    {
+        let hir = resolve(&[module(
+            &[],
+            "const N = 2\nfunc f(a i32) i32 {\n    let b = a\n    ret f(b + N)\n}\n",
+        )])
+        .unwrap();
+
+        let a = LocalId(0);
+        let b = LocalId(1);
+
+        assert_eq!(hir.modules[0].items, vec![ItemId(0), ItemId(1)]);
+        assert_eq!(hir[a].name, "a");
+        assert_eq!(hir[b].span, Span::new(40, 41));
+        assert_eq!(
+            body(&hir, "f").as_slice(),
+            [
+                Statement::Local {
+                    local: b,
+                    init: Some(Expression::Def {
+                        def: DefId::Parameter(a),
+                        span: Span::new(44, 45),
+                    }),
+                },
+                Statement::Return(Some(Expression::Call {
+                    callee: Callee::Def(DefId::Function(ItemId(1))),
+                    args: [Expression::Binary(Box::new(Node::Compound(Box::new((
+                        Node::Scalar(Expression::Def {
+                            def: DefId::Local(b),
+                            span: Span::new(56, 57),
+                        }),
+                        ast::Operator::Plus,
+                        Node::Scalar(Expression::Def {
+                            def: DefId::Const(ItemId(0)),
+                            span: Span::new(60, 61),
+                        }),
+                    )))))]
+                    .into(),
+                    span: Span::new(54, 55),
+                })),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_imports_methods_and_fields()
+    {
+        let hir = resolve(&[
+            module(&["lib", "math"], "const ONE = 1\nfunc add(a i32, b i32) i32 {\n    ret a + b\n}\n"),
+            module(
+                &[],
+                "import lib.math\nfunc main() {\n    let s = [1, 2]\n    math.add(lib.math.ONE, s.len)\n    s.len.neg()\n    assert(1 == 1)\n}\nfunc neg(a i32) i32 {\n    ret -a\n}\n",
+            ),
+        ])
+        .unwrap();
+
+        let s = |span| Expression::Def {
+            def: DefId::Local(LocalId(2)),
+            span,
+        };
+        let len = |span| Expression::Field {
+            target: Box::new(s(span)),
+            name: "len".into(),
+            span,
+        };
+
+        assert_eq!(
+            body(&hir, "main")[1..],
+            [
+                Statement::Expression(Expression::Call {
+                    callee: Callee::Def(DefId::Function(ItemId(1))),
+                    args: [
+                        Expression::Def {
+                            def: DefId::Const(ItemId(0)),
+                            span: Span::new(62, 74),
+                        },
+                        len(Span::new(76, 81)),
+                    ]
+                    .into(),
+                    span: Span::new(53, 61),
+                }),
+                Statement::Expression(Expression::Method {
+                    receiver: Box::new(len(Span::new(87, 96))),
+                    callee: DefId::Function(ItemId(3)),
+                    args: EcoVec::new(),
+                    span: Span::new(87, 96),
+                }),
+                Statement::Expression(Expression::Call {
+                    callee: Callee::Assert,
+                    args: [Expression::Binary(Box::new(Node::Compound(Box::new((
+                        Node::Scalar(Expression::Literal {
+                            value: "1".into(),
+                            r#type: compiler_lexer::definitions::LiteralType::Int,
+                            span: Span::single(110),
+                        }),
+                        ast::Operator::EqEq,
+                        Node::Scalar(Expression::Literal {
+                            value: "1".into(),
+                            r#type: compiler_lexer::definitions::LiteralType::Int,
+                            span: Span::single(115),
+                        }),
+                    )))))]
+                    .into(),
+                    span: Span::new(103, 109),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_undefined_names()
+    {
+        assert_eq!(
+            errors(&[
+                module(&["math"], "func add() {}\n"),
+                module(
+                    &[],
+                    "import math\nfunc main() {\n    a\n    math.sub()\n    other.f()\n    s.len\n}\n",
+                ),
+            ]),
+            [
+                "Identifier `a` not found",
+                "Identifier `math.sub` not found",
+                "Module `other` isn't imported",
+                "Module `s` isn't imported",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicates()
+    {
+        let errors = resolve(&[module(
+            &[],
+            "func f(a i32, a i32) {}\nconst f = 1\nfunc main() {\n    let a = 1\n    let g = func[a, a]() {}\n}\n",
+        )])
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                ResolveError::Duplicate {
+                    kind: "item",
+                    name: "f".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(30, 31),
+                    previous: Span::new(5, 6),
+                },
+                ResolveError::Duplicate {
+                    kind: "parameter",
+                    name: "a".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(14, 15),
+                    previous: Span::new(7, 8),
+                },
+                ResolveError::Duplicate {
+                    kind: "capture",
+                    name: "a".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(84, 85),
+                    previous: Span::new(81, 82),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_ambiguous_imports()
+    {
+        let errors = resolve(&[
+            module(&["a", "math"], "func add() {}\n"),
+            module(&["b", "math"], "func add() {}\n"),
+            module(
+                &[],
+                "import a.math\nimport b.math\nimport a.math\nfunc main() {\n    math.add()\n    b.math.add()\n}\n",
+            ),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            [
+                ResolveError::Duplicate {
+                    kind: "import",
+                    name: "a.math".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(35, 41),
+                    previous: Span::new(7, 13),
+                },
+                ResolveError::AmbiguousImport {
+                    name: "math".into(),
+                    first: "a.math".into(),
+                    second: "b.math".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(60, 68),
+                    first_import: Span::new(7, 13),
+                    second_import: Span::new(21, 27),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn scopes_locals_to_their_block()
+    {
+        assert_eq!(
+            errors(&[module(
+                &[],
+                "func main() {\n    let a = a\n    if 1 == 1 {\n        let b = 1\n    }\n    b\n}\n",
+            )]),
+            ["Identifier `a` not found", "Identifier `b` not found"]
+        );
+
+        // Shadowing ones get locals of their own
+        let hir = resolve(&[module(
+            &[],
+            "func main() {\n    let a = 1\n    let a = a\n}\n",
+        )])
+        .unwrap();
+
+        assert_eq!(
+            body(&hir, "main")[1],
+            Statement::Local {
+                local: LocalId(1),
+                init: Some(Expression::Def {
+                    def: DefId::Local(LocalId(0)),
+                    span: Span::new(40, 41),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn closures_only_see_what_they_capture()
+    {
+        let source = "func f() {}\nfunc main() {\n    let a = 1\n    let b = 2\n    let c = func[&a](x i32) {\n        a + x + b\n        f()\n    }\n    let d = func[f, e]() {}\n}\n";
+
+        assert_eq!(
+            resolve(&[module(&[], source)]).unwrap_err(),
+            [
+                ResolveError::NotCaptured {
+                    name: "b".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(100, 101),
+                    declaration: Span::new(48, 49),
+                    help: Some("Capture it, as in `func[b]`".into()),
+                },
+                ResolveError::CaptureOfItem {
+                    kind: "function",
+                    name: "f".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(137, 138),
+                    help: Some("Items are visible within closures without capturing them".into()),
+                },
+                ResolveError::Undefined {
+                    name: "e".into(),
+                    module: EcoVec::new(),
+                    span: Span::new(140, 141),
+                    help: None,
+                },
+            ]
+        );
+
+        let hir = resolve(&[module(
+            &[],
+            "func main() {\n    let a = 1\n    let c = func[&a](x i32) i32 {\n        ret a + x\n    }\n}\n",
+        )])
+        .unwrap();
+
+        let Statement::Local {
+            init:
+                Some(Expression::Closure {
+                    captures,
+                    parameters,
+                    body,
+                    ..
+                }),
+            ..
+        } = &body(&hir, "main")[1]
+        else
+        {
+            panic!("Expected a closure");
+        };
+
+        assert_eq!(
+            captures.as_slice(),
+            [Capture {
+                def: DefId::Local(LocalId(0)),
+                kind: ast::CaptureKind::Ref,
+                span: Span::new(46, 47),
+            }]
+        );
+        assert_eq!(parameters.as_slice(), [LocalId(1)]);
+        assert_eq!(
+            body[0],
+            Statement::Return(Some(Expression::Binary(Box::new(Node::Compound(
+                Box::new((
+                    Node::Scalar(Expression::Def {
+                        def: DefId::Local(LocalId(0)),
+                        span: Span::new(74, 75),
+                    }),
+                    ast::Operator::Plus,
+                    Node::Scalar(Expression::Def {
+                        def: DefId::Parameter(LocalId(1)),
+                        span: Span::new(78, 79),
+                    }),
+                ))
+            )))))
+        );
+    }
+}
@@ -0,0 +1,651 @@
+use std::{io::Write, rc::Rc};
+
+use anyhow::{Context, Result, bail, ensure};
+use compiler_parser::Operator;
+use compiler_typeck::{
+    BindingKind, Callee, Capture, Expression, ExpressionKind, LENGTH, Literal, LocalId, Type,
+};
+use ecow::EcoVec;
+
+use crate::{
+    Frame, Interpreter, returned,
+    value::{Closure, Place, Value, slot},
+};
+
+/// Narrower floats round each value to their precision
+#[inline]
+fn round(value: f64, width: u32) -> f64
+{
+    if width == 32
+    {
+        value as f32 as f64
+    }
+    else
+    {
+        value
+    }
+}
+
+/// Converts `value` to `to` as [`ExpressionKind::Coerce`] does, by borrowing it, reading it from behind a reference, taking a
+/// `&mut` as a `&` or an array as a slice
+pub fn coerce(value: Value, to: &Type) -> Result<Value>
+{
+    let from = value.r#type();
+
+    Ok(match (value, to)
+    {
+        (value, to) if &from == to => value,
+        (Value::MutRef { referent, place }, Type::Ref(box to)) if &referent == to =>
+        {
+            Value::Ref { referent, place }
+        }
+        // Arrays are borrowed as slices of all their items
+        (
+            Value::Ref {
+                referent: Type::Array { box scalar, size },
+                place,
+            }
+            | Value::MutRef {
+                referent: Type::Array { box scalar, size },
+                place,
+            },
+            Type::Slice(_),
+        ) => Value::Slice {
+            scalar,
+            place,
+            start: 0,
+            len: size as usize,
+        },
+        // Values borrowed get a temporary, so there's something to point into
+        (value @ Value::Array { .. }, Type::Slice(_)) => coerce(
+            Value::Ref {
+                referent: from,
+                place: slot(Some(value)).into(),
+            },
+            to,
+        )?,
+        (value, Type::Ref(box referent)) if &from == referent => Value::Ref {
+            referent: referent.clone(),
+            place: slot(Some(value)).into(),
+        },
+        (value, Type::MutRef(box referent)) if &from == referent => Value::MutRef {
+            referent: referent.clone(),
+            place: slot(Some(value)).into(),
+        },
+        (value @ (Value::Ref { .. } | Value::MutRef { .. }), to) if from.referent() == to =>
+        {
+            value.load()?
+        }
+        (_, to) => bail!("Can't convert `{from}` to `{to}`"),
+    })
+}
+
+/// `value as to`, converted as codegen does. Floats out of the range of the integer type they're cast to saturate to its
+/// smallest or largest value, NaN to 0
+fn cast(value: Value, to: &Type) -> Result<Value>
+{
+    Ok(match (value, to)
+    {
+        (value, to) if value.r#type() == *to => value,
+        (Value::Integer { value, .. }, Type::Integer { .. }) => Value::integer(value, to)?,
+        (Value::Integer { value, .. }, &Type::Float(width)) => Value::Float {
+            value: round(value as f64, width),
+            width,
+        },
+        // Rust's own casts saturate the same way, `u128`s being kept as the `i128`s of the same bits
+        (Value::Float { value, .. }, &Type::Integer { width, signed }) => Value::integer(
+            if signed
+            {
+                (value as i128).clamp(i128::MIN >> (128 - width), i128::MAX >> (128 - width))
+            }
+            else
+            {
+                (value as u128).min(u128::MAX >> (128 - width)) as i128
+            },
+            to,
+        )?,
+        (Value::Float { value, .. }, &Type::Float(width)) => Value::Float {
+            value: round(value, width),
+            width,
+        },
+        (value, to) => bail!(
+            "Only numbers are cast with `as`, can't cast `{}` to `{to}`",
+            value.r#type()
+        ),
+    })
+}
+
+fn negate(value: Value) -> Result<Value>
+{
+    Ok(match value.load()?
+    {
+        Value::Integer {
+            value,
+            width,
+            signed,
+        } => Value::integer(value.wrapping_neg(), &Type::Integer { width, signed })?,
+        Value::Float { value, width } => Value::Float {
+            value: -value,
+            width,
+        },
+        value => bail!("Can't negate a value of type `{}`", value.r#type()),
+    })
+}
+
+/// Whether `value` is a condition which holds
+pub fn holds(value: Value) -> Result<bool>
+{
+    match value.load()?
+    {
+        Value::Integer { value, .. } => Ok(value != 0),
+        value => bail!("Expected condition, got `{}`", value.r#type()),
+    }
+}
+
+fn binary_integer(op: Operator, (l, r): (i128, i128), r#type: &Type) -> Result<Value>
+{
+    let &Type::Integer { width, signed } = r#type
+    else
+    {
+        unreachable!()
+    };
+
+    let bool = |b: bool| {
+        Value::integer(
+            b.into(),
+            &Type::Integer {
+                width: 1,
+                signed: false,
+            },
+        )
+    };
+
+    let value = match op
+    {
+        Operator::Plus => l.wrapping_add(r),
+        Operator::Minus => l.wrapping_sub(r),
+        Operator::Star => l.wrapping_mul(r),
+        Operator::Div =>
+        {
+            ensure!(r != 0, "Division by zero");
+
+            l.wrapping_div(r)
+        }
+        Operator::And | Operator::BitAnd => l & r,
+        Operator::Or | Operator::BitOr => l | r,
+        Operator::Xor => l ^ r,
+        Operator::Shl | Operator::Shr =>
+        {
+            ensure!(
+                (0..width as i128).contains(&r),
+                "Shift by {r} overflows `{type}`"
+            );
+
+            if op == Operator::Shl
+            {
+                l << r
+            }
+            else
+            {
+                // Arithmetic on signed values, logical on the rest
+                l >> r
+            }
+        }
+        // Values are kept wrapped to their type, so they compare as signed or unsigned already
+        Operator::Lt => return bool(l < r),
+        Operator::Gt => return bool(l > r),
+        Operator::Le => return bool(l <= r),
+        Operator::Ge => return bool(l >= r),
+        Operator::EqEq => return bool(l == r),
+        Operator::Neq => return bool(l != r),
+    };
+
+    Value::integer(value, &Type::Integer { width, signed })
+}
+
+fn binary_float(op: Operator, (l, r): (f64, f64), width: u32) -> Result<Value>
+{
+    let bool = |b: bool| {
+        Value::integer(
+            b.into(),
+            &Type::Integer {
+                width: 1,
+                signed: false,
+            },
+        )
+    };
+
+    let value = match op
+    {
+        Operator::Plus => l + r,
+        Operator::Minus => l - r,
+        Operator::Star => l * r,
+        Operator::Div => l / r,
+        Operator::Lt => return bool(l < r),
+        Operator::Gt => return bool(l > r),
+        Operator::Le => return bool(l <= r),
+        Operator::Ge => return bool(l >= r),
+        Operator::EqEq => return bool(l == r),
+        Operator::Neq => return bool(l != r),
+        op => bail!(
+            "Operator `{op}` can't be applied to `{}`",
+            Type::Float(width)
+        ),
+    };
+
+    // Narrower floats are rounded on every operation
+    Ok(Value::Float {
+        value: round(value, width),
+        width,
+    })
+}
+
+impl<W: Write> Interpreter<W>
+{
+    /// Evaluates `expression` into a value of its type. Places, as locals or indexed items, are `&mut` or `&` references to them
+    pub fn eval(&mut self, frame: &mut Frame, expression: &Expression) -> Result<Value>
+    {
+        Ok(match &expression.kind
+        {
+            ExpressionKind::Literal(literal) => match (literal, &expression.r#type)
+            {
+                (&Literal::Integer(value), r#type) => Value::integer(value as i128, r#type)?,
+                (&Literal::Float(value), &Type::Float(width)) => Value::Float {
+                    value: round(value, width),
+                    width,
+                },
+                (Literal::String(contents), _) => Value::Array {
+                    scalar: Type::Integer {
+                        width: 8,
+                        signed: true,
+                    },
+                    items: contents
+                        .bytes()
+                        .map(|b| Value::Integer {
+                            value: b as i8 as i128,
+                            width: 8,
+                            signed: true,
+                        })
+                        .collect(),
+                },
+                (literal, r#type) => bail!("Literal `{literal:?}` can't be of type `{type}`"),
+            },
+            &ExpressionKind::Local(local) => self.eval_local(frame, local)?,
+            &ExpressionKind::Constant(item) => self.constant(item)?,
+            &ExpressionKind::Function(function) => Value::Function {
+                function,
+                qualified: self.qualified(function),
+                r#type: expression.r#type.clone(),
+                thir: self.thir.clone(),
+            },
+            ExpressionKind::Binary(l, op, r) =>
+            {
+                let l = self.eval(frame, l)?;
+                let r = self.eval(frame, r)?;
+
+                match (l, r)
+                {
+                    (
+                        Value::Integer { value: l, .. },
+                        Value::Integer {
+                            value: r,
+                            width,
+                            signed,
+                        },
+                    ) => binary_integer(*op, (l, r), &Type::Integer { width, signed })?,
+                    (Value::Float { value: l, .. }, Value::Float { value: r, width }) =>
+                    {
+                        binary_float(*op, (l, r), width)?
+                    }
+                    (l, _) => bail!("Operator `{op}` can't be applied to `{}`", l.r#type()),
+                }
+            }
+            ExpressionKind::Negate(e) =>
+            {
+                let value = self.eval(frame, e)?;
+
+                negate(value)?
+            }
+            ExpressionKind::Cast(e) =>
+            {
+                let value = self.eval(frame, e)?;
+
+                cast(value, &expression.r#type)?
+            }
+            ExpressionKind::Call { callee, args } =>
+            {
+                let arguments = args
+                    .iter()
+                    .map(|a| self.eval(frame, a))
+                    .collect::<Result<Vec<_>>>()?;
+
+                match callee
+                {
+                    &Callee::Function(function) => self.call(function, arguments)?,
+                    Callee::Pointer(pointer) => match self.eval(frame, pointer)?.load()?
+                    {
+                        Value::Function { function, thir, .. } =>
+                        {
+                            self.within(&thir, |i| i.call(function, arguments))?
+                        }
+                        Value::Closure(closure) => self.call_closure(&closure, arguments)?,
+                        value => bail!("A value of type `{}` isn't a function", value.r#type()),
+                    },
+                }
+            }
+            ExpressionKind::Assert { condition, message } =>
+            {
+                let condition = self.eval(frame, condition)?;
+
+                if !holds(condition)?
+                {
+                    let location = self.location(&frame.module, expression.span);
+
+                    match message
+                    {
+                        Some(message) => bail!("{location}: assertion failed: {message}"),
+                        None => bail!("{location}: assertion failed"),
+                    }
+                }
+
+                Value::Void
+            }
+            ExpressionKind::Len(target) =>
+            {
+                let (.., len, _) = self.eval_items(frame, target)?;
+
+                Value::integer(len as i128, &LENGTH)?
+            }
+            ExpressionKind::Index { target, index } =>
+            {
+                self.eval_index(frame, (target, index), expression)?
+            }
+            ExpressionKind::Slice { target, start, end } => self.eval_slice(
+                frame,
+                target,
+                (start.as_deref(), end.as_deref()),
+                expression,
+            )?,
+            ExpressionKind::If {
+                condition,
+                block,
+                else_block,
+            } =>
+            {
+                let condition = self.eval(frame, condition)?;
+
+                if holds(condition)?
+                {
+                    self.exec_block(frame, block)?;
+                }
+                else if let Some(else_block) = else_block
+                {
+                    self.exec_block(frame, else_block)?;
+                }
+
+                Value::Void
+            }
+            ExpressionKind::Array(items) =>
+            {
+                let Type::Array { box scalar, .. } = &expression.r#type
+                else
+                {
+                    bail!("Array of type `{}`", expression.r#type);
+                };
+
+                Value::Array {
+                    scalar: scalar.clone(),
+                    items: items
+                        .iter()
+                        .map(|i| self.eval(frame, i))
+                        .collect::<Result<_>>()?,
+                }
+            }
+            ExpressionKind::Closure {
+                captures,
+                parameters,
+                body,
+                ..
+            } => self.eval_closure(frame, captures, parameters, body, &expression.r#type)?,
+            ExpressionKind::Coerce(e) =>
+            {
+                let value = self.eval(frame, e)?;
+
+                coerce(value, &expression.r#type)?
+            }
+        })
+    }
+
+    /// Locals, and `mut` parameters, are read as `&mut` references to their slot, the rest as what they hold, as
+    /// [`Local::place_type`](compiler_typeck::Local::place_type) says
+    fn eval_local(&self, frame: &Frame, local: LocalId) -> Result<Value>
+    {
+        let declared = &self.thir[local];
+
+        let Some(slot) = frame.locals.get(&local)
+        else
+        {
+            bail!("`{}` isn't declared yet", declared.name);
+        };
+
+        Ok(match declared.kind
+        {
+            BindingKind::Local => Value::MutRef {
+                referent: declared.r#type.clone(),
+                place: slot.clone().into(),
+            },
+            BindingKind::Parameter if declared.mutable => Value::MutRef {
+                referent: declared.r#type.clone(),
+                place: slot.clone().into(),
+            },
+            _ => slot
+                .borrow()
+                .clone()
+                .with_context(|| format!("`{}` is read before it's assigned", declared.name))?,
+        })
+    }
+
+    /// The array `target` evaluates to, or points into: where it is, the type of its items, the first one in view, how many
+    /// there are and whether they may be written to. Values get a temporary, so there's something to point into
+    fn eval_items(
+        &mut self,
+        frame: &mut Frame,
+        target: &Expression,
+    ) -> Result<(Place, Type, usize, usize, bool)>
+    {
+        let target = match self.eval(frame, target)?
+        {
+            slice @ (Value::MutRef {
+                referent: Type::Slice(_),
+                ..
+            }
+            | Value::Ref {
+                referent: Type::Slice(_),
+                ..
+            }) => slice.load()?,
+            target => target,
+        };
+
+        Ok(match target
+        {
+            Value::MutRef {
+                referent: Type::Array { box scalar, size },
+                place,
+            } => (place, scalar, 0, size as usize, true),
+            Value::Ref {
+                referent: Type::Array { box scalar, size },
+                place,
+            } => (place, scalar, 0, size as usize, false),
+            Value::Array { scalar, items } =>
+            {
+                let size = items.len();
+
+                (
+                    slot(Some(Value::Array {
+                        scalar: scalar.clone(),
+                        items,
+                    }))
+                    .into(),
+                    scalar,
+                    0,
+                    size,
+                    false,
+                )
+            }
+            // Slices are read only
+            Value::Slice {
+                scalar,
+                place,
+                start,
+                len,
+            } => (place, scalar, start, len, false),
+            target => bail!("Can't index into a value of type `{}`", target.r#type()),
+        })
+    }
+
+    /// An index or a slice bound
+    fn eval_offset(&mut self, frame: &mut Frame, offset: &Expression) -> Result<i128>
+    {
+        match self.eval(frame, offset)?
+        {
+            Value::Integer { value, .. } => Ok(value),
+            offset => bail!("Arrays are indexed by integers, got `{}`", offset.r#type()),
+        }
+    }
+
+    /// `target[index]`, as a reference to the item. Writable if `target` is, as on codegen
+    fn eval_index(
+        &mut self,
+        frame: &mut Frame,
+        (target, index): (&Expression, &Expression),
+        expression: &Expression,
+    ) -> Result<Value>
+    {
+        let (place, scalar, start, len, writable) = self.eval_items(frame, target)?;
+        let index = self.eval_offset(frame, index)?;
+
+        let Some(index) = usize::try_from(index).ok().filter(|i| *i < len)
+        else
+        {
+            let location = self.location(&frame.module, expression.span);
+
+            bail!("{location}: index out of bounds: the length is {len} but the index is {index}");
+        };
+
+        let place = place.item(start + index);
+
+        Ok(
+            if writable
+            {
+                Value::MutRef {
+                    referent: scalar,
+                    place,
+                }
+            }
+            else
+            {
+                Value::Ref {
+                    referent: scalar,
+                    place,
+                }
+            },
+        )
+    }
+
+    /// `target[start..end]`, as a slice. Bounds left out span from the first item, or up to the last one
+    fn eval_slice(
+        &mut self,
+        frame: &mut Frame,
+        target: &Expression,
+        (start, end): (Option<&Expression>, Option<&Expression>),
+        expression: &Expression,
+    ) -> Result<Value>
+    {
+        let (place, scalar, first, len, _) = self.eval_items(frame, target)?;
+
+        let start = match start
+        {
+            Some(start) => self.eval_offset(frame, start)?,
+            None => 0,
+        };
+        let end = match end
+        {
+            Some(end) => self.eval_offset(frame, end)?,
+            None => len as i128,
+        };
+
+        // Negative bounds are out of bounds too
+        let Some((start, end)) = usize::try_from(start)
+            .ok()
+            .zip(usize::try_from(end).ok())
+            .filter(|&(start, end)| start <= end && end <= len)
+        else
+        {
+            let location = self.location(&frame.module, expression.span);
+
+            bail!(
+                "{location}: slice out of bounds: the range is {start}..{end} but the length is {len}"
+            );
+        };
+
+        Ok(Value::Slice {
+            scalar,
+            place,
+            start: first + start,
+            len: end - start,
+        })
+    }
+
+    fn call_closure(&mut self, closure: &Closure, arguments: Vec<Value>) -> Result<Value>
+    {
+        let Type::Closure {
+            box return_type, ..
+        } = &closure.r#type
+        else
+        {
+            unreachable!()
+        };
+
+        // Arguments shadow captures
+        let mut frame = Frame {
+            locals: closure.captures.clone(),
+            ..Frame::new(closure.module.clone())
+        };
+        frame.locals.extend(
+            closure
+                .parameters
+                .iter()
+                .zip(arguments)
+                .map(|(&parameter, argument)| (parameter, slot(Some(argument)))),
+        );
+
+        self.within(&closure.thir, |i| i.exec_block(&mut frame, &closure.body))?;
+
+        Ok(returned(frame, return_type))
+    }
+
+    /// Values are copied in, references point to the local's slot, as each capture's value says
+    fn eval_closure(
+        &mut self,
+        frame: &mut Frame,
+        captures: &[Capture],
+        parameters: &EcoVec<LocalId>,
+        body: &compiler_typeck::Block,
+        r#type: &Type,
+    ) -> Result<Value>
+    {
+        let captures = captures
+            .iter()
+            .map(|capture| Ok((capture.inner, slot(Some(self.eval(frame, &capture.value)?)))))
+            .collect::<Result<_>>()?;
+
+        Ok(Value::Closure(Rc::new(Closure {
+            r#type: r#type.clone(),
+            parameters: parameters.clone(),
+            body: body.clone(),
+            captures,
+            module: frame.module.clone(),
+            thir: self.thir.clone(),
+        })))
+    }
+}
@@ -0,0 +1,406 @@
+#![feature(box_patterns)]
+
+//! Tree-walking interpreter, running the typed IR directly. It needs no LLVM nor linker, and serves as the reference semantics the
+//! LLVM back-end is tested against: both should print the same for the same program.
+
+use std::{collections::HashMap, io::Write, mem, rc::Rc};
+
+use anyhow::{Context, Result, bail};
+use compiler_hir::ItemId;
+use compiler_lexer::definitions::Span;
+use compiler_typeck::{FunctionId, ItemKind, LocalId, Thir, Type};
+use ecow::{EcoString, EcoVec, eco_format};
+
+use crate::value::{Slot, Value, slot};
+
+mod expression;
+pub mod repl;
+mod statement;
+pub mod value;
+
+/// State of a call being run
+pub struct Frame
+{
+    /// Module of the function being run, which source locations point into
+    pub module: EcoVec<EcoString>,
+    /// Slots of the locals, parameters and captures declared so far
+    pub locals: HashMap<LocalId, Slot>,
+    /// Set by `ret`, which stops every block up to the function's
+    pub returned: Option<Value>,
+}
+
+impl Frame
+{
+    #[inline]
+    pub fn new(module: EcoVec<EcoString>) -> Self
+    {
+        Self {
+            module,
+            locals: HashMap::new(),
+            returned: None,
+        }
+    }
+}
+
+pub struct Interpreter<W>
+{
+    /// Where `putd`, `puts` and `prints` print to
+    pub out: W,
+    /// Shared with the functions and closures it makes values of, which run on it wherever they're called
+    pub thir: Rc<Thir>,
+}
+
+impl<W: Write> Interpreter<W>
+{
+    pub fn new(thir: Rc<Thir>, out: W) -> Self
+    {
+        Self { out, thir }
+    }
+
+    /// Runs the root module's `main`
+    pub fn run_main(&mut self) -> Result<()>
+    {
+        let main = self.thir.items.iter().find_map(|i| match i.kind
+        {
+            ItemKind::Function(function) if i.module.is_empty() && i.name == "main" =>
+            {
+                Some(function)
+            }
+            _ => None,
+        });
+
+        let Some(main) = main
+        else
+        {
+            bail!("There's no `main` function to run");
+        };
+
+        self.call(main, Vec::new())?;
+
+        Ok(())
+    }
+
+    /// Qualified name of `function`, see [`compiler_hir::qualify_in`]
+    pub fn qualified(&self, function: FunctionId) -> EcoString
+    {
+        let item = &self.thir[self.thir[function].item];
+
+        compiler_hir::qualify_in(&item.module, &item.name)
+    }
+
+    /// Where `span` is in `module`, as `file:line:column`
+    pub fn location(&self, module: &EcoVec<EcoString>, span: Span) -> EcoString
+    {
+        match self.thir.modules.iter().find(|m| &m.path == module)
+        {
+            Some(module) =>
+            {
+                let (line, column) = span.location(&module.source);
+
+                eco_format!("{}:{line}:{column}", module.file)
+            }
+            None => eco_format!("{}:{}", module.join("."), span.start),
+        }
+    }
+
+    /// Value of the constant `item`, as the type checker folds it
+    pub fn constant(&self, item: ItemId) -> Result<Value>
+    {
+        let declared = &self.thir[item];
+
+        let ItemKind::Const { folded, .. } = &declared.kind
+        else
+        {
+            bail!(
+                "`{}` isn't a constant",
+                compiler_hir::qualify_in(&declared.module, &declared.name)
+            );
+        };
+
+        Ok(folded.into())
+    }
+
+    /// Calls `function` with `arguments`, already of the types it takes
+    pub fn call(&mut self, function: FunctionId, arguments: Vec<Value>) -> Result<Value>
+    {
+        let thir = self.thir.clone();
+        let called = &thir[function];
+        let item = &thir[called.item];
+
+        let Some(body) = &called.body
+        else
+        {
+            let symbol = item
+                .attributes
+                .link_name
+                .clone()
+                .unwrap_or_else(|| item.name.clone());
+
+            return self.builtin(&symbol, arguments);
+        };
+
+        let mut frame = Frame::new(item.module.clone());
+        frame.locals.extend(
+            called
+                .parameters
+                .iter()
+                .zip(arguments)
+                .map(|(&parameter, argument)| (parameter, slot(Some(argument)))),
+        );
+
+        self.exec_block(&mut frame, body)
+            .with_context(|| format!("In function `{}`", self.qualified(function)))?;
+
+        Ok(returned(frame, &called.return_type))
+    }
+
+    /// Runs `run` on `thir`, which the ids of values made on another program index into, as those the REPL keeps from earlier
+    /// inputs do
+    fn within<T>(&mut self, thir: &Rc<Thir>, run: impl FnOnce(&mut Self) -> Result<T>)
+    -> Result<T>
+    {
+        if Rc::ptr_eq(thir, &self.thir)
+        {
+            return run(self);
+        }
+
+        let outer = mem::replace(&mut self.thir, thir.clone());
+
+        let result = run(self);
+
+        self.thir = outer;
+
+        result
+    }
+
+    /// Calls the extern function `symbol`, if it's built in
+    fn builtin(&mut self, symbol: &str, arguments: Vec<Value>) -> Result<Value>
+    {
+        // Strings are passed by reference
+        let arguments = arguments
+            .into_iter()
+            .map(Value::load)
+            .collect::<Result<Vec<_>>>()?;
+
+        match (symbol, arguments.as_slice())
+        {
+            ("putd", [Value::Integer { value, .. }]) => writeln!(self.out, "{value}")?,
+            ("puts", [Value::Array { items, .. }]) =>
+            {
+                let bytes = items
+                    .iter()
+                    .map_while(|i| match i
+                    {
+                        Value::Integer { value, .. } if *value != 0 => Some(*value as u8),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                writeln!(self.out, "{}", String::from_utf8_lossy(&bytes))?;
+            }
+            ("puts", [Value::Integer { value, .. }]) =>
+            {
+                writeln!(self.out, "{}", String::from_utf8_lossy(&[*value as u8]))?;
+            }
+            ("prints", [slice @ Value::Slice { .. }]) =>
+            {
+                let bytes = slice
+                    .items()
+                    .context("Reading a local which isn't assigned yet")?
+                    .iter()
+                    .map(|i| match i
+                    {
+                        Value::Integer { value, .. } => *value as u8,
+                        _ => 0,
+                    })
+                    .collect::<Vec<_>>();
+
+                writeln!(self.out, "{}", String::from_utf8_lossy(&bytes))?;
+            }
+            ("putd" | "puts" | "prints", arguments) => bail!(
+                "Unexpected arguments to `{symbol}`: {}",
+                arguments
+                    .iter()
+                    .map(|a| format!("`{}`", a.r#type()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => bail!(
+                "Extern function `{symbol}` isn't built into the interpreter, which only has `putd`, `puts` and `prints`"
+            ),
+        }
+
+        Ok(Value::Void)
+    }
+}
+
+/// What a call returns once its body is done running. Functions returning `()` give nothing, whatever `ret` was given
+#[inline]
+fn returned(frame: Frame, return_type: &Type) -> Value
+{
+    match (frame.returned, return_type)
+    {
+        (Some(value), r#type) if *r#type != Type::Void => value,
+        _ => Value::Void,
+    }
+}
+
+/// Interprets the checked program `thir`, running the root module's `main` and printing to `out`
+pub fn run(thir: Thir, out: impl Write) -> Result<()>
+{
+    Interpreter::new(Rc::new(thir), out).run_main()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn interpret(source: &str) -> Result<String>
+    {
+        let tokens = compiler_parser::lex(source)?;
+
+        let module = compiler_parser::Module {
+            path: EcoVec::new(),
+            file: "test.su".into(),
+            source: source.into(),
+            ast: compiler_parser::parse(source, tokens.into_iter())?,
+        };
+
+        let hir = compiler_hir::resolve(&[module]).map_err(|mut e| e.swap_remove(0))?;
+        let thir = compiler_typeck::check(&hir).map_err(|mut e| e.swap_remove(0))?;
+
+        let mut out = Vec::new();
+        run(thir, &mut out)?;
+
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn runs_functions_and_methods()
+    {
+        assert_eq!(
+            interpret(
+                "extern func putd(d i32)\nfunc add(a i32, b i32) i32 {\n    ret a + b\n}\nfunc double(n &mut i32) {\n    n = n + n\n}\nfunc main() {\n    let a i32 = 40\n    let b = 32\n    let c\n    c = a\n    let mut d = c.add(b)\n    d.double()\n    putd(d)\n}\n"
+            )
+            .unwrap(),
+            "144\n"
+        );
+    }
+
+    #[test]
+    fn captures_by_value_and_reference()
+    {
+        assert_eq!(
+            interpret(
+                "extern func putd(d i32)\nfunc twice(f func[](i32) i32, v i32) i32 {\n    ret f(f(v))\n}\nfunc incrementer() func[](i32) i32 {\n    ret func[](x i32) i32 {\n        ret x + 1\n    }\n}\nfunc main() {\n    let mut total = 0\n    let add = func[&mut total](n i32) {\n        total = total + n\n    }\n    add(4)\n    add(6)\n    putd(total)\n    let n = 3\n    let add_n = func[n](x i32) i32 {\n        ret x + n\n    }\n    putd(twice(add_n, 1))\n    putd(twice(incrementer(), 1))\n}\n"
+            )
+            .unwrap(),
+            "10\n7\n3\n"
+        );
+    }
+
+    #[test]
+    fn calls_whatever_calls_return()
+    {
+        assert_eq!(
+            interpret(
+                "extern func putd(d i32)\nfunc double(a i32) i32 {\n    ret a * 2\n}\nfunc get() func(i32) i32 {\n    ret double\n}\nfunc incrementer() func[](i32) i32 {\n    ret func[](x i32) i32 {\n        ret x + 1\n    }\n}\nfunc main() {\n    putd(get()(3))\n    putd((double)(4))\n    putd(incrementer()(2))\n}\n"
+            )
+            .unwrap(),
+            "6\n8\n3\n"
+        );
+    }
+
+    #[test]
+    fn wraps_to_the_type_width()
+    {
+        assert_eq!(
+            interpret(
+                "extern func putd(d i32)\nconst BIG u8 = 200\nconst MAX i32 = 2147483647\nfunc main() {\n    let a u8 = BIG + 100\n    if a < BIG {\n        putd(1)\n    }\n    if MAX + 1 < 0 {\n        putd(2)\n    }\n}\n"
+            )
+            .unwrap(),
+            "1\n2\n"
+        );
+    }
+
+    #[test]
+    fn prints_strings()
+    {
+        assert_eq!(
+            interpret(
+                "extern func puts(s &[5]i8)\nconst GREETING = \"Hello\"\nfunc main() {\n    puts(GREETING)\n}\n"
+            )
+            .unwrap(),
+            "Hello\n"
+        );
+    }
+
+    #[test]
+    fn indexes_arrays()
+    {
+        assert_eq!(
+            interpret(
+                "extern func putd(d i32)\nconst PRIMES [4]i32 = [2, 3, 5, 7]\nfunc bump(a &mut [2]i32, i u8) {\n    a[i] = a[i] + 1\n}\nfunc main() {\n    let mut grid [2][2]i32 = [[1, 2], [3, 4]]\n    grid[1][0] = PRIMES[3]\n    bump(grid[0], 1)\n    putd(grid[0][1] + grid[1][0])\n    putd([10, 20][1])\n}\n"
+            )
+            .unwrap(),
+            "10\n20\n"
+        );
+    }
+
+    #[test]
+    fn reports_out_of_bounds_indices()
+    {
+        assert_eq!(
+            interpret("func main() {\n    let a = [1, 2, 3]\n    let i = 3\n    a[i]\n}\n")
+                .map_err(|e| e.root_cause().to_string()),
+            Err("test.su:4:6: index out of bounds: the length is 3 but the index is 3".into())
+        );
+    }
+
+    #[test]
+    fn slices_arrays_and_strings()
+    {
+        assert_eq!(
+            interpret(
+                "extern func putd(d i32)\nextern func prints(s []i8)\nfunc sum(s []i32) i32 {\n    if s.len == 0 {\n        ret 0\n    }\n    ret s[0] + sum(s[1..])\n}\nfunc main() {\n    let a = [1, 2, 3, 4]\n    putd(sum(a))\n    putd(sum(a[1..3]))\n    let s []i32 = a\n    putd(s[2..][0])\n    prints(\"Hello, world\"[..5])\n}\n"
+            )
+            .unwrap(),
+            "10\n5\n3\nHello\n"
+        );
+    }
+
+    #[test]
+    fn reports_out_of_bounds_slices()
+    {
+        assert_eq!(
+            interpret("func main() {\n    let a = [1, 2, 3]\n    a[2..4]\n}\n")
+                .map_err(|e| e.root_cause().to_string()),
+            Err("test.su:3:6: slice out of bounds: the range is 2..4 but the length is 3".into())
+        );
+    }
+
+    #[test]
+    fn casts_numbers()
+    {
+        assert_eq!(
+            interpret(
+                "extern func putd(d i32)\nfunc main() {\n    let a i32 = 300\n    putd(a as u8 as i32)\n    let b i8 = -1\n    putd(b as u16 as i32)\n    let f = a as f64 / 8\n    putd(f as i32)\n    putd(2.9 as i32)\n    let huge = f * 100000000.0\n    putd(huge as i32)\n    let negative = -huge\n    putd(negative as u8 as i32)\n}\n"
+            )
+            .unwrap(),
+            "44\n65535\n37\n2\n2147483647\n0\n"
+        );
+    }
+
+    #[test]
+    fn reports_failed_assertions()
+    {
+        assert_eq!(
+            interpret("func main() {\n    assert(1 == 2, \"math\")\n}\n")
+                .map_err(|e| e.root_cause().to_string()),
+            Err("test.su:2:5: assertion failed: math".into())
+        );
+    }
+}
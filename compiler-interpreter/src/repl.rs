@@ -0,0 +1,382 @@
+//! Sessions of `sundae repl`. Items and statements are taken one input at a time, and whatever they define is kept for the
+//! next ones, as if they all were the body of a single function on the root module.
+
+use std::{fmt::Display, io::Write, rc::Rc};
+
+use anyhow::{Result, bail};
+use compiler_lexer::{
+    LexerEvent,
+    definitions::{Span, Token, TokenType},
+};
+use compiler_parser::{AST, ArgumentName, Item, Module, Name, item::FunctionSignature};
+use compiler_typeck::{ItemKind, Statement, Thir, Type};
+use ecow::{EcoString, EcoVec};
+
+use crate::{
+    Frame, Interpreter,
+    value::{Slot, Value},
+};
+
+/// Name of the function statements are run in, which no identifier can clash with
+const FUNCTION: &str = "<repl>";
+
+/// How many braces `source` leaves open, input isn't complete until they're closed. Errors are left to the parser, once complete
+pub fn depth(source: &str) -> isize
+{
+    compiler_lexer::tokenize(source)
+        .filter_map(|e| match e
+        {
+            LexerEvent::Token(t) if t.r#type == TokenType::Separator => Some(t),
+            _ => None,
+        })
+        .map(|t| match t.span.source(source)
+        {
+            "{" => 1,
+            "}" => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Whether `tokens` starts with an item, rather than a statement. Closures start with `func` too, but no name follows it
+fn is_item(source: &str, tokens: &[Token]) -> bool
+{
+    match tokens
+    {
+        [first, second, ..] if first.span.source(source) == "func" =>
+        {
+            second.r#type == TokenType::Identifier
+        }
+        [first, ..] => matches!(
+            first.span.source(source),
+            "import" | "const" | "extern" | "@"
+        ),
+        [] => false,
+    }
+}
+
+/// A local defined on an earlier input. Later ones see it as a parameter, sharing its slot
+struct Kept
+{
+    name: EcoString,
+    r#type: Type,
+    mutable: bool,
+    slot: Slot,
+}
+
+pub struct Repl<W>
+{
+    pub out: W,
+    /// Items defined so far
+    items: Vec<Item>,
+    /// Locals defined so far, in the order they were
+    locals: Vec<Kept>,
+}
+
+/// Every error found checking an input, one per line
+fn errors(errors: &[impl Display]) -> anyhow::Error
+{
+    anyhow::anyhow!(
+        "{}",
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// `r#type` as it's written on source
+fn written(r#type: &Type) -> Result<compiler_parser::Type>
+{
+    let text = r#type.to_string();
+
+    Ok(compiler_parser::Type(
+        compiler_parser::lex(&text)?
+            .iter()
+            .map(|t| t.span.source(&text).into())
+            .collect(),
+    ))
+}
+
+impl<W: Write> Repl<W>
+{
+    pub fn new(out: W) -> Self
+    {
+        Self {
+            out,
+            items: Vec::new(),
+            locals: Vec::new(),
+        }
+    }
+
+    /// Runs `input`, returning the values of the expressions it's made of. Items replace any other by the same name
+    pub fn eval(&mut self, input: &str) -> Result<Vec<Value>>
+    {
+        let tokens = compiler_parser::lex(input)?;
+
+        let start = tokens
+            .iter()
+            .position(|t| t.r#type != TokenType::Newline)
+            .unwrap_or(tokens.len());
+
+        if is_item(input, &tokens[start..])
+        {
+            let ast = compiler_parser::parse(input, tokens.into_iter())?;
+
+            return self.define(input, ast.0).map(|()| Vec::new());
+        }
+
+        let statements = compiler_parser::parse_statements(input, tokens.into_iter())?;
+
+        // Statements make up the body of a function taking the locals kept so far
+        let arguments = self
+            .locals
+            .iter()
+            .map(|kept| {
+                Ok(ArgumentName {
+                    name: kept.name.clone(),
+                    r#type: written(&kept.r#type)?,
+                    // Those declared without a value may still be assigned once
+                    mutable: kept.mutable || kept.slot.borrow().is_none(),
+                    span: Span::empty(0),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let mut items = self.items.clone();
+        items.push(Item::Function {
+            signature: FunctionSignature {
+                name: (FUNCTION.into(), None),
+                span: Span::empty(0),
+                generics: EcoVec::new(),
+                arguments,
+                variadic: false,
+            },
+            body: statements.into_iter().collect(),
+            attributes: EcoVec::new(),
+        });
+
+        // Kept values hold on to the program they were made on, its ids are this input's only
+        let thir = Rc::new(check(input, items)?);
+
+        let Some(function) = thir.items.iter().find_map(|i| match i.kind
+        {
+            ItemKind::Function(function) if i.name == FUNCTION => Some(function),
+            _ => None,
+        })
+        else
+        {
+            unreachable!()
+        };
+
+        let function = &thir[function];
+        let body = function.body.as_ref().expect("Functions have a body");
+
+        let mut interpreter = Interpreter::new(thir.clone(), &mut self.out);
+        let mut frame = Frame::new(EcoVec::new());
+
+        frame.locals.extend(
+            function
+                .parameters
+                .iter()
+                .zip(&self.locals)
+                .map(|(&parameter, kept)| (parameter, kept.slot.clone())),
+        );
+
+        let mut values = Vec::new();
+
+        for statement in body
+        {
+            match statement
+            {
+                Statement::Expression(e) =>
+                {
+                    let value = interpreter.eval(&mut frame, e)?.load()?;
+
+                    if !matches!(value, Value::Void)
+                    {
+                        values.push(value);
+                    }
+                }
+                statement => interpreter.exec_statement(&mut frame, statement)?,
+            }
+
+            // There's no function to return to, `ret` just stops the input
+            if frame.returned.is_some()
+            {
+                break;
+            }
+        }
+
+        // Locals declared on this input are kept for the next ones, shadowing those of the same name
+        for statement in body
+        {
+            if let Statement::Local { local, .. } = statement
+                && let Some(slot) = frame.locals.get(local)
+            {
+                let local = &thir[*local];
+
+                self.locals.retain(|kept| kept.name != local.name);
+                self.locals.push(Kept {
+                    name: local.name.clone(),
+                    r#type: local.r#type.clone(),
+                    mutable: local.mutable,
+                    slot: slot.clone(),
+                });
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Keeps `items`, replacing those by the same name, if the program they make up still checks
+    fn define(&mut self, input: &str, items: Vec<Item>) -> Result<()>
+    {
+        let mut defined = self.items.clone();
+
+        for item in items
+        {
+            let name = match &item
+            {
+                Item::Import(..) => bail!("Imports aren't supported on the REPL"),
+                Item::Const {
+                    name: Name(name, ..),
+                    ..
+                } => name.clone(),
+                Item::Function { signature, .. } | Item::Extern { signature, .. } =>
+                {
+                    signature.name.0.clone()
+                }
+            };
+
+            defined.retain(|i| match i
+            {
+                Item::Const {
+                    name: Name(n, ..), ..
+                } => *n != name,
+                Item::Function { signature, .. } | Item::Extern { signature, .. } =>
+                {
+                    signature.name.0 != name
+                }
+                Item::Import(..) => true,
+            });
+            defined.push(item);
+        }
+
+        check(input, defined.clone())?;
+
+        self.items = defined;
+
+        Ok(())
+    }
+}
+
+/// Resolves and type checks `items` as the root module. Locations point within the last input
+fn check(input: &str, items: Vec<Item>) -> Result<Thir>
+{
+    let module = Module {
+        path: EcoVec::new(),
+        file: "<repl>".into(),
+        source: input.into(),
+        ast: AST(items),
+    };
+
+    let hir = compiler_hir::resolve(&[module]).map_err(|e| errors(&e))?;
+
+    compiler_typeck::check(&hir).map_err(|e| errors(&e))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn session(inputs: &[&str]) -> Vec<String>
+    {
+        let mut repl = Repl::new(Vec::new());
+
+        inputs
+            .iter()
+            .flat_map(|i| match repl.eval(i)
+            {
+                Ok(values) => values
+                    .into_iter()
+                    .map(|v| format!("{v}: {}", v.r#type()))
+                    .collect(),
+                Err(error) => vec![format!("error: {}", error.root_cause())],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_open_braces()
+    {
+        assert_eq!(depth("func f() {"), 1);
+        assert_eq!(depth("func f() {\n    if a {\n    }"), 1);
+        assert_eq!(depth("func f() {\n}"), 0);
+        assert_eq!(depth("\"{\" `}`"), 0);
+    }
+
+    #[test]
+    fn keeps_definitions()
+    {
+        assert_eq!(
+            session(&[
+                "func square(n i32) i32 {\n    ret n * n\n}",
+                "let a = 4",
+                "square(a)",
+                "const HALF f32 = 0.5",
+                "HALF * 3",
+                "func square(n i32) i32 {\n    ret n\n}",
+                "square(a)",
+            ]),
+            ["16: i32", "1.5: f32", "4: i32"]
+        );
+    }
+
+    #[test]
+    fn keeps_functions_and_closures_as_values()
+    {
+        assert_eq!(
+            session(&[
+                "func a(n i32) i32 {\n    ret n * 100\n}",
+                "func b(n i32) i32 {\n    ret a(n)\n}",
+                "let g = b",
+                "let mut total = 0",
+                "let add = func[&mut total](n i32) i32 {\n    total = total + n\n    ret total\n}",
+                "func a(n i32) i32 {\n    ret n * 2\n}",
+                "g(2)",
+                "add(3)",
+                "add(4)",
+                "total",
+            ]),
+            ["200: i32", "3: i32", "7: i32", "7: i32"]
+        );
+    }
+
+    #[test]
+    fn types_literals()
+    {
+        assert_eq!(
+            session(&["1 + 2", "2.5", "let b u8 = 255", "b + 1", "b < 3", "`a`"]),
+            ["3: i32", "2.5: f64", "0: u8", "0: u1", "97: i8"]
+        );
+    }
+
+    #[test]
+    fn recovers_from_errors()
+    {
+        assert_eq!(
+            session(&["let a u8 = 256", "a", "let a = 1", "a / 0", "a"]),
+            [
+                "error: Literal `256` doesn't fit in `u8`",
+                "error: Identifier `a` not found",
+                "error: Division by zero",
+                "1: i32"
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::Write;
+
+use anyhow::{Result, bail};
+use compiler_typeck::Statement;
+
+use crate::{
+    Frame, Interpreter,
+    value::{Value, slot},
+};
+
+impl<W: Write> Interpreter<W>
+{
+    /// Runs `block`, stopping early once the function returns
+    pub fn exec_block(&mut self, frame: &mut Frame, block: &[Statement]) -> Result<()>
+    {
+        for statement in block
+        {
+            if frame.returned.is_some()
+            {
+                break;
+            }
+
+            self.exec_statement(frame, statement)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn exec_statement(&mut self, frame: &mut Frame, statement: &Statement) -> Result<()>
+    {
+        match statement
+        {
+            Statement::Return(e) =>
+            {
+                let value = match e
+                {
+                    Some(e) => self.eval(frame, e)?,
+                    None => Value::Void,
+                };
+
+                frame.returned = Some(value);
+            }
+            Statement::Expression(e) =>
+            {
+                self.eval(frame, e)?;
+            }
+            // Locals without an initial value may be assigned once even if immutable, which the type checker made sure of
+            Statement::Assign {
+                destination,
+                source,
+            } =>
+            {
+                // The destination is evaluated first, as on codegen
+                let Value::MutRef { place, .. } = self.eval(frame, destination)?
+                else
+                {
+                    bail!("Can't assign to a value of type `{}`", destination.r#type);
+                };
+
+                let value = self.eval(frame, source)?;
+
+                place.set(value)?;
+            }
+            Statement::Local { local, init } =>
+            {
+                // The initial value can't see the local itself, it may refer to one it shadows
+                let init = init.as_ref().map(|e| self.eval(frame, e)).transpose()?;
+
+                frame.locals.insert(*local, slot(init));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+//! Values of every [`Type`], as the interpreter holds them
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use anyhow::{Context, Result, bail};
+use compiler_typeck::{Block, Constant, FunctionId, LocalId, Thir, Type};
+use ecow::{EcoString, EcoVec};
+
+/// Where a local lives, which references point into. Empty until the local is first assigned
+pub type Slot = Rc<RefCell<Option<Value>>>;
+
+#[inline]
+pub fn slot(value: Option<Value>) -> Slot
+{
+    Rc::new(RefCell::new(value))
+}
+
+/// What a reference points to, a slot or an item of the array it holds, `items` indexing into each nested array in turn
+#[derive(Clone, Debug)]
+pub struct Place
+{
+    pub slot: Slot,
+    pub items: Vec<usize>,
+}
+
+impl From<Slot> for Place
+{
+    #[inline]
+    fn from(slot: Slot) -> Self
+    {
+        Self {
+            slot,
+            items: Vec::new(),
+        }
+    }
+}
+
+impl Place
+{
+    /// Item `index` of the array this place holds
+    pub fn item(&self, index: usize) -> Self
+    {
+        let mut items = self.items.clone();
+        items.push(index);
+
+        Self {
+            slot: self.slot.clone(),
+            items,
+        }
+    }
+
+    /// What this place holds, if its slot is assigned
+    pub fn get(&self) -> Option<Value>
+    {
+        let slot = self.slot.borrow();
+
+        self.items
+            .iter()
+            .try_fold(slot.as_ref()?, |value, &i| match value
+            {
+                Value::Array { items, .. } => items.get(i),
+                _ => None,
+            })
+            .cloned()
+    }
+
+    pub fn set(&self, value: Value) -> Result<()>
+    {
+        let mut slot = self.slot.borrow_mut();
+
+        if self.items.is_empty()
+        {
+            *slot = Some(value);
+
+            return Ok(());
+        }
+
+        match self.items.iter().fold(slot.as_mut(), |held, &i| match held
+        {
+            Some(Value::Array { items, .. }) => items.get_mut(i),
+            _ => None,
+        })
+        {
+            Some(held) => *held = value,
+            None => bail!("Writing through a reference to a local which isn't assigned yet"),
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Value
+{
+    /// Kept wrapped to its width, so signed ones are negative rather than large
+    Integer
+    {
+        value: i128,
+        width: u32,
+        signed: bool,
+    },
+    Float
+    {
+        value: f64,
+        width: u32,
+    },
+    Void,
+    Array
+    {
+        scalar: Type,
+        items: Vec<Value>,
+    },
+    Ref
+    {
+        referent: Type,
+        place: Place,
+    },
+    MutRef
+    {
+        referent: Type,
+        place: Place,
+    },
+    /// The `len` items of the array at `place` from `start` on
+    Slice
+    {
+        scalar: Type,
+        place: Place,
+        start: usize,
+        len: usize,
+    },
+    Tuple(Vec<Value>),
+    /// A function or an extern one, along with its qualified name and the program `function` is of
+    Function
+    {
+        function: FunctionId,
+        qualified: EcoString,
+        r#type: Type,
+        thir: Rc<Thir>,
+    },
+    Closure(Rc<Closure>),
+}
+
+/// A closure along with what it captured, which its body sees as locals
+#[derive(Debug)]
+pub struct Closure
+{
+    pub r#type: Type,
+    pub parameters: EcoVec<LocalId>,
+    pub body: Block,
+    /// Slots of the captures as seen from within the closure, holding what was captured
+    pub captures: HashMap<LocalId, Slot>,
+    /// Module of the function creating it, which source locations point into
+    pub module: EcoVec<EcoString>,
+    /// Program the ids in its body are of
+    pub thir: Rc<Thir>,
+}
+
+/// Two's complement truncation of `value` to `width` bits, as LLVM's integer arithmetic wraps
+pub fn wrap(value: i128, width: u32, signed: bool) -> i128
+{
+    if width == 0 || width >= 128
+    {
+        return value;
+    }
+
+    let mask = (1u128 << width) - 1;
+    let bits = value as u128 & mask;
+
+    if signed && bits >> (width - 1) == 1
+    {
+        (bits | !mask) as i128
+    }
+    else
+    {
+        bits as i128
+    }
+}
+
+impl From<&Constant> for Value
+{
+    fn from(constant: &Constant) -> Self
+    {
+        match constant
+        {
+            &Constant::Integer {
+                value,
+                width,
+                signed,
+            } => Self::Integer {
+                value,
+                width,
+                signed,
+            },
+            &Constant::Float { value, width } => Self::Float { value, width },
+            Constant::Array { scalar, items } => Self::Array {
+                scalar: scalar.clone(),
+                items: items.iter().map(Self::from).collect(),
+            },
+        }
+    }
+}
+
+impl Value
+{
+    /// An integer of type `r#type`, wrapped to its width
+    pub fn integer(value: i128, r#type: &Type) -> Result<Self>
+    {
+        let &Type::Integer { width, signed } = r#type
+        else
+        {
+            bail!("`{type}` isn't an integer type");
+        };
+
+        Ok(Self::Integer {
+            value: wrap(value, width, signed),
+            width,
+            signed,
+        })
+    }
+
+    pub fn r#type(&self) -> Type
+    {
+        match self
+        {
+            Self::Integer { width, signed, .. } => Type::Integer {
+                width: *width,
+                signed: *signed,
+            },
+            Self::Float { width, .. } => Type::Float(*width),
+            Self::Void => Type::Void,
+            Self::Array { scalar, items } => Type::Array {
+                scalar: Box::new(scalar.clone()),
+                size: items.len() as u32,
+            },
+            Self::Ref { referent, .. } => Type::Ref(Box::new(referent.clone())),
+            Self::MutRef { referent, .. } => Type::MutRef(Box::new(referent.clone())),
+            Self::Slice { scalar, .. } => Type::Slice(Box::new(scalar.clone())),
+            Self::Tuple(items) => Type::Tuple(items.iter().map(Self::r#type).collect()),
+            Self::Function { r#type, .. } => r#type.clone(),
+            Self::Closure(closure) => closure.r#type.clone(),
+        }
+    }
+
+    /// What a slice points to, if the array behind it is assigned
+    pub fn items(&self) -> Option<Vec<Value>>
+    {
+        let Self::Slice {
+            place, start, len, ..
+        } = self
+        else
+        {
+            return None;
+        };
+
+        (*start..start + len).map(|i| place.item(i).get()).collect()
+    }
+
+    /// Reads the value behind a reference, values are returned as they are
+    pub fn load(self) -> Result<Self>
+    {
+        match self
+        {
+            Self::Ref { place, .. } | Self::MutRef { place, .. } => place
+                .get()
+                .context("Reading through a reference to a local which isn't assigned yet"),
+            value => Ok(value),
+        }
+    }
+}
+
+impl fmt::Display for Value
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let list = |f: &mut fmt::Formatter<'_>, items: &[Value]| {
+            items.iter().enumerate().try_for_each(|(i, item)| {
+                if i > 0
+                {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{item}")
+            })
+        };
+
+        match self
+        {
+            Self::Integer { value, .. } => write!(f, "{value}"),
+            Self::Float { value, .. } => write!(f, "{value:?}"),
+            Self::Void => write!(f, "()"),
+            Self::Array { items, .. } =>
+            {
+                write!(f, "[")?;
+                list(f, items)?;
+                write!(f, "]")
+            }
+            Self::Ref { place, .. } | Self::MutRef { place, .. } =>
+            {
+                let prefix = if let Self::Ref { .. } = self
+                {
+                    "&"
+                }
+                else
+                {
+                    "&mut "
+                };
+
+                match place.get()
+                {
+                    Some(value) => write!(f, "{prefix}{value}"),
+                    None => write!(f, "{prefix}<unassigned>"),
+                }
+            }
+            Self::Slice { .. } => match self.items()
+            {
+                Some(items) =>
+                {
+                    write!(f, "&[")?;
+                    list(f, &items)?;
+                    write!(f, "]")
+                }
+                None => write!(f, "&[<unassigned>]"),
+            },
+            Self::Tuple(items) =>
+            {
+                write!(f, "(")?;
+                list(f, items)?;
+                write!(f, ")")
+            }
+            Self::Function { qualified, .. } => write!(f, "{qualified}"),
+            Self::Closure(_) => write!(f, "<closure>"),
+        }
+    }
+}
@@ -1,7 +1,9 @@
 // These arrays must remain sorted accordingly - Rust doesn't provide easy sorting at compile time (yet).
 
 // This array is binary searched. It must be sorted by Ord.
-pub const KEYWORDS: &[&[u8]] = &[b"const", b"func", b"if", b"let", b"mut", b"ret"];
+pub const KEYWORDS: &[&[u8]] = &[
+    b"as", b"const", b"extern", b"func", b"if", b"import", b"let", b"mut", b"ret",
+];
 
 // This array is searched first against the longest match. It must be sorted by descending length.
 pub const OPERATORS: &[&[u8]] = &[
@@ -13,7 +15,10 @@ pub const OPERATORS: &[&[u8]] = &[
 pub const KEYWORD_LIKE_OPERATORS: &[&[u8]] = &[b"and", b"or"];
 
 // This array is binary searched. It must be sorted by Ord.
-pub const SEPARATORS: &[u8] = &[b'(', b')', b',', b'.', b'=', b'[', b']', b'{', b'}'];
+pub const SEPARATORS: &[u8] = b"(),.=@[]{}";
+
+// Separators which take more than a byte, checked before those on `SEPARATORS`. It must be sorted by descending length.
+pub const LONG_SEPARATORS: &[&[u8]] = &[b"...", b".."];
 
 pub const HORIZONTAL_WHITESPACE: &[u8] = b" \t";
 
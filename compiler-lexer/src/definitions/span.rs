@@ -1,8 +1,9 @@
 use std::ops::{Range, RangeInclusive};
 
 use miette::SourceSpan;
+use serde::Serialize;
 
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Serialize)]
 pub struct Span
 {
     pub start: usize,
@@ -35,12 +36,25 @@ impl Span
         Self::new(start, end + 1)
     }
 
+    /// From the start of `self` to the end of `other`
+    #[inline]
+    pub const fn to(self, other: Self) -> Self
+    {
+        Self::new(self.start, other.end)
+    }
+
     #[inline]
     pub const fn len(self) -> usize
     {
         self.end - self.start
     }
 
+    #[inline]
+    pub const fn is_empty(self) -> bool
+    {
+        self.start == self.end
+    }
+
     #[inline]
     pub const fn range(self) -> Range<usize>
     {
@@ -52,6 +66,18 @@ impl Span
     {
         &source[self.range()]
     }
+
+    /// Line and column the span starts at in `source`, both counted from 1. Columns count characters, not bytes
+    pub fn location(self, source: &str) -> (usize, usize)
+    {
+        let before = &source[..self.start.min(source.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
 }
 
 impl From<Range<usize>> for Span
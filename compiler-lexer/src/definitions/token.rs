@@ -1,6 +1,8 @@
+use serde::Serialize;
+
 use super::Span;
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize)]
 pub enum LiteralType
 {
     String,
@@ -9,7 +11,7 @@ pub enum LiteralType
     Float,
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize)]
 pub enum TokenType
 {
     Keyword,
@@ -21,7 +23,7 @@ pub enum TokenType
     Newline,
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize)]
 pub struct Token
 {
     pub r#type: TokenType,
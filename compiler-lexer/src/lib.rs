@@ -141,6 +141,13 @@ impl<'a> Iterator for Lexer<'a>
         {
             Some(self.lex_operator(i, op))
         }
+        else if let Some(sep) = LONG_SEPARATORS
+            .iter()
+            .find(|sep| self.source[i..].starts_with(sep))
+        {
+            self.it.advance_by(sep.len()).unwrap();
+            Some(self.token(TokenType::Separator, Span::new(i, i + sep.len())))
+        }
         else if self
             .it
             .next_if(|&(_, b)| SEPARATORS.binary_search(&b).is_ok())
@@ -308,7 +315,8 @@ impl<'a> Lexer<'a>
                 {
                     self.it.next();
                 }
-                b'.' if !has_dot && !has_exp =>
+                // `1..2` is a range rather than `1.` followed by a dot
+                b'.' if !has_dot && !has_exp && self.source.get(i + 1) != Some(&b'.') =>
                 {
                     self.it.next();
                     has_dot = true;
@@ -322,8 +330,6 @@ impl<'a> Lexer<'a>
 
                     if !matches!(self.it.peek(), Some(&(_, b)) if b.is_ascii_digit())
                     {
-                        self.it.advance_by(end - start).unwrap();
-
                         return LexerEvent::Error(LexerError::MissingExp {
                             span: Span::inclusive(start, end),
                         });
@@ -426,10 +432,16 @@ impl<'a> Lexer<'a>
             return None;
         }
 
-        self.it.next();
+        // The whole of a non-ASCII character, so lexing goes on at the next one
+        let len = self.source[end + 1..]
+            .chars()
+            .next()
+            .map_or(1, char::len_utf8);
+
+        self.it.advance_by(len).unwrap();
 
         Some(LexerEvent::Error(LexerError::InvalidDigitOnNumber {
-            span: Span::single(end + 1),
+            span: Span::new(end + 1, end + 1 + len),
             float,
             base,
         }))
@@ -851,6 +863,19 @@ mod tests
         assert_token!("64.", 3, Literal(Float));
         assert_token!("00.", 3, Literal(Float));
 
+        // Ranges
+        assert_token!("1..2", 1, Literal(Int));
+        assert_eq!(
+            tokenize("1..2")
+                .filter_map(|e| match e
+                {
+                    LexerEvent::Token(t) => Some(t.r#type),
+                    LexerEvent::Error(_) => None,
+                })
+                .collect::<Vec<_>>(),
+            [Literal(Int), Separator, Literal(Int)]
+        );
+
         assert_err!(
             "42.0a",
             LexerError::InvalidDigitOnNumber {
@@ -859,6 +884,31 @@ mod tests
                 span: 4.into(),
             }
         );
+
+        // All of a non-ASCII character
+        assert_err!(
+            "1é",
+            LexerError::InvalidDigitOnNumber {
+                base: 10,
+                float: false,
+                span: Span::new(1, 3),
+            }
+        );
+        assert_eq!(tokenize("1é").count(), 1);
+
+        // Missing exponent digits, up to the end of the input
+        assert_err!(
+            "12e",
+            LexerError::MissingExp {
+                span: Span::inclusive(0, 1),
+            }
+        );
+        assert_err!(
+            "12.5e+",
+            LexerError::MissingExp {
+                span: Span::inclusive(0, 3),
+            }
+        );
     }
 
     #[test]
@@ -890,5 +940,25 @@ mod tests
             let sep = str::from_utf8(slice).unwrap();
             assert_token!(sep, sep.len(), Separator);
         }
+
+        for &sep in LONG_SEPARATORS
+        {
+            let sep = str::from_utf8(sep).unwrap();
+            assert_token!(sep, sep.len(), Separator);
+        }
+    }
+
+    #[test]
+    fn span_locations()
+    {
+        let source = "func main()\n{\n    puts(\"🚀\") assert(x)\n}";
+
+        assert_eq!(Span::empty(0).location(source), (1, 1));
+        assert_eq!(Span::empty(5).location(source), (1, 6));
+        assert_eq!(Span::empty(12).location(source), (2, 1));
+        assert_eq!(
+            Span::empty(source.find("assert").unwrap()).location(source),
+            (3, 15)
+        );
     }
 }
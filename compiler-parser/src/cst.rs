@@ -0,0 +1,535 @@
+//! Lossless concrete syntax tree. Unlike the [`AST`], every byte of the source ends up in a token, comments and whitespace
+//! included, so the tree prints back to exactly what it was built from. Items and statements are grouped into nodes, expressions are
+//! left as flat token runs for the AST to make sense of.
+
+use compiler_lexer::{
+    LexerError, LexerEvent,
+    definitions::{LiteralType, Span, TokenType},
+};
+pub use green::{GreenElement, GreenNode, GreenNodeBuilder, GreenToken};
+pub use red::{SyntaxElement, SyntaxNode, SyntaxToken};
+
+use crate::{AST, ParserError, expression::operator};
+
+pub mod green;
+pub mod red;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SyntaxKind
+{
+    // Tokens
+    /// Horizontal whitespace, which the lexer skips over
+    Whitespace,
+    Newline,
+    Comment,
+    Keyword,
+    Identifier,
+    Operator,
+    Literal(LiteralType),
+    Separator,
+
+    // Nodes
+    Root,
+    Import,
+    Const,
+    Function,
+    Extern,
+    /// `@name(args)`, within the item it's on
+    Attribute,
+    Block,
+    Statement,
+    /// Anything at the top level which doesn't start an item
+    Error,
+}
+
+impl SyntaxKind
+{
+    /// Tokens which carry no meaning for the parser
+    #[inline]
+    pub const fn is_trivia(self) -> bool
+    {
+        matches!(self, Self::Whitespace | Self::Newline | Self::Comment)
+    }
+}
+
+impl From<TokenType> for SyntaxKind
+{
+    #[inline]
+    fn from(value: TokenType) -> Self
+    {
+        match value
+        {
+            TokenType::Keyword => Self::Keyword,
+            TokenType::Identifier => Self::Identifier,
+            TokenType::Operator => Self::Operator,
+            TokenType::Literal(r#type) => Self::Literal(r#type),
+            TokenType::Separator => Self::Separator,
+            TokenType::Comment => Self::Comment,
+            TokenType::Newline => Self::Newline,
+        }
+    }
+}
+
+/// Builds the concrete syntax tree of `source`. Only lexer errors are reported, anything the parser would reject still makes it into the
+/// tree.
+pub fn parse(source: &str) -> Result<SyntaxNode, LexerError>
+{
+    let mut tokens = Vec::new();
+    let mut end = 0;
+
+    for event in compiler_lexer::tokenize(source)
+    {
+        let token = match event
+        {
+            LexerEvent::Token(token) => token,
+            LexerEvent::Error(error) => return Err(error),
+        };
+
+        if token.span.start > end
+        {
+            tokens.push((SyntaxKind::Whitespace, Span::new(end, token.span.start)));
+        }
+
+        tokens.push((token.r#type.into(), token.span));
+        end = token.span.end;
+    }
+
+    if source.len() > end
+    {
+        tokens.push((SyntaxKind::Whitespace, Span::new(end, source.len())));
+    }
+
+    let mut builder = Builder {
+        source,
+        tokens,
+        position: 0,
+        green: GreenNodeBuilder::default(),
+    };
+
+    builder.root();
+
+    Ok(SyntaxNode::new_root(builder.green.finish()))
+}
+
+impl SyntaxNode
+{
+    /// Lowers the tree to the [`AST`], dropping the trivia on the way
+    pub fn to_ast(&self) -> Result<AST, ParserError>
+    {
+        let text = self.text();
+        let tokens = crate::lex(&text).expect("The tree only holds tokens which lexed");
+
+        crate::parse(&text, tokens.into_iter())
+    }
+}
+
+struct Builder<'s>
+{
+    source: &'s str,
+    tokens: Vec<(SyntaxKind, Span)>,
+    position: usize,
+    green: GreenNodeBuilder,
+}
+
+impl Builder<'_>
+{
+    #[inline]
+    fn peek(&self) -> Option<(SyntaxKind, &str)>
+    {
+        self.nth(self.position)
+    }
+
+    #[inline]
+    fn nth(&self, n: usize) -> Option<(SyntaxKind, &str)>
+    {
+        self.tokens
+            .get(n)
+            .map(|&(kind, span)| (kind, span.source(self.source)))
+    }
+
+    #[inline]
+    fn bump(&mut self)
+    {
+        let (kind, span) = self.tokens[self.position];
+
+        self.green.token(kind, span.source(self.source));
+        self.position += 1;
+    }
+
+    /// Whether the comment at the cursor documents whatever follows it, which is the case if it starts its own line and no blank line
+    /// separates them
+    fn comment_attaches(&self) -> bool
+    {
+        let trailing = self.tokens[..self.position]
+            .iter()
+            .rfind(|(kind, _)| *kind != SyntaxKind::Whitespace)
+            .is_some_and(|(kind, _)| *kind != SyntaxKind::Newline);
+
+        if trailing
+        {
+            return false;
+        }
+
+        let mut newlines = 0;
+
+        for n in self.position..self.tokens.len()
+        {
+            match self.nth(n)
+            {
+                Some((SyntaxKind::Whitespace, _)) => (),
+                Some((SyntaxKind::Comment, _)) => newlines = 0,
+                Some((SyntaxKind::Newline, _)) if newlines == 0 => newlines = 1,
+                Some((SyntaxKind::Separator, "}")) => return false,
+                Some((kind, _)) => return !kind.is_trivia(),
+                None => break,
+            }
+        }
+
+        false
+    }
+
+    fn root(&mut self)
+    {
+        self.green.start_node(SyntaxKind::Root);
+
+        while let Some((kind, _)) = self.peek()
+        {
+            if kind.is_trivia() && !(kind == SyntaxKind::Comment && self.comment_attaches())
+            {
+                self.bump();
+                continue;
+            }
+
+            let item = self
+                .nth(self.after_attributes())
+                .map_or(SyntaxKind::Error, |t| match t
+                {
+                    (SyntaxKind::Keyword, "import") => SyntaxKind::Import,
+                    (SyntaxKind::Keyword, "const") => SyntaxKind::Const,
+                    (SyntaxKind::Keyword, "func") => SyntaxKind::Function,
+                    (SyntaxKind::Keyword, "extern") => SyntaxKind::Extern,
+                    _ => SyntaxKind::Error,
+                });
+
+            self.green.start_node(item);
+            self.leading_comments();
+
+            while self
+                .peek()
+                .is_some_and(|t| t == (SyntaxKind::Separator, "@"))
+            {
+                self.attribute();
+                self.leading_comments();
+            }
+
+            self.sequence(false);
+            self.green.finish_node();
+        }
+
+        self.green.finish_node();
+    }
+
+    /// Position of the first token past the trivia and attributes at the cursor
+    fn after_attributes(&self) -> usize
+    {
+        let mut n = self.position;
+
+        loop
+        {
+            while self.nth(n).is_some_and(|(kind, _)| kind.is_trivia())
+            {
+                n += 1;
+            }
+
+            if self.nth(n) != Some((SyntaxKind::Separator, "@"))
+            {
+                return n;
+            }
+
+            n = self.attribute_end(n);
+        }
+    }
+
+    /// Position past the attribute whose `@` is at `n`, that is its name and its arguments if any
+    fn attribute_end(&self, n: usize) -> usize
+    {
+        let skip_whitespace = |mut n| {
+            while self
+                .nth(n)
+                .is_some_and(|(kind, _)| kind == SyntaxKind::Whitespace)
+            {
+                n += 1;
+            }
+
+            n
+        };
+
+        let name = skip_whitespace(n + 1);
+        let open = skip_whitespace(name + 1);
+
+        if self.nth(open) != Some((SyntaxKind::Separator, "("))
+        {
+            return name + 1;
+        }
+
+        let mut depth = 0usize;
+
+        for m in open..self.tokens.len()
+        {
+            match self.nth(m)
+            {
+                Some((SyntaxKind::Separator, "(")) => depth += 1,
+                Some((SyntaxKind::Separator, ")")) if depth == 1 => return m + 1,
+                Some((SyntaxKind::Separator, ")")) => depth -= 1,
+                _ => (),
+            }
+        }
+
+        self.tokens.len()
+    }
+
+    fn attribute(&mut self)
+    {
+        let end = self.attribute_end(self.position).min(self.tokens.len());
+
+        self.green.start_node(SyntaxKind::Attribute);
+
+        while self.position < end
+        {
+            self.bump();
+        }
+
+        self.green.finish_node();
+    }
+
+    /// Takes the comments attached to a node into it, up to its first token
+    fn leading_comments(&mut self)
+    {
+        while self.peek().is_some_and(|(kind, _)| kind.is_trivia())
+        {
+            self.bump();
+        }
+    }
+
+    /// Consumes tokens up to the end of the line, or up to the `}` closing the enclosing block if `in_block`, leaving either in place.
+    /// Outside of a block a stray `}` is taken as part of the line.
+    /// Lines are joined inside of parentheses and brackets, after a binary operator and before an `else`.
+    fn sequence(&mut self, in_block: bool)
+    {
+        let mut depth = 0usize;
+
+        while let Some((kind, text)) = self.peek()
+        {
+            match (kind, text)
+            {
+                (SyntaxKind::Newline, _)
+                    if depth == 0 && !self.else_follows() && !self.operator_dangles() =>
+                {
+                    break;
+                }
+                (SyntaxKind::Separator, "}") if in_block => break,
+                (SyntaxKind::Separator, "{") =>
+                {
+                    self.block();
+                    continue;
+                }
+                (SyntaxKind::Separator, "(" | "[") => depth += 1,
+                (SyntaxKind::Separator, ")" | "]") => depth = depth.saturating_sub(1),
+                _ => (),
+            }
+
+            self.bump();
+        }
+    }
+
+    /// Whether the line before the cursor ends on a binary operator, which carries the expression over to the next one
+    fn operator_dangles(&self) -> bool
+    {
+        self.tokens[..self.position]
+            .iter()
+            .rfind(|(kind, _)| !kind.is_trivia())
+            .is_some_and(|&(kind, span)| {
+                kind == SyntaxKind::Operator && operator::binary(span.source(self.source)).is_some()
+            })
+    }
+
+    /// Whether the newline at the cursor is followed by an `else`, continuing an `if`
+    fn else_follows(&self) -> bool
+    {
+        self.tokens[self.position..]
+            .iter()
+            .map(|&(kind, span)| (kind, span.source(self.source)))
+            .find(|(kind, _)| !matches!(kind, SyntaxKind::Whitespace | SyntaxKind::Newline))
+            .is_some_and(|t| t == (SyntaxKind::Identifier, "else"))
+    }
+
+    fn block(&mut self)
+    {
+        self.green.start_node(SyntaxKind::Block);
+        self.bump();
+
+        while let Some((kind, text)) = self.peek()
+        {
+            if (kind, text) == (SyntaxKind::Separator, "}")
+            {
+                self.bump();
+                break;
+            }
+
+            if kind.is_trivia() && !(kind == SyntaxKind::Comment && self.comment_attaches())
+            {
+                self.bump();
+                continue;
+            }
+
+            self.green.start_node(SyntaxKind::Statement);
+            self.leading_comments();
+            self.sequence(true);
+            self.green.finish_node();
+        }
+
+        self.green.finish_node();
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const SOURCE: &str = "// Math helpers\nimport  std.io\n\n\n// Adds them up\n// twice\nfunc add(a i32, b i32) i32 {\n    // Sum\n    let c = (a +\n        b)\n\n    if c {\n        ret c\n    }\n    else {\n        ret 0 // Never\n    }\n}\n";
+
+    #[test]
+    fn round_trips()
+    {
+        let root = parse(SOURCE).unwrap();
+
+        assert_eq!(root.text(), SOURCE);
+        assert_eq!(root.span(), Span::new(0, SOURCE.len()));
+        assert_eq!(
+            root.tokens()
+                .iter()
+                .map(|t| t.text.as_str())
+                .collect::<String>(),
+            SOURCE
+        );
+    }
+
+    #[test]
+    fn groups_items_and_statements()
+    {
+        let root = parse(SOURCE).unwrap();
+        let items = root.child_nodes().collect::<Vec<_>>();
+
+        assert_eq!(
+            items.iter().map(SyntaxNode::kind).collect::<Vec<_>>(),
+            [SyntaxKind::Import, SyntaxKind::Function]
+        );
+        // Comments attach to the item right below them, but not across a blank line
+        assert_eq!(items[0].text(), "// Math helpers\nimport  std.io");
+        assert_eq!(
+            items[1].text(),
+            SOURCE[SOURCE.find("// Adds").unwrap()..SOURCE.len() - 1]
+        );
+
+        let block = items[1]
+            .child_nodes()
+            .find(|n| n.kind() == SyntaxKind::Block)
+            .unwrap();
+        let statements = block.child_nodes().map(|n| n.text()).collect::<Vec<_>>();
+
+        assert_eq!(
+            statements,
+            [
+                "// Sum\n    let c = (a +\n        b)",
+                "if c {\n        ret c\n    }\n    else {\n        ret 0 // Never\n    }"
+            ]
+        );
+
+        let span = block.child_nodes().next().unwrap().span();
+        assert_eq!(span.source(SOURCE), statements[0]);
+        assert_eq!(block.parent().unwrap().kind(), SyntaxKind::Function);
+    }
+
+    #[test]
+    fn trailing_comments_stay_behind()
+    {
+        let root = parse("func f() { // Body\n    ret\n}\n").unwrap();
+        let block = root
+            .child_nodes()
+            .flat_map(|n| n.child_nodes().collect::<Vec<_>>())
+            .next()
+            .unwrap();
+
+        assert_eq!(
+            block
+                .children()
+                .map(|c| c.kind())
+                .filter(|k| *k != SyntaxKind::Whitespace)
+                .collect::<Vec<_>>(),
+            [
+                SyntaxKind::Separator,
+                SyntaxKind::Comment,
+                SyntaxKind::Newline,
+                SyntaxKind::Statement,
+                SyntaxKind::Newline,
+                SyntaxKind::Separator
+            ]
+        );
+    }
+
+    #[test]
+    fn joins_lines_ending_on_an_operator()
+    {
+        let root =
+            parse("func f() i32 {\n    g(1 -\n        2)\n    ret 1 + // One\n\n        2\n}\n")
+                .unwrap();
+        let block = root
+            .child_nodes()
+            .flat_map(|n| n.child_nodes().collect::<Vec<_>>())
+            .next()
+            .unwrap();
+        let statements = block.child_nodes().map(|n| n.text()).collect::<Vec<_>>();
+
+        assert_eq!(
+            statements,
+            ["g(1 -\n        2)", "ret 1 + // One\n\n        2"]
+        );
+    }
+
+    #[test]
+    fn groups_attributes_with_their_item()
+    {
+        let source = "@inline\n// Doubles it\n@link_name(\"twice\") func double(a i32) i32 {\n    ret a * 2\n}\n";
+        let root = parse(source).unwrap();
+        let items = root.child_nodes().collect::<Vec<_>>();
+
+        assert_eq!(
+            items.iter().map(SyntaxNode::kind).collect::<Vec<_>>(),
+            [SyntaxKind::Function]
+        );
+        assert_eq!(
+            items[0]
+                .child_nodes()
+                .filter(|n| n.kind() == SyntaxKind::Attribute)
+                .map(|n| n.text())
+                .collect::<Vec<_>>(),
+            ["@inline", "@link_name(\"twice\")"]
+        );
+        assert_eq!(root.text(), source);
+    }
+
+    #[test]
+    fn converts_to_ast()
+    {
+        let source = "// Entry point\nfunc main() i32 {\n    ret 0 // Nothing\n}\n\nconst A i32 = 1 // One\n";
+        let tokens = crate::lex(source).unwrap();
+
+        assert_eq!(
+            parse(source).unwrap().to_ast().unwrap(),
+            crate::parse(source, tokens.into_iter()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Immutable, position independent half of the tree. Nodes only know their width, so identical subtrees may be shared.
+
+use std::{fmt, rc::Rc};
+
+use ecow::{EcoString, EcoVec};
+
+use super::SyntaxKind;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GreenToken
+{
+    pub kind: SyntaxKind,
+    pub text: EcoString,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GreenNode
+{
+    pub kind: SyntaxKind,
+    /// Length in bytes of the text under this node
+    pub width: usize,
+    pub children: EcoVec<GreenElement>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GreenElement
+{
+    Node(Rc<GreenNode>),
+    Token(Rc<GreenToken>),
+}
+
+impl GreenElement
+{
+    #[inline]
+    pub fn kind(&self) -> SyntaxKind
+    {
+        match self
+        {
+            Self::Node(node) => node.kind,
+            Self::Token(token) => token.kind,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize
+    {
+        match self
+        {
+            Self::Node(node) => node.width,
+            Self::Token(token) => token.text.len(),
+        }
+    }
+}
+
+impl GreenNode
+{
+    #[inline]
+    pub fn new(kind: SyntaxKind, children: EcoVec<GreenElement>) -> Self
+    {
+        Self {
+            kind,
+            width: children.iter().map(GreenElement::width).sum(),
+            children,
+        }
+    }
+}
+
+impl fmt::Display for GreenNode
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        self.children.iter().try_for_each(|c| match c
+        {
+            GreenElement::Node(node) => write!(f, "{node}"),
+            GreenElement::Token(token) => write!(f, "{}", token.text),
+        })
+    }
+}
+
+/// Builds a tree bottom up, rowan style: nodes are opened, filled with tokens and nested nodes, then closed
+#[derive(Default)]
+pub struct GreenNodeBuilder
+{
+    stack: Vec<(SyntaxKind, EcoVec<GreenElement>)>,
+    /// Where the last closed node ended up, in case it's the root
+    root: Option<Rc<GreenNode>>,
+}
+
+impl GreenNodeBuilder
+{
+    #[inline]
+    pub fn start_node(&mut self, kind: SyntaxKind)
+    {
+        self.stack.push((kind, EcoVec::new()));
+    }
+
+    #[inline]
+    pub fn token(&mut self, kind: SyntaxKind, text: &str)
+    {
+        let token = GreenElement::Token(Rc::new(GreenToken {
+            kind,
+            text: text.into(),
+        }));
+
+        self.stack
+            .last_mut()
+            .expect("Tokens must go into a node")
+            .1
+            .push(token);
+    }
+
+    pub fn finish_node(&mut self)
+    {
+        let (kind, children) = self.stack.pop().expect("No node to finish");
+        let node = Rc::new(GreenNode::new(kind, children));
+
+        match self.stack.last_mut()
+        {
+            Some((_, parent)) => parent.push(GreenElement::Node(node)),
+            None => self.root = Some(node),
+        }
+    }
+
+    /// Returns the root, once every node is finished
+    #[inline]
+    pub fn finish(self) -> Rc<GreenNode>
+    {
+        assert!(self.stack.is_empty(), "Unfinished nodes left");
+
+        self.root.expect("No root node")
+    }
+}
@@ -0,0 +1,205 @@
+//! Navigable half of the tree, built lazily on top of the green one. Red nodes know their absolute span and their parent.
+
+use std::{fmt, rc::Rc};
+
+use compiler_lexer::definitions::Span;
+use ecow::EcoString;
+
+use super::{
+    SyntaxKind,
+    green::{GreenElement, GreenNode},
+};
+
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData
+{
+    green: Rc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+#[derive(Clone)]
+pub struct SyntaxToken
+{
+    pub kind: SyntaxKind,
+    pub text: EcoString,
+    pub span: Span,
+    pub parent: SyntaxNode,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement
+{
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxElement
+{
+    #[inline]
+    pub fn kind(&self) -> SyntaxKind
+    {
+        match self
+        {
+            Self::Node(node) => node.kind(),
+            Self::Token(token) => token.kind,
+        }
+    }
+
+    #[inline]
+    pub fn span(&self) -> Span
+    {
+        match self
+        {
+            Self::Node(node) => node.span(),
+            Self::Token(token) => token.span,
+        }
+    }
+}
+
+impl SyntaxNode
+{
+    #[inline]
+    pub fn new_root(green: Rc<GreenNode>) -> Self
+    {
+        Self(Rc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    #[inline]
+    pub fn green(&self) -> &Rc<GreenNode>
+    {
+        &self.0.green
+    }
+
+    #[inline]
+    pub fn kind(&self) -> SyntaxKind
+    {
+        self.0.green.kind
+    }
+
+    #[inline]
+    pub fn span(&self) -> Span
+    {
+        Span::new(self.0.offset, self.0.offset + self.0.green.width)
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<SyntaxNode>
+    {
+        self.0.parent.clone()
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = SyntaxElement> + '_
+    {
+        self.0
+            .green
+            .children
+            .iter()
+            .scan(self.0.offset, move |offset, child| {
+                let start = *offset;
+                *offset += child.width();
+
+                Some(match child
+                {
+                    GreenElement::Node(node) =>
+                    {
+                        SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                            green: Rc::clone(node),
+                            offset: start,
+                            parent: Some(self.clone()),
+                        })))
+                    }
+                    GreenElement::Token(token) => SyntaxElement::Token(SyntaxToken {
+                        kind: token.kind,
+                        text: token.text.clone(),
+                        span: Span::new(start, *offset),
+                        parent: self.clone(),
+                    }),
+                })
+            })
+    }
+
+    #[inline]
+    pub fn child_nodes(&self) -> impl Iterator<Item = SyntaxNode> + '_
+    {
+        self.children().filter_map(|c| match c
+        {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Every token under this node, in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken>
+    {
+        self.children()
+            .flat_map(|c| match c
+            {
+                SyntaxElement::Node(node) => node.tokens(),
+                SyntaxElement::Token(token) => vec![token],
+            })
+            .collect()
+    }
+
+    /// The exact source text this node was built from
+    #[inline]
+    pub fn text(&self) -> String
+    {
+        self.0.green.to_string()
+    }
+}
+
+impl fmt::Display for SyntaxNode
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+/// Dumps the tree, a line per element, e.g. `Keyword@0..4 "func"`
+impl fmt::Debug for SyntaxNode
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let depth = f.width().unwrap_or_default();
+        let span = self.span();
+
+        writeln!(
+            f,
+            "{:depth$}{:?}@{}..{}",
+            "",
+            self.kind(),
+            span.start,
+            span.end
+        )?;
+
+        self.children().try_for_each(|c| match c
+        {
+            SyntaxElement::Node(node) => write!(f, "{:1$?}", node, depth + 2),
+            SyntaxElement::Token(token) => writeln!(f, "{:1$?}", token, depth + 2),
+        })
+    }
+}
+
+impl fmt::Debug for SyntaxToken
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let depth = f.width().unwrap_or_default();
+
+        write!(
+            f,
+            "{:depth$}{:?}@{}..{} {:?}",
+            "", self.kind, self.span.start, self.span.end, self.text
+        )
+    }
+}
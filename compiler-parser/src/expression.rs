@@ -1,9 +1,11 @@
-use compiler_lexer::definitions::{LiteralType, Token, TokenType};
+use compiler_lexer::definitions::{LiteralType, Span, Token, TokenType};
 use ecow::{EcoString, EcoVec};
 use operator::{Operator, to_operator};
+use serde::Serialize;
 
 use crate::{
-    ParserError, TokenIt,
+    ParserError, TokenIt, Type,
+    item::FunctionSignature,
     iterator::{ExhaustiveGet, TokenItTrait},
     statement::Statement,
 };
@@ -11,21 +13,72 @@ use crate::{
 pub mod binary;
 pub mod operator;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Expression
 {
     Literal
     {
         value: EcoString,
         r#type: LiteralType,
+        span: Span,
+    },
+    Path
+    {
+        path: EcoVec<EcoString>,
+        span: Span,
     },
-    Path(EcoVec<EcoString>),
     Binary(Box<binary::Node>),
     Unary(Operator, Box<Expression>),
+    /// `target as type`, binding tighter than any binary operator
+    Cast
+    {
+        target: Box<Expression>,
+        r#type: Type,
+        /// From the `as` to the end of the type
+        span: Span,
+    },
+    /// `callee(args)`, as in `get()(3)`. On paths like `a.b.c(args)`, whether `a.b` names a module or a value, making this a method
+    /// call on it, is only known once resolved
     Call
     {
-        path: EcoVec<EcoString>,
+        callee: Box<Expression>,
+        args: EcoVec<Expression>,
+        /// Of the `(`, which diagnostics on calls of anything but a path point at. Those of paths point at the path instead
+        span: Span,
+    },
+    /// `receiver.name(args)`, on a receiver which isn't a path, e.g. `f().g()`
+    Method
+    {
+        receiver: Box<Expression>,
+        name: EcoString,
         args: EcoVec<Expression>,
+        /// Of the name
+        span: Span,
+    },
+    /// `target.name`, on a target which isn't a path, e.g. `f().len`. Paths keep theirs, as on calls
+    Field
+    {
+        target: Box<Expression>,
+        name: EcoString,
+        /// Of the name
+        span: Span,
+    },
+    /// `target[index]`
+    Index
+    {
+        target: Box<Expression>,
+        index: Box<Expression>,
+        /// From the `[` to the `]`, which bounds check failures point at
+        span: Span,
+    },
+    /// `target[start..end]`, either bound may be left out
+    Slice
+    {
+        target: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+        /// From the `[` to the `]`, which bounds check failures point at
+        span: Span,
     },
     If
     {
@@ -36,9 +89,67 @@ pub enum Expression
     Parenthesis(Box<Expression>),
     Tuple(EcoVec<Expression>),
     Array(EcoVec<Expression>),
+    /// `func[a, &b](args) type { body }`, capturing the locals listed. The signature's name is left empty
+    Closure
+    {
+        captures: EcoVec<Capture>,
+        signature: Box<FunctionSignature>,
+        body: EcoVec<Statement>,
+    },
     // TODO Block
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum CaptureKind
+{
+    /// Copied into the closure as it's created
+    Value,
+    /// `&name`
+    Ref,
+    /// `&mut name`
+    MutRef,
+}
+
+/// A local captured by a closure, along with the span of its name
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Capture
+{
+    pub name: EcoString,
+    pub kind: CaptureKind,
+    pub span: Span,
+}
+
+impl Capture
+{
+    pub fn parse(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
+    {
+        let kind = if tokens.next_is("&").is_none()
+        {
+            CaptureKind::Value
+        }
+        else if tokens.next_is("mut").is_some()
+        {
+            CaptureKind::MutRef
+        }
+        else
+        {
+            CaptureKind::Ref
+        };
+
+        let identifier = tokens.next(|t| t.r#type == TokenType::Identifier).ok_or(
+            ParserError::ExpectedTokenType {
+                r#type: "Identifier",
+            },
+        )?;
+
+        Ok(Self {
+            name: tokens.text(&identifier).into(),
+            kind,
+            span: identifier.span,
+        })
+    }
+}
+
 impl<I: TokenItTrait> ExhaustiveGet<I> for Expression
 {
     fn find_predicate(tokens: &mut TokenIt<I>) -> Result<Self::ParsePredicate, ParserError>
@@ -47,6 +158,11 @@ impl<I: TokenItTrait> ExhaustiveGet<I> for Expression
 
         base_predicate(tokens)?; // Consume whichever base so we can peek ahead
 
+        // Casts only apply to the operand before them
+        let cast = tokens.peek_is("as");
+        while Self::parse_cast_type(tokens)?.is_some()
+        {}
+
         if tokens
             .0
             .peek()
@@ -56,7 +172,16 @@ impl<I: TokenItTrait> ExhaustiveGet<I> for Expression
         }
         else
         {
-            Ok(base_predicate)
+            Ok(
+                if cast
+                {
+                    Self::parse_cast
+                }
+                else
+                {
+                    base_predicate
+                },
+            )
         }
     }
 }
@@ -67,10 +192,14 @@ impl Expression
         tokens: &mut TokenIt<I>,
     ) -> Result<<Expression as ExhaustiveGet<I>>::ParsePredicate, ParserError>
     {
-        if tokens.0.peek().is_some_and(|t| t.value == "if")
+        if tokens.peek_is("if")
         {
             Ok(Self::parse_if)
         }
+        else if tokens.peek_is("func")
+        {
+            Ok(Self::parse_closure)
+        }
         else if tokens
             .0
             .peek()
@@ -85,7 +214,7 @@ impl Expression
         {
             Ok(Self::parse_literal)
         }
-        else if tokens.0.peek().is_some_and(|t| t.value == "[")
+        else if tokens.peek_is("[")
         {
             Ok(Self::parse_array)
         }
@@ -94,10 +223,10 @@ impl Expression
             {
                 let mut tokens = tokens.clone();
 
-                if tokens.next(|t| t.value == "(").is_some() && Expression::get(&mut tokens).is_ok()
+                if tokens.next_is("(").is_some() && Expression::get(&mut tokens).is_ok()
                 {
                     // TODO this won't work properly with a leading colon, as probably other things won't either. make a decision on this
-                    if tokens.0.peek().is_some_and(|t| t.value == ")")
+                    if tokens.peek_is(")")
                     {
                         return Ok(Self::parse_parenthesis);
                     }
@@ -113,9 +242,9 @@ impl Expression
 
                 if Self::parse_path(&mut tokens).is_ok()
                 {
-                    if tokens.0.peek().is_some_and(|t| t.value == "(")
+                    if tokens.peek_is("(") || tokens.peek_is("[")
                     {
-                        return Ok(Self::parse_call);
+                        return Ok(Self::parse_indexed);
                     }
                     else
                     {
@@ -132,9 +261,8 @@ impl Expression
     pub fn parse_literal(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
     {
         let Token {
-            value,
             r#type: TokenType::Literal(lit_type),
-            ..
+            span,
         } = tokens
             .next(|t| matches!(t.r#type, TokenType::Literal(_)))
             .ok_or(ParserError::ExpectedTokenType { r#type: "Literal" })?
@@ -143,81 +271,252 @@ impl Expression
             unreachable!()
         };
 
-        Ok(Self::Literal {
-            value,
+        let literal = Self::Literal {
+            value: span.source(tokens.1).into(),
             r#type: lit_type,
-        })
+            span,
+        };
+
+        // Strings are arrays, which may be indexed and sliced
+        if lit_type == LiteralType::String
+        {
+            return Self::parse_postfix(tokens, literal);
+        }
+
+        Ok(literal)
     }
 
+    #[inline]
     pub fn parse_path(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
+    {
+        Self::parse_segments(tokens).map(|(path, span)| Self::Path { path, span })
+    }
+
+    /// Parses the segments of `a.b.c`, along with the span they cover
+    pub(crate) fn parse_segments(
+        tokens: &mut TokenIt<impl TokenItTrait>,
+    ) -> Result<(EcoVec<EcoString>, Span), ParserError>
     {
         let mut path = EcoVec::new();
+        let mut span = Span::empty(0);
 
-        while path.is_empty() || tokens.next(|t| t.value == ".").is_some()
+        // Not `tokens.next`, which would take the newline ending a statement along with it
+        while path.is_empty() || tokens.next_if_is(".").is_some()
         {
-            let segment = tokens
-                .next(|t| t.r#type == TokenType::Identifier)
-                .ok_or(ParserError::ExpectedTokenType {
+            let segment = tokens.next(|t| t.r#type == TokenType::Identifier).ok_or(
+                ParserError::ExpectedTokenType {
                     r#type: "Identifier",
-                })?
-                .value;
-            path.push(segment);
+                },
+            )?;
+
+            span = if path.is_empty()
+            {
+                segment.span
+            }
+            else
+            {
+                Span::new(span.start, segment.span.end)
+            };
+            path.push(tokens.text(&segment).into());
+        }
+
+        Ok((path, span))
+    }
+
+    /// A path followed by calls, indexing or method calls, as in `a[i].f()` or `get()(3)`
+    #[inline]
+    pub fn parse_indexed(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
+    {
+        let path = Self::parse_path(tokens)?;
+
+        Self::parse_postfix(tokens, path)
+    }
+
+    /// An operand followed by any casts of it, as in `a as i64 as f64`
+    pub fn parse_cast(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
+    {
+        let target = (Self::shallow_find_predicate(&mut tokens.clone())?)(tokens)?;
+
+        Self::parse_casts(tokens, target)
+    }
+
+    /// Wraps `target` in the casts following it, if any
+    pub fn parse_casts(
+        tokens: &mut TokenIt<impl TokenItTrait>,
+        mut target: Self,
+    ) -> Result<Self, ParserError>
+    {
+        while let Some((r#type, span)) = Self::parse_cast_type(tokens)?
+        {
+            target = Self::Cast {
+                target: Box::new(target),
+                r#type,
+                span,
+            };
         }
 
-        Ok(Self::Path(path))
+        Ok(target)
+    }
+
+    /// `as type`, where the type is a single name such as `i64`. Casts only convert between numbers, so nothing longer is needed
+    fn parse_cast_type(
+        tokens: &mut TokenIt<impl TokenItTrait>,
+    ) -> Result<Option<(Type, Span)>, ParserError>
+    {
+        let Some(r#as) = tokens.next_if_is("as")
+        else
+        {
+            return Ok(None);
+        };
+
+        let r#type = tokens.next(|t| t.r#type == TokenType::Identifier).ok_or(
+            ParserError::ExpectedTokenType {
+                r#type: "Identifier",
+            },
+        )?;
+
+        Ok(Some((
+            Type(vec![tokens.text(&r#type).into()]),
+            Span::new(r#as.span.start, r#type.span.end),
+        )))
     }
 
     #[inline]
     pub fn parse_binary(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
     {
-        println!("parse_binary");
         // TODO RPN should prolly be bettered.
         let node = binary::Node::parse(tokens)?;
-        println!("parse_binary_end");
 
         Ok(Self::Binary(Box::new(node)))
     }
 
-    pub fn parse_call(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
+    /// Chains the calls, method calls, fields, indexing and slicing following `receiver`, as in `receiver.a()[i..].b(c)`
+    pub fn parse_postfix(
+        tokens: &mut TokenIt<impl TokenItTrait>,
+        mut receiver: Self,
+    ) -> Result<Self, ParserError>
     {
-        let Self::Path(path) = Self::parse_path(tokens)?
-        else
+        // As on paths, the newline ending a statement must stay
+        loop
         {
-            unreachable!()
-        };
+            if let Some(open) = tokens.0.peek().filter(|t| t.span.source(tokens.1) == "(")
+            {
+                let span = Span::single(open.span.start);
+                let args = tokens.consume_generic_list(("(", ")"), Expression::get, Some(","))?;
+
+                receiver = Self::Call {
+                    callee: Box::new(receiver),
+                    args,
+                    span,
+                };
+
+                continue;
+            }
+
+            if let Some(open) = tokens.next_if_is("[")
+            {
+                tokens.ignore_newlines();
+
+                let start = if tokens.peek_is("..")
+                {
+                    None
+                }
+                else
+                {
+                    Some(Box::new(Expression::get(tokens)?))
+                };
+
+                let range = tokens.next_is("..").is_some();
+                let end = if range && tokens.0.peek().is_some() && !tokens.peek_is("]")
+                {
+                    Some(Box::new(Expression::get(tokens)?))
+                }
+                else
+                {
+                    None
+                };
 
-        let args = tokens.consume_generic_list(("(", ")"), Expression::get, Some(","))?;
+                let close = tokens
+                    .next_is("]")
+                    .ok_or(ParserError::ExpectedTokenValue { value: "]".into() })?;
+                let span = Span::new(open.span.start, close.span.end);
 
-        Ok(Self::Call { path, args })
+                receiver = match (range, start)
+                {
+                    (false, Some(index)) => Self::Index {
+                        target: Box::new(receiver),
+                        index,
+                        span,
+                    },
+                    (_, start) => Self::Slice {
+                        target: Box::new(receiver),
+                        start,
+                        end,
+                        span,
+                    },
+                };
+
+                continue;
+            }
+
+            if tokens.next_if_is(".").is_none()
+            {
+                break;
+            }
+
+            let name = tokens.next(|t| t.r#type == TokenType::Identifier).ok_or(
+                ParserError::ExpectedTokenType {
+                    r#type: "Identifier",
+                },
+            )?;
+
+            if !tokens.peek_is("(")
+            {
+                receiver = Self::Field {
+                    target: Box::new(receiver),
+                    name: tokens.text(&name).into(),
+                    span: name.span,
+                };
+
+                continue;
+            }
+
+            let args = tokens.consume_generic_list(("(", ")"), Expression::get, Some(","))?;
+
+            receiver = Self::Method {
+                receiver: Box::new(receiver),
+                name: tokens.text(&name).into(),
+                args,
+                span: name.span,
+            };
+        }
+
+        Ok(receiver)
     }
 
     pub fn parse_if(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
     {
         tokens
-            .next(|t| t.value == "if")
+            .next_is("if")
             .ok_or(ParserError::ExpectedTokenValue { value: "if".into() })?;
 
         // TODO ignore_newlines might not be necessary? if when we get next we always skip newline. is this viable? try and test.
         tokens.ignore_newlines();
 
-        println!("0");
-
         let condition = Expression::get(tokens)?;
 
-        println!("1");
-
         tokens.ignore_newlines();
 
-        println!("2");
-
         let block = tokens.consume_block()?;
 
-        println!("3");
-
-        tokens.ignore_newlines();
+        // The newlines only belong to the `if` when an `else` follows, otherwise they end the statement
+        let mut lookahead = tokens.clone();
+        lookahead.ignore_newlines();
 
-        let r#else = if tokens.next(|t| t.value == "else").is_some()
+        let r#else = if lookahead.peek_is("else")
         {
+            *tokens = lookahead;
+            tokens.0.next();
             tokens.ignore_newlines();
 
             Some(tokens.consume_block()?)
@@ -234,18 +533,57 @@ impl Expression
         })
     }
 
+    pub fn parse_closure(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
+    {
+        let func = tokens
+            .next_is("func")
+            .ok_or(ParserError::ExpectedTokenValue {
+                value: "func".into(),
+            })?;
+
+        let captures = if tokens.peek_is("[")
+        {
+            tokens.consume_generic_list(("[", "]"), Capture::parse, Some(","))?
+        }
+        else
+        {
+            EcoVec::new()
+        };
+
+        let signature =
+            FunctionSignature::parse_unnamed(tokens, (EcoString::new(), func.span), |_, t| {
+                t != "{"
+            })?;
+
+        if signature.variadic || !signature.generics.is_empty()
+        {
+            return Err(ParserError::ExpectedASTStructure {
+                name: "non-generic, non-variadic closure",
+            });
+        }
+
+        let body = tokens.consume_block()?;
+
+        Ok(Self::Closure {
+            captures,
+            signature: Box::new(signature),
+            body,
+        })
+    }
+
     pub fn parse_unary(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
     {
         let operator = tokens
             .next(|t| t.r#type == TokenType::Operator)
             .ok_or(ParserError::ExpectedTokenType { r#type: "Operator" })?;
-        let operator @ (Operator::Minus | Operator::Star) = to_operator(&operator)
+        let Ok(operator @ (Operator::Minus | Operator::Star)) = to_operator(&operator, tokens.1)
         else
         {
             return Err(ParserError::IllegalUnary { token: operator });
         };
 
-        let e = Expression::get(tokens)?;
+        // Only the operand right after the operator, so that `-a - b` is `(-a) - b`
+        let e = (Self::shallow_find_predicate(&mut tokens.clone())?)(tokens)?;
 
         Ok(Self::Unary(operator, Box::new(e)))
     }
@@ -253,16 +591,16 @@ impl Expression
     pub fn parse_parenthesis(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
     {
         tokens
-            .next(|t| t.value == "(")
+            .next_is("(")
             .ok_or(ParserError::ExpectedTokenValue { value: "(".into() })?;
 
         let e = Expression::get(tokens)?;
 
         tokens
-            .next(|t| t.value == ")")
+            .next_is(")")
             .ok_or(ParserError::ExpectedTokenValue { value: ")".into() })?;
 
-        Ok(Self::Parenthesis(Box::new(e)))
+        Self::parse_postfix(tokens, Self::Parenthesis(Box::new(e)))
     }
 
     #[inline]
@@ -278,18 +616,16 @@ impl Expression
     #[inline]
     pub fn parse_array(tokens: &mut TokenIt<impl TokenItTrait>) -> Result<Self, ParserError>
     {
-        Ok(Self::Array(tokens.consume_generic_list(
-            ("[", "]"),
-            Expression::get,
-            Some(","),
-        )?))
+        let array =
+            Self::Array(tokens.consume_generic_list(("[", "]"), Expression::get, Some(","))?);
+
+        Self::parse_postfix(tokens, array)
     }
 }
 
 #[cfg(test)]
 mod tests
 {
-    use compiler_lexer::definitions::Span;
     use pretty_assertions::assert_eq;
 
     use super::*;
@@ -301,101 +637,401 @@ mod tests
     fn path_passes()
     {
         assert_eq!(
-            Expression::parse_path(&mut TokenIt(
-                compiler_lexer::tokenize("a.path.to").flatten().peekable()
-            )),
-            Ok(Expression::Path(
-                vec!["a".into(), "path".into(), "to".into()].into()
-            ))
+            Expression::parse_path(&mut TokenIt::lexed("a.path.to")),
+            Ok(Expression::Path {
+                path: vec!["a".into(), "path".into(), "to".into()].into(),
+                span: Span::new(0, 9)
+            })
         );
+
+        let mut tokens = TokenIt::lexed("a.b\nc");
+
+        assert_eq!(
+            Expression::parse_path(&mut tokens),
+            Ok(Expression::Path {
+                path: vec!["a".into(), "b".into()].into(),
+                span: Span::new(0, 3)
+            })
+        );
+        assert_eq!(tokens.0.next().map(|t| t.r#type), Some(TokenType::Newline));
     }
 
     #[test]
     fn call_passes()
     {
         assert_eq!(
-            Expression::parse_call(&mut TokenIt(
-                compiler_lexer::tokenize("call_me(     )")
-                    .flatten()
-                    .peekable()
-            )),
+            Expression::parse_indexed(&mut TokenIt::lexed("call_me(     )")),
             Ok(Expression::Call {
-                path: vec!["call_me".into()].into(),
-                args: vec![].into()
+                callee: Box::new(Expression::Path {
+                    path: vec!["call_me".into()].into(),
+                    span: Span::new(0, 7)
+                }),
+                args: vec![].into(),
+                span: Span::single(7)
             })
         );
 
         assert_eq!(
-            Expression::parse_call(&mut TokenIt(
-                compiler_lexer::tokenize("call  .me()").flatten().peekable()
-            )),
+            Expression::parse_indexed(&mut TokenIt::lexed("call  .me()")),
             Ok(Expression::Call {
-                path: vec!["call".into(), "me".into()].into(),
-                args: vec![].into()
+                callee: Box::new(Expression::Path {
+                    path: vec!["call".into(), "me".into()].into(),
+                    span: Span::new(0, 9)
+                }),
+                args: vec![].into(),
+                span: Span::single(9)
             })
         );
 
         assert_eq!(
-            Expression::parse_call(&mut TokenIt(
-                compiler_lexer::tokenize("fn    (2)").flatten().peekable()
-            )),
+            Expression::parse_indexed(&mut TokenIt::lexed("fn    (2)")),
             Ok(Expression::Call {
-                path: vec!["fn".into()].into(),
+                callee: Box::new(Expression::Path {
+                    path: vec!["fn".into()].into(),
+                    span: Span::new(0, 2)
+                }),
                 args: vec![Expression::Literal {
                     value: "2".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(7, 8),
                 }]
-                .into()
+                .into(),
+                span: Span::single(6)
             })
         );
 
         assert_eq!(
-            Expression::parse_call(&mut TokenIt(
-                compiler_lexer::tokenize("fn. path(\n\n\n420,`j`\n\n ,\n6\n)")
-                    .flatten()
-                    .peekable()
-            )),
+            Expression::parse_indexed(&mut TokenIt::lexed("fn. path(\n\n\n420,`j`\n\n ,\n6\n)")),
             Ok(Expression::Call {
-                path: vec!["fn".into(), "path".into()].into(),
+                callee: Box::new(Expression::Path {
+                    path: vec!["fn".into(), "path".into()].into(),
+                    span: Span::new(0, 8)
+                }),
                 args: vec![
                     Expression::Literal {
                         value: "420".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(12, 15),
                     },
                     Expression::Literal {
                         value: "`j`".into(),
-                        r#type: LiteralType::Rune
+                        r#type: LiteralType::Rune,
+                        span: Span::new(16, 19),
                     },
                     Expression::Literal {
                         value: "6".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(24, 25),
                     }
                 ]
-                .into()
+                .into(),
+                span: Span::single(8)
+            })
+        );
+
+        // Whatever a call returns may be called in turn
+        assert_eq!(
+            Expression::get(&mut TokenIt::lexed("get()(3)\n(f)(4)")),
+            Ok(Expression::Call {
+                callee: Box::new(Expression::Call {
+                    callee: Box::new(Expression::Path {
+                        path: vec!["get".into()].into(),
+                        span: Span::new(0, 3)
+                    }),
+                    args: vec![].into(),
+                    span: Span::single(3)
+                }),
+                args: vec![Expression::Literal {
+                    value: "3".into(),
+                    r#type: LiteralType::Int,
+                    span: Span::single(6),
+                }]
+                .into(),
+                span: Span::single(5)
+            })
+        );
+
+        assert_eq!(
+            Expression::get(&mut TokenIt::lexed("(f)(4)")),
+            Ok(Expression::Call {
+                callee: Box::new(Expression::Parenthesis(Box::new(Expression::Path {
+                    path: vec!["f".into()].into(),
+                    span: Span::single(1)
+                }))),
+                args: vec![Expression::Literal {
+                    value: "4".into(),
+                    r#type: LiteralType::Int,
+                    span: Span::single(4),
+                }]
+                .into(),
+                span: Span::single(3)
             })
         );
 
         // TODO better this, make sure we have good errors
         // also this probably panics atm lol gotta make this good
-        assert!(
-            Expression::parse_call(&mut TokenIt(
-                compiler_lexer::tokenize("fn.()").flatten().peekable()
-            ))
-            .is_err()
+        assert!(Expression::parse_indexed(&mut TokenIt::lexed("fn.()")).is_err());
+
+        assert!(Expression::parse_indexed(&mut TokenIt::lexed("fn(42, )")).is_err());
+
+        assert!(Expression::parse_indexed(&mut TokenIt::lexed("fn(, 42)")).is_err());
+    }
+
+    #[test]
+    fn method_passes()
+    {
+        let mut tokens = TokenIt::lexed("a.b(1).c()\nd");
+
+        assert_eq!(
+            Expression::parse_indexed(&mut tokens),
+            Ok(Expression::Method {
+                receiver: Box::new(Expression::Call {
+                    callee: Box::new(Expression::Path {
+                        path: vec!["a".into(), "b".into()].into(),
+                        span: Span::new(0, 3)
+                    }),
+                    args: vec![Expression::Literal {
+                        value: "1".into(),
+                        r#type: LiteralType::Int,
+                        span: Span::new(4, 5),
+                    }]
+                    .into(),
+                    span: Span::single(3)
+                }),
+                name: "c".into(),
+                args: vec![].into(),
+                span: Span::single(7)
+            })
         );
+        assert_eq!(tokens.0.next().map(|t| t.r#type), Some(TokenType::Newline));
 
-        assert!(
-            Expression::parse_call(&mut TokenIt(
-                compiler_lexer::tokenize("fn(42, )").flatten().peekable()
-            ))
-            .is_err()
+        assert_eq!(
+            Expression::get(&mut TokenIt::lexed("(a).b(c)")),
+            Ok(Expression::Method {
+                receiver: Box::new(Expression::Parenthesis(Box::new(Expression::Path {
+                    path: vec!["a".into()].into(),
+                    span: Span::single(1)
+                }))),
+                name: "b".into(),
+                args: vec![Expression::Path {
+                    path: vec!["c".into()].into(),
+                    span: Span::single(6)
+                }]
+                .into(),
+                span: Span::single(4)
+            })
         );
 
-        assert!(
-            Expression::parse_call(&mut TokenIt(
-                compiler_lexer::tokenize("fn(, 42)").flatten().peekable()
-            ))
-            .is_err()
+        // Without arguments, it's a field
+        assert_eq!(
+            Expression::parse_indexed(&mut TokenIt::lexed("a().b")),
+            Ok(Expression::Field {
+                target: Box::new(Expression::Call {
+                    callee: Box::new(Expression::Path {
+                        path: vec!["a".into()].into(),
+                        span: Span::single(0)
+                    }),
+                    args: vec![].into(),
+                    span: Span::single(1)
+                }),
+                name: "b".into(),
+                span: Span::single(4)
+            })
+        );
+    }
+
+    #[test]
+    fn index_passes()
+    {
+        let mut tokens = TokenIt::lexed("a[i][1].f()\n[2]");
+
+        assert_eq!(
+            Expression::get(&mut tokens),
+            Ok(Expression::Method {
+                receiver: Box::new(Expression::Index {
+                    target: Box::new(Expression::Index {
+                        target: Box::new(Expression::Path {
+                            path: vec!["a".into()].into(),
+                            span: Span::single(0)
+                        }),
+                        index: Box::new(Expression::Path {
+                            path: vec!["i".into()].into(),
+                            span: Span::single(2)
+                        }),
+                        span: Span::new(1, 4)
+                    }),
+                    index: Box::new(Expression::Literal {
+                        value: "1".into(),
+                        r#type: LiteralType::Int,
+                        span: Span::new(5, 6),
+                    }),
+                    span: Span::new(4, 7)
+                }),
+                name: "f".into(),
+                args: vec![].into(),
+                span: Span::single(8)
+            })
+        );
+        // Brackets on the next line start an array rather than index
+        assert_eq!(tokens.0.next().map(|t| t.r#type), Some(TokenType::Newline));
+
+        assert_eq!(
+            Expression::get(&mut TokenIt::lexed("[1, 2][0] + f()[1]")),
+            Ok(Expression::Binary(Box::new(Node::Compound(Box::new((
+                Node::Scalar(Expression::Index {
+                    target: Box::new(Expression::Array(
+                        vec![
+                            Expression::Literal {
+                                value: "1".into(),
+                                r#type: LiteralType::Int,
+                                span: Span::new(1, 2),
+                            },
+                            Expression::Literal {
+                                value: "2".into(),
+                                r#type: LiteralType::Int,
+                                span: Span::new(4, 5),
+                            }
+                        ]
+                        .into()
+                    )),
+                    index: Box::new(Expression::Literal {
+                        value: "0".into(),
+                        r#type: LiteralType::Int,
+                        span: Span::new(7, 8),
+                    }),
+                    span: Span::new(6, 9)
+                }),
+                Operator::Plus,
+                Node::Scalar(Expression::Index {
+                    target: Box::new(Expression::Call {
+                        callee: Box::new(Expression::Path {
+                            path: vec!["f".into()].into(),
+                            span: Span::single(12)
+                        }),
+                        args: vec![].into(),
+                        span: Span::single(13)
+                    }),
+                    index: Box::new(Expression::Literal {
+                        value: "1".into(),
+                        r#type: LiteralType::Int,
+                        span: Span::new(16, 17),
+                    }),
+                    span: Span::new(15, 18)
+                })
+            ))))))
+        );
+    }
+
+    #[test]
+    fn slice_passes()
+    {
+        let mut tokens = TokenIt::lexed("s[1..n].len");
+
+        assert_eq!(
+            Expression::get(&mut tokens),
+            Ok(Expression::Field {
+                target: Box::new(Expression::Slice {
+                    target: Box::new(Expression::Path {
+                        path: vec!["s".into()].into(),
+                        span: Span::single(0)
+                    }),
+                    start: Some(Box::new(Expression::Literal {
+                        value: "1".into(),
+                        r#type: LiteralType::Int,
+                        span: Span::new(2, 3),
+                    })),
+                    end: Some(Box::new(Expression::Path {
+                        path: vec!["n".into()].into(),
+                        span: Span::single(5)
+                    })),
+                    span: Span::new(1, 7)
+                }),
+                name: "len".into(),
+                span: Span::new(8, 11)
+            })
+        );
+
+        let mut tokens = TokenIt::lexed("s[..]");
+
+        assert_eq!(
+            Expression::get(&mut tokens),
+            Ok(Expression::Slice {
+                target: Box::new(Expression::Path {
+                    path: vec!["s".into()].into(),
+                    span: Span::single(0)
+                }),
+                start: None,
+                end: None,
+                span: Span::new(1, 5)
+            })
+        );
+
+        let mut tokens = TokenIt::lexed("\"abc\"[1..]");
+
+        assert_eq!(
+            Expression::get(&mut tokens),
+            Ok(Expression::Slice {
+                target: Box::new(Expression::Literal {
+                    value: "\"abc\"".into(),
+                    r#type: LiteralType::String,
+                    span: Span::new(0, 5),
+                }),
+                start: Some(Box::new(Expression::Literal {
+                    value: "1".into(),
+                    r#type: LiteralType::Int,
+                    span: Span::new(6, 7),
+                })),
+                end: None,
+                span: Span::new(5, 10)
+            })
+        );
+    }
+
+    #[test]
+    fn cast_passes()
+    {
+        assert_eq!(
+            Expression::get(&mut TokenIt::lexed("a as i64 + 1")),
+            Ok(Expression::Binary(Box::new(Node::Compound(Box::new((
+                Node::Scalar(Expression::Cast {
+                    target: Box::new(Expression::Path {
+                        path: vec!["a".into()].into(),
+                        span: Span::single(0)
+                    }),
+                    r#type: Type(vec!["i64".into()]),
+                    span: Span::new(2, 8)
+                }),
+                Operator::Plus,
+                Node::Scalar(Expression::Literal {
+                    value: "1".into(),
+                    r#type: LiteralType::Int,
+                    span: Span::new(11, 12),
+                })
+            ))))))
+        );
+
+        assert_eq!(
+            Expression::get(&mut TokenIt::lexed("f(x) as u8 as f32")),
+            Ok(Expression::Cast {
+                target: Box::new(Expression::Cast {
+                    target: Box::new(Expression::Call {
+                        callee: Box::new(Expression::Path {
+                            path: vec!["f".into()].into(),
+                            span: Span::single(0)
+                        }),
+                        args: vec![Expression::Path {
+                            path: vec!["x".into()].into(),
+                            span: Span::single(2)
+                        }]
+                        .into(),
+                        span: Span::single(1)
+                    }),
+                    r#type: Type(vec!["u8".into()]),
+                    span: Span::new(5, 10)
+                }),
+                r#type: Type(vec!["f32".into()]),
+                span: Span::new(11, 17)
+            })
         );
     }
 
@@ -542,38 +1178,120 @@ mod tests
     //     );
     // }
 
+    #[test]
+    fn if_leaves_trailing_newlines()
+    {
+        let mut tokens = TokenIt::lexed("if a {\n    ret\n}\n\nb()\n");
+
+        assert_eq!(
+            Expression::parse_if(&mut tokens),
+            Ok(Expression::If {
+                condition: Box::new(Expression::Path {
+                    path: vec!["a".into()].into(),
+                    span: Span::single(3)
+                }),
+                block: vec![Statement::Return(None)].into(),
+                else_block: None
+            })
+        );
+        assert_eq!(tokens.0.next().map(|t| t.r#type), Some(TokenType::Newline));
+    }
+
+    #[test]
+    fn closure_passes()
+    {
+        let mut tokens = TokenIt::lexed("func[a, &b, &mut c](x i32) i32 {\n    ret x\n}\nd");
+
+        assert_eq!(
+            Expression::get(&mut tokens),
+            Ok(Expression::Closure {
+                captures: vec![
+                    Capture {
+                        name: "a".into(),
+                        kind: CaptureKind::Value,
+                        span: Span::single(5)
+                    },
+                    Capture {
+                        name: "b".into(),
+                        kind: CaptureKind::Ref,
+                        span: Span::single(9)
+                    },
+                    Capture {
+                        name: "c".into(),
+                        kind: CaptureKind::MutRef,
+                        span: Span::single(17)
+                    }
+                ]
+                .into(),
+                signature: Box::new(FunctionSignature {
+                    name: ("".into(), Some(crate::Type(vec!["i32".into()]))),
+                    span: Span::new(0, 4),
+                    generics: vec![].into(),
+                    arguments: vec![crate::ArgumentName {
+                        name: "x".into(),
+                        r#type: crate::Type(vec!["i32".into()]),
+                        mutable: false,
+                        span: Span::single(20)
+                    }]
+                    .into(),
+                    variadic: false
+                }),
+                body: vec![Statement::Return(Some(Expression::Path {
+                    path: vec!["x".into()].into(),
+                    span: Span::single(41)
+                }))]
+                .into()
+            })
+        );
+        assert_eq!(tokens.0.next().map(|t| t.r#type), Some(TokenType::Newline));
+
+        assert_eq!(
+            Expression::parse_closure(&mut TokenIt::lexed("func() {}")),
+            Ok(Expression::Closure {
+                captures: vec![].into(),
+                signature: Box::new(FunctionSignature {
+                    name: ("".into(), None),
+                    span: Span::new(0, 4),
+                    generics: vec![].into(),
+                    arguments: vec![].into(),
+                    variadic: false
+                }),
+                body: vec![].into()
+            })
+        );
+    }
+
     #[test]
     fn unary_passes()
     {
         assert_eq!(
-            Expression::parse_unary(&mut TokenIt(
-                compiler_lexer::tokenize("-2").flatten().peekable()
-            )),
+            Expression::parse_unary(&mut TokenIt::lexed("-2")),
             Ok(Expression::Unary(
                 Operator::Minus,
                 Box::new(Expression::Literal {
                     value: "2".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(1, 2),
                 })
             ))
         );
 
         assert_eq!(
-            Expression::parse_unary(&mut TokenIt(
-                compiler_lexer::tokenize("-(2 - 4)").flatten().peekable()
-            )),
+            Expression::parse_unary(&mut TokenIt::lexed("-(2 - 4)")),
             Ok(Expression::Unary(
                 Operator::Minus,
                 Box::new(Expression::Parenthesis(Box::new(Expression::Binary(
                     Box::new(Node::Compound(Box::new((
                         Node::Scalar(Expression::Literal {
                             value: "2".into(),
-                            r#type: LiteralType::Int
+                            r#type: LiteralType::Int,
+                            span: Span::new(2, 3),
                         }),
                         Operator::Minus,
                         Node::Scalar(Expression::Literal {
                             value: "4".into(),
-                            r#type: LiteralType::Int
+                            r#type: LiteralType::Int,
+                            span: Span::new(6, 7),
                         }),
                     ))))
                 ))))
@@ -581,30 +1299,52 @@ mod tests
         );
 
         assert_eq!(
-            Expression::parse_unary(&mut TokenIt(
-                compiler_lexer::tokenize("*v").flatten().peekable()
-            )),
+            Expression::parse_unary(&mut TokenIt::lexed("*v")),
             Ok(Expression::Unary(
                 Operator::Star,
-                Box::new(Expression::Path(vec!["v".into()].into()))
+                Box::new(Expression::Path {
+                    path: vec!["v".into()].into(),
+                    span: Span::single(1)
+                })
             ))
         );
 
         assert_eq!(
-            Expression::parse_unary(&mut TokenIt(
-                compiler_lexer::tokenize("+2").flatten().peekable()
-            )),
+            Expression::parse_unary(&mut TokenIt::lexed("+2")),
             Err(ParserError::IllegalUnary {
                 token: Token {
                     r#type: TokenType::Operator,
-                    value: "+".into(),
-                    span: Span {
-                        from: (0, 0),
-                        to: (0, 0)
-                    }
+                    span: Span::single(0)
                 }
             })
         );
+
+        // Binding to the operand right after it only
+        assert_eq!(
+            Expression::get(&mut TokenIt::lexed("-3 - 1")),
+            Ok(Expression::Binary(Box::new(Node::Compound(Box::new((
+                Node::Scalar(Expression::Unary(
+                    Operator::Minus,
+                    Box::new(Expression::Literal {
+                        value: "3".into(),
+                        r#type: LiteralType::Int,
+                        span: Span::single(1),
+                    })
+                )),
+                Operator::Minus,
+                Node::Scalar(Expression::Literal {
+                    value: "1".into(),
+                    r#type: LiteralType::Int,
+                    span: Span::single(5),
+                }),
+            ))))))
+        );
+
+        // Not an operator of its own outside `!=`
+        assert!(matches!(
+            Expression::parse_unary(&mut TokenIt::lexed("!v")),
+            Err(ParserError::IllegalUnary { token }) if token.span == Span::single(0)
+        ));
     }
 
     // TODO statement always asks for a newline, let it also work with the scope end
@@ -1,5 +1,6 @@
-use compiler_lexer::definitions::{Token, TokenType};
+use compiler_lexer::definitions::TokenType;
 use ecow::EcoVec;
+use serde::Serialize;
 
 use super::{
     Expression,
@@ -13,8 +14,9 @@ const OPERATOR_PRIORITY: &[&[Operator]] = {
     &[&[Plus, Minus], &[Star, Div]]
 };
 
+/// How tightly `operator` binds its operands, operators of a higher priority being evaluated first
 #[inline]
-fn priority(operator: Operator) -> usize
+pub fn priority(operator: Operator) -> usize
 {
     OPERATOR_PRIORITY
         .iter()
@@ -25,7 +27,7 @@ fn priority(operator: Operator) -> usize
         .unwrap_or_default()
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Node
 {
     Scalar(Expression),
@@ -52,8 +54,14 @@ impl Node
 
         let mut last_was_scalar = false;
 
+        // A newline ends the expression, unless it's left dangling on an operator
+        let source = tokens.1;
+
         while let Some(p) = tokens.0.peek()
-            && (p.r#type != TokenType::Separator || p.value == "(")
+            && (p.r#type != TokenType::Separator
+                || p.span.source(source) == "("
+                || (p.span.source(source) == "[" && !last_was_scalar))
+            && (p.r#type != TokenType::Newline || !last_was_scalar)
         {
             if last_was_scalar
             {
@@ -62,43 +70,30 @@ impl Node
                 let t = tokens
                     .next(|t| t.r#type == TokenType::Operator)
                     .ok_or(ParserError::ExpectedTokenType { r#type: "Operator" })?;
+                let op = to_operator(&t, tokens.1)?;
 
-                while let Some(
-                    t2 @ Token {
-                        r#type: TokenType::Operator,
-                        ..
-                    },
-                ) = operator_stack.last()
+                while let Some(&op2) = operator_stack.last()
+                    && priority(op2) >= priority(op)
                 {
-                    let op = to_operator(&t);
-                    let op2 = to_operator(t2);
-
-                    if priority(op2) >= priority(op)
-                    {
-                        output_queue.push(RPNItem::Operator(to_operator(
-                            &operator_stack.pop().unwrap(),
-                        )));
-                    }
-                    else
-                    {
-                        break;
-                    }
+                    operator_stack.pop();
+                    output_queue.push(RPNItem::Operator(op2));
                 }
 
-                operator_stack.push(t.clone());
+                operator_stack.push(op);
             }
             else
             {
                 last_was_scalar = true;
+                tokens.ignore_newlines();
 
-                let e = (Expression::shallow_find_predicate(&mut tokens.clone())?)(tokens)?;
+                let e = Expression::parse_cast(tokens)?;
                 output_queue.push(RPNItem::Scalar(e));
             }
         }
 
-        while let Some(t) = operator_stack.pop()
+        while let Some(op) = operator_stack.pop()
         {
-            output_queue.push(RPNItem::Operator(to_operator(&t)));
+            output_queue.push(RPNItem::Operator(op));
         }
 
         Ok(output_queue)
@@ -136,7 +131,7 @@ impl Node
 #[cfg(test)]
 mod tests
 {
-    use compiler_lexer::definitions::LiteralType;
+    use compiler_lexer::definitions::{LiteralType, Span};
     use pretty_assertions::assert_eq;
 
     use super::*;
@@ -145,18 +140,18 @@ mod tests
     fn simple_binary_passes()
     {
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("9 + 10").flatten().peekable()
-            )),
+            Node::parse(&mut TokenIt::lexed("9 + 10")),
             Ok(Node::Compound(Box::new((
                 Node::Scalar(Expression::Literal {
                     value: "9".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(0, 1),
                 }),
                 Operator::Plus,
                 Node::Scalar(Expression::Literal {
                     value: "10".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(4, 6),
                 })
             ))))
         );
@@ -166,20 +161,20 @@ mod tests
     fn hacky_binary_with_unary_passes()
     {
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("10 - -1").flatten().peekable()
-            )),
+            Node::parse(&mut TokenIt::lexed("10 - -1")),
             Ok(Node::Compound(Box::new((
                 Node::Scalar(Expression::Literal {
                     value: "10".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(0, 2),
                 }),
                 Operator::Minus,
                 Node::Scalar(Expression::Unary(
                     Operator::Minus,
                     Box::new(Expression::Literal {
                         value: "1".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(6, 7),
                     })
                 ))
             ))))
@@ -190,34 +185,34 @@ mod tests
     fn priority_binary_passes()
     {
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("9 - 2 * 4 + 1")
-                    .flatten()
-                    .peekable()
-            )),
+            Node::parse(&mut TokenIt::lexed("9 - 2 * 4 + 1")),
             Ok(Node::Compound(Box::new((
                 Node::Compound(Box::new((
                     Node::Scalar(Expression::Literal {
                         value: "9".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(0, 1),
                     }),
                     Operator::Minus,
                     Node::Compound(Box::new((
                         Node::Scalar(Expression::Literal {
                             value: "2".into(),
-                            r#type: LiteralType::Int
+                            r#type: LiteralType::Int,
+                            span: Span::new(4, 5),
                         }),
                         Operator::Star,
                         Node::Scalar(Expression::Literal {
                             value: "4".into(),
-                            r#type: LiteralType::Int
+                            r#type: LiteralType::Int,
+                            span: Span::new(8, 9),
                         })
                     )))
                 ))),
                 Operator::Plus,
                 Node::Scalar(Expression::Literal {
                     value: "1".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(12, 13),
                 })
             ))))
         );
@@ -227,34 +222,34 @@ mod tests
     fn custom_priority_binary_passes()
     {
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("9 - 2 * 4 >> 1")
-                    .flatten()
-                    .peekable()
-            )),
+            Node::parse(&mut TokenIt::lexed("9 - 2 * 4 >> 1")),
             Ok(Node::Compound(Box::new((
                 Node::Compound(Box::new((
                     Node::Scalar(Expression::Literal {
                         value: "9".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(0, 1),
                     }),
                     Operator::Minus,
                     Node::Compound(Box::new((
                         Node::Scalar(Expression::Literal {
                             value: "2".into(),
-                            r#type: LiteralType::Int
+                            r#type: LiteralType::Int,
+                            span: Span::new(4, 5),
                         }),
                         Operator::Star,
                         Node::Scalar(Expression::Literal {
                             value: "4".into(),
-                            r#type: LiteralType::Int
+                            r#type: LiteralType::Int,
+                            span: Span::new(8, 9),
                         })
                     )))
                 ))),
                 Operator::Shr,
                 Node::Scalar(Expression::Literal {
                     value: "1".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(13, 14),
                 })
             ))))
         );
@@ -264,33 +259,33 @@ mod tests
     fn parenthesis_binary_passes()
     {
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("9 - 2 * (4 + 1)")
-                    .flatten()
-                    .peekable()
-            )),
+            Node::parse(&mut TokenIt::lexed("9 - 2 * (4 + 1)")),
             Ok(Node::Compound(Box::new((
                 Node::Scalar(Expression::Literal {
                     value: "9".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(0, 1),
                 }),
                 Operator::Minus,
                 Node::Compound(Box::new((
                     Node::Scalar(Expression::Literal {
                         value: "2".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(4, 5),
                     }),
                     Operator::Star,
                     Node::Scalar(Expression::Parenthesis(Box::new(Expression::Binary(
                         Box::new(Node::Compound(Box::new((
                             Node::Scalar(Expression::Literal {
                                 value: "4".into(),
-                                r#type: LiteralType::Int
+                                r#type: LiteralType::Int,
+                                span: Span::new(9, 10),
                             }),
                             Operator::Plus,
                             Node::Scalar(Expression::Literal {
                                 value: "1".into(),
-                                r#type: LiteralType::Int
+                                r#type: LiteralType::Int,
+                                span: Span::new(13, 14),
                             })
                         ))))
                     ))))
@@ -303,43 +298,48 @@ mod tests
     fn binary_with_call_passes()
     {
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("9 << 2 * (add(2, 4) + 1)")
-                    .flatten()
-                    .peekable()
-            )),
+            Node::parse(&mut TokenIt::lexed("9 << 2 * (add(2, 4) + 1)")),
             Ok(Node::Compound(Box::new((
                 Node::Scalar(Expression::Literal {
                     value: "9".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(0, 1),
                 }),
                 Operator::Shl,
                 Node::Compound(Box::new((
                     Node::Scalar(Expression::Literal {
                         value: "2".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(5, 6),
                     }),
                     Operator::Star,
                     Node::Scalar(Expression::Parenthesis(Box::new(Expression::Binary(
                         Box::new(Node::Compound(Box::new((
                             Node::Scalar(Expression::Call {
-                                path: vec!["add".into()].into(),
+                                callee: Box::new(Expression::Path {
+                                    path: vec!["add".into()].into(),
+                                    span: Span::new(10, 13)
+                                }),
                                 args: vec![
                                     Expression::Literal {
                                         value: "2".into(),
-                                        r#type: LiteralType::Int
+                                        r#type: LiteralType::Int,
+                                        span: Span::new(14, 15),
                                     },
                                     Expression::Literal {
                                         value: "4".into(),
-                                        r#type: LiteralType::Int
+                                        r#type: LiteralType::Int,
+                                        span: Span::new(17, 18),
                                     }
                                 ]
-                                .into()
+                                .into(),
+                                span: Span::single(13)
                             }),
                             Operator::Plus,
                             Node::Scalar(Expression::Literal {
                                 value: "1".into(),
-                                r#type: LiteralType::Int
+                                r#type: LiteralType::Int,
+                                span: Span::new(22, 23),
                             })
                         ))))
                     ))))
@@ -348,21 +348,45 @@ mod tests
         );
     }
 
+    #[test]
+    fn binary_ends_at_newline()
+    {
+        let mut tokens = TokenIt::lexed("a +\n    b\nc");
+
+        assert_eq!(
+            Node::parse(&mut tokens),
+            Ok(Node::Compound(Box::new((
+                Node::Scalar(Expression::Path {
+                    path: vec!["a".into()].into(),
+                    span: Span::single(0)
+                }),
+                Operator::Plus,
+                Node::Scalar(Expression::Path {
+                    path: vec!["b".into()].into(),
+                    span: Span::single(8)
+                })
+            ))))
+        );
+        assert_eq!(tokens.0.next().map(|t| t.r#type), Some(TokenType::Newline));
+    }
+
     #[test]
     fn invalid_binary_passes()
     {
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("2 + 4 2").flatten().peekable(),
-            )),
+            Node::parse(&mut TokenIt::lexed("2 + 4 2")),
             Err(ParserError::ExpectedTokenType { r#type: "Operator" })
         );
 
         assert_eq!(
-            Node::parse(&mut TokenIt(
-                compiler_lexer::tokenize("2 + 4 -").flatten().peekable(),
-            )),
+            Node::parse(&mut TokenIt::lexed("2 + 4 -")),
             Err(ParserError::ExpectedASTStructure { name: "Expression" })
         );
+
+        // Only valid as a unary or in an assignment
+        assert!(matches!(
+            Node::parse(&mut TokenIt::lexed("2 ! 4")),
+            Err(ParserError::IllegalBinary { token }) if token.span.source("2 ! 4") == "!"
+        ));
     }
 }
@@ -1,8 +1,11 @@
 use std::fmt;
 
 use compiler_lexer::definitions::{Token, TokenType};
+use serde::Serialize;
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+use crate::ParserError;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
 pub enum Operator
 {
     Plus,
@@ -48,16 +51,23 @@ const OPERATOR_MAP: &[(&str, Operator)] = {
     ]
 };
 
-pub fn to_operator(token: &Token) -> Operator
+/// The binary operator `token`, lexed from `source`, stands for. Operators only valid elsewhere, as `!` or `+=`, aren't
+pub fn to_operator(token: &Token, source: &str) -> Result<Operator, ParserError>
 {
-    assert_eq!(token.r#type, TokenType::Operator, "Token isn't an operator");
+    binary(token.span.source(source))
+        .filter(|_| token.r#type == TokenType::Operator)
+        .ok_or_else(|| ParserError::IllegalBinary { token: *token })
+}
 
+/// The binary operator spelled `text`, if any
+#[inline]
+pub fn binary(text: &str) -> Option<Operator>
+{
     OPERATOR_MAP
         .iter()
         .copied()
-        .find(|&(k, _)| k == token.value)
-        .unwrap()
-        .1
+        .find(|&(k, _)| k == text)
+        .map(|(_, op)| op)
 }
 
 impl fmt::Display for Operator
@@ -1,6 +1,7 @@
-use compiler_lexer::definitions::TokenType;
+use compiler_lexer::definitions::{Span, Token, TokenType};
 use ecow::{EcoString, EcoVec};
-use itertools::Itertools;
+use itertools::{Either, Itertools};
+use serde::Serialize;
 
 use crate::{
     ArgumentName, Name, ParserError, TokenIt, Type,
@@ -9,24 +10,213 @@ use crate::{
     statement::Statement,
 };
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct FunctionSignature
 {
     pub name: (EcoString, Option<Type>),
+    /// Of the name, or of the `func` of closures
+    pub span: Span,
+    /// Generic type parameters, declared among the arguments as `T const type`. They're inferred on each call rather than passed
+    pub generics: EcoVec<EcoString>,
     pub arguments: EcoVec<ArgumentName>,
+    /// Trailing `...`, only valid on `extern` functions
+    pub variadic: bool,
 }
 
-#[derive(Debug, PartialEq)]
+impl FunctionSignature
+{
+    /// Parses `name(args) type`, where the return type spans every token accepted by `is_type`, given along with its text
+    pub fn parse<I: TokenItTrait>(
+        tokens: &mut TokenIt<I>,
+        is_type: impl Fn(&Token, &str) -> bool,
+    ) -> Result<Self, ParserError>
+    {
+        let identifier = tokens.next(|t| t.r#type == TokenType::Identifier).ok_or(
+            ParserError::ExpectedTokenType {
+                r#type: "Identifier",
+            },
+        )?;
+
+        let name = tokens.text(&identifier).into();
+
+        Self::parse_unnamed(tokens, (name, identifier.span), is_type)
+    }
+
+    /// Parses `(args) type`, as on closures, which have no name of their own. `span` is what the signature's points at
+    pub fn parse_unnamed<I: TokenItTrait>(
+        tokens: &mut TokenIt<I>,
+        (identifier, span): (EcoString, Span),
+        is_type: impl Fn(&Token, &str) -> bool,
+    ) -> Result<Self, ParserError>
+    {
+        // `None` stands for `...`
+        let arguments = tokens.consume_generic_list(
+            ("(", ")"),
+            |t| {
+                if t.next_is("...").is_some()
+                {
+                    return Ok(None);
+                }
+
+                let mutable = t.next_is("mut").is_some();
+
+                let identifier = t.next(|t| t.r#type == TokenType::Identifier).ok_or(
+                    ParserError::ExpectedTokenType {
+                        r#type: "Identifier",
+                    },
+                )?;
+
+                // Parentheses nest, as on function pointer types like `func(i32, i32) i32`
+                let source = t.1;
+                let mut depth = 0usize;
+                let r#type = Type(
+                    t.0.peeking_take_while(|t| match t.span.source(source)
+                    {
+                        "(" =>
+                        {
+                            depth += 1;
+                            true
+                        }
+                        "," | ")" if depth == 0 => false,
+                        ")" =>
+                        {
+                            depth -= 1;
+                            true
+                        }
+                        _ => true,
+                    })
+                    // The list may be broken over several lines
+                    .filter(|t| t.r#type != TokenType::Newline)
+                    .map(|t| t.span.source(source).into())
+                    .collect(),
+                );
+
+                Ok(Some(ArgumentName {
+                    name: t.text(&identifier).into(),
+                    r#type,
+                    mutable,
+                    span: identifier.span,
+                }))
+            },
+            Some(","),
+        )?;
+
+        let variadic = arguments.last().is_some_and(Option::is_none);
+
+        let arguments = arguments[..arguments.len() - usize::from(variadic)]
+            .iter()
+            .cloned()
+            .collect::<Option<EcoVec<_>>>()
+            .ok_or(ParserError::ExpectedASTStructure {
+                name: "`...` as the last argument",
+            })?;
+
+        let (generics, arguments) = arguments.into_iter().partition_map(|a| {
+            if let [c, t] = a.r#type.0.as_slice()
+                && c == "const"
+                && t == "type"
+            {
+                Either::Left(a.name)
+            }
+            else
+            {
+                Either::Right(a)
+            }
+        });
+
+        let r#type = {
+            let source = tokens.1;
+            let r#type = tokens
+                .0
+                .peeking_take_while(|t| is_type(t, t.span.source(source)))
+                .map(|t| t.span.source(source).into())
+                .collect::<Vec<_>>();
+
+            if r#type.is_empty()
+            {
+                None
+            }
+            else
+            {
+                Some(Type(r#type))
+            }
+        };
+
+        Ok(Self {
+            name: (identifier, r#type),
+            span,
+            generics,
+            arguments,
+            variadic,
+        })
+    }
+}
+
+/// `@name` or `@name(args)`, preceding an item. Which ones exist and what they take is up to the code generator
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Attribute
+{
+    pub name: EcoString,
+    pub args: EcoVec<Expression>,
+    /// From the `@` up to the name
+    pub span: Span,
+}
+
+impl Attribute
+{
+    pub fn parse<I: TokenItTrait>(tokens: &mut TokenIt<I>) -> Result<Self, ParserError>
+    {
+        let at = tokens
+            .next_is("@")
+            .ok_or(ParserError::ExpectedTokenValue { value: "@".into() })?;
+
+        // On the same line as the `@`, as in `@inline`
+        let name = tokens
+            .0
+            .next_if(|t| t.r#type == TokenType::Identifier)
+            .ok_or(ParserError::ExpectedTokenType {
+                r#type: "Identifier",
+            })?;
+
+        let args = if tokens.peek_is("(")
+        {
+            tokens.consume_generic_list(("(", ")"), Expression::get, Some(","))?
+        }
+        else
+        {
+            EcoVec::new()
+        };
+
+        Ok(Self {
+            name: tokens.text(&name).into(),
+            args,
+            span: Span::new(at.span.start, name.span.end),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Item
 {
+    /// `import a.b`, with the span of its path
+    Import(EcoVec<EcoString>, Span),
     Const
     {
-        name: Name, value: Expression
+        name: Name,
+        value: Expression,
+        attributes: EcoVec<Attribute>,
     },
     Function
     {
         signature: FunctionSignature,
         body: EcoVec<Statement>,
+        attributes: EcoVec<Attribute>,
+    },
+    /// A function defined elsewhere and linked in, following the C ABI
+    Extern
+    {
+        signature: FunctionSignature,
+        attributes: EcoVec<Attribute>,
     },
 }
 
@@ -34,14 +224,26 @@ impl<I: TokenItTrait> ExhaustiveGet<I> for Item
 {
     fn find_predicate(tokens: &mut TokenIt<I>) -> Result<Self::ParsePredicate, ParserError>
     {
-        if tokens.0.peek().is_some_and(|t| t.value == "const")
+        if tokens.peek_is("import")
+        {
+            Ok(Self::parse_import)
+        }
+        else if tokens.peek_is("const")
         {
             Ok(Self::parse_const)
         }
-        else if tokens.0.peek().is_some_and(|t| t.value == "func")
+        else if tokens.peek_is("func")
         {
             Ok(Self::parse_function)
         }
+        else if tokens.peek_is("extern")
+        {
+            Ok(Self::parse_extern)
+        }
+        else if tokens.peek_is("@")
+        {
+            Ok(Self::parse_attributed)
+        }
         else
         {
             Err(ParserError::ExpectedASTStructure { name: "Item" })
@@ -51,27 +253,89 @@ impl<I: TokenItTrait> ExhaustiveGet<I> for Item
 
 impl Item
 {
+    /// Items without a block must end on a newline (or at the end of the file)
+    #[inline]
+    fn consume_end<I: TokenItTrait>(tokens: &mut TokenIt<I>) -> Result<(), ParserError>
+    {
+        if tokens
+            .0
+            .next_if(|t| t.r#type == TokenType::Newline)
+            .is_none()
+            && tokens.0.peek().is_some()
+        {
+            Err(ParserError::ExpectedNewline)
+        }
+        else
+        {
+            Ok(())
+        }
+    }
+
+    /// Parses the attributes on their own lines or on the item's, then the item they're on
+    pub fn parse_attributed<I: TokenItTrait>(tokens: &mut TokenIt<I>) -> Result<Self, ParserError>
+    {
+        let mut attributes = EcoVec::new();
+
+        while tokens.peek_is("@")
+        {
+            attributes.push(Attribute::parse(tokens)?);
+            tokens.ignore_newlines();
+        }
+
+        let mut item = Self::get(tokens)?;
+
+        match &mut item
+        {
+            Self::Import(..) =>
+            {
+                return Err(ParserError::ExpectedASTStructure {
+                    name: "item which takes attributes",
+                });
+            }
+            Self::Const { attributes: a, .. }
+            | Self::Function { attributes: a, .. }
+            | Self::Extern { attributes: a, .. } => *a = attributes,
+        }
+
+        Ok(item)
+    }
+
+    pub fn parse_import<I: TokenItTrait>(tokens: &mut TokenIt<I>) -> Result<Self, ParserError>
+    {
+        tokens
+            .next_is("import")
+            .ok_or(ParserError::ExpectedTokenValue {
+                value: "import".into(),
+            })?;
+
+        let (path, span) = Expression::parse_segments(tokens)?;
+
+        Self::consume_end(tokens)?;
+
+        Ok(Self::Import(path, span))
+    }
+
     pub fn parse_const<I: TokenItTrait>(tokens: &mut TokenIt<I>) -> Result<Self, ParserError>
     {
         tokens
-            .next(|t| t.value == "const")
+            .next_is("const")
             .ok_or(ParserError::ExpectedTokenValue {
                 value: "const".into(),
             })?;
 
         // partially shared on statement.rs. make this better
-        let identifier = tokens
-            .next(|t| t.r#type == TokenType::Identifier)
-            .ok_or(ParserError::ExpectedTokenType {
+        let identifier = tokens.next(|t| t.r#type == TokenType::Identifier).ok_or(
+            ParserError::ExpectedTokenType {
                 r#type: "Identifier",
-            })?
-            .value;
+            },
+        )?;
 
         let r#type = {
+            let source = tokens.1;
             let r#type = tokens
                 .0
-                .peeking_take_while(|t| t.value != "=")
-                .map(|t| t.value)
+                .peeking_take_while(|t| t.span.source(source) != "=")
+                .map(|t| t.span.source(source).into())
                 .collect::<Vec<_>>();
 
             if r#type.is_empty()
@@ -84,75 +348,42 @@ impl Item
             }
         };
 
+        tokens
+            .next_is("=")
+            .ok_or(ParserError::ExpectedTokenValue { value: "=".into() })?;
+
         let value = Expression::get(tokens)?;
 
-        tokens
-            .next(|t| t.r#type == TokenType::Newline)
-            .ok_or(ParserError::ExpectedNewline)?;
+        Self::consume_end(tokens)?;
 
         Ok(Self::Const {
-            name: Name(identifier, r#type),
+            name: Name(tokens.text(&identifier).into(), r#type, identifier.span),
             value,
+            attributes: EcoVec::new(),
         })
     }
 
     pub fn parse_function<I: TokenItTrait>(tokens: &mut TokenIt<I>) -> Result<Self, ParserError>
     {
         tokens
-            .next(|t| t.value == "func")
+            .next_is("func")
             .ok_or(ParserError::ExpectedTokenValue {
                 value: "func".into(),
             })?;
 
-        let identifier = tokens
-            .next(|t| t.r#type == TokenType::Identifier)
-            .ok_or(ParserError::ExpectedTokenType {
-                r#type: "Identifier",
-            })?
-            .value;
-
-        let arguments = tokens.consume_generic_list(
-            ("(", ")"),
-            |t| {
-                let identifier = t
-                    .next(|t| t.r#type == TokenType::Identifier)
-                    .ok_or(ParserError::ExpectedTokenType {
-                        r#type: "Identifier",
-                    })?
-                    .value;
-
-                let r#type = Type(
-                    t.0.peeking_take_while(|t| t.value != "," && t.value != ")")
-                        .map(|t| t.value)
-                        .collect(),
-                );
-
-                Ok(ArgumentName(identifier, r#type))
-            },
-            Some(","),
-        )?;
-
-        let r#type = {
-            let r#type = tokens
-                .0
-                .peeking_take_while(|t| t.value != "{")
-                .map(|t| t.value)
-                .collect::<Vec<_>>();
+        let signature = FunctionSignature::parse(tokens, |_, t| t != "{")?;
 
-            if r#type.is_empty()
-            {
-                None
-            }
-            else
-            {
-                Some(Type(r#type))
-            }
-        };
+        if signature.variadic
+        {
+            return Err(ParserError::ExpectedASTStructure {
+                name: "non-variadic function",
+            });
+        }
 
         let body = tokens.consume_block()?;
 
         /* TODO!
-        if let Some(ref r#type) = r#type
+        if let Some(ref r#type) = signature.name.1
             && body
                 .iter()
                 .find(|&s| matches!(s, Statement::Return(_)))
@@ -160,20 +391,341 @@ impl Item
         {
             return Some(Err(anyhow!(
                 "Function {} must return {}, returns void",
-                identifier,
+                signature.name.0,
                 r#type
             )));
         }
         */
 
         Ok(Self::Function {
-            signature: FunctionSignature {
-                name: (identifier, r#type),
-                arguments,
-            },
+            signature,
             body,
+            attributes: EcoVec::new(),
+        })
+    }
+
+    pub fn parse_extern<I: TokenItTrait>(tokens: &mut TokenIt<I>) -> Result<Self, ParserError>
+    {
+        tokens
+            .next_is("extern")
+            .ok_or(ParserError::ExpectedTokenValue {
+                value: "extern".into(),
+            })?;
+
+        tokens
+            .next_is("func")
+            .ok_or(ParserError::ExpectedTokenValue {
+                value: "func".into(),
+            })?;
+
+        let signature = FunctionSignature::parse(tokens, |t, text| {
+            t.r#type != TokenType::Newline && text != "{"
+        })?;
+
+        if !signature.generics.is_empty()
+        {
+            return Err(ParserError::ExpectedASTStructure {
+                name: "non-generic function",
+            });
+        }
+
+        Self::consume_end(tokens)?;
+
+        Ok(Self::Extern {
+            signature,
+            attributes: EcoVec::new(),
         })
     }
 }
 
-// TODO tests
+#[cfg(test)]
+mod tests
+{
+    use compiler_lexer::definitions::Span;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn import_passes()
+    {
+        assert_eq!(
+            Item::parse_import(&mut TokenIt::lexed("import foo\n")),
+            Ok(Item::Import(vec!["foo".into()].into(), Span::new(7, 10)))
+        );
+
+        assert_eq!(
+            Item::parse_import(&mut TokenIt::lexed("import foo.bar")),
+            Ok(Item::Import(
+                vec!["foo".into(), "bar".into()].into(),
+                Span::new(7, 14)
+            ))
+        );
+
+        assert_eq!(
+            Item::parse_import(&mut TokenIt::lexed("import foo bar\n")),
+            Err(ParserError::ExpectedNewline)
+        );
+
+        assert_eq!(
+            Item::parse_import(&mut TokenIt::lexed("import foo.\n")),
+            Err(ParserError::ExpectedTokenType {
+                r#type: "Identifier"
+            })
+        );
+    }
+
+    #[test]
+    fn const_passes()
+    {
+        assert_eq!(
+            Item::parse_const(&mut TokenIt::lexed("const MAGIC_NUMBER = 42\n")),
+            Ok(Item::Const {
+                name: Name("MAGIC_NUMBER".into(), None, Span::new(6, 18)),
+                value: Expression::Literal {
+                    value: "42".into(),
+                    r#type: compiler_lexer::definitions::LiteralType::Int,
+                    span: Span::new(21, 23),
+                },
+                attributes: EcoVec::new(),
+            })
+        );
+
+        assert_eq!(
+            Item::parse_const(&mut TokenIt::lexed("const MAGIC_NUMBER u8 = 42")),
+            Ok(Item::Const {
+                name: Name(
+                    "MAGIC_NUMBER".into(),
+                    Some(Type(vec!["u8".into()])),
+                    Span::new(6, 18)
+                ),
+                value: Expression::Literal {
+                    value: "42".into(),
+                    r#type: compiler_lexer::definitions::LiteralType::Int,
+                    span: Span::new(24, 26),
+                },
+                attributes: EcoVec::new(),
+            })
+        );
+
+        assert_eq!(
+            Item::parse_const(&mut TokenIt::lexed("const MAGIC_NUMBER 42\n")),
+            Err(ParserError::ExpectedTokenValue { value: "=".into() })
+        );
+    }
+
+    #[test]
+    fn extern_passes()
+    {
+        assert_eq!(
+            Item::parse_extern(&mut TokenIt::lexed("extern func putd(d i32)\n")),
+            Ok(Item::Extern {
+                signature: FunctionSignature {
+                    name: ("putd".into(), None),
+                    span: Span::new(12, 16),
+                    generics: vec![].into(),
+                    arguments: vec![ArgumentName {
+                        name: "d".into(),
+                        r#type: Type(vec!["i32".into()]),
+                        mutable: false,
+                        span: Span::single(17)
+                    }]
+                    .into(),
+                    variadic: false,
+                },
+                attributes: EcoVec::new(),
+            })
+        );
+
+        assert_eq!(
+            Item::parse_extern(&mut TokenIt::lexed(
+                "extern func printf(format &i8, ...) i32"
+            )),
+            Ok(Item::Extern {
+                signature: FunctionSignature {
+                    name: ("printf".into(), Some(Type(vec!["i32".into()]))),
+                    span: Span::new(12, 18),
+                    generics: vec![].into(),
+                    arguments: vec![ArgumentName {
+                        name: "format".into(),
+                        r#type: Type(vec!["&".into(), "i8".into()]),
+                        mutable: false,
+                        span: Span::new(19, 25)
+                    }]
+                    .into(),
+                    variadic: true,
+                },
+                attributes: EcoVec::new(),
+            })
+        );
+
+        assert_eq!(
+            Item::parse_extern(&mut TokenIt::lexed(
+                "extern func sort(cmp func(i32, i32) i32, n i32)"
+            )),
+            Ok(Item::Extern {
+                signature: FunctionSignature {
+                    name: ("sort".into(), None),
+                    span: Span::new(12, 16),
+                    generics: vec![].into(),
+                    arguments: vec![
+                        ArgumentName {
+                            name: "cmp".into(),
+                            r#type: Type(
+                                ["func", "(", "i32", ",", "i32", ")", "i32"]
+                                    .map(EcoString::from)
+                                    .to_vec()
+                            ),
+                            mutable: false,
+                            span: Span::new(17, 20)
+                        },
+                        ArgumentName {
+                            name: "n".into(),
+                            r#type: Type(vec!["i32".into()]),
+                            mutable: false,
+                            span: Span::single(41)
+                        }
+                    ]
+                    .into(),
+                    variadic: false,
+                },
+                attributes: EcoVec::new(),
+            })
+        );
+
+        assert_eq!(
+            Item::parse_extern(&mut TokenIt::lexed("extern func f(..., a i32)\n")),
+            Err(ParserError::ExpectedASTStructure {
+                name: "`...` as the last argument"
+            })
+        );
+
+        assert_eq!(
+            Item::parse_extern(&mut TokenIt::lexed("extern func f() {}\n")),
+            Err(ParserError::ExpectedNewline)
+        );
+    }
+
+    #[test]
+    fn generic_function_passes()
+    {
+        let Ok(Item::Function { signature, .. }) = Item::parse_function(&mut TokenIt::lexed(
+            "func swap(T const type, a &mut T, mut b &mut T) {}",
+        ))
+        else
+        {
+            panic!("Expected a function");
+        };
+
+        assert_eq!(
+            signature,
+            FunctionSignature {
+                name: ("swap".into(), None),
+                span: Span::new(5, 9),
+                generics: vec!["T".into()].into(),
+                arguments: vec![
+                    ArgumentName {
+                        name: "a".into(),
+                        r#type: Type(vec!["&".into(), "mut".into(), "T".into()]),
+                        mutable: false,
+                        span: Span::single(24)
+                    },
+                    ArgumentName {
+                        name: "b".into(),
+                        r#type: Type(vec!["&".into(), "mut".into(), "T".into()]),
+                        mutable: true,
+                        span: Span::single(38)
+                    }
+                ]
+                .into(),
+                variadic: false,
+            }
+        );
+
+        assert_eq!(
+            Item::parse_extern(&mut TokenIt::lexed("extern func f(T const type, a T)\n")),
+            Err(ParserError::ExpectedASTStructure {
+                name: "non-generic function"
+            })
+        );
+    }
+
+    #[test]
+    fn broken_signature_passes()
+    {
+        let Ok(Item::Function { signature, .. }) = Item::parse_function(&mut TokenIt::lexed(
+            "func add(\n    a i32,\n    b i32\n) i32 {}",
+        ))
+        else
+        {
+            panic!("Expected a function");
+        };
+
+        assert_eq!(
+            signature
+                .arguments
+                .iter()
+                .map(|a| a.r#type.clone())
+                .collect::<Vec<_>>(),
+            [Type(vec!["i32".into()]), Type(vec!["i32".into()])]
+        );
+    }
+
+    #[test]
+    fn attributes_pass()
+    {
+        assert_eq!(
+            Item::get(&mut TokenIt::lexed(
+                "@cold
+@link_name(\"puts\") extern func put(s &i8) i32\n"
+            )),
+            Ok(Item::Extern {
+                signature: FunctionSignature {
+                    name: ("put".into(), Some(Type(vec!["i32".into()]))),
+                    span: Span::new(37, 40),
+                    generics: vec![].into(),
+                    arguments: vec![ArgumentName {
+                        name: "s".into(),
+                        r#type: Type(vec!["&".into(), "i8".into()]),
+                        mutable: false,
+                        span: Span::single(41)
+                    }]
+                    .into(),
+                    variadic: false,
+                },
+                attributes: vec![
+                    Attribute {
+                        name: "cold".into(),
+                        args: vec![].into(),
+                        span: Span::new(0, 5)
+                    },
+                    Attribute {
+                        name: "link_name".into(),
+                        args: vec![Expression::Literal {
+                            value: "\"puts\"".into(),
+                            r#type: compiler_lexer::definitions::LiteralType::String,
+                            span: Span::new(17, 23),
+                        }]
+                        .into(),
+                        span: Span::new(6, 16)
+                    }
+                ]
+                .into(),
+            })
+        );
+
+        assert_eq!(
+            Item::get(&mut TokenIt::lexed("@inline\nimport foo\n")),
+            Err(ParserError::ExpectedASTStructure {
+                name: "item which takes attributes"
+            })
+        );
+
+        assert_eq!(
+            Item::get(&mut TokenIt::lexed("@\ninline func f() {}\n")),
+            Err(ParserError::ExpectedTokenType {
+                r#type: "Identifier"
+            })
+        );
+    }
+}
@@ -8,16 +8,18 @@ use crate::{ParserError, Statement};
 
 pub trait TokenItTrait = Iterator<Item = Token> + Clone;
 
+/// Tokens to parse, along with the source they were lexed from, which their text is read out of through their spans
 #[derive(Clone)]
-pub struct TokenIt<I: TokenItTrait>(pub Peekable<I>);
+pub struct TokenIt<'s, I: TokenItTrait>(pub Peekable<I>, pub &'s str);
+
+/// Parses a `T` out of the tokens
+pub type ParseFn<I, T> = fn(&mut TokenIt<I>) -> Result<T, ParserError>;
 
 pub trait ExhaustiveGet<I: TokenItTrait>: Sized
 {
-    type ParsePredicate = fn(&mut TokenIt<I>) -> Result<Self, ParserError>;
+    type ParsePredicate = ParseFn<I, Self>;
 
-    fn find_predicate(
-        tokens: &mut TokenIt<I>,
-    ) -> Result<fn(&mut TokenIt<I>) -> Result<Self, ParserError>, ParserError>;
+    fn find_predicate(tokens: &mut TokenIt<I>) -> Result<ParseFn<I, Self>, ParserError>;
 
     #[inline]
     fn get(tokens: &mut TokenIt<I>) -> Result<Self, ParserError>
@@ -26,8 +28,44 @@ pub trait ExhaustiveGet<I: TokenItTrait>: Sized
     }
 }
 
-impl<I: TokenItTrait> TokenIt<I>
+impl<'s, I: TokenItTrait> TokenIt<'s, I>
 {
+    /// Text of `token` in the source
+    #[inline]
+    pub fn text(&self, token: &Token) -> &'s str
+    {
+        token.span.source(self.1)
+    }
+
+    /// Whether the next token, newlines included, reads `value`
+    #[inline]
+    pub fn peek_is(&mut self, value: &str) -> bool
+    {
+        let source = self.1;
+
+        self.0
+            .peek()
+            .is_some_and(|t| t.span.source(source) == value)
+    }
+
+    /// Takes the next token if it reads `value`, without skipping newlines
+    #[inline]
+    pub fn next_if_is(&mut self, value: &str) -> Option<Token>
+    {
+        let source = self.1;
+
+        self.0.next_if(|t| t.span.source(source) == value)
+    }
+
+    /// Takes the next token past any newlines if it reads `value`
+    #[inline]
+    pub fn next_is(&mut self, value: &str) -> Option<Token>
+    {
+        let source = self.1;
+
+        self.next(|t| t.span.source(source) == value)
+    }
+
     #[inline]
     pub fn ignore_newlines(&mut self)
     {
@@ -55,7 +93,7 @@ impl<I: TokenItTrait> TokenIt<I>
         sep_predicate: Option<&str>,
     ) -> Result<EcoVec<T>, ParserError>
     {
-        self.next(|t| t.value == left_bound)
+        self.next_is(left_bound)
             .ok_or_else(|| ParserError::ExpectedTokenValue {
                 value: left_bound.into(),
             })?;
@@ -66,7 +104,7 @@ impl<I: TokenItTrait> TokenIt<I>
         {
             self.ignore_newlines();
 
-            if self.next(|t| t.value == right_bound).is_some()
+            if self.next_is(right_bound).is_some()
             {
                 break;
             }
@@ -75,7 +113,7 @@ impl<I: TokenItTrait> TokenIt<I>
             {
                 if !buffer.is_empty()
                 {
-                    let Some(_) = self.next(|t| t.value == sep_predicate)
+                    let Some(_) = self.next_is(sep_predicate)
                     else
                     {
                         return Err(ParserError::ExpectedComma);
@@ -99,3 +137,13 @@ impl<I: TokenItTrait> TokenIt<I>
         self.consume_generic_list(("{", "}"), Statement::get, None)
     }
 }
+
+#[cfg(test)]
+impl<'s> TokenIt<'s, std::vec::IntoIter<Token>>
+{
+    /// Tokens of `source` as the parser takes them, for tests
+    pub fn lexed(source: &'s str) -> Self
+    {
+        TokenIt(crate::lex(source).unwrap().into_iter().peekable(), source)
+    }
+}
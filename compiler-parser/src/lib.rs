@@ -4,19 +4,26 @@
 
 use std::fmt;
 
-use compiler_lexer::definitions::{Token, TokenType};
-use ecow::EcoString;
-pub use expression::{Expression, binary::Node, operator::Operator};
+use compiler_lexer::{
+    LexerError, LexerEvent,
+    definitions::{Span, Token, TokenType},
+};
+use ecow::{EcoString, EcoVec};
+pub use expression::{Capture, CaptureKind, Expression, binary::Node, operator::Operator};
 pub use item::Item;
 use iterator::{ExhaustiveGet, TokenIt, TokenItTrait};
 use miette::Diagnostic;
+use serde::Serialize;
 pub use statement::Statement;
 use thiserror::Error;
+pub use visit::{Visitor, fold::Folder, mut_visit::MutVisitor};
 
+pub mod cst;
 pub mod expression;
 pub mod item;
 mod iterator;
 pub mod statement;
+pub mod visit;
 
 #[derive(Error, Debug, Diagnostic, PartialEq)]
 #[error(transparent)]
@@ -46,6 +53,11 @@ pub enum ParserError
     {
         token: Token
     },
+    #[error("Unknown binary with `{:#?}`", token)]
+    IllegalBinary
+    {
+        token: Token
+    },
     #[error("Expected {}", name)]
     ExpectedASTStructure
     {
@@ -53,7 +65,7 @@ pub enum ParserError
     },
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct Type(pub Vec<EcoString>);
 
 impl fmt::Display for Type
@@ -65,37 +77,98 @@ impl fmt::Display for Type
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct ArgumentName(pub EcoString, pub Type);
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ArgumentName
+{
+    pub name: EcoString,
+    pub r#type: Type,
+    /// Declared as `mut name type`, so it may be assigned to
+    pub mutable: bool,
+    /// Span of the name
+    pub span: Span,
+}
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct Name(pub EcoString, pub Option<Type>);
+/// A declared name, with its type if annotated, and the span of the name
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Name(pub EcoString, pub Option<Type>, pub Span);
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct AST(pub Vec<Item>);
 
-pub struct Parser<'s, I: TokenItTrait>
+impl AST
+{
+    #[inline]
+    pub fn imports(&self) -> impl Iterator<Item = &EcoVec<EcoString>>
+    {
+        self.0.iter().filter_map(|i| match i
+        {
+            Item::Import(path, _) => Some(path),
+            _ => None,
+        })
+    }
+}
+
+/// A single source file. `path` is its namespace relative to the compilation root, where `import foo.bar` maps to `foo/bar.su`. The root file itself has an empty path.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Module
+{
+    pub path: EcoVec<EcoString>,
+    /// Path of the file as shown to the user, e.g. in source locations
+    pub file: EcoString,
+    pub source: EcoString,
+    pub ast: AST,
+}
+
+/// Lexes `source` into the tokens the parser takes, which leaves comments out
+pub fn lex(source: &str) -> Result<Vec<Token>, LexerError>
 {
-    source: &'s [u8],
-    tokens: TokenIt<I>,
+    compiler_lexer::tokenize(source)
+        .filter_map(|event| match event
+        {
+            LexerEvent::Token(Token {
+                r#type: TokenType::Comment,
+                ..
+            }) => None,
+            LexerEvent::Token(token) => Some(Ok(token)),
+            LexerEvent::Error(error) => Some(Err(error)),
+        })
+        .collect()
 }
 
+/// Parses the tokens lexed from `source`, whose text is read out of it
 #[inline(always)]
-pub fn parse(input: &[u8], tokens: impl TokenItTrait) -> Result<AST, ParserError>
+pub fn parse(source: &str, tokens: impl TokenItTrait) -> Result<AST, ParserError>
 {
-    let mut parser = Parser {
-        source: input,
-        tokens: TokenIt(tokens.peekable()),
-    };
+    let mut tokens = TokenIt(tokens.peekable(), source);
     let mut items = Vec::new();
 
-    while iterator.0.peek().is_some()
+    tokens.ignore_newlines();
+
+    while tokens.0.peek().is_some()
     {
-        if iterator.next(|t| t.r#type == TokenType::Newline).is_none()
-        {
-            items.push(Item::get(&mut iterator)?);
-        }
+        items.push(Item::get(&mut tokens)?);
+        tokens.ignore_newlines();
     }
 
     Ok(AST(items))
 }
+
+/// Parses a sequence of statements, as found within a block, e.g. for input typed into a REPL
+pub fn parse_statements(
+    source: &str,
+    tokens: impl TokenItTrait,
+) -> Result<EcoVec<Statement>, ParserError>
+{
+    let mut tokens = TokenIt(tokens.peekable(), source);
+    let mut statements = EcoVec::new();
+
+    tokens.ignore_newlines();
+
+    while tokens.0.peek().is_some()
+    {
+        statements.push(Statement::get(&mut tokens)?);
+        tokens.ignore_newlines();
+    }
+
+    Ok(statements)
+}
@@ -1,5 +1,6 @@
 use compiler_lexer::definitions::{Token, TokenType};
 use itertools::Itertools;
+use serde::Serialize;
 
 use crate::{
     Name, ParserError, TokenIt, Type,
@@ -7,7 +8,7 @@ use crate::{
     iterator::{ExhaustiveGet, TokenItTrait},
 };
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Statement
 {
     Return(Option<Expression>),
@@ -30,11 +31,11 @@ impl<I: TokenItTrait> ExhaustiveGet<I> for Statement
 {
     fn find_predicate(tokens: &mut TokenIt<I>) -> Result<Self::ParsePredicate, ParserError>
     {
-        if tokens.0.peek().is_some_and(|t| t.value == "ret")
+        if tokens.peek_is("ret")
         {
             Ok(Self::parse_return)
         }
-        else if tokens.0.peek().is_some_and(|t| t.value == "let")
+        else if tokens.peek_is("let")
         {
             Ok(Self::parse_local)
         }
@@ -44,7 +45,7 @@ impl<I: TokenItTrait> ExhaustiveGet<I> for Statement
                 let mut tokens = tokens.clone();
                 if Expression::get(&mut tokens).is_ok()
                 {
-                    if tokens.next(|t| t.value == "=").is_some()
+                    if tokens.next_is("=").is_some()
                     {
                         return Ok(Self::parse_assign);
                     }
@@ -92,7 +93,7 @@ impl Statement
     {
         Self::assert_end(tokens, |tokens| {
             tokens
-                .next(|t| t.value == "ret")
+                .next_is("ret")
                 .ok_or(ParserError::ExpectedTokenValue {
                     value: "ret".into(),
                 })?;
@@ -137,7 +138,7 @@ impl Statement
             let destination = Expression::get(tokens)?;
 
             tokens
-                .next(|t| t.value == "=")
+                .next_is("=")
                 .ok_or(ParserError::ExpectedTokenValue { value: "=".into() })?;
 
             let source = Expression::get(tokens)?;
@@ -153,28 +154,29 @@ impl Statement
     {
         Self::assert_end(tokens, |tokens| {
             tokens
-                .next(|t| t.value == "let")
+                .next_is("let")
                 .ok_or(ParserError::ExpectedTokenValue {
                     value: "let".into(),
                 })?;
 
-            let identifier = tokens
-                .next(|t| t.r#type == TokenType::Identifier)
-                .ok_or(ParserError::ExpectedTokenType {
-                    r#type: "Identifier",
-                })?
-                .value;
-
-            let mutable = tokens.next(|t| t.value == "mut").is_some();
-
             // shouldn't mut always only be intrinsic to the type?
             // No. a variable can be mutable. a type does not have this qualification. a pointer, however, may or may not be mutable.
+            let mutable = tokens.next_is("mut").is_some();
+
+            let identifier = tokens.next(|t| t.r#type == TokenType::Identifier).ok_or(
+                ParserError::ExpectedTokenType {
+                    r#type: "Identifier",
+                },
+            )?;
 
             let r#type = {
+                let source = tokens.1;
                 let r#type = tokens
                     .0
-                    .peeking_take_while(|t| t.value != "=" && t.r#type != TokenType::Newline)
-                    .map(|t| t.value)
+                    .peeking_take_while(|t| {
+                        t.span.source(source) != "=" && t.r#type != TokenType::Newline
+                    })
+                    .map(|t| t.span.source(source).into())
                     .collect::<Vec<_>>();
 
                 if r#type.is_empty()
@@ -187,7 +189,8 @@ impl Statement
                 }
             };
 
-            let init = if tokens.next(|t| t.value == "=").is_some()
+            // Without an initialiser the newline must stay, it ends the statement
+            let init = if tokens.next_if_is("=").is_some()
             {
                 Some(Expression::get(tokens)?)
             }
@@ -197,7 +200,7 @@ impl Statement
             };
 
             Ok(Self::Local {
-                name: Name(identifier, r#type),
+                name: Name(tokens.text(&identifier).into(), r#type, identifier.span),
                 mutable,
                 init,
             })
@@ -208,7 +211,7 @@ impl Statement
 #[cfg(test)]
 mod tests
 {
-    use compiler_lexer::definitions::LiteralType;
+    use compiler_lexer::definitions::{LiteralType, Span};
     use pretty_assertions::assert_eq;
 
     use super::*;
@@ -219,32 +222,22 @@ mod tests
     #[test]
     fn return_passes()
     {
-        println!(
-            "{:#?}",
-            compiler_lexer::tokenize("ret ").collect::<Vec<_>>()
-        );
-
         assert_eq!(
-            Statement::parse_return(&mut TokenIt(
-                compiler_lexer::tokenize("ret \n").flatten().peekable()
-            )),
+            Statement::parse_return(&mut TokenIt::lexed("ret \n")),
             Ok(Statement::Return(None))
         );
 
         assert_eq!(
-            Statement::parse_return(&mut TokenIt(
-                compiler_lexer::tokenize("ret 42").flatten().peekable()
-            )),
+            Statement::parse_return(&mut TokenIt::lexed("ret 42")),
             Ok(Statement::Return(Some(Expression::Literal {
                 value: "42".into(),
-                r#type: LiteralType::Int
+                r#type: LiteralType::Int,
+                span: Span::new(4, 6),
             })))
         );
 
         assert_eq!(
-            Statement::parse_return(&mut TokenIt(
-                compiler_lexer::tokenize("ret ret\n\n").flatten().peekable()
-            )),
+            Statement::parse_return(&mut TokenIt::lexed("ret ret\n\n")),
             Err(ParserError::ExpectedASTStructure { name: "Expression" })
         );
     }
@@ -253,35 +246,38 @@ mod tests
     fn assign_passes()
     {
         assert_eq!(
-            Statement::parse_assign(&mut TokenIt(
-                compiler_lexer::tokenize("a = 2").flatten().peekable()
-            )),
+            Statement::parse_assign(&mut TokenIt::lexed("a = 2")),
             Ok(Statement::Assign {
-                destination: Expression::Path(vec!["a".into()].into()),
+                destination: Expression::Path {
+                    path: vec!["a".into()].into(),
+                    span: Span::single(0)
+                },
                 source: Expression::Literal {
                     value: "2".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(4, 5),
                 }
             })
         );
 
         assert_eq!(
-            Statement::parse_assign(&mut TokenIt(
-                compiler_lexer::tokenize("*func_to_ptr() = 42")
-                    .flatten()
-                    .peekable()
-            )),
+            Statement::parse_assign(&mut TokenIt::lexed("*func_to_ptr() = 42")),
             Ok(Statement::Assign {
                 destination: Expression::Unary(
                     Operator::Star,
                     Box::new(Expression::Call {
-                        path: vec!["func_to_ptr".into()].into(),
-                        args: vec![].into()
+                        callee: Box::new(Expression::Path {
+                            path: vec!["func_to_ptr".into()].into(),
+                            span: Span::new(1, 12)
+                        }),
+                        args: vec![].into(),
+                        span: Span::single(12)
                     })
                 ),
                 source: Expression::Literal {
                     value: "42".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(17, 19),
                 }
             })
         );
@@ -291,86 +287,97 @@ mod tests
     fn local_passes()
     {
         assert_eq!(
-            Statement::parse_local(&mut TokenIt(
-                compiler_lexer::tokenize("let v").flatten().peekable()
-            )),
+            Statement::parse_local(&mut TokenIt::lexed("let v")),
+            Ok(Statement::Local {
+                mutable: false,
+                name: Name("v".into(), None, Span::single(4)),
+                init: None,
+            })
+        );
+
+        assert_eq!(
+            Statement::parse_local(&mut TokenIt::lexed("let v\nv = 2\n")),
             Ok(Statement::Local {
                 mutable: false,
-                name: Name("v".into(), None),
+                name: Name("v".into(), None, Span::single(4)),
                 init: None,
             })
         );
 
         assert_eq!(
-            Statement::parse_local(&mut TokenIt(
-                compiler_lexer::tokenize("let a = 2\n").flatten().peekable()
-            )),
+            Statement::parse_local(&mut TokenIt::lexed("let a = 2\n")),
             Ok(Statement::Local {
                 mutable: false,
-                name: Name("a".into(), None),
+                name: Name("a".into(), None, Span::single(4)),
                 init: Some(Expression::Literal {
                     value: "2".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(8, 9),
                 })
             })
         );
 
         assert_eq!(
-            Statement::parse_local(&mut TokenIt(
-                compiler_lexer::tokenize("let b i32 = 4\n")
-                    .flatten()
-                    .peekable()
-            )),
+            Statement::parse_local(&mut TokenIt::lexed("let b i32 = 4\n")),
             Ok(Statement::Local {
                 mutable: false,
-                name: Name("b".into(), Some(Type(vec!["i32".into()]))),
+                name: Name("b".into(), Some(Type(vec!["i32".into()])), Span::single(4)),
                 init: Some(Expression::Literal {
                     value: "4".into(),
-                    r#type: LiteralType::Int
+                    r#type: LiteralType::Int,
+                    span: Span::new(12, 13),
                 })
             })
         );
 
         assert_eq!(
-            Statement::parse_local(&mut TokenIt(
-                compiler_lexer::tokenize("let b i32\n").flatten().peekable()
-            )),
+            Statement::parse_local(&mut TokenIt::lexed("let b i32\n")),
             Ok(Statement::Local {
                 mutable: false,
-                name: Name("b".into(), Some(Type(vec!["i32".into()]))),
+                name: Name("b".into(), Some(Type(vec!["i32".into()])), Span::single(4)),
                 init: None
             })
         );
 
         assert_eq!(
-            Statement::parse_local(&mut TokenIt(
-                compiler_lexer::tokenize("let c *i32\n")
-                    .flatten()
-                    .peekable()
-            )),
+            Statement::parse_local(&mut TokenIt::lexed("let c *i32\n")),
             Ok(Statement::Local {
                 mutable: false,
-                name: Name("c".into(), Some(Type(vec!["*".into(), "i32".into()]))),
+                name: Name(
+                    "c".into(),
+                    Some(Type(vec!["*".into(), "i32".into()])),
+                    Span::single(4)
+                ),
                 init: None
             })
         );
 
         assert_eq!(
-            Statement::parse_local(&mut TokenIt(
-                compiler_lexer::tokenize("let s []i32\n")
-                    .flatten()
-                    .peekable()
-            )),
+            Statement::parse_local(&mut TokenIt::lexed("let s []i32\n")),
             Ok(Statement::Local {
                 mutable: false,
                 name: Name(
                     "s".into(),
-                    Some(Type(vec!["[".into(), "]".into(), "i32".into()]))
+                    Some(Type(vec!["[".into(), "]".into(), "i32".into()])),
+                    Span::single(4)
                 ),
                 init: None
             })
         );
 
+        assert_eq!(
+            Statement::parse_local(&mut TokenIt::lexed("let mut d i32 = 1\n")),
+            Ok(Statement::Local {
+                mutable: true,
+                name: Name("d".into(), Some(Type(vec!["i32".into()])), Span::single(8)),
+                init: Some(Expression::Literal {
+                    value: "1".into(),
+                    r#type: LiteralType::Int,
+                    span: Span::new(16, 17),
+                })
+            })
+        );
+
         // TODO finish tests
     }
 }
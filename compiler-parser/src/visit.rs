@@ -0,0 +1,310 @@
+//! Traversal over the AST. Implementors override the `visit_*` methods for the nodes they care about, calling the matching `walk_*`
+//! function to keep descending into their children. [`MutVisitor`](mut_visit::MutVisitor) does the same in place, and
+//! [`Folder`](fold::Folder) rebuilds the tree by value.
+
+use compiler_lexer::definitions::LiteralType;
+use ecow::EcoString;
+
+use crate::{
+    AST, ArgumentName, Capture, Name, Type,
+    expression::{Expression, binary::Node, operator::Operator},
+    item::{Attribute, FunctionSignature, Item},
+    statement::Statement,
+};
+
+pub mod fold;
+pub mod mut_visit;
+
+pub trait Visitor: Sized
+{
+    fn visit_ast(&mut self, ast: &AST)
+    {
+        walk_ast(self, ast);
+    }
+
+    fn visit_item(&mut self, item: &Item)
+    {
+        walk_item(self, item);
+    }
+
+    fn visit_attribute(&mut self, attribute: &Attribute)
+    {
+        walk_attribute(self, attribute);
+    }
+
+    fn visit_function_signature(&mut self, signature: &FunctionSignature)
+    {
+        walk_function_signature(self, signature);
+    }
+
+    fn visit_argument(&mut self, argument: &ArgumentName)
+    {
+        walk_argument(self, argument);
+    }
+
+    fn visit_block(&mut self, block: &[Statement])
+    {
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement)
+    {
+        walk_statement(self, statement);
+    }
+
+    fn visit_name(&mut self, name: &Name)
+    {
+        walk_name(self, name);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression)
+    {
+        walk_expression(self, expression);
+    }
+
+    fn visit_node(&mut self, node: &Node)
+    {
+        walk_node(self, node);
+    }
+
+    fn visit_literal(&mut self, _value: &EcoString, _type: LiteralType) {}
+
+    fn visit_path(&mut self, _path: &[EcoString]) {}
+
+    fn visit_capture(&mut self, _capture: &Capture) {}
+
+    fn visit_operator(&mut self, _operator: Operator) {}
+
+    fn visit_type(&mut self, _type: &Type) {}
+}
+
+pub fn walk_ast<V: Visitor>(visitor: &mut V, ast: &AST)
+{
+    ast.0.iter().for_each(|i| visitor.visit_item(i));
+}
+
+pub fn walk_item<V: Visitor>(visitor: &mut V, item: &Item)
+{
+    match item
+    {
+        Item::Import(path, _) => visitor.visit_path(path),
+        Item::Const {
+            name,
+            value,
+            attributes,
+        } =>
+        {
+            attributes.iter().for_each(|a| visitor.visit_attribute(a));
+            visitor.visit_name(name);
+            visitor.visit_expression(value);
+        }
+        Item::Function {
+            signature,
+            body,
+            attributes,
+        } =>
+        {
+            attributes.iter().for_each(|a| visitor.visit_attribute(a));
+            visitor.visit_function_signature(signature);
+            visitor.visit_block(body);
+        }
+        Item::Extern {
+            signature,
+            attributes,
+        } =>
+        {
+            attributes.iter().for_each(|a| visitor.visit_attribute(a));
+            visitor.visit_function_signature(signature);
+        }
+    }
+}
+
+pub fn walk_attribute<V: Visitor>(visitor: &mut V, attribute: &Attribute)
+{
+    attribute
+        .args
+        .iter()
+        .for_each(|e| visitor.visit_expression(e));
+}
+
+pub fn walk_function_signature<V: Visitor>(visitor: &mut V, signature: &FunctionSignature)
+{
+    signature
+        .arguments
+        .iter()
+        .for_each(|a| visitor.visit_argument(a));
+
+    if let Some(r#type) = &signature.name.1
+    {
+        visitor.visit_type(r#type);
+    }
+}
+
+pub fn walk_argument<V: Visitor>(visitor: &mut V, argument: &ArgumentName)
+{
+    visitor.visit_type(&argument.r#type);
+}
+
+pub fn walk_block<V: Visitor>(visitor: &mut V, block: &[Statement])
+{
+    block.iter().for_each(|s| visitor.visit_statement(s));
+}
+
+pub fn walk_statement<V: Visitor>(visitor: &mut V, statement: &Statement)
+{
+    match statement
+    {
+        Statement::Return(e) =>
+        {
+            if let Some(e) = e
+            {
+                visitor.visit_expression(e);
+            }
+        }
+        Statement::Expression(e) => visitor.visit_expression(e),
+        Statement::Assign {
+            destination,
+            source,
+        } =>
+        {
+            visitor.visit_expression(destination);
+            visitor.visit_expression(source);
+        }
+        Statement::Local { name, init, .. } =>
+        {
+            visitor.visit_name(name);
+
+            if let Some(init) = init
+            {
+                visitor.visit_expression(init);
+            }
+        }
+    }
+}
+
+pub fn walk_name<V: Visitor>(visitor: &mut V, name: &Name)
+{
+    if let Some(r#type) = &name.1
+    {
+        visitor.visit_type(r#type);
+    }
+}
+
+pub fn walk_expression<V: Visitor>(visitor: &mut V, expression: &Expression)
+{
+    match expression
+    {
+        Expression::Literal { value, r#type, .. } => visitor.visit_literal(value, *r#type),
+        Expression::Path { path, .. } => visitor.visit_path(path),
+        Expression::Binary(node) => visitor.visit_node(node),
+        Expression::Unary(operator, e) =>
+        {
+            visitor.visit_operator(*operator);
+            visitor.visit_expression(e);
+        }
+        Expression::Cast { target, r#type, .. } =>
+        {
+            visitor.visit_expression(target);
+            visitor.visit_type(r#type);
+        }
+        Expression::Call { callee, args, .. } =>
+        {
+            visitor.visit_expression(callee);
+            args.iter().for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::Method { receiver, args, .. } =>
+        {
+            visitor.visit_expression(receiver);
+            args.iter().for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::Field { target, .. } => visitor.visit_expression(target),
+        Expression::Index { target, index, .. } =>
+        {
+            visitor.visit_expression(target);
+            visitor.visit_expression(index);
+        }
+        Expression::Slice {
+            target, start, end, ..
+        } =>
+        {
+            visitor.visit_expression(target);
+            start.iter().for_each(|e| visitor.visit_expression(e));
+            end.iter().for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::If {
+            condition,
+            block,
+            else_block,
+        } =>
+        {
+            visitor.visit_expression(condition);
+            visitor.visit_block(block);
+
+            if let Some(else_block) = else_block
+            {
+                visitor.visit_block(else_block);
+            }
+        }
+        Expression::Parenthesis(e) => visitor.visit_expression(e),
+        Expression::Tuple(items) | Expression::Array(items) =>
+        {
+            items.iter().for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::Closure {
+            captures,
+            signature,
+            body,
+        } =>
+        {
+            captures.iter().for_each(|c| visitor.visit_capture(c));
+            visitor.visit_function_signature(signature);
+            visitor.visit_block(body);
+        }
+    }
+}
+
+pub fn walk_node<V: Visitor>(visitor: &mut V, node: &Node)
+{
+    match node
+    {
+        Node::Scalar(e) => visitor.visit_expression(e),
+        Node::Compound(box (l, operator, r)) =>
+        {
+            visitor.visit_node(l);
+            visitor.visit_operator(*operator);
+            visitor.visit_node(r);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{TokenIt, iterator::ExhaustiveGet};
+
+    #[derive(Default)]
+    struct Paths(Vec<String>);
+
+    impl Visitor for Paths
+    {
+        fn visit_path(&mut self, path: &[EcoString])
+        {
+            self.0.push(path.join("."));
+        }
+    }
+
+    #[test]
+    fn visitor_reaches_nested_nodes()
+    {
+        let item = Item::get(&mut TokenIt::lexed("func f(a i32) i32 {\n    let c = -math.add(a, (d))\n    if b {\n        ret c\n    } else {\n        ret e\n    }\n}"))
+        .unwrap();
+
+        let mut paths = Paths::default();
+        paths.visit_item(&item);
+
+        assert_eq!(paths.0, ["math.add", "a", "d", "b", "c", "e"]);
+    }
+}
@@ -0,0 +1,377 @@
+//! By value counterpart of [`Visitor`](super::Visitor), each `fold_*` returns the node to put in place of the one it's given.
+//! Fit for desugaring passes which replace nodes with nodes of another kind.
+
+use ecow::{EcoString, EcoVec};
+
+use crate::{
+    AST, ArgumentName, Capture, Name, Type,
+    expression::{Expression, binary::Node, operator::Operator},
+    item::{Attribute, FunctionSignature, Item},
+    statement::Statement,
+};
+
+pub trait Folder: Sized
+{
+    fn fold_ast(&mut self, ast: AST) -> AST
+    {
+        walk_ast(self, ast)
+    }
+
+    fn fold_item(&mut self, item: Item) -> Item
+    {
+        walk_item(self, item)
+    }
+
+    fn fold_attribute(&mut self, attribute: Attribute) -> Attribute
+    {
+        walk_attribute(self, attribute)
+    }
+
+    fn fold_function_signature(&mut self, signature: FunctionSignature) -> FunctionSignature
+    {
+        walk_function_signature(self, signature)
+    }
+
+    fn fold_argument(&mut self, argument: ArgumentName) -> ArgumentName
+    {
+        walk_argument(self, argument)
+    }
+
+    fn fold_block(&mut self, block: EcoVec<Statement>) -> EcoVec<Statement>
+    {
+        walk_block(self, block)
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement
+    {
+        walk_statement(self, statement)
+    }
+
+    fn fold_name(&mut self, name: Name) -> Name
+    {
+        walk_name(self, name)
+    }
+
+    fn fold_expression(&mut self, expression: Expression) -> Expression
+    {
+        walk_expression(self, expression)
+    }
+
+    fn fold_node(&mut self, node: Node) -> Node
+    {
+        walk_node(self, node)
+    }
+
+    fn fold_path(&mut self, path: EcoVec<EcoString>) -> EcoVec<EcoString>
+    {
+        path
+    }
+
+    fn fold_capture(&mut self, capture: Capture) -> Capture
+    {
+        capture
+    }
+
+    fn fold_operator(&mut self, operator: Operator) -> Operator
+    {
+        operator
+    }
+
+    fn fold_type(&mut self, r#type: Type) -> Type
+    {
+        r#type
+    }
+}
+
+pub fn walk_ast<F: Folder>(folder: &mut F, ast: AST) -> AST
+{
+    AST(ast.0.into_iter().map(|i| folder.fold_item(i)).collect())
+}
+
+pub fn walk_item<F: Folder>(folder: &mut F, item: Item) -> Item
+{
+    match item
+    {
+        Item::Import(path, span) => Item::Import(folder.fold_path(path), span),
+        Item::Const {
+            name,
+            value,
+            attributes,
+        } => Item::Const {
+            attributes: fold_attributes(folder, attributes),
+            name: folder.fold_name(name),
+            value: folder.fold_expression(value),
+        },
+        Item::Function {
+            signature,
+            body,
+            attributes,
+        } => Item::Function {
+            attributes: fold_attributes(folder, attributes),
+            signature: folder.fold_function_signature(signature),
+            body: folder.fold_block(body),
+        },
+        Item::Extern {
+            signature,
+            attributes,
+        } => Item::Extern {
+            attributes: fold_attributes(folder, attributes),
+            signature: folder.fold_function_signature(signature),
+        },
+    }
+}
+
+#[inline]
+fn fold_attributes<F: Folder>(folder: &mut F, attributes: EcoVec<Attribute>) -> EcoVec<Attribute>
+{
+    attributes
+        .into_iter()
+        .map(|a| folder.fold_attribute(a))
+        .collect()
+}
+
+pub fn walk_attribute<F: Folder>(folder: &mut F, attribute: Attribute) -> Attribute
+{
+    Attribute {
+        args: attribute
+            .args
+            .into_iter()
+            .map(|e| folder.fold_expression(e))
+            .collect(),
+        ..attribute
+    }
+}
+
+pub fn walk_function_signature<F: Folder>(
+    folder: &mut F,
+    signature: FunctionSignature,
+) -> FunctionSignature
+{
+    FunctionSignature {
+        arguments: signature
+            .arguments
+            .into_iter()
+            .map(|a| folder.fold_argument(a))
+            .collect(),
+        name: (
+            signature.name.0,
+            signature.name.1.map(|t| folder.fold_type(t)),
+        ),
+        ..signature
+    }
+}
+
+pub fn walk_argument<F: Folder>(folder: &mut F, argument: ArgumentName) -> ArgumentName
+{
+    ArgumentName {
+        r#type: folder.fold_type(argument.r#type),
+        ..argument
+    }
+}
+
+pub fn walk_block<F: Folder>(folder: &mut F, block: EcoVec<Statement>) -> EcoVec<Statement>
+{
+    block
+        .into_iter()
+        .map(|s| folder.fold_statement(s))
+        .collect()
+}
+
+pub fn walk_statement<F: Folder>(folder: &mut F, statement: Statement) -> Statement
+{
+    match statement
+    {
+        Statement::Return(e) => Statement::Return(e.map(|e| folder.fold_expression(e))),
+        Statement::Expression(e) => Statement::Expression(folder.fold_expression(e)),
+        Statement::Assign {
+            destination,
+            source,
+        } => Statement::Assign {
+            destination: folder.fold_expression(destination),
+            source: folder.fold_expression(source),
+        },
+        Statement::Local {
+            mutable,
+            name,
+            init,
+        } => Statement::Local {
+            mutable,
+            name: folder.fold_name(name),
+            init: init.map(|e| folder.fold_expression(e)),
+        },
+    }
+}
+
+pub fn walk_name<F: Folder>(folder: &mut F, name: Name) -> Name
+{
+    Name(name.0, name.1.map(|t| folder.fold_type(t)), name.2)
+}
+
+pub fn walk_expression<F: Folder>(folder: &mut F, expression: Expression) -> Expression
+{
+    match expression
+    {
+        e @ Expression::Literal { .. } => e,
+        Expression::Path { path, span } => Expression::Path {
+            path: folder.fold_path(path),
+            span,
+        },
+        Expression::Binary(box node) => Expression::Binary(Box::new(folder.fold_node(node))),
+        Expression::Unary(operator, box e) => Expression::Unary(
+            folder.fold_operator(operator),
+            Box::new(folder.fold_expression(e)),
+        ),
+        Expression::Cast {
+            box target,
+            r#type,
+            span,
+        } => Expression::Cast {
+            target: Box::new(folder.fold_expression(target)),
+            r#type: folder.fold_type(r#type),
+            span,
+        },
+        Expression::Call {
+            box callee,
+            args,
+            span,
+        } => Expression::Call {
+            callee: Box::new(folder.fold_expression(callee)),
+            args: args
+                .into_iter()
+                .map(|e| folder.fold_expression(e))
+                .collect(),
+            span,
+        },
+        Expression::Method {
+            box receiver,
+            name,
+            args,
+            span,
+        } => Expression::Method {
+            receiver: Box::new(folder.fold_expression(receiver)),
+            name,
+            args: args
+                .into_iter()
+                .map(|e| folder.fold_expression(e))
+                .collect(),
+            span,
+        },
+        Expression::Field {
+            box target,
+            name,
+            span,
+        } => Expression::Field {
+            target: Box::new(folder.fold_expression(target)),
+            name,
+            span,
+        },
+        Expression::Index {
+            box target,
+            box index,
+            span,
+        } => Expression::Index {
+            target: Box::new(folder.fold_expression(target)),
+            index: Box::new(folder.fold_expression(index)),
+            span,
+        },
+        Expression::Slice {
+            box target,
+            start,
+            end,
+            span,
+        } => Expression::Slice {
+            target: Box::new(folder.fold_expression(target)),
+            start: start.map(|box e| Box::new(folder.fold_expression(e))),
+            end: end.map(|box e| Box::new(folder.fold_expression(e))),
+            span,
+        },
+        Expression::If {
+            condition,
+            block,
+            else_block,
+        } => Expression::If {
+            condition: Box::new(folder.fold_expression(*condition)),
+            block: folder.fold_block(block),
+            else_block: else_block.map(|b| folder.fold_block(b)),
+        },
+        Expression::Parenthesis(box e) =>
+        {
+            Expression::Parenthesis(Box::new(folder.fold_expression(e)))
+        }
+        Expression::Tuple(items) => Expression::Tuple(
+            items
+                .into_iter()
+                .map(|e| folder.fold_expression(e))
+                .collect(),
+        ),
+        Expression::Array(items) => Expression::Array(
+            items
+                .into_iter()
+                .map(|e| folder.fold_expression(e))
+                .collect(),
+        ),
+        Expression::Closure {
+            captures,
+            box signature,
+            body,
+        } => Expression::Closure {
+            captures: captures
+                .into_iter()
+                .map(|c| folder.fold_capture(c))
+                .collect(),
+            signature: Box::new(folder.fold_function_signature(signature)),
+            body: folder.fold_block(body),
+        },
+    }
+}
+
+pub fn walk_node<F: Folder>(folder: &mut F, node: Node) -> Node
+{
+    match node
+    {
+        Node::Scalar(e) => Node::Scalar(folder.fold_expression(e)),
+        Node::Compound(box (l, operator, r)) => Node::Compound(Box::new((
+            folder.fold_node(l),
+            folder.fold_operator(operator),
+            folder.fold_node(r),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{TokenIt, iterator::ExhaustiveGet};
+
+    /// Drops parentheses that don't group anything
+    struct Unparenthesise;
+
+    impl Folder for Unparenthesise
+    {
+        fn fold_expression(&mut self, expression: Expression) -> Expression
+        {
+            match walk_expression(self, expression)
+            {
+                Expression::Parenthesis(
+                    box e @ (Expression::Literal { .. } | Expression::Path { .. }),
+                ) => e,
+                e => e,
+            }
+        }
+    }
+
+    #[test]
+    fn folder_replaces_nodes()
+    {
+        let statement = |source| Statement::get(&mut TokenIt::lexed(source)).unwrap();
+
+        // Spaced so that `2` and `a` keep their spans
+        assert_eq!(
+            Unparenthesise.fold_statement(statement("ret f((2), ((a)))\n")),
+            statement("ret f( 2,    a)\n")
+        );
+    }
+}
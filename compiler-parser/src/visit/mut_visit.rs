@@ -0,0 +1,362 @@
+//! In place counterpart of [`Visitor`](super::Visitor). Blocks and paths are handed out as their vectors, so passes may add or remove
+//! statements and segments.
+
+use compiler_lexer::definitions::LiteralType;
+use ecow::{EcoString, EcoVec};
+
+use crate::{
+    AST, ArgumentName, Capture, Name, Type,
+    expression::{Expression, binary::Node, operator::Operator},
+    item::{Attribute, FunctionSignature, Item},
+    statement::Statement,
+};
+
+pub trait MutVisitor: Sized
+{
+    fn visit_ast(&mut self, ast: &mut AST)
+    {
+        walk_ast(self, ast);
+    }
+
+    fn visit_item(&mut self, item: &mut Item)
+    {
+        walk_item(self, item);
+    }
+
+    fn visit_attribute(&mut self, attribute: &mut Attribute)
+    {
+        walk_attribute(self, attribute);
+    }
+
+    fn visit_function_signature(&mut self, signature: &mut FunctionSignature)
+    {
+        walk_function_signature(self, signature);
+    }
+
+    fn visit_argument(&mut self, argument: &mut ArgumentName)
+    {
+        walk_argument(self, argument);
+    }
+
+    fn visit_block(&mut self, block: &mut EcoVec<Statement>)
+    {
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &mut Statement)
+    {
+        walk_statement(self, statement);
+    }
+
+    fn visit_name(&mut self, name: &mut Name)
+    {
+        walk_name(self, name);
+    }
+
+    fn visit_expression(&mut self, expression: &mut Expression)
+    {
+        walk_expression(self, expression);
+    }
+
+    fn visit_node(&mut self, node: &mut Node)
+    {
+        walk_node(self, node);
+    }
+
+    fn visit_literal(&mut self, _value: &mut EcoString, _type: &mut LiteralType) {}
+
+    fn visit_path(&mut self, _path: &mut EcoVec<EcoString>) {}
+
+    fn visit_capture(&mut self, _capture: &mut Capture) {}
+
+    fn visit_operator(&mut self, _operator: &mut Operator) {}
+
+    fn visit_type(&mut self, _type: &mut Type) {}
+}
+
+pub fn walk_ast<V: MutVisitor>(visitor: &mut V, ast: &mut AST)
+{
+    ast.0.iter_mut().for_each(|i| visitor.visit_item(i));
+}
+
+pub fn walk_item<V: MutVisitor>(visitor: &mut V, item: &mut Item)
+{
+    match item
+    {
+        Item::Import(path, _) => visitor.visit_path(path),
+        Item::Const {
+            name,
+            value,
+            attributes,
+        } =>
+        {
+            walk_attributes(visitor, attributes);
+            visitor.visit_name(name);
+            visitor.visit_expression(value);
+        }
+        Item::Function {
+            signature,
+            body,
+            attributes,
+        } =>
+        {
+            walk_attributes(visitor, attributes);
+            visitor.visit_function_signature(signature);
+            visitor.visit_block(body);
+        }
+        Item::Extern {
+            signature,
+            attributes,
+        } =>
+        {
+            walk_attributes(visitor, attributes);
+            visitor.visit_function_signature(signature);
+        }
+    }
+}
+
+#[inline]
+fn walk_attributes<V: MutVisitor>(visitor: &mut V, attributes: &mut EcoVec<Attribute>)
+{
+    attributes
+        .make_mut()
+        .iter_mut()
+        .for_each(|a| visitor.visit_attribute(a));
+}
+
+pub fn walk_attribute<V: MutVisitor>(visitor: &mut V, attribute: &mut Attribute)
+{
+    attribute
+        .args
+        .make_mut()
+        .iter_mut()
+        .for_each(|e| visitor.visit_expression(e));
+}
+
+pub fn walk_function_signature<V: MutVisitor>(visitor: &mut V, signature: &mut FunctionSignature)
+{
+    signature
+        .arguments
+        .make_mut()
+        .iter_mut()
+        .for_each(|a| visitor.visit_argument(a));
+
+    if let Some(r#type) = &mut signature.name.1
+    {
+        visitor.visit_type(r#type);
+    }
+}
+
+pub fn walk_argument<V: MutVisitor>(visitor: &mut V, argument: &mut ArgumentName)
+{
+    visitor.visit_type(&mut argument.r#type);
+}
+
+pub fn walk_block<V: MutVisitor>(visitor: &mut V, block: &mut EcoVec<Statement>)
+{
+    block
+        .make_mut()
+        .iter_mut()
+        .for_each(|s| visitor.visit_statement(s));
+}
+
+pub fn walk_statement<V: MutVisitor>(visitor: &mut V, statement: &mut Statement)
+{
+    match statement
+    {
+        Statement::Return(e) =>
+        {
+            if let Some(e) = e
+            {
+                visitor.visit_expression(e);
+            }
+        }
+        Statement::Expression(e) => visitor.visit_expression(e),
+        Statement::Assign {
+            destination,
+            source,
+        } =>
+        {
+            visitor.visit_expression(destination);
+            visitor.visit_expression(source);
+        }
+        Statement::Local { name, init, .. } =>
+        {
+            visitor.visit_name(name);
+
+            if let Some(init) = init
+            {
+                visitor.visit_expression(init);
+            }
+        }
+    }
+}
+
+pub fn walk_name<V: MutVisitor>(visitor: &mut V, name: &mut Name)
+{
+    if let Some(r#type) = &mut name.1
+    {
+        visitor.visit_type(r#type);
+    }
+}
+
+pub fn walk_expression<V: MutVisitor>(visitor: &mut V, expression: &mut Expression)
+{
+    match expression
+    {
+        Expression::Literal { value, r#type, .. } => visitor.visit_literal(value, r#type),
+        Expression::Path { path, .. } => visitor.visit_path(path),
+        Expression::Binary(node) => visitor.visit_node(node),
+        Expression::Unary(operator, e) =>
+        {
+            visitor.visit_operator(operator);
+            visitor.visit_expression(e);
+        }
+        Expression::Cast { target, r#type, .. } =>
+        {
+            visitor.visit_expression(target);
+            visitor.visit_type(r#type);
+        }
+        Expression::Call { callee, args, .. } =>
+        {
+            visitor.visit_expression(callee);
+            args.make_mut()
+                .iter_mut()
+                .for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::Method { receiver, args, .. } =>
+        {
+            visitor.visit_expression(receiver);
+            args.make_mut()
+                .iter_mut()
+                .for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::Field { target, .. } => visitor.visit_expression(target),
+        Expression::Index { target, index, .. } =>
+        {
+            visitor.visit_expression(target);
+            visitor.visit_expression(index);
+        }
+        Expression::Slice {
+            target, start, end, ..
+        } =>
+        {
+            visitor.visit_expression(target);
+            start.iter_mut().for_each(|e| visitor.visit_expression(e));
+            end.iter_mut().for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::If {
+            condition,
+            block,
+            else_block,
+        } =>
+        {
+            visitor.visit_expression(condition);
+            visitor.visit_block(block);
+
+            if let Some(else_block) = else_block
+            {
+                visitor.visit_block(else_block);
+            }
+        }
+        Expression::Parenthesis(e) => visitor.visit_expression(e),
+        Expression::Tuple(items) | Expression::Array(items) =>
+        {
+            items
+                .make_mut()
+                .iter_mut()
+                .for_each(|e| visitor.visit_expression(e));
+        }
+        Expression::Closure {
+            captures,
+            signature,
+            body,
+        } =>
+        {
+            captures
+                .make_mut()
+                .iter_mut()
+                .for_each(|c| visitor.visit_capture(c));
+            visitor.visit_function_signature(signature);
+            visitor.visit_block(body);
+        }
+    }
+}
+
+pub fn walk_node<V: MutVisitor>(visitor: &mut V, node: &mut Node)
+{
+    match node
+    {
+        Node::Scalar(e) => visitor.visit_expression(e),
+        Node::Compound(box (l, operator, r)) =>
+        {
+            visitor.visit_node(l);
+            visitor.visit_operator(operator);
+            visitor.visit_node(r);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{TokenIt, Visitor, iterator::ExhaustiveGet};
+
+    /// Qualifies every use of `name` with `module`
+    struct Qualify
+    {
+        name: &'static str,
+        module: &'static str,
+    }
+
+    impl MutVisitor for Qualify
+    {
+        fn visit_path(&mut self, path: &mut EcoVec<EcoString>)
+        {
+            if path.as_slice() == [self.name]
+            {
+                *path = vec![self.module.into(), self.name.into()].into();
+            }
+        }
+    }
+
+    struct Paths(Vec<Vec<String>>);
+
+    impl Visitor for Paths
+    {
+        fn visit_path(&mut self, path: &[EcoString])
+        {
+            self.0.push(path.iter().map(ToString::to_string).collect());
+        }
+    }
+
+    #[test]
+    fn mut_visitor_rewrites_in_place()
+    {
+        let mut statement = Statement::get(&mut TokenIt::lexed("a = add(add(1, a), b)\n")).unwrap();
+
+        Qualify {
+            name: "add",
+            module: "math",
+        }
+        .visit_statement(&mut statement);
+
+        // Spans are left as they were, so only the paths are compared
+        let mut paths = Paths(Vec::new());
+        paths.visit_statement(&statement);
+
+        assert_eq!(
+            paths.0,
+            [
+                vec!["a"],
+                vec!["math", "add"],
+                vec!["math", "add"],
+                vec!["a"],
+                vec!["b"]
+            ]
+        );
+    }
+}
@@ -1,9 +1,9 @@
-use compiler_lexer::definitions::LiteralType;
+use compiler_lexer::definitions::{LiteralType, Span};
 use compiler_parser::{AST, Expression, Item, Name, Statement, Type, item::FunctionSignature};
 use pretty_assertions::assert_eq;
 
 const SOURCE: &str = r#"func function() {
-    let value mut = 42
+    let mut value = 42
     let float f64 = 2.45
     let spec u8 = 0b010
     let a_rune rune
@@ -16,60 +16,91 @@ const SOURCE: &str = r#"func function() {
 #[test]
 fn parser_passes()
 {
+    let tokens = compiler_parser::lex(SOURCE).unwrap();
+
     assert_eq!(
-        compiler_parser::parse(compiler_lexer::tokenize(SOURCE).flatten()),
+        compiler_parser::parse(SOURCE, tokens.into_iter()),
         Ok(AST(vec![Item::Function {
             signature: FunctionSignature {
                 name: ("function".into(), None),
-                arguments: vec![].into()
+                span: Span::new(5, 13),
+                generics: vec![].into(),
+                arguments: vec![].into(),
+                variadic: false,
             },
             body: vec![
                 Statement::Local {
-                    name: Name("value".into(), None),
+                    name: Name("value".into(), None, Span::new(30, 35)),
                     mutable: true,
                     init: Some(Expression::Literal {
                         value: "42".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(38, 40),
                     })
                 },
                 Statement::Local {
-                    name: Name("float".into(), Some(Type(vec!["f64".into()]))),
+                    name: Name(
+                        "float".into(),
+                        Some(Type(vec!["f64".into()])),
+                        Span::new(49, 54)
+                    ),
                     mutable: false,
                     init: Some(Expression::Literal {
                         value: "2.45".into(),
-                        r#type: LiteralType::Float
+                        r#type: LiteralType::Float,
+                        span: Span::new(61, 65),
                     })
                 },
                 Statement::Local {
-                    name: Name("spec".into(), Some(Type(vec!["u8".into()]))),
+                    name: Name(
+                        "spec".into(),
+                        Some(Type(vec!["u8".into()])),
+                        Span::new(74, 78)
+                    ),
                     mutable: false,
                     init: Some(Expression::Literal {
                         value: "0b010".into(),
-                        r#type: LiteralType::Int
+                        r#type: LiteralType::Int,
+                        span: Span::new(84, 89),
                     })
                 },
                 Statement::Local {
-                    name: Name("a_rune".into(), Some(Type(vec!["rune".into()]))),
+                    name: Name(
+                        "a_rune".into(),
+                        Some(Type(vec!["rune".into()])),
+                        Span::new(98, 104)
+                    ),
                     mutable: false,
                     init: None
                 },
                 Statement::Local {
                     name: Name(
                         "a_str".into(),
-                        Some(Type(vec!["[".into(), "]".into(), "rune".into()]))
+                        Some(Type(vec!["[".into(), "]".into(), "rune".into()])),
+                        Span::new(118, 123)
                     ),
                     mutable: false,
                     init: Some(Expression::Literal {
                         value: "\"bruh\"".into(),
-                        r#type: LiteralType::String
+                        r#type: LiteralType::String,
+                        span: Span::new(133, 139),
                     })
                 },
                 Statement::Expression(Expression::Call {
-                    path: vec!["call".into()].into(),
-                    args: vec![Expression::Path(vec!["number".into()].into())].into()
+                    callee: Box::new(Expression::Path {
+                        path: vec!["call".into()].into(),
+                        span: Span::new(145, 149)
+                    }),
+                    args: vec![Expression::Path {
+                        path: vec!["number".into()].into(),
+                        span: Span::new(150, 156)
+                    }]
+                    .into(),
+                    span: Span::single(149)
                 })
             ]
-            .into()
+            .into(),
+            attributes: vec![].into()
         }]))
     );
 }
@@ -0,0 +1,1444 @@
+//! The checker. Signatures and declared types come first, so items may be used before they're declared, then the types of the
+//! constants declared without one are inferred from their values, each on demand, and last every body is checked. Instances of
+//! generic functions are declared as they're called and checked once the bodies before them are. Once the whole program checks,
+//! the values of constants are evaluated, for both back-ends to take as they are.
+
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use compiler_hir::{self as hir, DefId, Hir, ItemId};
+use compiler_lexer::definitions::Span;
+use compiler_parser::{Type as ParserType, item::FunctionSignature};
+use ecow::{EcoString, EcoVec};
+
+use crate::{
+    Attributes, BindingKind, Block, Expression, Function, FunctionId, Item, ItemKind, Local,
+    LocalId, Statement, Thir, Type, TypeError, TypeWarning,
+};
+
+mod assignment;
+mod attribute;
+mod call;
+mod constant;
+mod escape;
+mod expression;
+
+type Result<T> = std::result::Result<T, Box<TypeError>>;
+
+/// What an item was declared as, before its body or value is checked
+#[derive(Clone, Copy, Debug)]
+enum Declared
+{
+    Const,
+    Function(FunctionId),
+    Extern(FunctionId),
+    Generic,
+}
+
+/// What the body being checked sees
+#[derive(Default)]
+struct Frame
+{
+    module: EcoVec<EcoString>,
+    /// Function whose body is being checked, if any
+    item: Option<ItemId>,
+    generics: HashMap<EcoString, Type>,
+    scope: HashMap<hir::LocalId, LocalId>,
+    /// Locals declared without a value, which may be assigned once even if immutable
+    deferred: HashSet<hir::LocalId>,
+    return_type: Type,
+    /// Whether returning without a value is fine regardless, as it is on the root `main`, which returns 0 then
+    implicit_return: bool,
+    /// Where expressions without a span of their own point at, the function or the closure they're in
+    at: Span,
+}
+
+/// A type a local declared without one is assigned, along with whether it's from an untyped literal
+type Candidate = (Type, bool);
+
+struct Checker<'h>
+{
+    hir: &'h Hir,
+    declared: Vec<Declared>,
+    /// Of every item, as checked when it's declared
+    attributes: Vec<Attributes>,
+    functions: Vec<Function>,
+    locals: Vec<Local>,
+    /// Of every constant, once declared or inferred
+    constants: HashMap<ItemId, Result<Type>>,
+    /// Values of the constants checked so far
+    values: HashMap<ItemId, Expression>,
+    /// Constants whose types are being inferred, the innermost last, to catch those depending on themselves
+    inferring: Vec<ItemId>,
+    /// Instances of generic functions by their generic arguments
+    instances: HashMap<(ItemId, Vec<Type>), FunctionId>,
+    /// Locals declared without a type nor a value, along with the types assigned to them so far. They're only there while the
+    /// statements after their declaration are probed for those
+    unknown: HashMap<LocalId, Vec<Candidate>>,
+    frame: Frame,
+    errors: Vec<TypeError>,
+    warnings: Vec<TypeWarning>,
+}
+
+/// Checks the types of the whole program. Every body is checked on its own, so there's up to an error for each
+pub fn check(hir: &Hir) -> std::result::Result<Thir, Vec<TypeError>>
+{
+    let mut checker = Checker {
+        hir,
+        declared: Vec::with_capacity(hir.items.len()),
+        attributes: Vec::with_capacity(hir.items.len()),
+        functions: Vec::new(),
+        locals: Vec::new(),
+        constants: HashMap::new(),
+        values: HashMap::new(),
+        inferring: Vec::new(),
+        instances: HashMap::new(),
+        unknown: HashMap::new(),
+        frame: Frame::default(),
+        errors: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    checker.declare();
+
+    // Bodies make no sense without the signatures they use
+    if checker.errors.is_empty()
+    {
+        checker.check_constants();
+        checker.check_functions();
+    }
+
+    // Nor values without the types of what they're made of
+    let mut folded = if checker.errors.is_empty()
+    {
+        let (folded, errors) = constant::fold(hir, &checker.values);
+        checker.errors.extend(errors);
+
+        folded
+    }
+    else
+    {
+        HashMap::new()
+    };
+
+    if !checker.errors.is_empty()
+    {
+        let mut errors = Vec::new();
+
+        for error in checker.errors
+        {
+            if !errors.contains(&error)
+            {
+                errors.push(error);
+            }
+        }
+
+        return Err(errors);
+    }
+
+    let items = hir
+        .items
+        .iter()
+        .zip(checker.attributes)
+        .enumerate()
+        .map(|(i, (item, attributes))| Item {
+            name: item.name.clone(),
+            module: item.module.clone(),
+            span: item.span,
+            attributes,
+            kind: match checker.declared[i]
+            {
+                Declared::Const => ItemKind::Const {
+                    r#type: checker.constants[&ItemId(i)].clone().unwrap(),
+                    value: Box::new(checker.values.remove(&ItemId(i)).unwrap()),
+                    folded: folded.remove(&ItemId(i)).unwrap(),
+                },
+                Declared::Function(id) => ItemKind::Function(id),
+                Declared::Extern(id) => ItemKind::Extern(id),
+                Declared::Generic => ItemKind::Generic {
+                    instances: (0..checker.functions.len())
+                        .filter(|&f| checker.functions[f].item == ItemId(i))
+                        .map(FunctionId)
+                        .collect(),
+                },
+            },
+        })
+        .collect();
+
+    // Instances of generic functions check the same body again
+    let mut warnings = Vec::new();
+
+    for warning in checker.warnings
+    {
+        if !warnings.contains(&warning)
+        {
+            warnings.push(warning);
+        }
+    }
+
+    Ok(Thir {
+        modules: hir.modules.clone(),
+        items,
+        functions: checker.functions,
+        locals: checker.locals,
+        warnings,
+    })
+}
+
+/// Name of `item` qualified by its module's path, as in `math.add`
+fn qualified(item: &hir::Item) -> EcoString
+{
+    item.module
+        .iter()
+        .map(EcoString::as_str)
+        .chain([item.name.as_str()])
+        .collect::<Vec<_>>()
+        .join(".")
+        .into()
+}
+
+impl Checker<'_>
+{
+    #[inline]
+    fn module(&self) -> EcoVec<EcoString>
+    {
+        self.frame.module.clone()
+    }
+
+    /// Converts a type as written in the current frame. `span` is what errors point at, as types carry no span of their own
+    fn resolve(&self, r#type: ParserType, span: Span) -> Result<Type>
+    {
+        Type::resolve(r#type, &self.frame.generics).map_err(|error| {
+            TypeError::InvalidType {
+                error,
+                module: self.module(),
+                span,
+            }
+            .into()
+        })
+    }
+
+    /// Types of the arguments and return type `signature` declares, in the current frame
+    fn signature(&self, signature: &FunctionSignature) -> Result<(Vec<Type>, Type)>
+    {
+        let arguments = signature
+            .arguments
+            .iter()
+            .map(|a| self.resolve(a.r#type.clone(), a.span))
+            .collect::<Result<_>>()?;
+
+        let return_type = signature
+            .name
+            .1
+            .clone()
+            .map(|t| self.resolve(t, signature.span))
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok((arguments, return_type))
+    }
+
+    fn declare_local(&mut self, local: Local) -> LocalId
+    {
+        self.locals.push(local);
+
+        LocalId(self.locals.len() - 1)
+    }
+
+    /// Declares a local for each of the arguments of `signature`, of `types`
+    fn declare_parameters(
+        &mut self,
+        signature: &FunctionSignature,
+        types: &[Type],
+    ) -> EcoVec<LocalId>
+    {
+        signature
+            .arguments
+            .iter()
+            .zip(types)
+            .map(|(argument, r#type)| {
+                self.declare_local(Local {
+                    name: argument.name.clone(),
+                    kind: BindingKind::Parameter,
+                    mutable: argument.mutable,
+                    r#type: r#type.clone(),
+                    span: argument.span,
+                })
+            })
+            .collect()
+    }
+
+    /// Declares the signature of every function and the type of every constant that has one
+    fn declare(&mut self)
+    {
+        for (i, item) in self.hir.items.iter().enumerate()
+        {
+            self.frame = Frame {
+                module: item.module.clone(),
+                at: item.span,
+                ..Default::default()
+            };
+
+            let attributes = self.attributes(item).unwrap_or_else(|error| {
+                self.errors.push(*error);
+                Attributes::default()
+            });
+
+            // Symbols of tests and instances are chosen for them
+            if let hir::ItemKind::Function { signature, .. } = &item.kind
+                && attributes.symbol(&item.name).is_some()
+                && (attributes.test || !signature.generics.is_empty())
+            {
+                let (what, reason) = if attributes.test
+                {
+                    ("Test", "the harness calls it")
+                }
+                else
+                {
+                    ("Generic function", "as each instance has its own")
+                };
+
+                self.errors.push(TypeError::ChosenSymbol {
+                    what,
+                    name: qualified(item),
+                    reason,
+                    module: self.module(),
+                    span: item.span,
+                });
+            }
+
+            let test = attributes.test;
+            self.attributes.push(attributes);
+
+            let declared = match &item.kind
+            {
+                hir::ItemKind::Const { r#type, .. } =>
+                {
+                    if let Some(r#type) = r#type
+                    {
+                        let r#type = self.resolve(r#type.clone(), item.span);
+
+                        if let Err(error) = &r#type
+                        {
+                            self.errors.push(*error.clone());
+                        }
+
+                        self.constants.insert(ItemId(i), r#type);
+                    }
+
+                    Declared::Const
+                }
+                hir::ItemKind::Function { signature, .. } if !signature.generics.is_empty() =>
+                {
+                    if test
+                    {
+                        self.errors.push(TypeError::TestSignature {
+                            name: qualified(item),
+                            module: self.module(),
+                            span: item.span,
+                        });
+                    }
+
+                    Declared::Generic
+                }
+                hir::ItemKind::Function { signature, .. } | hir::ItemKind::Extern { signature } =>
+                {
+                    let (arguments, mut return_type) = match self.signature(signature)
+                    {
+                        Ok(signature) => signature,
+                        Err(error) =>
+                        {
+                            self.errors.push(*error);
+                            continue;
+                        }
+                    };
+
+                    let is_extern = matches!(item.kind, hir::ItemKind::Extern { .. });
+
+                    if test && (!arguments.is_empty() || return_type != Type::Void)
+                    {
+                        self.errors.push(TypeError::TestSignature {
+                            name: qualified(item),
+                            module: self.module(),
+                            span: item.span,
+                        });
+                    }
+
+                    // The root `main` is the program's, which exits with what it returns
+                    if !is_extern && item.module.is_empty() && item.name == "main"
+                    {
+                        return_type = Type::Integer {
+                            width: 32,
+                            signed: true,
+                        };
+                    }
+
+                    let parameters = if is_extern
+                    {
+                        EcoVec::new()
+                    }
+                    else
+                    {
+                        self.declare_parameters(signature, &arguments)
+                    };
+
+                    self.functions.push(Function {
+                        item: ItemId(i),
+                        generics: Vec::new(),
+                        parameters,
+                        arguments,
+                        return_type,
+                        variadic: signature.variadic,
+                        body: None,
+                    });
+
+                    let id = FunctionId(self.functions.len() - 1);
+
+                    if is_extern
+                    {
+                        Declared::Extern(id)
+                    }
+                    else
+                    {
+                        Declared::Function(id)
+                    }
+                }
+            };
+
+            self.declared.push(declared);
+        }
+    }
+
+    /// Type of the constant `item`, inferring it from its value if it isn't declared
+    fn constant(&mut self, item: ItemId) -> Result<Type>
+    {
+        if let Some(r#type) = self.constants.get(&item)
+        {
+            return r#type.clone();
+        }
+
+        let declaration = &self.hir[item];
+
+        if let Some(start) = self.inferring.iter().position(|&i| i == item)
+        {
+            let cycle = self.inferring[start..]
+                .iter()
+                .chain([&item])
+                .map(|&i| format!("`{}`", qualified(&self.hir[i])))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(TypeError::ConstCycle {
+                name: qualified(declaration),
+                module: declaration.module.clone(),
+                span: declaration.span,
+                help: Some(format!("Through {cycle}, declare its type")),
+            }
+            .into());
+        }
+
+        let hir::ItemKind::Const { value, .. } = &declaration.kind
+        else
+        {
+            unreachable!("Only constants are looked up as such");
+        };
+
+        let frame = mem::replace(
+            &mut self.frame,
+            Frame {
+                module: declaration.module.clone(),
+                at: declaration.span,
+                ..Default::default()
+            },
+        );
+        self.inferring.push(item);
+
+        let value = self.value(value, None);
+
+        self.inferring.pop();
+        self.frame = frame;
+
+        let r#type = value
+            .as_ref()
+            .map(|v| v.r#type.clone())
+            .map_err(Clone::clone);
+
+        if let Ok(value) = value
+        {
+            self.values.insert(item, value);
+        }
+
+        self.constants.insert(item, r#type.clone());
+
+        r#type
+    }
+
+    fn check_constants(&mut self)
+    {
+        for (i, item) in self.hir.items.iter().enumerate()
+        {
+            let hir::ItemKind::Const { value, .. } = &item.kind
+            else
+            {
+                continue;
+            };
+
+            let id = ItemId(i);
+
+            let checked = match self.constant(id)
+            {
+                Ok(_) if self.values.contains_key(&id) => continue,
+                Ok(r#type) =>
+                {
+                    self.frame = Frame {
+                        module: item.module.clone(),
+                        at: item.span,
+                        ..Default::default()
+                    };
+
+                    self.coerce(value, &r#type)
+                }
+                Err(error) => Err(error),
+            };
+
+            match checked
+            {
+                Ok(value) =>
+                {
+                    self.values.insert(id, value);
+                }
+                Err(error) => self.errors.push(*error),
+            }
+        }
+    }
+
+    /// Checks the bodies of every function, along with those of the instances they call, which are declared as they go
+    fn check_functions(&mut self)
+    {
+        let mut next = 0;
+
+        while next < self.functions.len()
+        {
+            let id = FunctionId(next);
+            next += 1;
+
+            let function = &self.functions[id.0];
+            let item = &self.hir[function.item];
+
+            let hir::ItemKind::Function {
+                signature,
+                parameters,
+                body,
+            } = &item.kind
+            else
+            {
+                continue;
+            };
+
+            self.frame = Frame {
+                module: item.module.clone(),
+                item: Some(function.item),
+                generics: signature
+                    .generics
+                    .iter()
+                    .cloned()
+                    .zip(function.generics.iter().cloned())
+                    .collect(),
+                scope: parameters
+                    .iter()
+                    .copied()
+                    .zip(function.parameters.iter().copied())
+                    .collect(),
+                deferred: HashSet::new(),
+                return_type: function.return_type.clone(),
+                implicit_return: item.module.is_empty() && item.name == "main",
+                at: item.span,
+            };
+
+            match self.block(body)
+            {
+                Ok(body) =>
+                {
+                    self.errors
+                        .extend(assignment::check(&self.locals, self.module(), &body));
+                    self.errors
+                        .extend(escape::check(&self.locals, self.module(), &body));
+                    self.functions[id.0].body = Some(body);
+                }
+                Err(error) => self.errors.push(*error),
+            }
+        }
+    }
+
+    /// The instance of the generic function `item` for `generics`, declaring it if it's the first
+    fn instantiate(&mut self, item: ItemId, generics: Vec<Type>) -> Result<FunctionId>
+    {
+        if let Some(&id) = self.instances.get(&(item, generics.clone()))
+        {
+            return Ok(id);
+        }
+
+        let declaration = &self.hir[item];
+
+        let hir::ItemKind::Function { signature, .. } = &declaration.kind
+        else
+        {
+            unreachable!("Only functions are generic");
+        };
+
+        // Its signature is resolved where it's declared
+        let frame = mem::replace(
+            &mut self.frame,
+            Frame {
+                module: declaration.module.clone(),
+                generics: signature
+                    .generics
+                    .iter()
+                    .cloned()
+                    .zip(generics.iter().cloned())
+                    .collect(),
+                ..Default::default()
+            },
+        );
+
+        let declared = self.signature(signature);
+
+        self.frame = frame;
+
+        let (arguments, return_type) = declared?;
+        let parameters = self.declare_parameters(signature, &arguments);
+
+        self.functions.push(Function {
+            item,
+            generics: generics.clone(),
+            parameters,
+            arguments,
+            return_type,
+            variadic: false,
+            body: None,
+        });
+
+        let id = FunctionId(self.functions.len() - 1);
+        self.instances.insert((item, generics), id);
+
+        Ok(id)
+    }
+
+    fn block(&mut self, block: &[hir::Statement]) -> Result<Block>
+    {
+        block
+            .iter()
+            .enumerate()
+            .map(|(i, s)| self.statement(s, &block[i + 1..]))
+            .collect()
+    }
+
+    /// Checks `statement`, `rest` being the statements following it on the same block
+    fn statement(
+        &mut self,
+        statement: &hir::Statement,
+        rest: &[hir::Statement],
+    ) -> Result<Statement>
+    {
+        Ok(match statement
+        {
+            // Functions returning `()` may return whatever, which is evaluated and dropped
+            hir::Statement::Return(Some(value)) if self.frame.return_type == Type::Void =>
+            {
+                Statement::Return(Some(self.expression(value, None)?))
+            }
+            hir::Statement::Return(Some(value)) =>
+            {
+                let return_type = self.frame.return_type.clone();
+
+                Statement::Return(Some(self.coerce(value, &return_type)?))
+            }
+            hir::Statement::Return(None) =>
+            {
+                if self.frame.return_type != Type::Void && !self.frame.implicit_return
+                {
+                    return Err(TypeError::MissingReturnValue {
+                        r#type: self.frame.return_type.clone(),
+                        module: self.module(),
+                        span: self.frame.at,
+                    }
+                    .into());
+                }
+
+                Statement::Return(None)
+            }
+            hir::Statement::Expression(expression) =>
+            {
+                Statement::Expression(self.expression(expression, None)?)
+            }
+            hir::Statement::Assign {
+                destination,
+                source,
+            } => self.assign(destination, source)?,
+            hir::Statement::Local { local, init } => self.local(*local, init.as_ref(), rest)?,
+        })
+    }
+
+    fn assign(
+        &mut self,
+        destination: &hir::Expression,
+        source: &hir::Expression,
+    ) -> Result<Statement>
+    {
+        // Locals being probed for their type only take note of it
+        if let hir::Expression::Def {
+            def: DefId::Local(local),
+            ..
+        } = destination
+            && let Some(&id) = self.frame.scope.get(local)
+            && self.unknown.contains_key(&id)
+        {
+            let literal = expression::is_untyped_literal(source);
+            let source = self.value(source, None)?;
+
+            self.unknown
+                .get_mut(&id)
+                .unwrap()
+                .push((source.r#type.clone(), literal));
+
+            return Ok(Statement::Expression(source));
+        }
+
+        // Locals declared without a value are given one by assigning it, whether that's only once is up to `assignment`
+        let deferred = matches!(
+            destination,
+            hir::Expression::Def {
+                def: DefId::Local(local),
+                ..
+            } if self.frame.deferred.contains(local)
+        );
+
+        if !deferred
+        {
+            self.ensure_mutable(destination, false)?;
+        }
+
+        let destination = self.expression(destination, None)?;
+
+        let Type::MutRef(box r#type) = destination.r#type.clone()
+        else
+        {
+            return Err(TypeError::NotAssignable {
+                r#type: destination.r#type,
+                module: self.module(),
+                span: destination.span,
+            }
+            .into());
+        };
+
+        let source = self.coerce(source, &r#type)?;
+
+        Ok(Statement::Assign {
+            destination,
+            source,
+        })
+    }
+
+    fn local(
+        &mut self,
+        local: hir::LocalId,
+        init: Option<&hir::Expression>,
+        rest: &[hir::Statement],
+    ) -> Result<Statement>
+    {
+        let declaration = &self.hir[local];
+        let declared = declaration
+            .r#type
+            .clone()
+            .map(|t| self.resolve(t, declaration.span))
+            .transpose();
+
+        if init.is_none()
+        {
+            self.frame.deferred.insert(local);
+        }
+
+        // The initial value can't see the local itself, it may refer to one it shadows
+        let checked = match (declared, init)
+        {
+            (Err(error), _) => Err((None, error)),
+            (Ok(Some(r#type)), Some(init)) => self
+                .coerce(init, &r#type)
+                .map(|init| (Some(r#type.clone()), Some(init)))
+                .map_err(|error| (Some(r#type), error)),
+            (Ok(None), Some(init)) => self
+                .value(init, None)
+                .map(|init| (Some(init.r#type.clone()), Some(init)))
+                .map_err(|error| (None, error)),
+            (Ok(r#type), None) => Ok((r#type, None)),
+        };
+
+        // Still declared if its type or value doesn't check, as locals being probed for their type check what follows anyway
+        let (r#type, init, error) = match checked
+        {
+            Ok((r#type, init)) => (r#type, init, None),
+            Err((r#type, error)) => (r#type, None, Some(error)),
+        };
+
+        let declaration = &self.hir[local];
+        let id = self.declare_local(Local {
+            name: declaration.name.clone(),
+            kind: BindingKind::Local,
+            mutable: declaration.mutable,
+            r#type: r#type.clone().unwrap_or_default(),
+            span: declaration.span,
+        });
+
+        self.frame.scope.insert(local, id);
+
+        if let Some(error) = error
+        {
+            return Err(error);
+        }
+
+        if r#type.is_none()
+        {
+            self.locals[id.0].r#type = self.infer_local(id, rest)?;
+        }
+
+        Ok(Statement::Local { local: id, init })
+    }
+
+    /// Infers the type of a local declared without a type nor a value from what's assigned to it later on. The statements after
+    /// it are checked to find out, then the checker is rolled back to where it was
+    fn infer_local(&mut self, local: LocalId, rest: &[hir::Statement]) -> Result<Type>
+    {
+        let (functions, locals, warnings, instances, scope) = (
+            self.functions.len(),
+            self.locals.len(),
+            self.warnings.len(),
+            self.instances.clone(),
+            self.frame.scope.clone(),
+        );
+
+        self.unknown.insert(local, Vec::new());
+
+        // Statements which don't check, as those reading the local, are left for after it's known
+        for (i, statement) in rest.iter().enumerate()
+        {
+            let _ = self.statement(statement, &rest[i + 1..]);
+        }
+
+        let candidates = self.unknown.remove(&local).unwrap_or_default();
+
+        self.functions.truncate(functions);
+        self.locals.truncate(locals);
+        self.warnings.truncate(warnings);
+        self.instances = instances;
+        self.frame.scope = scope;
+
+        // Literals adapt to whatever type the other assignments settle on, they only count when alone
+        let (literals, typed): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|(_, l)| *l);
+        let candidates = if typed.is_empty() { literals } else { typed };
+
+        let name = &self.locals[local.0].name;
+
+        let Some(((first, _), others)) = candidates.split_first()
+        else
+        {
+            return Err(TypeError::UninferredLocal {
+                name: name.clone(),
+                module: self.module(),
+                span: self.locals[local.0].span,
+            }
+            .into());
+        };
+
+        if let Some((second, _)) = others.iter().find(|(t, _)| t != first)
+        {
+            return Err(TypeError::AmbiguousLocal {
+                name: name.clone(),
+                first: first.clone(),
+                second: second.clone(),
+                module: self.module(),
+                span: self.locals[local.0].span,
+            }
+            .into());
+        }
+
+        Ok(first.clone())
+    }
+
+    /// Ensures the place `expression` refers to may be written to, as when assigned to (or borrowed as `&mut` if `borrow`)
+    fn ensure_mutable(&self, expression: &hir::Expression, borrow: bool) -> Result<()>
+    {
+        // Items are as writable as what they're in
+        let mut place = expression;
+        while let hir::Expression::Index { target, .. } = place
+        {
+            place = target;
+        }
+
+        let (kind, name, module, declaration) = match place
+        {
+            hir::Expression::Def {
+                def: DefId::Local(local) | DefId::Parameter(local),
+                ..
+            } =>
+            {
+                let Some(local) = self.frame.scope.get(local).map(|&id| &self.locals[id.0])
+                else
+                {
+                    return Ok(());
+                };
+
+                // `&mut` parameters are written through rather than rebound
+                if local.mutable
+                    || local.kind == BindingKind::Parameter
+                        && matches!(local.r#type, Type::MutRef(_))
+                {
+                    return Ok(());
+                }
+
+                (local.kind, local.name.clone(), self.module(), local.span)
+            }
+            hir::Expression::Def {
+                def: DefId::Const(item),
+                ..
+            } =>
+            {
+                let item = &self.hir[*item];
+
+                (
+                    BindingKind::Constant,
+                    item.name.clone(),
+                    item.module.clone(),
+                    item.span,
+                )
+            }
+            // Other places are checked by their types
+            _ => return Ok(()),
+        };
+
+        let help = match kind
+        {
+            BindingKind::Local => Some(format!("Declare it as `let mut {name}`")),
+            BindingKind::Parameter => Some(format!("Declare it as `mut {name}`")),
+            BindingKind::Constant => None,
+            BindingKind::Capture => Some(format!("Capture it as `&mut {name}`")),
+        };
+
+        Err(if borrow
+        {
+            TypeError::MutBorrowOfImmutable {
+                kind,
+                name,
+                module,
+                declaration,
+                help,
+            }
+        }
+        else
+        {
+            TypeError::AssignToImmutable {
+                kind,
+                name,
+                declaration: (module == self.module()).then_some(declaration),
+                module: self.module(),
+                span: self.span_of(expression),
+                help,
+            }
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{Callee, ExpressionKind, Literal};
+
+    fn thir(source: &str) -> std::result::Result<Thir, Vec<TypeError>>
+    {
+        let tokens = compiler_parser::lex(source).unwrap();
+
+        let module = compiler_parser::Module {
+            path: EcoVec::new(),
+            file: "test.su".into(),
+            source: source.into(),
+            ast: compiler_parser::parse(source, tokens.into_iter()).unwrap(),
+        };
+
+        check(&compiler_hir::resolve(&[module]).unwrap())
+    }
+
+    fn errors(source: &str) -> Vec<String>
+    {
+        thir(source)
+            .unwrap_err()
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// The body of the first function named `name`
+    fn body<'t>(thir: &'t Thir, name: &str) -> &'t Block
+    {
+        thir.functions
+            .iter()
+            .find(|f| thir[f.item].name == name)
+            .and_then(|f| f.body.as_ref())
+            .unwrap()
+    }
+
+    #[test]
+    fn types_literals_by_their_use()
+    {
+        let thir = thir(
+            "func f(a u8) u8 {\n    ret a + 1\n}\nfunc main() {\n    let b u16 = -0\n    let c = 2.5\n    let d = b > 3\n}\n",
+        )
+        .unwrap();
+
+        let Statement::Return(Some(Expression {
+            kind: ExpressionKind::Binary(_, _, box one),
+            r#type,
+            ..
+        })) = &body(&thir, "f")[0]
+        else
+        {
+            panic!("Expected a return of a sum");
+        };
+
+        let u8 = Type::Integer {
+            width: 8,
+            signed: false,
+        };
+
+        assert_eq!(*r#type, u8);
+        assert_eq!(one.r#type, u8);
+        assert_eq!(one.kind, ExpressionKind::Literal(Literal::Integer(1)));
+
+        let types = body(&thir, "main")
+            .iter()
+            .map(|s| match s
+            {
+                Statement::Local { local, .. } => thir[*local].r#type.clone(),
+                _ => panic!("Expected a local"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            types,
+            [
+                Type::Integer {
+                    width: 16,
+                    signed: false
+                },
+                crate::DEFAULT_FLOAT,
+                crate::BOOL
+            ]
+        );
+
+        // The root `main` returns 0 unless told otherwise
+        assert_eq!(thir.functions[1].return_type, crate::DEFAULT_INT);
+    }
+
+    #[test]
+    fn instantiates_generics_once_per_arguments()
+    {
+        let thir = thir(
+            "func id(T const type, a T) T {\n    ret a\n}\nfunc main() {\n    id(1)\n    id(2)\n    id(3 as u8)\n}\n",
+        )
+        .unwrap();
+
+        let ItemKind::Generic { instances } = &thir.items[0].kind
+        else
+        {
+            panic!("Expected a generic function");
+        };
+
+        let generics = instances
+            .iter()
+            .map(|&i| thir[i].generics.clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            generics,
+            [
+                vec![crate::DEFAULT_INT],
+                vec![Type::Integer {
+                    width: 8,
+                    signed: false
+                }]
+            ]
+        );
+
+        let callees = body(&thir, "main")
+            .iter()
+            .map(|s| match s
+            {
+                Statement::Expression(Expression {
+                    kind:
+                        ExpressionKind::Call {
+                            callee: Callee::Function(id),
+                            ..
+                        },
+                    ..
+                }) => *id,
+                _ => panic!("Expected a call"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(callees, [instances[0], instances[0], instances[1]]);
+
+        // Literals take the type the other arguments bind, wherever they are
+        let thir = self::thir(
+            "func max(T const type, a T, b T) T {\n    if a > b {\n        ret a\n    }\n    ret b\n}\nfunc main() {\n    let x i64 = 3\n    max(x, 5)\n    max(5, x)\n}\n",
+        )
+        .unwrap();
+
+        let ItemKind::Generic { instances } = &thir.items[0].kind
+        else
+        {
+            panic!("Expected a generic function");
+        };
+
+        assert_eq!(
+            instances
+                .iter()
+                .map(|&i| thir[i].generics.clone())
+                .collect::<Vec<_>>(),
+            [vec![Type::Integer {
+                width: 64,
+                signed: true
+            }]]
+        );
+    }
+
+    #[test]
+    fn infers_locals_from_their_assignments()
+    {
+        let thir = thir(
+            "func f() u64 {\n    ret 1\n}\nfunc main() {\n    let mut a\n    a = 1\n    a = f()\n    let mut b\n    b = 2\n}\n",
+        )
+        .unwrap();
+
+        let types = body(&thir, "main")
+            .iter()
+            .filter_map(|s| match s
+            {
+                Statement::Local { local, .. } => Some(thir[*local].r#type.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            types,
+            [
+                Type::Integer {
+                    width: 64,
+                    signed: false
+                },
+                crate::DEFAULT_INT
+            ]
+        );
+    }
+
+    #[test]
+    fn infers_locals_past_uses_which_only_check_once_inferred()
+    {
+        // Locals declared along the way are still in scope for what follows
+        let thir = thir(
+            "func f(a u8) u8 {\n    ret a\n}\nfunc main() {\n    let mut c\n    c = 2 as u8\n    let mut d = c.f()\n    d = d + 1\n}\n",
+        )
+        .unwrap();
+
+        let Statement::Local { local, .. } = body(&thir, "main")[0]
+        else
+        {
+            panic!("Expected a local");
+        };
+
+        assert_eq!(
+            thir[local].r#type,
+            Type::Integer {
+                width: 8,
+                signed: false
+            }
+        );
+
+        // Even those whose type doesn't resolve
+        assert_eq!(
+            errors("func main() {\n    let c\n    c = 1\n    let d bogus\n    d\n}\n"),
+            ["Unknown type `bogus`"]
+        );
+    }
+
+    #[test]
+    fn reports_mismatches()
+    {
+        assert_eq!(
+            errors(
+                "func f(a i64) {}\nfunc g() u8 {\n    let b i32 = 1\n    ret b\n}\nfunc main() {\n    f(1.5)\n    let c u8 = 256\n}\nfunc h() {\n    let d = 1 + 2.0\n    if 1 {}\n}\n"
+            ),
+            [
+                "Expected `u8`, got `i32`, convert it with `as u8`",
+                "Expected `i64`, got `f64`, convert it with `as i64`",
+                "Operator `+` can't be applied to `i32` and `f64`, convert the left side with `as f64`",
+            ]
+        );
+
+        assert_eq!(
+            errors("func main() {\n    let a i32 = 1\n    let b i64 = 2\n    a + b\n}\n"),
+            [
+                "Operator `+` can't be applied to `i32` and `i64`, convert the left side with `as i64`"
+            ]
+        );
+
+        assert_eq!(
+            errors("func main() {\n    let a i8 = 1\n    if a {}\n    f(a)\n}\nfunc f() {}\n"),
+            ["`if` expects a condition, got `i8`"]
+        );
+
+        assert_eq!(
+            errors("func main() {\n    f(1)\n    let a = 2\n    a = 3\n}\nfunc f() {}\n"),
+            ["Function `f` expects 0 arguments, got 1"]
+        );
+
+        assert_eq!(
+            errors(
+                "func get() func(i32) i32 {\n    ret double\n}\nfunc double(a i32) i32 {\n    ret a * 2\n}\nfunc main() {\n    get()(1, 2)\n}\nfunc f() {\n    get()(3)(4)\n}\n"
+            ),
+            [
+                "Function expects 1 arguments, got 2",
+                "What's called of type `i32` isn't a function"
+            ]
+        );
+
+        // Whatever the receiver is held in
+        assert_eq!(
+            errors(
+                "func twice(a i32) i32 {\n    ret a * 2\n}\nfunc main() {\n    let x i64 = 3\n    x.twice()\n}\n"
+            ),
+            ["Method `twice` takes `i32` as its receiver, got `i64`"]
+        );
+
+        // Pointing at the assignment and at the declaration
+        let errors = thir("func main() {\n    let a = 1\n    a = 2\n}\n").unwrap_err();
+
+        assert_eq!(errors[0].to_string(), "Can't assign to immutable local `a`");
+
+        let TypeError::AssignToImmutable {
+            span, declaration, ..
+        } = &errors[0]
+        else
+        {
+            panic!("Expected an assignment to an immutable local");
+        };
+
+        assert_eq!((span.start, declaration.map(|d| d.start)), (32, Some(22)));
+    }
+
+    #[test]
+    fn reports_reads_of_possibly_unassigned_locals()
+    {
+        // Assigned on every path, or only read where it is
+        assert!(
+            thir(
+                "func f(c u1) i32 {\n    let a i32\n    if c {\n        a = 1\n    } else {\n        ret 0\n    }\n    let b\n    if c {\n        b = a\n        ret b\n    }\n    ret a\n}\n"
+            )
+            .is_ok()
+        );
+
+        assert_eq!(
+            errors(
+                "func f(c u1) i32 {\n    let a i32\n    if c {\n        a = 1\n    }\n    ret a + a\n}\nfunc g() {\n    let mut b i32\n    let h = func[b]() {}\n}\n"
+            ),
+            [
+                "Local `a` may be read before it's assigned",
+                "Local `b` may be read before it's assigned",
+            ]
+        );
+
+        // Pointing at the read and at the declaration
+        let errors = thir("func main() {\n    let a i32\n    ret a\n}\n").unwrap_err();
+
+        let TypeError::Unassigned {
+            span, declaration, ..
+        } = &errors[0]
+        else
+        {
+            panic!("Expected a read of an unassigned local");
+        };
+
+        assert_eq!((span.start, declaration.start), (36, 22));
+    }
+
+    #[test]
+    fn assigns_immutable_locals_once()
+    {
+        assert!(thir("func f(c u1) {\n    let a\n    if c {\n        a = 1\n    } else {\n        a = 2\n    }\n}\n").is_ok());
+
+        assert_eq!(
+            errors("func f(c u1) {\n    let a\n    if c {\n        a = 1\n    }\n    a = 2\n}\n"),
+            ["Immutable local `a` may already be assigned"]
+        );
+
+        assert_eq!(
+            errors("func main() {\n    let a [2]i32\n    a = [1, 2]\n    a[0] = 3\n}\n"),
+            ["Can't assign to immutable local `a`"]
+        );
+    }
+
+    #[test]
+    fn reports_escaping_closures()
+    {
+        // Pointing at where it's taken out and at the local it captures
+        let escaping = thir(
+            "func f() func[]() i32 {\n    let mut total = 0\n    let g = func[&mut total]() i32 {\n        ret total\n    }\n    ret g\n}\n",
+        )
+        .unwrap_err();
+
+        let TypeError::ClosureEscapes {
+            span, declaration, ..
+        } = &escaping[0]
+        else
+        {
+            panic!("Expected an escaping closure");
+        };
+
+        assert_eq!((span.start, declaration.start), (115, 36));
+
+        // Even by value, as nothing would free their environment. Those capturing nothing may, as may the rest within it
+        assert_eq!(
+            errors(
+                "func f(n i32) func[]() i32 {\n    let g = func[n]() i32 {\n        ret n\n    }\n    g()\n    ret func[n]() i32 {\n        ret n\n    }\n}\nfunc h() func[]() i32 {\n    ret func[]() i32 {\n        ret 1\n    }\n}\n"
+            ),
+            ["Closure capturing `n` outlives its function"]
+        );
+    }
+
+    #[test]
+    fn warns_on_lossy_casts()
+    {
+        let thir = thir(
+            "func f(a i32, b u8) {\n    let c = a as u8\n    let d = b as i32\n    let e = a as f64 as f32\n}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            thir.warnings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "Cast from `i32` to `u8` may change the value",
+                "Cast from `f64` to `f32` may change the value",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_attributes()
+    {
+        let thir = thir(
+            "@deprecated(\"Use `g` instead\")\nfunc f() {\n    f()\n}\nfunc g() {\n    f()\n    f()\n}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            thir.warnings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "Use of deprecated function `f`",
+                "Use of deprecated function `f`",
+            ]
+        );
+
+        assert_eq!(
+            errors(
+                "@cold @cold\nfunc f() {}\n@test\nfunc t(a i32) {}\n@inline\nconst A = 1\n@test @export\nfunc u() {}\n"
+            ),
+            [
+                "Attribute `@cold` conflicts with `@cold`",
+                "Test `t` must take no arguments and return nothing",
+                "Attribute `@inline` doesn't apply to constants",
+                "Test `u` can't choose its symbol, the harness calls it",
+            ]
+        );
+
+        // Tests may use each other, as they're built together
+        assert_eq!(
+            errors(
+                "@test\nfunc t() {}\n@test\nfunc u() {\n    t()\n}\nfunc main() {\n    let f = t\n}\n"
+            ),
+            ["Test `t` can't be used outside of tests"]
+        );
+    }
+
+    #[test]
+    fn evaluates_constants()
+    {
+        let folded = |source| {
+            thir(source)
+                .unwrap()
+                .items
+                .iter()
+                .map(|i| match &i.kind
+                {
+                    ItemKind::Const { folded, .. } => folded.to_string(),
+                    _ => panic!("Expected a constant"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            folded(
+                "const A i8 = -B * 2\nconst B = 3 as i8\nconst C [2]f32 = [0.1, A as f32]\nconst D = C[1] < 0.0\nconst E = 300.5 as u8\n"
+            ),
+            ["-6", "3", "[0.10000000149011612, -6.0]", "1", "255"]
+        );
+
+        // Negating only the operand right after the `-`
+        assert_eq!(
+            folded(
+                "const A i32 = -3 - 1\nconst B i32 = -2147483647 - 1\nconst C i32 = -1073741824 * 2\n"
+            ),
+            ["-4", "-2147483648", "-2147483648"]
+        );
+
+        assert_eq!(
+            errors(
+                "const A i32 = 2147483647 + 1\nconst B = [1, 2][2]\nconst C = 1 / (1 - 1)\nconst D i8 = E\nconst E i8 = D\n"
+            ),
+            [
+                "Constant `A` overflows `i32` on `2147483647 + 1`",
+                "Constant `B` indexes out of bounds",
+                "Constant `C` divides by zero",
+                "Constant `D` depends on itself",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_uninferable_types()
+    {
+        assert_eq!(
+            errors(
+                "func id(T const type, a i32) {}\nfunc main() {\n    id(1)\n}\nfunc f() {\n    let mut a\n}\nfunc g() {\n    let b = []\n}\n"
+            ),
+            [
+                "Couldn't infer `T` on call to `id`",
+                "Couldn't infer the type of `a`, as it's never assigned",
+                "Can't infer the type of an empty array",
+            ]
+        );
+
+        assert_eq!(
+            errors("const A = B\nconst B = A + 1\n"),
+            ["Type of constant `A` depends on itself"]
+        );
+    }
+}
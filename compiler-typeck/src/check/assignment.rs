@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+
+use ecow::{EcoString, EcoVec};
+
+use crate::{Block, Callee, Expression, ExpressionKind, Local, LocalId, Statement, TypeError};
+
+/// What may hold at some point of a body, as far as locals declared without a value go
+#[derive(Clone, Default)]
+struct Flow
+{
+    /// Those which may not be assigned yet
+    unassigned: HashSet<LocalId>,
+    /// Immutable ones which may already be assigned
+    assigned: HashSet<LocalId>,
+    /// Whether the point is never reached, as past a return
+    diverges: bool,
+}
+
+impl Flow
+{
+    /// Where two paths meet, whatever may hold on either may hold after. Paths which never get there don't count
+    fn merge(self, other: Self) -> Self
+    {
+        match (self.diverges, other.diverges)
+        {
+            (true, _) => other,
+            (_, true) => self,
+            _ => Self {
+                unassigned: &self.unassigned | &other.unassigned,
+                assigned: &self.assigned | &other.assigned,
+                diverges: false,
+            },
+        }
+    }
+}
+
+struct Assignments<'c>
+{
+    locals: &'c [Local],
+    module: EcoVec<EcoString>,
+    /// Locals declared without a value
+    deferred: HashSet<LocalId>,
+    errors: Vec<TypeError>,
+}
+
+/// Checks that the locals of `body` declared without a value are assigned on every path before they're read, and immutable ones
+/// at most once. Closures within it are checked on their own, reading what they capture where they're created
+pub(super) fn check(locals: &[Local], module: EcoVec<EcoString>, body: &Block) -> Vec<TypeError>
+{
+    let mut assignments = Assignments {
+        locals,
+        module,
+        deferred: HashSet::new(),
+        errors: Vec::new(),
+    };
+
+    assignments.block(body, &mut Flow::default());
+
+    assignments.errors
+}
+
+impl Assignments<'_>
+{
+    fn block(&mut self, block: &Block, flow: &mut Flow)
+    {
+        for statement in block
+        {
+            // Whatever follows a return is never reached
+            if flow.diverges
+            {
+                break;
+            }
+
+            self.statement(statement, flow);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement, flow: &mut Flow)
+    {
+        match statement
+        {
+            Statement::Return(value) =>
+            {
+                if let Some(value) = value
+                {
+                    self.expression(value, flow);
+                }
+
+                flow.diverges = true;
+            }
+            Statement::Expression(expression) => self.expression(expression, flow),
+            Statement::Assign {
+                destination,
+                source,
+            } =>
+            {
+                self.expression(source, flow);
+
+                match destination.kind
+                {
+                    ExpressionKind::Local(local) => self.assign(local, destination, flow),
+                    // Writing into an item reads what it's in
+                    _ => self.expression(destination, flow),
+                }
+            }
+            Statement::Local { local, init } => match init
+            {
+                Some(init) => self.expression(init, flow),
+                None =>
+                {
+                    self.deferred.insert(*local);
+                    flow.unassigned.insert(*local);
+                }
+            },
+        }
+    }
+
+    fn assign(&mut self, local: LocalId, destination: &Expression, flow: &mut Flow)
+    {
+        let declared = &self.locals[local.0];
+
+        flow.unassigned.remove(&local);
+
+        // Mutable ones may be assigned any number of times, locals declared with a value were checked to be mutable already
+        if !self.deferred.contains(&local) || declared.mutable
+        {
+            return;
+        }
+
+        if !flow.assigned.insert(local)
+        {
+            self.errors.push(TypeError::AssignedTwice {
+                name: declared.name.clone(),
+                module: self.module.clone(),
+                span: destination.span,
+                declaration: declared.span,
+                help: Some(format!("Declare it as `let mut {}`", declared.name)),
+            });
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, flow: &mut Flow)
+    {
+        match &expression.kind
+        {
+            ExpressionKind::Literal(_)
+            | ExpressionKind::Constant(_)
+            | ExpressionKind::Function(_) =>
+            {}
+            ExpressionKind::Local(local) =>
+            {
+                // Reported once, as if it was assigned from then on
+                if flow.unassigned.remove(local)
+                {
+                    let declared = &self.locals[local.0];
+
+                    self.errors.push(TypeError::Unassigned {
+                        name: declared.name.clone(),
+                        module: self.module.clone(),
+                        span: expression.span,
+                        declaration: declared.span,
+                    });
+                }
+            }
+            ExpressionKind::Binary(left, _, right) =>
+            {
+                self.expression(left, flow);
+                self.expression(right, flow);
+            }
+            ExpressionKind::Negate(e)
+            | ExpressionKind::Cast(e)
+            | ExpressionKind::Len(e)
+            | ExpressionKind::Coerce(e)
+            | ExpressionKind::Assert { condition: e, .. } => self.expression(e, flow),
+            ExpressionKind::Call { callee, args } =>
+            {
+                if let Callee::Pointer(pointer) = callee
+                {
+                    self.expression(pointer, flow);
+                }
+
+                for arg in args
+                {
+                    self.expression(arg, flow);
+                }
+            }
+            ExpressionKind::Index { target, index } =>
+            {
+                self.expression(target, flow);
+                self.expression(index, flow);
+            }
+            ExpressionKind::Slice { target, start, end } =>
+            {
+                self.expression(target, flow);
+
+                for bound in [start, end].into_iter().flatten()
+                {
+                    self.expression(bound, flow);
+                }
+            }
+            ExpressionKind::If {
+                condition,
+                block,
+                else_block,
+            } =>
+            {
+                self.expression(condition, flow);
+
+                let mut then = flow.clone();
+                self.block(block, &mut then);
+
+                let mut otherwise = flow.clone();
+
+                if let Some(else_block) = else_block
+                {
+                    self.block(else_block, &mut otherwise);
+                }
+
+                *flow = then.merge(otherwise);
+            }
+            ExpressionKind::Array(items) =>
+            {
+                for item in items
+                {
+                    self.expression(item, flow);
+                }
+            }
+            ExpressionKind::Closure { captures, body, .. } =>
+            {
+                for capture in captures
+                {
+                    self.expression(&capture.value, flow);
+                }
+
+                self.block(body, &mut Flow::default());
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Attributes on items. The parser takes any `@name(args)`, which ones exist and what they mean is checked here
+
+use compiler_hir::{self as hir, ItemId};
+use compiler_lexer::definitions::{LiteralType, Span};
+use compiler_parser::{Expression, item::Attribute};
+use ecow::EcoString;
+
+use super::{Checker, Result, qualified};
+use crate::{Attributes, Deprecation, Inline, TypeError, TypeWarning};
+
+/// Kind of item attributes are on, which decides the ones allowed
+#[derive(Clone, Copy, Debug)]
+enum Target
+{
+    Function,
+    Extern,
+    Constant,
+}
+
+impl Target
+{
+    #[inline]
+    fn of(kind: &hir::ItemKind) -> Self
+    {
+        match kind
+        {
+            hir::ItemKind::Function { .. } => Self::Function,
+            hir::ItemKind::Extern { .. } => Self::Extern,
+            hir::ItemKind::Const { .. } => Self::Constant,
+        }
+    }
+
+    #[inline]
+    fn allowed(self) -> &'static [&'static str]
+    {
+        match self
+        {
+            Self::Function => &[
+                "inline",
+                "noinline",
+                "cold",
+                "export",
+                "link_name",
+                "deprecated",
+                "test",
+            ],
+            Self::Extern => &["cold", "link_name", "deprecated"],
+            Self::Constant => &["export", "link_name"],
+        }
+    }
+
+    #[inline]
+    fn describe(self) -> &'static str
+    {
+        match self
+        {
+            Self::Function => "functions",
+            Self::Extern => "extern functions",
+            Self::Constant => "constants",
+        }
+    }
+}
+
+const LINK_NAME: &str = "a symbol, as in `@link_name(\"puts\")`";
+const DEPRECATED: &str = "nothing or a note, as in `@deprecated(\"Use `g` instead\")`";
+
+/// Contents of a string literal, without its quotes
+#[inline]
+fn string(expression: &Expression) -> Option<EcoString>
+{
+    match expression
+    {
+        Expression::Literal {
+            value,
+            r#type: LiteralType::String,
+            ..
+        } => Some(value[1..value.len() - 1].into()),
+        _ => None,
+    }
+}
+
+impl Checker<'_>
+{
+    /// Checks the attributes on `item`, in the current frame
+    pub(super) fn attributes(&self, item: &hir::Item) -> Result<Attributes>
+    {
+        let target = Target::of(&item.kind);
+        let mut result = Attributes::default();
+        let mut seen: Vec<&Attribute> = Vec::new();
+
+        for attribute in &item.attributes
+        {
+            let Attribute { name, args, span } = attribute;
+
+            if !target.allowed().contains(&name.as_str())
+            {
+                return Err(TypeError::UnknownAttribute {
+                    name: name.clone(),
+                    target: target.describe(),
+                    module: self.module(),
+                    span: *span,
+                    help: Some(format!(
+                        "Those on {} are {}",
+                        target.describe(),
+                        target
+                            .allowed()
+                            .iter()
+                            .map(|a| format!("`@{a}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )),
+                }
+                .into());
+            }
+
+            // Inlining is asked for once, either way
+            let inlining = |a: &EcoString| matches!(a.as_str(), "inline" | "noinline");
+
+            if let Some(other) = seen
+                .iter()
+                .find(|a| a.name == *name || inlining(&a.name) && inlining(name))
+            {
+                return Err(TypeError::ConflictingAttributes {
+                    name: name.clone(),
+                    other: other.name.clone(),
+                    module: self.module(),
+                    span: *span,
+                    previous: other.span,
+                }
+                .into());
+            }
+
+            seen.push(attribute);
+
+            let expected = |expected| -> Box<TypeError> {
+                TypeError::AttributeArguments {
+                    name: name.clone(),
+                    expected,
+                    module: self.module(),
+                    span: *span,
+                }
+                .into()
+            };
+
+            match (name.as_str(), args.as_slice())
+            {
+                ("inline", []) => result.inline = Some(Inline::Hint),
+                ("inline", [Expression::Path { path, .. }]) if path.as_slice() == ["always"] =>
+                {
+                    result.inline = Some(Inline::Always);
+                }
+                ("inline", _) => return Err(expected("nothing or `always`")),
+                ("noinline", []) => result.inline = Some(Inline::Never),
+                ("cold", []) => result.cold = true,
+                ("export", []) => result.export = true,
+                ("link_name", [symbol]) =>
+                {
+                    result.link_name = Some(string(symbol).ok_or_else(|| expected(LINK_NAME))?);
+                }
+                ("link_name", _) => return Err(expected(LINK_NAME)),
+                ("deprecated", []) => result.deprecated = Some(Deprecation { note: None }),
+                ("deprecated", [note]) =>
+                {
+                    result.deprecated = Some(Deprecation {
+                        note: Some(string(note).ok_or_else(|| expected(DEPRECATED))?),
+                    });
+                }
+                ("deprecated", _) => return Err(expected(DEPRECATED)),
+                ("test", []) => result.test = true,
+                _ => return Err(expected("no arguments")),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Ensures the function `item`, used at `span`, isn't a test unless the body using it is one too, as tests are left out of
+    /// every other build
+    pub(super) fn check_test_use(&self, item: ItemId, span: Span) -> Result<()>
+    {
+        let within_test = self.frame.item.is_some_and(|i| self.attributes[i.0].test);
+
+        if self.attributes[item.0].test && !within_test
+        {
+            return Err(TypeError::TestUse {
+                name: qualified(&self.hir[item]),
+                module: self.module(),
+                span,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Warns about a call at `span` to the function `item`, if it's deprecated. Deprecated functions may still call themselves
+    pub(super) fn check_deprecation(&mut self, item: ItemId, span: Span)
+    {
+        let Some(deprecation) = &self.attributes[item.0].deprecated
+        else
+        {
+            return;
+        };
+
+        if self.frame.item == Some(item)
+        {
+            return;
+        }
+
+        self.warnings.push(TypeWarning::Deprecated {
+            name: self.hir[item].name.clone(),
+            module: self.module(),
+            span,
+            note: deprecation.note.clone(),
+        });
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+
+use compiler_hir::{self as hir, DefId};
+use compiler_lexer::definitions::{LiteralType, Span};
+use ecow::{EcoString, EcoVec};
+
+use super::{
+    Checker, Declared, Result,
+    expression::{is_untyped_literal, load},
+};
+use crate::{Callee, Expression, ExpressionKind, FunctionId, Type, TypeError, generic};
+
+/// An argument of a call, along with what it was checked as if it already was, as method receivers are
+struct Argument<'h>
+{
+    hir: &'h hir::Expression,
+    checked: Option<Expression>,
+}
+
+/// Where a call goes, along with the types of the arguments it takes
+enum Target
+{
+    Function(FunctionId),
+    Generic(hir::ItemId),
+    Pointer
+    {
+        pointer: Expression,
+        arguments: Vec<Type>,
+        return_type: Type,
+    },
+}
+
+impl Checker<'_>
+{
+    pub(super) fn call(
+        &mut self,
+        callee: &hir::Callee,
+        args: &[hir::Expression],
+        span: Span,
+    ) -> Result<Expression>
+    {
+        let arguments = || {
+            args.iter()
+                .map(|hir| Argument { hir, checked: None })
+                .collect()
+        };
+
+        match callee
+        {
+            hir::Callee::Assert => self.assert(args, span),
+            &hir::Callee::Def(def) => self.call_def(def, arguments(), span),
+            hir::Callee::Value(callee) =>
+            {
+                let pointer = self.value(callee, None)?;
+                let target = self.pointer(pointer, None, span)?;
+
+                self.call_target(None, target, arguments(), span)
+            }
+        }
+    }
+
+    /// `receiver.name(args)` is `name(receiver, args)`. The receiver is borrowed as the first parameter asks, `&` or `&mut`, or read
+    /// from behind a reference if taken by value
+    pub(super) fn method(
+        &mut self,
+        receiver: &hir::Expression,
+        callee: DefId,
+        args: &[hir::Expression],
+        span: Span,
+    ) -> Result<Expression>
+    {
+        let mut receiver = Argument {
+            hir: receiver,
+            checked: None,
+        };
+
+        // Generic ones infer their parameters from the receiver instead
+        if let DefId::Function(item) | DefId::Extern(item) = callee
+            && let Declared::Function(function) | Declared::Extern(function) = self.declared[item.0]
+        {
+            let name = self.hir[item].name.clone();
+
+            let Some(parameter) = self.functions[function.0].arguments.first().cloned()
+            else
+            {
+                return Err(TypeError::NoReceiver {
+                    name,
+                    module: self.module(),
+                    span,
+                }
+                .into());
+            };
+
+            // Literals take the type of the parameter
+            if !is_untyped_literal(receiver.hir)
+            {
+                let checked = self.expression(receiver.hir, None)?;
+
+                if checked.r#type.referent() != parameter.referent()
+                {
+                    return Err(TypeError::Receiver {
+                        name,
+                        parameter,
+                        found: checked.r#type.referent().clone(),
+                        module: self.module(),
+                        span,
+                    }
+                    .into());
+                }
+
+                receiver.checked = Some(checked);
+            }
+        }
+
+        let arguments = [receiver]
+            .into_iter()
+            .chain(args.iter().map(|hir| Argument { hir, checked: None }))
+            .collect();
+
+        self.call_def(callee, arguments, span)
+    }
+
+    /// Calls the function `def` names, either directly or through a pointer held by a local or a constant. `span` is that of the
+    /// name at the call
+    fn call_def(&mut self, def: DefId, args: Vec<Argument>, span: Span) -> Result<Expression>
+    {
+        let (name, target) = match def
+        {
+            DefId::Function(item) | DefId::Extern(item) =>
+            {
+                self.check_test_use(item, span)?;
+                self.check_deprecation(item, span);
+
+                (
+                    self.hir[item].name.clone(),
+                    Some(match self.declared[item.0]
+                    {
+                        Declared::Function(id) | Declared::Extern(id) => Target::Function(id),
+                        _ => Target::Generic(item),
+                    }),
+                )
+            }
+            DefId::Local(local) | DefId::Parameter(local) => (self.hir[local].name.clone(), None),
+            DefId::Const(item) => (self.hir[item].name.clone(), None),
+        };
+
+        let target = match target
+        {
+            Some(target) => target,
+            None =>
+            {
+                let pointer = self.value(&hir::Expression::Def { def, span }, None)?;
+
+                self.pointer(pointer, Some(name.clone()), span)?
+            }
+        };
+
+        self.call_target(Some(name), target, args, span)
+    }
+
+    /// Calls `target`, named `name` unless it's a value other than a path
+    fn call_target(
+        &mut self,
+        name: Option<EcoString>,
+        target: Target,
+        mut args: Vec<Argument>,
+        span: Span,
+    ) -> Result<Expression>
+    {
+        let (arguments, variadic) = match &target
+        {
+            &Target::Function(id) =>
+            {
+                let function = &self.functions[id.0];
+
+                (function.arguments.len(), function.variadic)
+            }
+            &Target::Generic(item) =>
+            {
+                let hir::ItemKind::Function { signature, .. } = &self.hir[item].kind
+                else
+                {
+                    unreachable!("Only functions are generic");
+                };
+
+                (signature.arguments.len(), false)
+            }
+            Target::Pointer { arguments, .. } => (arguments.len(), false),
+        };
+
+        if args.len() < arguments || (!variadic && args.len() > arguments)
+        {
+            return Err(TypeError::Arity {
+                name,
+                at_least: if variadic { "at least " } else { "" },
+                expected: arguments,
+                found: args.len(),
+                module: self.module(),
+                span,
+            }
+            .into());
+        }
+
+        // Generic parameters are inferred from the arguments, the rest give their types to them
+        let (callee, parameters, return_type) = match target
+        {
+            Target::Function(id) =>
+            {
+                let function = &self.functions[id.0];
+
+                (
+                    Callee::Function(id),
+                    function.arguments.clone(),
+                    function.return_type.clone(),
+                )
+            }
+            Target::Generic(item) =>
+            {
+                let name = self.hir[item].name.clone();
+                let id = self.instantiate_for(item, &mut args, &name, span)?;
+                let function = &self.functions[id.0];
+
+                (
+                    Callee::Function(id),
+                    function.arguments.clone(),
+                    function.return_type.clone(),
+                )
+            }
+            Target::Pointer {
+                pointer,
+                arguments,
+                return_type,
+            } => (Callee::Pointer(Box::new(pointer)), arguments, return_type),
+        };
+
+        let args = args
+            .into_iter()
+            .enumerate()
+            .map(|(i, argument)| match (parameters.get(i), argument.checked)
+            {
+                // `&mut` arguments borrow the values passed implicitly
+                (Some(parameter), checked) =>
+                {
+                    if let Type::MutRef(_) = parameter
+                    {
+                        self.ensure_mutable(argument.hir, true)?;
+                    }
+
+                    match checked
+                    {
+                        Some(checked) => self.convert(checked, parameter),
+                        None => self.coerce(argument.hir, parameter),
+                    }
+                }
+                // Variadic arguments have no declared type, they're passed by value
+                (None, Some(checked)) => Ok(load(checked)),
+                (None, None) => self.value(argument.hir, None),
+            })
+            .collect::<Result<EcoVec<_>>>()?;
+
+        Ok(Expression {
+            kind: ExpressionKind::Call { callee, args },
+            r#type: return_type,
+            span,
+        })
+    }
+
+    /// Calls through `pointer`, which must be a function pointer or a closure. It's named `name` if it's read from a local or a constant
+    fn pointer(&self, pointer: Expression, name: Option<EcoString>, span: Span) -> Result<Target>
+    {
+        match pointer.r#type.clone()
+        {
+            Type::Function {
+                arguments,
+                box return_type,
+            }
+            | Type::Closure {
+                arguments,
+                box return_type,
+            } => Ok(Target::Pointer {
+                pointer,
+                arguments,
+                return_type,
+            }),
+            r#type => Err(TypeError::NotCallable {
+                name,
+                r#type,
+                module: self.module(),
+                span,
+            }
+            .into()),
+        }
+    }
+
+    /// The instance of the generic function `item` whose parameters `args` bind. They're checked without a type to infer them from
+    fn instantiate_for(
+        &mut self,
+        item: hir::ItemId,
+        args: &mut [Argument],
+        name: &EcoString,
+        span: Span,
+    ) -> Result<FunctionId>
+    {
+        let hir::ItemKind::Function { signature, .. } = &self.hir[item].kind
+        else
+        {
+            unreachable!("Only functions are generic");
+        };
+
+        let mut bindings = HashMap::new();
+
+        // Literals come last, taking the type the other arguments bind their parameter to, as in `max(x, 5)`
+        for literals in [false, true]
+        {
+            for (argument, parameter) in args.iter_mut().zip(&signature.arguments)
+            {
+                if (argument.checked.is_none() && is_untyped_literal(argument.hir)) != literals
+                {
+                    continue;
+                }
+
+                let checked = match argument.checked.take()
+                {
+                    Some(checked) => checked,
+                    None =>
+                    {
+                        let hint = Type::resolve(parameter.r#type.clone(), &bindings).ok();
+
+                        self.expression(argument.hir, hint.as_ref().map(Type::referent))?
+                    }
+                };
+
+                generic::infer(
+                    &parameter.r#type.0,
+                    &checked.r#type,
+                    &signature.generics,
+                    &mut bindings,
+                )
+                .map_err(|conflict| TypeError::Conflict {
+                    conflict,
+                    function: name.clone(),
+                    module: self.module(),
+                    span: checked.span,
+                })?;
+
+                argument.checked = Some(checked);
+            }
+        }
+
+        let generics = signature
+            .generics
+            .iter()
+            .map(|generic| {
+                bindings
+                    .get(generic)
+                    .cloned()
+                    .ok_or_else(|| TypeError::Uninferred {
+                        name: generic.clone(),
+                        function: name.clone(),
+                        module: self.module(),
+                        span,
+                    })
+                    .map_err(Box::new)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.instantiate(item, generics)
+    }
+
+    /// `assert(condition)`, or `assert(condition, "message")`
+    fn assert(&mut self, args: &[hir::Expression], span: Span) -> Result<Expression>
+    {
+        let (condition, message) = match args
+        {
+            [condition] => (condition, None),
+            [condition, message] => (condition, Some(message)),
+            _ =>
+            {
+                return Err(TypeError::AssertArguments {
+                    module: self.module(),
+                    span,
+                }
+                .into());
+            }
+        };
+
+        let condition = self.value(condition, None)?;
+
+        if !matches!(condition.r#type, Type::Integer { .. })
+        {
+            return Err(TypeError::Condition {
+                construct: "`assert`",
+                found: condition.r#type,
+                module: self.module(),
+                span: condition.span,
+                help: None,
+            }
+            .into());
+        }
+
+        let message = match message
+        {
+            Some(hir::Expression::Literal {
+                value,
+                r#type: LiteralType::String,
+                ..
+            }) => Some(value[1..value.len() - 1].into()),
+            Some(message) =>
+            {
+                return Err(TypeError::AssertMessage {
+                    module: self.module(),
+                    span: self.span_of(message),
+                }
+                .into());
+            }
+            None => None,
+        };
+
+        Ok(Expression {
+            kind: ExpressionKind::Assert {
+                condition: Box::new(condition),
+                message,
+            },
+            r#type: Type::Void,
+            span,
+        })
+    }
+}
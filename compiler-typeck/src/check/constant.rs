@@ -0,0 +1,502 @@
+//! Compile-time evaluation of `const` initialisers, which the back-ends take as they are rather than evaluating them on their
+//! own. Constants may refer to each other in any order, so they're evaluated on demand
+
+use std::collections::HashMap;
+
+use compiler_hir::{Hir, ItemId};
+use compiler_lexer::definitions::Span;
+use compiler_parser::Operator;
+use ecow::{EcoString, EcoVec};
+
+use super::qualified;
+use crate::{Constant, Expression, ExpressionKind, Literal, Type, TypeError, is_comparison};
+
+type Result<T> = std::result::Result<T, Box<TypeError>>;
+
+struct Folder<'c>
+{
+    hir: &'c Hir,
+    /// Checked values of every constant
+    values: &'c HashMap<ItemId, Expression>,
+    /// Of the constants evaluated so far, each failing at most once
+    folded: HashMap<ItemId, Result<Constant>>,
+    /// Constants being evaluated, the innermost last, to catch those depending on themselves
+    evaluating: Vec<ItemId>,
+}
+
+/// Evaluates the checked `values` of every constant, with an error for each which can't be
+pub(super) fn fold(
+    hir: &Hir,
+    values: &HashMap<ItemId, Expression>,
+) -> (HashMap<ItemId, Constant>, Vec<TypeError>)
+{
+    let mut folder = Folder {
+        hir,
+        values,
+        folded: HashMap::new(),
+        evaluating: Vec::new(),
+    };
+
+    let mut items = values.keys().copied().collect::<Vec<_>>();
+    items.sort_by_key(|i| i.0);
+
+    let mut folded = HashMap::new();
+    let mut errors = Vec::new();
+
+    for item in items
+    {
+        match folder.constant(item)
+        {
+            Ok(constant) =>
+            {
+                folded.insert(item, constant);
+            }
+            Err(error) => errors.push(*error),
+        }
+    }
+
+    (folded, errors)
+}
+
+/// Smallest and largest values of an integer type of `width` bits, 1 to 128 as checked
+fn bounds(width: u32, signed: bool) -> (i128, i128)
+{
+    if signed
+    {
+        (i128::MIN >> (128 - width), i128::MAX >> (128 - width))
+    }
+    else
+    {
+        // `u128` constants are limited to `i128`'s range
+        (0, i128::MAX >> 128u32.saturating_sub(width + 1))
+    }
+}
+
+/// `value` rounded to the precision of floats of `width` bits, as they hold it at runtime
+fn round(value: f64, width: u32) -> f64
+{
+    if width == 32
+    {
+        value as f32 as f64
+    }
+    else
+    {
+        value
+    }
+}
+
+impl Folder<'_>
+{
+    /// Value of the constant `item`, evaluating it unless that's already done
+    fn constant(&mut self, item: ItemId) -> Result<Constant>
+    {
+        if let Some(constant) = self.folded.get(&item)
+        {
+            return constant.clone();
+        }
+
+        let declaration = &self.hir[item];
+
+        if let Some(start) = self.evaluating.iter().position(|&i| i == item)
+        {
+            let cycle = self.evaluating[start..]
+                .iter()
+                .chain([&item])
+                .map(|&i| format!("`{}`", qualified(&self.hir[i])))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(TypeError::ValueCycle {
+                name: qualified(declaration),
+                module: declaration.module.clone(),
+                span: declaration.span,
+                help: Some(format!("Through {cycle}")),
+            }
+            .into());
+        }
+
+        let values = self.values;
+
+        self.evaluating.push(item);
+
+        let constant = self.evaluate(&values[&item]);
+
+        self.evaluating.pop();
+
+        self.folded.insert(item, constant.clone());
+
+        constant
+    }
+
+    /// Constant being evaluated, along with its module, which the spans diagnostics point at are in
+    fn evaluated(&self) -> (EcoString, EcoVec<EcoString>)
+    {
+        let declaration = &self.hir[*self.evaluating.last().unwrap()];
+
+        (qualified(declaration), declaration.module.clone())
+    }
+
+    /// `what`, at `span`, can't be evaluated at compile time
+    fn not_constant(&self, what: &str, span: Span) -> Box<TypeError>
+    {
+        let (name, module) = self.evaluated();
+
+        TypeError::NotConstant {
+            name,
+            module,
+            span,
+            help: Some(format!("{what} only happen at runtime")),
+        }
+        .into()
+    }
+
+    /// `value`, if it fits in `width` bits. `operation` describes where it came from otherwise, and `span` is that of its
+    /// expression
+    fn fit(
+        &self,
+        value: Option<i128>,
+        width: u32,
+        signed: bool,
+        operation: impl FnOnce() -> String,
+        span: Span,
+    ) -> Result<Constant>
+    {
+        let (min, max) = bounds(width, signed);
+
+        match value
+        {
+            Some(value) if (min..=max).contains(&value) => Ok(Constant::Integer {
+                value,
+                width,
+                signed,
+            }),
+            _ =>
+            {
+                let (name, module) = self.evaluated();
+
+                Err(TypeError::ConstOverflow {
+                    name,
+                    operation: operation(),
+                    r#type: Type::Integer { width, signed },
+                    module,
+                    span,
+                }
+                .into())
+            }
+        }
+    }
+
+    /// Evaluates `expression`, of the type it's checked as
+    fn evaluate(&mut self, expression: &Expression) -> Result<Constant>
+    {
+        let span = expression.span;
+
+        match &expression.kind
+        {
+            ExpressionKind::Literal(literal) =>
+            {
+                self.evaluate_literal(literal, &expression.r#type, span)
+            }
+            &ExpressionKind::Constant(item) => self.constant(item),
+            ExpressionKind::Function(_) =>
+            {
+                Err(self.not_constant("Uses of functions as values", span))
+            }
+            ExpressionKind::Local(_) => Err(self.not_constant("Reads of locals", span)),
+            ExpressionKind::Negate(e) => match self.evaluate(e)?
+            {
+                Constant::Integer {
+                    value,
+                    width,
+                    signed,
+                } => self.fit(
+                    value.checked_neg(),
+                    width,
+                    signed,
+                    || format!("`-{value}`"),
+                    span,
+                ),
+                Constant::Float { value, width } => Ok(Constant::Float {
+                    value: -value,
+                    width,
+                }),
+                Constant::Array { .. } => Err(self.not_constant("Negations of arrays", span)),
+            },
+            ExpressionKind::Binary(l, op, r) =>
+            {
+                let l = self.evaluate(l)?;
+                let r = self.evaluate(r)?;
+
+                self.evaluate_binary(l, *op, r, span)
+            }
+            ExpressionKind::Array(items) =>
+            {
+                let Type::Array { box scalar, .. } = &expression.r#type
+                else
+                {
+                    return Err(self.not_constant("Arrays of other types", span));
+                };
+
+                Ok(Constant::Array {
+                    scalar: scalar.clone(),
+                    items: items
+                        .iter()
+                        .map(|item| self.evaluate(item))
+                        .collect::<Result<_>>()?,
+                })
+            }
+            ExpressionKind::Len(target) => match self.evaluate(target)?
+            {
+                Constant::Array { items, .. } => Ok(Constant::Integer {
+                    value: items.len() as i128,
+                    width: 64,
+                    signed: false,
+                }),
+                _ => Err(self.not_constant("Lengths of slices", span)),
+            },
+            ExpressionKind::Slice { .. } => Err(self.not_constant("Slices", span)),
+            ExpressionKind::Cast(target) =>
+            {
+                let value = self.evaluate(target)?;
+
+                self.cast(value, &expression.r#type, span)
+            }
+            ExpressionKind::Index { target, index } =>
+            {
+                let Constant::Array { items, .. } = self.evaluate(target)?
+                else
+                {
+                    return Err(self.not_constant("Indexing into slices", span));
+                };
+
+                let Constant::Integer { value: index, .. } = self.evaluate(index)?
+                else
+                {
+                    return Err(self.not_constant("Indices other than integers", span));
+                };
+
+                match usize::try_from(index).ok().and_then(|i| items.get(i))
+                {
+                    Some(item) => Ok(item.clone()),
+                    None =>
+                    {
+                        let (name, module) = self.evaluated();
+
+                        Err(TypeError::ConstIndexOutOfBounds {
+                            name,
+                            module,
+                            span,
+                            help: Some(format!(
+                                "The length is {} but the index is {index}",
+                                items.len()
+                            )),
+                        }
+                        .into())
+                    }
+                }
+            }
+            // Items are read as they are, anything else would borrow
+            ExpressionKind::Coerce(value) =>
+            {
+                let constant = self.evaluate(value)?;
+
+                if constant.r#type() != expression.r#type
+                {
+                    return Err(self.not_constant("Borrows", span));
+                }
+
+                Ok(constant)
+            }
+            ExpressionKind::Call { .. } | ExpressionKind::Assert { .. } =>
+            {
+                Err(self.not_constant("Calls", span))
+            }
+            ExpressionKind::If { .. } => Err(self.not_constant("Branches", span)),
+            ExpressionKind::Closure { .. } => Err(self.not_constant("Closures", span)),
+        }
+    }
+
+    /// `value as to`, converted as at runtime. Floats out of the range of the integer type they're cast to saturate to its
+    /// smallest or largest value, NaN to 0
+    fn cast(&self, value: Constant, to: &Type, span: Span) -> Result<Constant>
+    {
+        Ok(match (&value, to)
+        {
+            (value, to) if value.r#type() == *to => value.clone(),
+            (&Constant::Integer { value, .. }, &Type::Integer { width, signed }) =>
+            {
+                let (min, max) = bounds(width, signed);
+                // Two's complement truncation, as LLVM's `trunc`
+                let range = max.wrapping_sub(min).wrapping_add(1);
+                let wrapped = if range == 0
+                {
+                    value
+                }
+                else
+                {
+                    (value - min).rem_euclid(range) + min
+                };
+
+                Constant::Integer {
+                    value: wrapped,
+                    width,
+                    signed,
+                }
+            }
+            (&Constant::Integer { value, .. }, &Type::Float(width)) => Constant::Float {
+                value: round(value as f64, width),
+                width,
+            },
+            (&Constant::Float { value, .. }, &Type::Integer { width, signed }) =>
+            {
+                let (min, max) = bounds(width, signed);
+
+                Constant::Integer {
+                    value: (value as i128).clamp(min, max),
+                    width,
+                    signed,
+                }
+            }
+            (&Constant::Float { value, .. }, &Type::Float(width)) => Constant::Float {
+                value: round(value, width),
+                width,
+            },
+            _ => return Err(self.not_constant("Casts of arrays", span)),
+        })
+    }
+
+    /// The literal `literal` at `span`, of type `r#type`
+    fn evaluate_literal(&self, literal: &Literal, r#type: &Type, span: Span) -> Result<Constant>
+    {
+        match (literal, r#type)
+        {
+            // Two's complement, sign extended from the type's width if it's signed
+            (&Literal::Integer(bits), &Type::Integer { width, signed }) =>
+            {
+                let value = if signed
+                {
+                    Some(((bits << (128 - width)) as i128) >> (128 - width))
+                }
+                else
+                {
+                    i128::try_from(bits).ok()
+                };
+
+                self.fit(value, width, signed, || format!("`{bits}`"), span)
+            }
+            (&Literal::Float(value), &Type::Float(width)) => Ok(Constant::Float {
+                value: round(value, width),
+                width,
+            }),
+            (Literal::String(contents), _) => Ok(Constant::Array {
+                scalar: Type::Integer {
+                    width: 8,
+                    signed: true,
+                },
+                items: contents
+                    .bytes()
+                    .map(|b| Constant::Integer {
+                        value: b as i8 as i128,
+                        width: 8,
+                        signed: true,
+                    })
+                    .collect(),
+            }),
+            _ => Err(self.not_constant("Literals of other types", span)),
+        }
+    }
+
+    /// `l op r` at `span`, both of the same type. Comparisons give a single bit
+    fn evaluate_binary(
+        &self,
+        l: Constant,
+        op: Operator,
+        r: Constant,
+        span: Span,
+    ) -> Result<Constant>
+    {
+        let operation = || format!("`{l} {op} {r}`");
+
+        let holds = |ordering: Option<std::cmp::Ordering>| {
+            let holds = match op
+            {
+                Operator::Lt => ordering.is_some_and(|o| o.is_lt()),
+                Operator::Gt => ordering.is_some_and(|o| o.is_gt()),
+                Operator::Le => ordering.is_some_and(|o| o.is_le()),
+                Operator::Ge => ordering.is_some_and(|o| o.is_ge()),
+                Operator::EqEq => ordering.is_some_and(|o| o.is_eq()),
+                _ => ordering.is_none_or(|o| o.is_ne()),
+            };
+
+            Constant::Integer {
+                value: holds as i128,
+                width: 1,
+                signed: false,
+            }
+        };
+
+        match (&l, &r)
+        {
+            (
+                &Constant::Integer {
+                    value: a,
+                    width,
+                    signed,
+                },
+                &Constant::Integer { value: b, .. },
+            ) =>
+            {
+                let value = match op
+                {
+                    _ if is_comparison(op) => return Ok(holds(a.partial_cmp(&b))),
+                    Operator::Plus => a.checked_add(b),
+                    Operator::Minus => a.checked_sub(b),
+                    Operator::Star => a.checked_mul(b),
+                    Operator::Div =>
+                    {
+                        if b == 0
+                        {
+                            let (name, module) = self.evaluated();
+
+                            return Err(
+                                TypeError::ConstDivisionByZero { name, module, span }.into()
+                            );
+                        }
+
+                        a.checked_div(b)
+                    }
+                    Operator::And | Operator::BitAnd => Some(a & b),
+                    Operator::Or | Operator::BitOr => Some(a | b),
+                    Operator::Xor => Some(a ^ b),
+                    // Shifting bits out, or by the width or more, overflows
+                    Operator::Shl => u32::try_from(b)
+                        .ok()
+                        .filter(|b| *b < width)
+                        .and_then(|b| a.checked_mul(1 << b)),
+                    Operator::Shr => u32::try_from(b).ok().filter(|b| *b < width).map(|b| a >> b),
+                    _ => return Err(self.not_constant(&format!("Uses of `{op}`"), span)),
+                };
+
+                self.fit(value, width, signed, operation, span)
+            }
+            (&Constant::Float { value: a, width }, &Constant::Float { value: b, .. }) =>
+            {
+                let value = match op
+                {
+                    _ if is_comparison(op) => return Ok(holds(a.partial_cmp(&b))),
+                    Operator::Plus => a + b,
+                    Operator::Minus => a - b,
+                    Operator::Star => a * b,
+                    Operator::Div => a / b,
+                    _ => return Err(self.not_constant(&format!("Uses of `{op}`"), span)),
+                };
+
+                Ok(Constant::Float {
+                    value: round(value, width),
+                    width,
+                })
+            }
+            _ => Err(self.not_constant(&format!("Uses of `{op}` on arrays"), span)),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::{collections::HashMap, mem};
+
+use compiler_lexer::definitions::Span;
+use ecow::{EcoString, EcoVec};
+
+use crate::{
+    BindingKind, Block, Callee, Expression, ExpressionKind, Local, LocalId, Statement, TypeError,
+};
+
+/// The first local of the function captured by a closure a value may hold. Environments live on the stack of the function creating
+/// them, so those capturing anything can't leave it
+type Held = Option<LocalId>;
+
+struct Escapes<'c>
+{
+    locals: &'c [Local],
+    module: EcoVec<EcoString>,
+    /// What the locals of the function being checked hold, once they hold a closure capturing something
+    held: HashMap<LocalId, Held>,
+    errors: Vec<TypeError>,
+}
+
+/// Finds the closures of `body` which may outlive the function creating them, by being returned or assigned through a
+/// reference. Those capturing anything are reported, as nothing would free their environment, and those capturing by reference
+/// would point into a frame which is gone. Closures within it are checked on their own, as their returns leave their own frame
+pub(super) fn check(locals: &[Local], module: EcoVec<EcoString>, body: &Block) -> Vec<TypeError>
+{
+    let mut escapes = Escapes {
+        locals,
+        module,
+        held: HashMap::new(),
+        errors: Vec::new(),
+    };
+
+    escapes.block(body);
+
+    escapes.errors
+}
+
+impl Escapes<'_>
+{
+    fn block(&mut self, block: &Block)
+    {
+        for statement in block
+        {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement)
+    {
+        match statement
+        {
+            Statement::Return(value) =>
+            {
+                if let Some(value) = value
+                {
+                    let held = self.expression(value);
+
+                    self.escape(held, value.span);
+                }
+            }
+            Statement::Expression(expression) =>
+            {
+                self.expression(expression);
+            }
+            Statement::Assign {
+                destination,
+                source,
+            } =>
+            {
+                let held = self.expression(source);
+                self.expression(destination);
+
+                // Locals of this frame may hold them, anything else may outlive it
+                if let ExpressionKind::Local(local) = destination.kind
+                    && matches!(
+                        self.locals[local.0],
+                        Local {
+                            kind: BindingKind::Local,
+                            ..
+                        } | Local {
+                            kind: BindingKind::Parameter,
+                            mutable: true,
+                            ..
+                        }
+                    )
+                {
+                    let entry = self.held.entry(local).or_default();
+                    *entry = entry.or(held);
+                }
+                else
+                {
+                    self.escape(held, destination.span);
+                }
+            }
+            Statement::Local { local, init } =>
+            {
+                if let Some(init) = init
+                {
+                    let held = self.expression(init);
+
+                    self.held.insert(*local, held);
+                }
+            }
+        }
+    }
+
+    /// `held` leaves the function at `span`
+    fn escape(&mut self, held: Held, span: Span)
+    {
+        let Some(local) = held
+        else
+        {
+            return;
+        };
+
+        let declared = &self.locals[local.0];
+
+        self.errors.push(TypeError::ClosureEscapes {
+            name: declared.name.clone(),
+            module: self.module.clone(),
+            span,
+            declaration: declared.span,
+            help: Some(format!(
+                "Only closures capturing nothing may outlive their function, pass `{}` as an argument instead",
+                declared.name
+            )),
+        });
+    }
+
+    /// Checks the closures within `expression`, giving what its value may hold
+    fn expression(&mut self, expression: &Expression) -> Held
+    {
+        match &expression.kind
+        {
+            ExpressionKind::Literal(_)
+            | ExpressionKind::Constant(_)
+            | ExpressionKind::Function(_) => None,
+            ExpressionKind::Local(local) => self.held.get(local).copied().flatten(),
+            ExpressionKind::Binary(left, _, right) =>
+            {
+                self.expression(left);
+                self.expression(right);
+
+                None
+            }
+            ExpressionKind::Negate(e)
+            | ExpressionKind::Cast(e)
+            | ExpressionKind::Len(e)
+            | ExpressionKind::Assert { condition: e, .. } =>
+            {
+                self.expression(e);
+
+                None
+            }
+            ExpressionKind::Coerce(e) => self.expression(e),
+            ExpressionKind::Index { target, index } =>
+            {
+                let held = self.expression(target);
+                self.expression(index);
+
+                held
+            }
+            ExpressionKind::Slice { target, start, end } =>
+            {
+                let held = self.expression(target);
+
+                for bound in [start, end].into_iter().flatten()
+                {
+                    self.expression(bound);
+                }
+
+                held
+            }
+            // Calls may hand back any closure passed to them
+            ExpressionKind::Call { callee, args } =>
+            {
+                let mut held = match callee
+                {
+                    Callee::Pointer(pointer) => self.expression(pointer),
+                    Callee::Function(_) => None,
+                };
+
+                for arg in args
+                {
+                    held = held.or(self.expression(arg));
+                }
+
+                held
+            }
+            ExpressionKind::If {
+                condition,
+                block,
+                else_block,
+            } =>
+            {
+                self.expression(condition);
+                self.block(block);
+
+                if let Some(else_block) = else_block
+                {
+                    self.block(else_block);
+                }
+
+                None
+            }
+            ExpressionKind::Array(items) =>
+            {
+                let mut held = None;
+
+                for item in items
+                {
+                    held = held.or(self.expression(item));
+                }
+
+                held
+            }
+            ExpressionKind::Closure { captures, body, .. } =>
+            {
+                for capture in captures
+                {
+                    self.expression(&capture.value);
+                }
+
+                let outer = mem::take(&mut self.held);
+                self.block(body);
+                self.held = outer;
+
+                captures.first().map(|c| c.local)
+            }
+        }
+    }
+}
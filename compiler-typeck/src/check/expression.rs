@@ -0,0 +1,876 @@
+use compiler_hir::{self as hir, DefId, Node};
+use compiler_lexer::definitions::{LiteralType, Span};
+use compiler_parser::{CaptureKind, Operator, item::FunctionSignature};
+use ecow::EcoVec;
+
+use super::{Checker, Declared, Result};
+use crate::{
+    BOOL, BindingKind, Capture, DEFAULT_FLOAT, DEFAULT_INT, Expression, ExpressionKind, LENGTH,
+    Literal, Local, Type, TypeError, TypeWarning, cast_hint, is_comparison, is_lossy,
+    operands_hint, parse_int_literal,
+};
+
+/// Whether `expression` is a numeric literal without a type of its own, which takes whichever type it's used as
+pub(super) fn is_untyped_literal(expression: &hir::Expression) -> bool
+{
+    match expression
+    {
+        hir::Expression::Literal {
+            r#type: LiteralType::Int | LiteralType::Float,
+            ..
+        } => true,
+        hir::Expression::Unary(_, box e) => is_untyped_literal(e),
+        _ => false,
+    }
+}
+
+impl Checker<'_>
+{
+    /// Where `expression` is in the source. Those without a span of their own span from their first to their last part
+    pub(super) fn span_of(&self, expression: &hir::Expression) -> Span
+    {
+        match expression
+        {
+            hir::Expression::Literal { span, .. }
+            | hir::Expression::Def { span, .. }
+            | hir::Expression::Cast { span, .. }
+            | hir::Expression::Call { span, .. }
+            | hir::Expression::Method { span, .. }
+            | hir::Expression::Field { span, .. }
+            | hir::Expression::Index { span, .. }
+            | hir::Expression::Slice { span, .. } => *span,
+            hir::Expression::Binary(box node) => self.node_span(node),
+            hir::Expression::Unary(_, box e)
+            | hir::Expression::If {
+                condition: box e, ..
+            } => self.span_of(e),
+            hir::Expression::Tuple(items) | hir::Expression::Array(items) =>
+            {
+                match (items.first(), items.last())
+                {
+                    (Some(first), Some(last)) => self.span_of(first).to(self.span_of(last)),
+                    _ => self.frame.at,
+                }
+            }
+            hir::Expression::Closure { signature, .. } => signature.span,
+        }
+    }
+
+    fn node_span(&self, node: &Node) -> Span
+    {
+        match node
+        {
+            Node::Scalar(e) => self.span_of(e),
+            Node::Compound(box (l, _, r)) => self.node_span(l).to(self.node_span(r)),
+        }
+    }
+
+    /// Checks `expression`, which is used as a `hint` if known. Literals and arrays take that type if they may, anything else is
+    /// left as it is, it's up to the caller to convert it
+    pub(super) fn expression(
+        &mut self,
+        expression: &hir::Expression,
+        hint: Option<&Type>,
+    ) -> Result<Expression>
+    {
+        let span = self.span_of(expression);
+
+        // Literals passed by reference get a temporary of the type behind it
+        let hint = hint.map(Type::referent);
+
+        let (kind, r#type) = match expression
+        {
+            hir::Expression::Literal { value, r#type, .. } =>
+            {
+                return self.literal(value, *r#type, false, hint, span);
+            }
+            hir::Expression::Unary(
+                Operator::Minus,
+                box hir::Expression::Literal {
+                    value,
+                    r#type: r#type @ (LiteralType::Int | LiteralType::Float),
+                    ..
+                },
+            ) => return self.literal(value, *r#type, true, hint, span),
+            hir::Expression::Unary(Operator::Minus, box e) =>
+            {
+                let value = self.value(e, hint)?;
+
+                if !value.r#type.is_numeric()
+                {
+                    return Err(TypeError::Operator {
+                        op: Operator::Minus,
+                        r#type: value.r#type,
+                        module: self.module(),
+                        span,
+                    }
+                    .into());
+                }
+
+                let r#type = value.r#type.clone();
+
+                (ExpressionKind::Negate(Box::new(value)), r#type)
+            }
+            &hir::Expression::Unary(op, _) =>
+            {
+                return Err(TypeError::UnaryOperator {
+                    op,
+                    module: self.module(),
+                    span,
+                }
+                .into());
+            }
+            hir::Expression::Binary(box node) => return self.binary(node, hint),
+            hir::Expression::Def { def, span } => return self.def(*def, *span),
+            hir::Expression::Cast {
+                box target,
+                r#type,
+                span,
+            } =>
+            {
+                let to = self.resolve(r#type.clone(), *span)?;
+
+                return self.cast(target, to, *span);
+            }
+            hir::Expression::Call { callee, args, span } =>
+            {
+                return self.call(callee, args, *span);
+            }
+            hir::Expression::Method {
+                box receiver,
+                callee,
+                args,
+                span,
+            } => return self.method(receiver, *callee, args, *span),
+            hir::Expression::Field {
+                box target,
+                name,
+                span,
+            } =>
+            {
+                let target = self.expression(target, None)?;
+
+                match target.r#type.referent()
+                {
+                    Type::Array { .. } | Type::Slice(_) if name == "len" =>
+                    {
+                        (ExpressionKind::Len(Box::new(target)), LENGTH)
+                    }
+                    r#type =>
+                    {
+                        return Err(TypeError::NoField {
+                            r#type: r#type.clone(),
+                            name: name.clone(),
+                            module: self.module(),
+                            span: *span,
+                        }
+                        .into());
+                    }
+                }
+            }
+            hir::Expression::Index {
+                box target,
+                box index,
+                span,
+            } =>
+            {
+                let (target, scalar, writable) = self.items(target, *span)?;
+                let index = self.offset(index)?;
+
+                (
+                    ExpressionKind::Index {
+                        target: Box::new(target),
+                        index: Box::new(index),
+                    },
+                    if writable
+                    {
+                        Type::MutRef(Box::new(scalar))
+                    }
+                    else
+                    {
+                        Type::Ref(Box::new(scalar))
+                    },
+                )
+            }
+            hir::Expression::Slice {
+                box target,
+                start,
+                end,
+                span,
+            } =>
+            {
+                let (target, scalar, _) = self.items(target, *span)?;
+                let start = start.as_ref().map(|s| self.offset(s)).transpose()?;
+                let end = end.as_ref().map(|e| self.offset(e)).transpose()?;
+
+                (
+                    ExpressionKind::Slice {
+                        target: Box::new(target),
+                        start: start.map(Box::new),
+                        end: end.map(Box::new),
+                    },
+                    Type::Slice(Box::new(scalar)),
+                )
+            }
+            hir::Expression::If {
+                box condition,
+                block,
+                else_block,
+            } =>
+            {
+                let condition = self.value(condition, None)?;
+
+                if condition.r#type != BOOL
+                {
+                    return Err(TypeError::Condition {
+                        construct: "`if`",
+                        help: matches!(condition.r#type, Type::Integer { .. })
+                            .then(|| "Compare it, as in `x != 0`".into()),
+                        found: condition.r#type,
+                        module: self.module(),
+                        span: condition.span,
+                    }
+                    .into());
+                }
+
+                let block = self.block(block)?;
+                let else_block = else_block.as_ref().map(|b| self.block(b)).transpose()?;
+
+                (
+                    ExpressionKind::If {
+                        condition: Box::new(condition),
+                        block,
+                        else_block,
+                    },
+                    Type::Void,
+                )
+            }
+            hir::Expression::Tuple(_) =>
+            {
+                return Err(TypeError::Unsupported {
+                    what: "Tuples",
+                    module: self.module(),
+                    span,
+                }
+                .into());
+            }
+            hir::Expression::Array(items) => return self.array(items, hint, span),
+            hir::Expression::Closure {
+                captures,
+                box signature,
+                parameters,
+                body,
+            } => return self.closure(captures, signature, parameters, body),
+        };
+
+        Ok(Expression { kind, r#type, span })
+    }
+
+    /// Checks `expression` as a value, read from behind a reference if it's one
+    pub(super) fn value(
+        &mut self,
+        expression: &hir::Expression,
+        hint: Option<&Type>,
+    ) -> Result<Expression>
+    {
+        let value = self.expression(expression, hint)?;
+
+        if value.r#type == Type::Void
+        {
+            return Err(TypeError::Void {
+                module: self.module(),
+                span: value.span,
+            }
+            .into());
+        }
+
+        Ok(load(value))
+    }
+
+    /// Checks `expression` as a value of type `to`
+    #[inline]
+    pub(super) fn coerce(&mut self, expression: &hir::Expression, to: &Type) -> Result<Expression>
+    {
+        let value = self.expression(expression, Some(to))?;
+
+        self.convert(value, to)
+    }
+
+    /// Converts `value` to `to`, if it's the same type but borrowed, read from behind a reference, taken as a `&` rather than a
+    /// `&mut`, or an array rather than a slice of its items
+    pub(super) fn convert(&self, value: Expression, to: &Type) -> Result<Expression>
+    {
+        let converts = match (&value.r#type, to)
+        {
+            (from, to) if from == to => return Ok(value),
+            (Type::MutRef(box from), Type::Ref(box to)) => from == to,
+            (
+                Type::Array { box scalar, .. }
+                | Type::Ref(box Type::Array { box scalar, .. })
+                | Type::MutRef(box Type::Array { box scalar, .. }),
+                Type::Slice(box to),
+            ) if scalar == to => true,
+            (from, Type::Ref(box inner) | Type::MutRef(box inner)) if from == inner => true,
+            (Type::Ref(box from) | Type::MutRef(box from), to) => from == to,
+            _ => false,
+        };
+
+        if !converts
+        {
+            // References are read implicitly, so they're mismatched by what they point to unless one is expected
+            let found = match to
+            {
+                Type::Ref(_) | Type::MutRef(_) => value.r#type.clone(),
+                _ => value.r#type.referent().clone(),
+            };
+
+            return Err(TypeError::Mismatch {
+                hint: cast_hint(&found, to),
+                expected: to.clone(),
+                found,
+                module: self.module(),
+                span: value.span,
+            }
+            .into());
+        }
+
+        Ok(Expression {
+            span: value.span,
+            r#type: to.clone(),
+            kind: ExpressionKind::Coerce(Box::new(value)),
+        })
+    }
+
+    /// A literal, `-value` if `negative`, of the type it's used as if it may take it
+    fn literal(
+        &self,
+        value: &str,
+        r#type: LiteralType,
+        negative: bool,
+        hint: Option<&Type>,
+        span: Span,
+    ) -> Result<Expression>
+    {
+        let sign = if negative { "-" } else { "" };
+
+        let (literal, r#type) = match (r#type, hint)
+        {
+            (LiteralType::String, _) =>
+            {
+                let contents = &value[1..value.len() - 1];
+
+                (
+                    Literal::String(contents.into()),
+                    Type::Array {
+                        scalar: Box::new(Type::Integer {
+                            width: 8,
+                            signed: true,
+                        }),
+                        size: contents.len() as u32,
+                    },
+                )
+            }
+            (LiteralType::Rune, _) => (
+                Literal::Integer(value.as_bytes()[1].into()),
+                Type::Integer {
+                    width: 8,
+                    signed: true,
+                },
+            ),
+            (LiteralType::Int, Some(&Type::Float(width))) => (
+                Literal::Float(
+                    parse_int_literal(value).map_or(f64::INFINITY, |v| v as f64)
+                        * if negative { -1.0 } else { 1.0 },
+                ),
+                Type::Float(width),
+            ),
+            (LiteralType::Int, hint) =>
+            {
+                let r#type = match hint
+                {
+                    Some(r#type @ Type::Integer { .. }) => r#type.clone(),
+                    _ => DEFAULT_INT,
+                };
+
+                let Type::Integer { width, signed } = r#type
+                else
+                {
+                    unreachable!()
+                };
+
+                let max = match (signed, negative)
+                {
+                    (true, true) => 1 << (width - 1),
+                    (true, false) => (1 << (width - 1)) - 1,
+                    (false, true) => 0,
+                    (false, false) => 1u128.checked_shl(width).map_or(u128::MAX, |m| m - 1),
+                };
+
+                let Some(magnitude) = parse_int_literal(value).filter(|&m| m <= max)
+                else
+                {
+                    return Err(TypeError::LiteralOverflow {
+                        literal: format!("{sign}{value}").into(),
+                        r#type,
+                        module: self.module(),
+                        span,
+                    }
+                    .into());
+                };
+
+                // Two's complement, it's up to the code generator to truncate it to the type's width
+                (
+                    Literal::Integer(
+                        if negative
+                        {
+                            magnitude.wrapping_neg()
+                        }
+                        else
+                        {
+                            magnitude
+                        },
+                    ),
+                    r#type,
+                )
+            }
+            (LiteralType::Float, hint) => (
+                Literal::Float(format!("{sign}{value}").parse().unwrap_or(f64::NAN)),
+                match hint
+                {
+                    Some(r#type @ Type::Float(_)) => r#type.clone(),
+                    _ => DEFAULT_FLOAT,
+                },
+            ),
+        };
+
+        Ok(Expression {
+            kind: ExpressionKind::Literal(literal),
+            r#type,
+            span,
+        })
+    }
+
+    /// Both operands must be of the same type, which untyped literals take from the other side, or from `hint` if both are. The
+    /// hint means nothing to comparisons, which give a [`BOOL`]
+    fn binary(&mut self, node: &Node, hint: Option<&Type>) -> Result<Expression>
+    {
+        let (l, op, r) = match node
+        {
+            Node::Scalar(e) => return self.expression(e, hint),
+            Node::Compound(box (l, op, r)) => (l, *op, r),
+        };
+
+        let hint = hint.filter(|_| !is_comparison(op));
+        let untyped = |node: &Node| matches!(node, Node::Scalar(e) if is_untyped_literal(e));
+
+        let (l, r) = if untyped(l) && !untyped(r)
+        {
+            let r = self.operand(r, None)?;
+            let l = self.operand(l, Some(&r.r#type))?;
+
+            (l, r)
+        }
+        else
+        {
+            let l = self.operand(l, if untyped(l) { hint } else { None })?;
+            let r = self.operand(r, untyped(r).then_some(&l.r#type))?;
+
+            (l, r)
+        };
+
+        let span = l.span.to(r.span);
+
+        if l.r#type != r.r#type
+        {
+            return Err(TypeError::Operands {
+                op,
+                hint: operands_hint(&l.r#type, &r.r#type),
+                left: l.r#type,
+                right: r.r#type,
+                module: self.module(),
+                span,
+            }
+            .into());
+        }
+
+        let applies = match l.r#type
+        {
+            Type::Integer { .. } => true,
+            Type::Float(_) =>
+            {
+                is_comparison(op)
+                    || matches!(
+                        op,
+                        Operator::Plus | Operator::Minus | Operator::Star | Operator::Div
+                    )
+            }
+            _ => false,
+        };
+
+        if !applies
+        {
+            return Err(TypeError::Operator {
+                op,
+                r#type: l.r#type,
+                module: self.module(),
+                span,
+            }
+            .into());
+        }
+
+        Ok(Expression {
+            r#type: if is_comparison(op)
+            {
+                BOOL
+            }
+            else
+            {
+                l.r#type.clone()
+            },
+            kind: ExpressionKind::Binary(Box::new(l), op, Box::new(r)),
+            span,
+        })
+    }
+
+    fn operand(&mut self, node: &Node, hint: Option<&Type>) -> Result<Expression>
+    {
+        match node
+        {
+            Node::Scalar(e) => self.value(e, hint),
+            node => self.binary(node, hint),
+        }
+    }
+
+    /// What the name `def` refers to. Locals are places, references to their slot, the rest are values
+    fn def(&mut self, def: DefId, span: Span) -> Result<Expression>
+    {
+        let (kind, r#type) = match def
+        {
+            DefId::Local(local) | DefId::Parameter(local) =>
+            {
+                let local = self.frame.scope[&local];
+
+                (
+                    ExpressionKind::Local(local),
+                    self.locals[local.0].place_type(),
+                )
+            }
+            DefId::Const(item) => (ExpressionKind::Constant(item), self.constant(item)?),
+            DefId::Function(item) | DefId::Extern(item) =>
+            {
+                self.check_test_use(item, span)?;
+
+                let function = match self.declared[item.0]
+                {
+                    Declared::Function(id) | Declared::Extern(id) => id,
+                    _ =>
+                    {
+                        return Err(TypeError::FunctionAsValue {
+                            kind: "Generic",
+                            name: self.hir[item].name.clone(),
+                            module: self.module(),
+                            span,
+                            help: Some("There's nothing to infer its parameters from".into()),
+                        }
+                        .into());
+                    }
+                };
+
+                if self.functions[function.0].variadic
+                {
+                    return Err(TypeError::FunctionAsValue {
+                        kind: "Variadic",
+                        name: self.hir[item].name.clone(),
+                        module: self.module(),
+                        span,
+                        help: None,
+                    }
+                    .into());
+                }
+
+                (
+                    ExpressionKind::Function(function),
+                    self.functions[function.0].pointer_type(),
+                )
+            }
+        };
+
+        Ok(Expression { kind, r#type, span })
+    }
+
+    /// `target as to`, between numbers. Literals which may take the type directly aren't converted at all, as `1 as u8` or
+    /// `2 as f32`
+    fn cast(&mut self, target: &hir::Expression, to: Type, span: Span) -> Result<Expression>
+    {
+        if is_untyped_literal(target)
+        {
+            let literal = self.expression(target, None)?;
+
+            if let (Type::Integer { .. }, Type::Integer { .. }) | (_, Type::Float(_)) =
+                (&literal.r#type, &to)
+            {
+                return self.expression(target, Some(&to));
+            }
+        }
+
+        let value = self.value(target, None)?;
+
+        if !value.r#type.is_numeric() || !to.is_numeric()
+        {
+            return Err(TypeError::Cast {
+                from: value.r#type,
+                to,
+                module: self.module(),
+                span,
+            }
+            .into());
+        }
+
+        if is_lossy(&value.r#type, &to)
+        {
+            self.warnings.push(TypeWarning::LossyCast {
+                from: value.r#type.clone(),
+                to: to.clone(),
+                module: self.module(),
+                span,
+            });
+        }
+
+        Ok(Expression {
+            kind: ExpressionKind::Cast(Box::new(value)),
+            r#type: to,
+            span,
+        })
+    }
+
+    /// Checks what's indexed or sliced, giving the type of its items and whether they may be written to, which only those of
+    /// `&mut` arrays may
+    fn items(&mut self, target: &hir::Expression, span: Span) -> Result<(Expression, Type, bool)>
+    {
+        let target = self.expression(target, None)?;
+
+        let (scalar, writable) = match &target.r#type
+        {
+            Type::MutRef(box Type::Array { box scalar, .. }) => (scalar.clone(), true),
+            Type::Array { box scalar, .. }
+            | Type::Ref(box Type::Array { box scalar, .. })
+            | Type::Slice(box scalar)
+            | Type::Ref(box Type::Slice(box scalar))
+            | Type::MutRef(box Type::Slice(box scalar)) => (scalar.clone(), false),
+            r#type =>
+            {
+                return Err(TypeError::NotIndexable {
+                    r#type: r#type.clone(),
+                    module: self.module(),
+                    span,
+                }
+                .into());
+            }
+        };
+
+        Ok((target, scalar, writable))
+    }
+
+    /// An index or a slice bound. Untyped literals can't be negative, so they're taken as lengths
+    fn offset(&mut self, offset: &hir::Expression) -> Result<Expression>
+    {
+        if is_untyped_literal(offset)
+        {
+            return self.coerce(offset, &LENGTH);
+        }
+
+        let offset = self.value(offset, None)?;
+
+        match offset.r#type
+        {
+            Type::Integer { width, .. } if width <= 64 => Ok(offset),
+            r#type => Err(TypeError::Offset {
+                r#type,
+                module: self.module(),
+                span: offset.span,
+            }
+            .into()),
+        }
+    }
+
+    /// `[a, b, c]`, of the items `hint` asks for if it's an array or a slice. Otherwise the first item gives its type to the rest
+    fn array(
+        &mut self,
+        items: &[hir::Expression],
+        hint: Option<&Type>,
+        span: Span,
+    ) -> Result<Expression>
+    {
+        let scalar = match hint
+        {
+            Some(&Type::Array {
+                box ref scalar,
+                size,
+            }) =>
+            {
+                if items.len() != size as usize
+                {
+                    return Err(TypeError::ArrayLength {
+                        expected: size,
+                        found: items.len(),
+                        module: self.module(),
+                        span,
+                    }
+                    .into());
+                }
+
+                Some(scalar.clone())
+            }
+            Some(Type::Slice(box scalar)) => Some(scalar.clone()),
+            _ => None,
+        };
+
+        let mut values = EcoVec::with_capacity(items.len());
+        let mut scalar = scalar;
+
+        for item in items
+        {
+            let value = match &scalar
+            {
+                Some(scalar) => self.coerce(item, scalar)?,
+                None => self.value(item, None)?,
+            };
+
+            scalar.get_or_insert_with(|| value.r#type.clone());
+            values.push(value);
+        }
+
+        let Some(scalar) = scalar
+        else
+        {
+            return Err(TypeError::EmptyArray {
+                module: self.module(),
+                span,
+                help: Some("Declare its type".into()),
+            }
+            .into());
+        };
+
+        Ok(Expression {
+            kind: ExpressionKind::Array(values),
+            r#type: Type::Array {
+                scalar: Box::new(scalar),
+                size: items.len() as u32,
+            },
+            span,
+        })
+    }
+
+    fn closure(
+        &mut self,
+        captures: &[hir::Capture],
+        signature: &FunctionSignature,
+        parameters: &[hir::LocalId],
+        body: &hir::Block,
+    ) -> Result<Expression>
+    {
+        let (arguments, return_type) = self.signature(signature)?;
+
+        // Values are copied in, references point to the local's slot, or to a temporary if it has none
+        let mut checked = EcoVec::with_capacity(captures.len());
+        let mut scope = self.frame.scope.clone();
+
+        for capture in captures
+        {
+            let (DefId::Local(local) | DefId::Parameter(local)) = capture.def
+            else
+            {
+                unreachable!("Only locals are captured");
+            };
+
+            let outer = self.frame.scope[&local];
+            let place = Expression {
+                kind: ExpressionKind::Local(outer),
+                r#type: self.locals[outer.0].place_type(),
+                span: capture.span,
+            };
+
+            let value = match capture.kind
+            {
+                CaptureKind::Value => load(place),
+                CaptureKind::Ref =>
+                {
+                    let referent = place.r#type.referent().clone();
+
+                    self.convert(place, &Type::Ref(Box::new(referent)))?
+                }
+                CaptureKind::MutRef =>
+                {
+                    self.ensure_mutable(
+                        &hir::Expression::Def {
+                            def: capture.def,
+                            span: capture.span,
+                        },
+                        true,
+                    )?;
+
+                    place
+                }
+            };
+
+            let inner = self.declare_local(Local {
+                name: self.locals[outer.0].name.clone(),
+                kind: BindingKind::Capture,
+                mutable: capture.kind == CaptureKind::MutRef,
+                r#type: value.r#type.clone(),
+                span: capture.span,
+            });
+
+            // Within the closure, the name refers to the capture
+            scope.insert(local, inner);
+            checked.push(Capture {
+                local: outer,
+                value,
+                inner,
+                kind: capture.kind,
+            });
+        }
+
+        let declared = self.declare_parameters(signature, &arguments);
+        scope.extend(parameters.iter().copied().zip(declared.iter().copied()));
+
+        let scope = std::mem::replace(&mut self.frame.scope, scope);
+        let outer_return = std::mem::replace(&mut self.frame.return_type, return_type.clone());
+        let implicit_return = std::mem::replace(&mut self.frame.implicit_return, false);
+        let at = std::mem::replace(&mut self.frame.at, signature.span);
+
+        let body = self.block(body);
+
+        self.frame.scope = scope;
+        self.frame.return_type = outer_return;
+        self.frame.implicit_return = implicit_return;
+        self.frame.at = at;
+
+        Ok(Expression {
+            kind: ExpressionKind::Closure {
+                captures: checked,
+                parameters: declared,
+                body: body?,
+            },
+            r#type: Type::Closure {
+                arguments,
+                return_type: Box::new(return_type),
+            },
+            span: signature.span,
+        })
+    }
+}
+
+/// Reads `value` from behind a reference, values are returned as they are
+pub(super) fn load(value: Expression) -> Expression
+{
+    match value.r#type.clone()
+    {
+        Type::Ref(box r#type) | Type::MutRef(box r#type) => Expression {
+            span: value.span,
+            r#type,
+            kind: ExpressionKind::Coerce(Box::new(value)),
+        },
+        _ => value,
+    }
+}
@@ -0,0 +1,80 @@
+//! Inference of the generic parameters of a call from the types of its arguments
+
+use std::collections::HashMap;
+
+use ecow::EcoString;
+use thiserror::Error;
+
+use crate::Type;
+
+/// A generic parameter bound to two different types by the arguments of a call
+#[derive(Error, Clone, Debug, PartialEq)]
+#[error("`{name}` is inferred as both `{bound}` and `{argument}`")]
+pub struct Conflict
+{
+    pub name: EcoString,
+    pub bound: Type,
+    pub argument: Type,
+}
+
+/// Binds the generic parameters on `parameter` by matching it against the argument's type
+pub fn infer(
+    parameter: &[EcoString],
+    argument: &Type,
+    generics: &[EcoString],
+    bindings: &mut HashMap<EcoString, Type>,
+) -> Result<(), Conflict>
+{
+    match parameter
+    {
+        [a, b, rest @ ..] if a == "&" && b == "mut" => match argument
+        {
+            Type::MutRef(box argument) => infer(rest, argument, generics, bindings),
+            // Values are borrowed implicitly
+            argument => infer(rest, argument, generics, bindings),
+        },
+        [a, rest @ ..] if a == "&" => match argument
+        {
+            Type::Ref(box argument) | Type::MutRef(box argument) =>
+            {
+                infer(rest, argument, generics, bindings)
+            }
+            argument => infer(rest, argument, generics, bindings),
+        },
+        // Arrays coerce to slices, their items bind the same
+        [a, b, rest @ ..] if a == "[" && b == "]" => match argument.referent()
+        {
+            Type::Slice(box scalar) | Type::Array { box scalar, .. } =>
+            {
+                infer(rest, scalar, generics, bindings)
+            }
+            _ => Ok(()),
+        },
+        [name] if generics.contains(name) =>
+        {
+            // Locals are references to their stack slot, the generic binds to what they hold
+            let mut argument = argument;
+            while let Type::Ref(box inner) | Type::MutRef(box inner) = argument
+            {
+                argument = inner;
+            }
+
+            match bindings.get(name)
+            {
+                Some(bound) if bound != argument => Err(Conflict {
+                    name: name.clone(),
+                    bound: bound.clone(),
+                    argument: argument.clone(),
+                }),
+                Some(_) => Ok(()),
+                None =>
+                {
+                    bindings.insert(name.clone(), argument.clone());
+                    Ok(())
+                }
+            }
+        }
+        // Concrete types are checked once the arguments are converted
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,937 @@
+#![feature(box_patterns)]
+
+//! Type checking, turning the [`Hir`](compiler_hir::Hir) into the typed IR the code generator takes. Every expression gets the
+//! type of the value it evaluates to, literals get the type they're used as, and every implicit conversion, as borrowing a value
+//! or reading one from behind a reference, is made explicit as [`ExpressionKind::Coerce`]. Generic functions are instantiated
+//! for each set of generic arguments they're called with, so the code generator has nothing left to infer.
+
+use std::{fmt, ops::Index};
+
+use compiler_hir::ItemId;
+use compiler_lexer::definitions::Span;
+use compiler_parser::{CaptureKind, Operator};
+use ecow::{EcoString, EcoVec};
+use miette::Diagnostic;
+use serde::Serialize;
+use thiserror::Error;
+
+pub use crate::{check::check, types::*};
+
+mod check;
+pub mod generic;
+mod types;
+
+/// Errors pointing at source code. `module` is the path of the module the spans refer to, as on the resolver's
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum TypeError
+{
+    #[error("Expected `{expected}`, got `{found}`{hint}")]
+    Mismatch
+    {
+        expected: Type,
+        found: Type,
+        hint: String,
+        module: EcoVec<EcoString>,
+        #[label("Expected `{expected}`")]
+        span: Span,
+    },
+    #[error("Operator `{op}` can't be applied to `{left}` and `{right}`{hint}")]
+    Operands
+    {
+        op: Operator,
+        left: Type,
+        right: Type,
+        hint: String,
+        module: EcoVec<EcoString>,
+        #[label("Both sides must be of the same type")]
+        span: Span,
+    },
+    #[error("Operator `{op}` can't be applied to `{type}`")]
+    Operator
+    {
+        op: Operator,
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Applied here")]
+        span: Span,
+    },
+    #[error("Unary `{op}` isn't supported")]
+    UnaryOperator
+    {
+        op: Operator,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+    },
+    #[error("{error}")]
+    InvalidType
+    {
+        error: InvalidType,
+        module: EcoVec<EcoString>,
+        #[label("In the type of this")]
+        span: Span,
+    },
+    #[error("Literal `{literal}` doesn't fit in `{type}`")]
+    LiteralOverflow
+    {
+        literal: EcoString,
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+    },
+    #[error(
+        "Function{} expects {at_least}{expected} arguments, got {found}",
+        name.as_ref().map(|n| format!(" `{n}`")).unwrap_or_default()
+    )]
+    Arity
+    {
+        /// Left out on callees other than paths
+        name: Option<EcoString>,
+        /// `"at least "` for variadic ones
+        at_least: &'static str,
+        expected: usize,
+        found: usize,
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+    },
+    #[error(
+        "{} of type `{type}` isn't a function",
+        name.as_ref().map_or("What's called".into(), |n| format!("`{n}`"))
+    )]
+    NotCallable
+    {
+        /// Left out on callees other than paths
+        name: Option<EcoString>,
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+    },
+    #[error("Function `{name}` takes no arguments, so it can't be called as a method")]
+    NoReceiver
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+    },
+    #[error("Method `{name}` takes `{parameter}` as its receiver, got `{found}`")]
+    Receiver
+    {
+        name: EcoString,
+        parameter: Type,
+        found: Type,
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+    },
+    #[error("{kind} function `{name}` can't be used as a value")]
+    FunctionAsValue
+    {
+        kind: &'static str,
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Couldn't infer `{name}` on call to `{function}`")]
+    Uninferred
+    {
+        name: EcoString,
+        function: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+    },
+    #[error("{conflict} on call to `{function}`")]
+    Conflict
+    {
+        conflict: generic::Conflict,
+        function: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+    },
+    #[error("Couldn't infer the type of `{name}`, as it's never assigned")]
+    #[diagnostic(help("Annotate it as `let {name} <type>`"))]
+    UninferredLocal
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        span: Span,
+    },
+    #[error("Type of `{name}` is ambiguous, it's assigned both `{first}` and `{second}`")]
+    #[diagnostic(help("Annotate it as `let {name} <type>`"))]
+    AmbiguousLocal
+    {
+        name: EcoString,
+        first: Type,
+        second: Type,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        span: Span,
+    },
+    #[error("Can't infer the type of an empty array")]
+    EmptyArray
+    {
+        module: EcoVec<EcoString>,
+        #[label("In here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Expected {expected} items, got {found}")]
+    ArrayLength
+    {
+        expected: u32,
+        found: usize,
+        module: EcoVec<EcoString>,
+        #[label("Of this array")]
+        span: Span,
+    },
+    #[error("Can't index into a value of type `{type}`")]
+    NotIndexable
+    {
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Indexed here")]
+        span: Span,
+    },
+    #[error("Arrays are indexed by integers of up to 64 bits, got `{type}`")]
+    Offset
+    {
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Used as an index here")]
+        span: Span,
+    },
+    #[error("`{type}` has no field `{name}`")]
+    NoField
+    {
+        r#type: Type,
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Read here")]
+        span: Span,
+    },
+    #[error("{what} aren't supported yet")]
+    Unsupported
+    {
+        what: &'static str,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+    },
+    #[error("Only numbers are cast with `as`, can't cast `{from}` to `{to}`")]
+    Cast
+    {
+        from: Type,
+        to: Type,
+        module: EcoVec<EcoString>,
+        #[label("Cast here")]
+        span: Span,
+    },
+    #[error("`assert` takes a condition and, optionally, a message")]
+    AssertArguments
+    {
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+    },
+    #[error("`assert` messages must be string literals")]
+    AssertMessage
+    {
+        module: EcoVec<EcoString>,
+        #[label("Not a string literal")]
+        span: Span,
+    },
+    #[error("{construct} expects a condition, got `{found}`")]
+    Condition
+    {
+        construct: &'static str,
+        found: Type,
+        module: EcoVec<EcoString>,
+        #[label("Of type `{found}`")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Can't assign to immutable {kind} `{name}`")]
+    AssignToImmutable
+    {
+        kind: BindingKind,
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Assigned here")]
+        span: Span,
+        /// Left out for constants of other modules
+        #[label("Declared here")]
+        declaration: Option<Span>,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Closure capturing `{name}` outlives its function")]
+    ClosureEscapes
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Taken out of its function here")]
+        span: Span,
+        #[label("Declared here")]
+        declaration: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Local `{name}` may be read before it's assigned")]
+    #[diagnostic(help("Assign it on every path leading here, or declare it with a value"))]
+    Unassigned
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Read here")]
+        span: Span,
+        #[label("Declared here without a value")]
+        declaration: Span,
+    },
+    #[error("Immutable local `{name}` may already be assigned")]
+    AssignedTwice
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Assigned again here")]
+        span: Span,
+        #[label("Declared here without a value")]
+        declaration: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Can't borrow immutable {kind} `{name}` as mutable")]
+    MutBorrowOfImmutable
+    {
+        kind: BindingKind,
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        declaration: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Can't assign to a value of type `{type}`")]
+    NotAssignable
+    {
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Not a place")]
+        span: Span,
+    },
+    #[error("Expected a value, got `()`")]
+    Void
+    {
+        module: EcoVec<EcoString>,
+        #[label("Evaluates to nothing")]
+        span: Span,
+    },
+    #[error("Function returning `{type}` returns without a value")]
+    MissingReturnValue
+    {
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Returns here")]
+        span: Span,
+    },
+    #[error("Type of constant `{name}` depends on itself")]
+    ConstCycle
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Constant `{name}` depends on itself")]
+    ValueCycle
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Constant `{name}` overflows `{type}` on {operation}")]
+    ConstOverflow
+    {
+        name: EcoString,
+        operation: String,
+        r#type: Type,
+        module: EcoVec<EcoString>,
+        #[label("Out of the range of its type")]
+        span: Span,
+    },
+    #[error("Constant `{name}` divides by zero")]
+    ConstDivisionByZero
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Divides by zero")]
+        span: Span,
+    },
+    #[error("Constant `{name}` indexes out of bounds")]
+    ConstIndexOutOfBounds
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Indexed here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Constant `{name}` can't be evaluated at compile time")]
+    NotConstant
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Only known at runtime")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Attribute `@{name}` doesn't apply to {target}")]
+    UnknownAttribute
+    {
+        name: EcoString,
+        target: &'static str,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+        #[help]
+        help: Option<String>,
+    },
+    #[error("Attribute `@{name}` expects {expected}")]
+    AttributeArguments
+    {
+        name: EcoString,
+        expected: &'static str,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+    },
+    #[error("Attribute `@{name}` conflicts with `@{other}`")]
+    ConflictingAttributes
+    {
+        name: EcoString,
+        other: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+        #[label("Along with this one")]
+        previous: Span,
+    },
+    #[error("Test `{name}` must take no arguments and return nothing")]
+    TestSignature
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        span: Span,
+    },
+    #[error("Test `{name}` can't be used outside of tests")]
+    #[diagnostic(help(
+        "Only `sundae test` builds tests, move what it needs into a function of its own"
+    ))]
+    TestUse
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Used here")]
+        span: Span,
+    },
+    #[error("{what} `{name}` can't choose its symbol, {reason}")]
+    ChosenSymbol
+    {
+        what: &'static str,
+        name: EcoString,
+        reason: &'static str,
+        module: EcoVec<EcoString>,
+        #[label("Declared here")]
+        span: Span,
+    },
+}
+
+impl TypeError
+{
+    #[inline]
+    pub fn module(&self) -> &EcoVec<EcoString>
+    {
+        match self
+        {
+            Self::Mismatch { module, .. }
+            | Self::Operands { module, .. }
+            | Self::Operator { module, .. }
+            | Self::UnaryOperator { module, .. }
+            | Self::InvalidType { module, .. }
+            | Self::LiteralOverflow { module, .. }
+            | Self::Arity { module, .. }
+            | Self::NotCallable { module, .. }
+            | Self::NoReceiver { module, .. }
+            | Self::Receiver { module, .. }
+            | Self::FunctionAsValue { module, .. }
+            | Self::Uninferred { module, .. }
+            | Self::Conflict { module, .. }
+            | Self::UninferredLocal { module, .. }
+            | Self::AmbiguousLocal { module, .. }
+            | Self::EmptyArray { module, .. }
+            | Self::ArrayLength { module, .. }
+            | Self::NotIndexable { module, .. }
+            | Self::Offset { module, .. }
+            | Self::NoField { module, .. }
+            | Self::Unsupported { module, .. }
+            | Self::Cast { module, .. }
+            | Self::AssertArguments { module, .. }
+            | Self::AssertMessage { module, .. }
+            | Self::Condition { module, .. }
+            | Self::AssignToImmutable { module, .. }
+            | Self::ClosureEscapes { module, .. }
+            | Self::Unassigned { module, .. }
+            | Self::AssignedTwice { module, .. }
+            | Self::MutBorrowOfImmutable { module, .. }
+            | Self::NotAssignable { module, .. }
+            | Self::Void { module, .. }
+            | Self::MissingReturnValue { module, .. }
+            | Self::ConstCycle { module, .. }
+            | Self::ValueCycle { module, .. }
+            | Self::ConstOverflow { module, .. }
+            | Self::ConstDivisionByZero { module, .. }
+            | Self::ConstIndexOutOfBounds { module, .. }
+            | Self::NotConstant { module, .. }
+            | Self::UnknownAttribute { module, .. }
+            | Self::AttributeArguments { module, .. }
+            | Self::ConflictingAttributes { module, .. }
+            | Self::TestSignature { module, .. }
+            | Self::TestUse { module, .. }
+            | Self::ChosenSymbol { module, .. } => module,
+        }
+    }
+}
+
+/// Diagnostics which don't stop compilation, collected on the [`Thir`]. `module` is as on [`TypeError`]
+#[derive(Error, Debug, Diagnostic, Clone, PartialEq)]
+pub enum TypeWarning
+{
+    #[error("Cast from `{from}` to `{to}` may change the value")]
+    #[diagnostic(severity(Warning))]
+    LossyCast
+    {
+        from: Type,
+        to: Type,
+        module: EcoVec<EcoString>,
+        #[label("Not every `{from}` fits in `{to}`")]
+        span: Span,
+    },
+    #[error("Use of deprecated function `{name}`")]
+    #[diagnostic(severity(Warning))]
+    Deprecated
+    {
+        name: EcoString,
+        module: EcoVec<EcoString>,
+        #[label("Called here")]
+        span: Span,
+        #[help]
+        note: Option<EcoString>,
+    },
+}
+
+impl TypeWarning
+{
+    #[inline]
+    pub fn module(&self) -> &EcoVec<EcoString>
+    {
+        match self
+        {
+            Self::LossyCast { module, .. } | Self::Deprecated { module, .. } => module,
+        }
+    }
+}
+
+/// Index of a function, or of an instance of a generic one, in [`Thir::functions`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+pub struct FunctionId(pub usize);
+
+/// Index of a local, a parameter or a capture in [`Thir::locals`]. Each instance of a generic function has locals of its own, as
+/// their types differ
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize)]
+pub struct LocalId(pub usize);
+
+/// The whole program, typed
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Thir
+{
+    /// As on the [`Hir`](compiler_hir::Hir), in dependency order
+    pub modules: Vec<compiler_hir::Module>,
+    /// Indexed by the same [`ItemId`]s as on the [`Hir`](compiler_hir::Hir)
+    pub items: Vec<Item>,
+    pub functions: Vec<Function>,
+    pub locals: Vec<Local>,
+    /// Found checking the program, once each
+    #[serde(skip)]
+    pub warnings: Vec<TypeWarning>,
+}
+
+impl Index<ItemId> for Thir
+{
+    type Output = Item;
+
+    #[inline]
+    fn index(&self, index: ItemId) -> &Self::Output
+    {
+        &self.items[index.0]
+    }
+}
+
+impl Index<FunctionId> for Thir
+{
+    type Output = Function;
+
+    #[inline]
+    fn index(&self, index: FunctionId) -> &Self::Output
+    {
+        &self.functions[index.0]
+    }
+}
+
+impl Index<LocalId> for Thir
+{
+    type Output = Local;
+
+    #[inline]
+    fn index(&self, index: LocalId) -> &Self::Output
+    {
+        &self.locals[index.0]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Item
+{
+    pub name: EcoString,
+    /// Path of the module it's declared in
+    pub module: EcoVec<EcoString>,
+    /// Of the name
+    pub span: Span,
+    pub attributes: Attributes,
+    pub kind: ItemKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum Inline
+{
+    /// `@inline`, left for LLVM to weigh
+    Hint,
+    /// `@inline(always)`
+    Always,
+    /// `@noinline`
+    Never,
+}
+
+/// What the attributes on an item ask for, once checked against the kind of item they're on
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Attributes
+{
+    pub inline: Option<Inline>,
+    /// `@cold`, for functions which are rarely called
+    pub cold: bool,
+    /// `@export`, keeping the item's own name as its symbol rather than its qualified one, so C may refer to it
+    pub export: bool,
+    /// `@link_name("symbol")`, the symbol the item is defined as or linked against
+    pub link_name: Option<EcoString>,
+    /// `@deprecated` or `@deprecated("note")`, warning on every call
+    pub deprecated: Option<Deprecation>,
+    /// `@test`, making the function a test, which only `sundae test` builds
+    pub test: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Deprecation
+{
+    pub note: Option<EcoString>,
+}
+
+impl Attributes
+{
+    /// Symbol the item `name` is defined as or linked against, if its attributes choose one
+    #[inline]
+    pub fn symbol(&self, name: &EcoString) -> Option<EcoString>
+    {
+        self.link_name
+            .clone()
+            .or_else(|| self.export.then(|| name.clone()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ItemKind
+{
+    Const
+    {
+        r#type: Type,
+        value: Box<Expression>,
+        /// What `value` evaluates to, at compile time
+        folded: Constant,
+    },
+    Function(FunctionId),
+    Extern(FunctionId),
+    /// Only its instances are generated, one for each set of generic arguments it's called with
+    Generic
+    {
+        instances: Vec<FunctionId>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Function
+{
+    pub item: ItemId,
+    /// Types of the generic parameters, in the order they're declared. Empty unless it's an instance
+    pub generics: Vec<Type>,
+    /// One for each argument, none for extern functions
+    pub parameters: EcoVec<LocalId>,
+    pub arguments: Vec<Type>,
+    /// `i32` for the root `main`, which returns 0 unless told otherwise
+    pub return_type: Type,
+    pub variadic: bool,
+    /// `None` for extern functions
+    pub body: Option<Block>,
+}
+
+impl Function
+{
+    /// Type of a pointer to this function
+    #[inline]
+    pub fn pointer_type(&self) -> Type
+    {
+        Type::Function {
+            arguments: self.arguments.clone(),
+            return_type: Box::new(self.return_type.clone()),
+        }
+    }
+}
+
+/// A local, a parameter, or a capture as seen from within its closure
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Local
+{
+    pub name: EcoString,
+    pub kind: BindingKind,
+    pub mutable: bool,
+    /// Of what it holds
+    pub r#type: Type,
+    /// Of the name where it's declared, or captured
+    pub span: Span,
+}
+
+impl Local
+{
+    /// Type it's read as. Locals and `mut` parameters live in a stack slot, so they're `&mut` to it, the rest are values. Captures
+    /// hold whatever they were captured as
+    #[inline]
+    pub fn place_type(&self) -> Type
+    {
+        match self.kind
+        {
+            BindingKind::Local => Type::MutRef(Box::new(self.r#type.clone())),
+            BindingKind::Parameter if self.mutable => Type::MutRef(Box::new(self.r#type.clone())),
+            _ => self.r#type.clone(),
+        }
+    }
+}
+
+pub type Block = EcoVec<Statement>;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Statement
+{
+    /// Of a value of the function's return type, or of whatever a function returning `()` is given
+    Return(Option<Expression>),
+    Expression(Expression),
+    /// `destination` is a `&mut` to what `source` is written to
+    Assign
+    {
+        destination: Expression,
+        source: Expression,
+    },
+    Local
+    {
+        local: LocalId,
+        init: Option<Expression>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Expression
+{
+    pub kind: ExpressionKind,
+    /// Of what it evaluates to. Places, as locals or indexed items, are references to them
+    pub r#type: Type,
+    pub span: Span,
+}
+
+/// A literal value, of its expression's type
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Literal
+{
+    /// Two's complement, truncated to the type's width. Runes are `i8`
+    Integer(u128),
+    Float(f64),
+    /// Contents, without quotes, as an array of `i8`
+    String(EcoString),
+}
+
+/// A value known at compile time, as constants evaluate to
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Constant
+{
+    /// Kept within the range of its type, so signed ones are negative rather than wrapped
+    Integer
+    {
+        value: i128,
+        width: u32,
+        signed: bool,
+    },
+    Float
+    {
+        value: f64, width: u32
+    },
+    /// Strings are arrays of `i8`, as at runtime
+    Array
+    {
+        scalar: Type, items: Vec<Constant>
+    },
+}
+
+impl fmt::Display for Constant
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Self::Integer { value, .. } => write!(f, "{value}"),
+            Self::Float { value, .. } => write!(f, "{value:?}"),
+            Self::Array { items, .. } =>
+            {
+                write!(f, "[")?;
+                items.iter().enumerate().try_for_each(|(i, item)| {
+                    if i > 0
+                    {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")
+                })?;
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+impl Constant
+{
+    pub fn r#type(&self) -> Type
+    {
+        match self
+        {
+            Self::Integer { width, signed, .. } => Type::Integer {
+                width: *width,
+                signed: *signed,
+            },
+            Self::Float { width, .. } => Type::Float(*width),
+            Self::Array { scalar, items } => Type::Array {
+                scalar: Box::new(scalar.clone()),
+                size: items.len() as u32,
+            },
+        }
+    }
+}
+
+/// What a call goes to. Both follow the C calling convention
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Callee
+{
+    /// A function known by name
+    Function(FunctionId),
+    /// A function pointer or a closure, only known once called
+    Pointer(Box<Expression>),
+}
+
+/// A local captured by a closure
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Capture
+{
+    /// Local of the enclosing function
+    pub local: LocalId,
+    /// What's stored in the closure's environment, `local` read, borrowed, or as is if it's by `&mut`
+    pub value: Expression,
+    /// As seen from within the closure, holding `value`
+    pub inner: LocalId,
+    pub kind: CaptureKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum ExpressionKind
+{
+    Literal(Literal),
+    Local(LocalId),
+    Constant(ItemId),
+    /// Pointer to a function
+    Function(FunctionId),
+    /// Both operands are values of the same type. Comparisons give a [`BOOL`], the rest a value of that type
+    Binary(Box<Expression>, Operator, Box<Expression>),
+    Negate(Box<Expression>),
+    /// `target as type`, between numbers
+    Cast(Box<Expression>),
+    Call
+    {
+        callee: Callee,
+        args: EcoVec<Expression>,
+    },
+    /// `assert(condition)`, or `assert(condition, "message")`
+    Assert
+    {
+        condition: Box<Expression>,
+        message: Option<EcoString>,
+    },
+    /// How many items an array or a slice, or a reference to either, holds
+    Len(Box<Expression>),
+    /// A reference to the item, writable if `target` is a `&mut` to an array
+    Index
+    {
+        target: Box<Expression>,
+        index: Box<Expression>,
+    },
+    Slice
+    {
+        target: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+    },
+    If
+    {
+        condition: Box<Expression>,
+        block: Block,
+        else_block: Option<Block>,
+    },
+    Array(EcoVec<Expression>),
+    Closure
+    {
+        captures: EcoVec<Capture>,
+        parameters: EcoVec<LocalId>,
+        body: Block,
+    },
+    /// Converts the value to the expression's type, by borrowing it, reading it from behind a reference, taking a `&mut` as a `&`
+    /// or an array as a slice
+    Coerce(Box<Expression>),
+}
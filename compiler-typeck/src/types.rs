@@ -0,0 +1,488 @@
+use std::{collections::HashMap, fmt};
+
+use compiler_parser::{Operator, Type as ParserType};
+use ecow::EcoString;
+use serde::Serialize;
+use thiserror::Error;
+
+// Unconstrained literals default to these
+pub const DEFAULT_INT: Type = Type::Integer {
+    width: 32,
+    signed: true,
+};
+pub const DEFAULT_FLOAT: Type = Type::Float(64);
+
+/// Lengths of arrays and slices, as `.len` gives them
+pub const LENGTH: Type = Type::Integer {
+    width: 64,
+    signed: false,
+};
+
+/// Comparisons give a single bit
+pub const BOOL: Type = Type::Integer {
+    width: 1,
+    signed: false,
+};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+pub enum Type
+{
+    Integer
+    {
+        width: u32,
+        signed: bool,
+    },
+    Float(u32),
+    Void,
+    Array
+    {
+        scalar: Box<Type>,
+        size: u32,
+    },
+    /// `[]T`, a pointer to the first item along with how many there are, as a `u64`. Borrowed from arrays, which coerce to it.
+    /// C takes it as a struct of both, which its calling convention passes as two arguments anyway
+    Slice(Box<Type>),
+    Ref(Box<Type>),
+    MutRef(Box<Type>),
+    Tuple(Vec<Type>),
+    /// Pointer to a function, as in `func(i32, i32) i32`. Called with the C convention, so they may be passed as callbacks to C
+    Function
+    {
+        arguments: Vec<Type>,
+        return_type: Box<Type>,
+    },
+    /// Closure, as in `func[](i32) i32`. A pointer to its code along with one to its captures, which the code takes first
+    Closure
+    {
+        arguments: Vec<Type>,
+        return_type: Box<Type>,
+    },
+}
+
+/// A type as written on source which doesn't name one
+#[derive(Error, Clone, Debug, PartialEq)]
+#[error("{0}")]
+pub struct InvalidType(pub String);
+
+impl Default for Type
+{
+    #[inline]
+    fn default() -> Self
+    {
+        Self::Void
+    }
+}
+
+impl fmt::Display for Type
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Type::Integer { width, signed } =>
+            {
+                write!(f, "{}{width}", if *signed { "i" } else { "u" })
+            }
+            Type::Float(width) => write!(f, "f{width}"),
+            Type::Void => write!(f, "()"),
+            Type::Array { box scalar, size } => write!(f, "[{size}]{scalar}"),
+            Type::Slice(box scalar) => write!(f, "[]{scalar}"),
+            Type::Ref(box v) => write!(f, "&{v}"),
+            Type::MutRef(box v) => write!(f, "&mut {v}"),
+            Type::Tuple(items) =>
+            {
+                write!(f, "(")?;
+                items.iter().enumerate().try_for_each(|(i, item)| {
+                    if i > 0
+                    {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")
+                })?;
+                write!(f, ")")
+            }
+            Type::Function {
+                arguments,
+                box return_type,
+            }
+            | Type::Closure {
+                arguments,
+                box return_type,
+            } =>
+            {
+                write!(f, "func")?;
+
+                if let Type::Closure { .. } = self
+                {
+                    write!(f, "[]")?;
+                }
+
+                write!(f, "(")?;
+                arguments.iter().enumerate().try_for_each(|(i, argument)| {
+                    if i > 0
+                    {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{argument}")
+                })?;
+                write!(f, ")")?;
+
+                if *return_type != Type::Void
+                {
+                    write!(f, " {return_type}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl TryFrom<ParserType> for Type
+{
+    type Error = InvalidType;
+
+    #[inline]
+    fn try_from(value: ParserType) -> Result<Self, Self::Error>
+    {
+        Self::resolve(value, &HashMap::new())
+    }
+}
+
+impl Type
+{
+    /// Converts a type as written on source, with `generics` standing in for the generic parameters in scope
+    pub fn resolve(
+        value: ParserType,
+        generics: &HashMap<EcoString, Type>,
+    ) -> Result<Self, InvalidType>
+    {
+        let invalid = |message: String| Err(InvalidType(message));
+
+        if let [a, b, c @ ..] = value.0.as_slice()
+            && let ["&", "mut"] = [a.as_str(), b.as_str()]
+        {
+            Ok(Self::MutRef(Box::new(Self::resolve(
+                ParserType(c.to_vec()),
+                generics,
+            )?)))
+        }
+        else if let [a, b @ ..] = value.0.as_slice()
+            && let ["&"] = [a.as_str()]
+        {
+            Ok(Self::Ref(Box::new(Self::resolve(
+                ParserType(b.to_vec()),
+                generics,
+            )?)))
+        }
+        else if let [a, rest @ ..] = value.0.as_slice()
+            && a == "func"
+        {
+            // Closures are told apart by their empty capture list
+            let (closure, rest) = match rest
+            {
+                [a, b, rest @ ..] if a == "[" && b == "]" => (true, rest),
+                rest => (false, rest),
+            };
+
+            let Some((_, rest)) = rest.split_first().filter(|(open, _)| *open == "(")
+            else
+            {
+                return invalid(
+                    "Function pointer types are written as `func(arguments) type`, closures as `func[](arguments) type`"
+                        .into(),
+                );
+            };
+
+            // Split the arguments on the commas outside of any nested parentheses
+            let mut arguments = vec![Vec::new()];
+            let mut depth = 0usize;
+            let mut end = None;
+
+            for (i, token) in rest.iter().enumerate()
+            {
+                match token.as_str()
+                {
+                    ")" if depth == 0 =>
+                    {
+                        end = Some(i);
+                        break;
+                    }
+                    "," if depth == 0 => arguments.push(Vec::new()),
+                    t =>
+                    {
+                        match t
+                        {
+                            "(" => depth += 1,
+                            ")" => depth -= 1,
+                            _ => (),
+                        }
+
+                        arguments.last_mut().unwrap().push(token.clone());
+                    }
+                }
+            }
+
+            let Some(end) = end
+            else
+            {
+                return invalid("Unclosed function pointer type".into());
+            };
+
+            if arguments.last().is_some_and(Vec::is_empty)
+            {
+                arguments.pop();
+            }
+
+            let arguments = arguments
+                .into_iter()
+                .map(|a| Self::resolve(ParserType(a), generics))
+                .collect::<Result<_, _>>()?;
+
+            let return_type = &rest[end + 1..];
+            let return_type = Box::new(
+                if return_type.is_empty()
+                {
+                    Self::Void
+                }
+                else
+                {
+                    Self::resolve(ParserType(return_type.to_vec()), generics)?
+                },
+            );
+
+            Ok(
+                if closure
+                {
+                    Self::Closure {
+                        arguments,
+                        return_type,
+                    }
+                }
+                else
+                {
+                    Self::Function {
+                        arguments,
+                        return_type,
+                    }
+                },
+            )
+        }
+        else if let [a, .., c] = value.0.as_slice()
+            && let ["(", ")"] = [a.as_str(), c.as_str()]
+        {
+            invalid("Tuple types aren't supported yet".into())
+        }
+        else if let [open, close, scalar @ ..] = value.0.as_slice()
+            && let ["[", "]"] = [open.as_str(), close.as_str()]
+        {
+            Ok(Self::Slice(Box::new(Self::resolve(
+                ParserType(scalar.to_vec()),
+                generics,
+            )?)))
+        }
+        else if let [open, size, close, scalar @ ..] = value.0.as_slice()
+            && let ["[", "]"] = [open.as_str(), close.as_str()]
+        {
+            Ok(Self::Array {
+                scalar: Box::new(Self::resolve(ParserType(scalar.to_vec()), generics)?),
+                size: size
+                    .parse()
+                    .map_err(|_| InvalidType(format!("Array size `{size}` isn't a number")))?,
+            })
+        }
+        else
+        {
+            // TODO assume for now, modify when we have structs (and fields, thus)
+            let Some(r#type) = value.0.first()
+            else
+            {
+                return invalid("Expected a type".into());
+            };
+
+            if let Some(r#type) = generics.get(r#type)
+            {
+                return Ok(r#type.clone());
+            }
+
+            if let Some((signed, bits)) = r#type
+                .strip_prefix('i')
+                .map(|bits| (true, bits))
+                .or_else(|| r#type.strip_prefix('u').map(|bits| (false, bits)))
+            {
+                // LLVM takes wider ones, which nothing else here handles
+                let width = bits
+                    .parse()
+                    .ok()
+                    .filter(|w| (1..=128).contains(w))
+                    .ok_or_else(|| {
+                        InvalidType(format!("Integers are 1 to 128 bits wide, got `{type}`"))
+                    })?;
+
+                Ok(Self::Integer { width, signed })
+            }
+            else
+            {
+                match r#type.as_str()
+                {
+                    "f16" => Ok(Self::Float(16)),
+                    "f32" => Ok(Self::Float(32)),
+                    "f64" => Ok(Self::Float(64)),
+                    "f128" => Ok(Self::Float(128)),
+                    "void" => Ok(Self::Void),
+                    // Bytes of UTF-8 text, as rune literals are
+                    "rune" => Ok(Self::Integer {
+                        width: 8,
+                        signed: true,
+                    }),
+                    _ => invalid(format!("Unknown type `{type}`")),
+                }
+            }
+        }
+    }
+
+    /// What a reference points to, anything else is taken as it is
+    #[inline]
+    pub fn referent(&self) -> &Type
+    {
+        match self
+        {
+            Type::Ref(box r#type) | Type::MutRef(box r#type) => r#type,
+            r#type => r#type,
+        }
+    }
+
+    #[inline]
+    pub fn is_numeric(&self) -> bool
+    {
+        matches!(self, Type::Integer { .. } | Type::Float(_))
+    }
+}
+
+/// How a name is bound, for diagnostics about writing to it
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+pub enum BindingKind
+{
+    Local,
+    Parameter,
+    Constant,
+    /// A local of the enclosing function, as seen from a closure capturing it
+    Capture,
+}
+
+impl fmt::Display for BindingKind
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(
+            f,
+            "{}",
+            match self
+            {
+                Self::Local => "local",
+                Self::Parameter => "parameter",
+                Self::Constant => "constant",
+                Self::Capture => "capture",
+            }
+        )
+    }
+}
+
+/// Parses an integer literal as lexed, `0x`, `0o` and `0b` prefixes included. `None` if it doesn't fit in a `u128`
+pub fn parse_int_literal(value: &str) -> Option<u128>
+{
+    let (digits, radix) = match value.get(..2)
+    {
+        Some("0x") => (&value[2..], 16),
+        Some("0o") => (&value[2..], 8),
+        Some("0b") => (&value[2..], 2),
+        _ => (value, 10),
+    };
+
+    u128::from_str_radix(digits, radix).ok()
+}
+
+/// Whether `op` compares its operands, giving a [`BOOL`] rather than a value of their type
+#[inline]
+pub fn is_comparison(op: Operator) -> bool
+{
+    matches!(
+        op,
+        Operator::Lt | Operator::Gt | Operator::Le | Operator::Ge | Operator::EqEq | Operator::Neq
+    )
+}
+
+/// Bits of precision of the floats of `width` bits, the implicit one included
+fn mantissa(width: u32) -> u32
+{
+    match width
+    {
+        16 => 11,
+        32 => 24,
+        64 => 53,
+        _ => 113,
+    }
+}
+
+/// Whether some values of the number type `from` change once cast to `to`, by wrapping around, losing their sign or their
+/// fractional part, or being rounded
+pub fn is_lossy(from: &Type, to: &Type) -> bool
+{
+    match (from, to)
+    {
+        (Type::Integer { signed: true, .. }, Type::Integer { signed: false, .. }) => true,
+        // Unsigned values need one more bit to stay positive as signed ones
+        (
+            &Type::Integer {
+                width: from,
+                signed: from_signed,
+            },
+            &Type::Integer {
+                width: to,
+                signed: to_signed,
+            },
+        ) => to < from + u32::from(!from_signed && to_signed),
+        (&Type::Integer { width, signed }, &Type::Float(to)) =>
+        {
+            width - u32::from(signed) > mantissa(to)
+        }
+        (Type::Float(_), Type::Integer { .. }) => true,
+        (Type::Float(from), Type::Float(to)) => to < from,
+        _ => false,
+    }
+}
+
+/// Hint for a mismatch between the types `from` and `to`, if a cast would convert one to the other
+pub fn cast_hint(from: &Type, to: &Type) -> String
+{
+    if from.is_numeric() && to.is_numeric()
+    {
+        format!(", convert it with `as {to}`")
+    }
+    else
+    {
+        String::new()
+    }
+}
+
+/// Hint for a binary operator applied to `l` and `r`, casting whichever side may be without changing its value
+pub fn operands_hint(l: &Type, r: &Type) -> String
+{
+    if !l.is_numeric() || !r.is_numeric()
+    {
+        String::new()
+    }
+    else if !is_lossy(l, r)
+    {
+        format!(", convert the left side with `as {r}`")
+    }
+    else if !is_lossy(r, l)
+    {
+        format!(", convert the right side with `as {l}`")
+    }
+    else
+    {
+        ", convert either side with `as`".into()
+    }
+}
@@ -0,0 +1,382 @@
+//! What a test expects, from comments in its source. `//@ directive` lines configure how it's run, `//~ KIND message`
+//! annotations expect a diagnostic of `KIND` (`ERROR` or `WARN`) whose text contains `message`:
+//!
+//! - `//~ ERROR message` on the line it points at,
+//! - `//~^ ERROR message` on the line above, one more `^` for each line further up,
+//! - `//~? ERROR message` anywhere, or nowhere for those without a label.
+
+use std::fmt;
+
+use anyhow::{Result, bail};
+
+use crate::diagnostic::Diagnostic;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind
+{
+    Error,
+    Warn,
+}
+
+impl fmt::Display for Kind
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        f.write_str(match self
+        {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+        })
+    }
+}
+
+/// A `//~` annotation
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Expected
+{
+    pub kind: Kind,
+    /// 1-based line the diagnostic points at, `None` for `//~?`
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// What the `//@` directives ask for
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Directives
+{
+    /// `//@ check-pass`, only compile the program rather than running it too
+    pub check_pass: bool,
+    /// `//@ exit-code: N`, what running the program exits with, 0 otherwise
+    pub exit_code: i32,
+    /// Unless `//@ ignore-interp`, the interpreter must print the same as the program when it exits with 0
+    pub interp: bool,
+}
+
+impl Default for Directives
+{
+    fn default() -> Self
+    {
+        Self {
+            check_pass: false,
+            exit_code: 0,
+            interp: true,
+        }
+    }
+}
+
+/// Parses the directives and annotations of `source`
+pub fn parse(source: &str) -> Result<(Directives, Vec<Expected>)>
+{
+    let mut directives = Directives::default();
+    let mut expected = Vec::new();
+
+    for (i, line) in source.lines().enumerate()
+    {
+        let number = i + 1;
+
+        if let Some(directive) = line.trim_start().strip_prefix("//@")
+        {
+            match directive.trim().split_once(':')
+            {
+                None if directive.trim() == "check-pass" => directives.check_pass = true,
+                None if directive.trim() == "ignore-interp" => directives.interp = false,
+                Some(("exit-code", code)) => match code.trim().parse()
+                {
+                    Ok(code) => directives.exit_code = code,
+                    Err(_) => bail!(
+                        "{number}: expected a number for `exit-code`, got `{}`",
+                        code.trim()
+                    ),
+                },
+                _ => bail!(
+                    "{number}: unknown directive `{}`, expected `check-pass`, `exit-code: N` or `ignore-interp`",
+                    directive.trim()
+                ),
+            }
+
+            continue;
+        }
+
+        let Some((_, annotation)) = line.split_once("//~")
+        else
+        {
+            continue;
+        };
+
+        let (line, rest) = if let Some(rest) = annotation.strip_prefix('?')
+        {
+            (None, rest)
+        }
+        else
+        {
+            let rest = annotation.trim_start_matches('^');
+            let up = annotation.len() - rest.len();
+
+            if up >= number
+            {
+                bail!("{number}: annotation points {up} lines up, before the start of the file");
+            }
+
+            (Some(number - up), rest)
+        };
+
+        let rest = rest.trim();
+        let (kind, message) = rest.split_once(' ').unwrap_or((rest, ""));
+
+        let kind = match kind
+        {
+            "ERROR" => Kind::Error,
+            "WARN" => Kind::Warn,
+            _ => bail!("{number}: expected `ERROR` or `WARN` in annotation, got `{kind}`"),
+        };
+
+        expected.push(Expected {
+            kind,
+            line,
+            message: message.trim().to_owned(),
+        });
+    }
+
+    Ok((directives, expected))
+}
+
+/// Pairs up the `expected` diagnostics with those `reported`, returning those of each left over. Located annotations are
+/// matched first, so a `//~?` doesn't take a diagnostic one of them was meant for
+pub fn matches<'a>(
+    expected: &'a [Expected],
+    reported: &'a [Diagnostic],
+) -> (Vec<&'a Expected>, Vec<&'a Diagnostic>)
+{
+    let mut unmatched = reported.iter().collect::<Vec<_>>();
+    let mut missing = Vec::new();
+
+    let (located, anywhere): (Vec<_>, Vec<_>) = expected.iter().partition(|e| e.line.is_some());
+
+    for expected in located.into_iter().chain(anywhere)
+    {
+        let position = unmatched.iter().position(|d| {
+            d.kind == expected.kind
+                && expected.line.is_none_or(|line| d.line == Some(line))
+                && d.contains(&expected.message)
+        });
+
+        match position
+        {
+            Some(i) =>
+            {
+                unmatched.remove(i);
+            }
+            None => missing.push(expected),
+        }
+    }
+
+    (missing, unmatched)
+}
+
+/// Sets the `exit-code` directive of `source` to `code`, which is left out when 0
+pub fn with_exit_code(source: &str, code: i32) -> String
+{
+    let mut lines = source
+        .lines()
+        .filter(|l| {
+            !l.trim_start()
+                .strip_prefix("//@")
+                .is_some_and(|d| d.trim().starts_with("exit-code"))
+        })
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+
+    if code != 0
+    {
+        // After the other directives, if any
+        let at = lines
+            .iter()
+            .take_while(|l| l.trim_start().starts_with("//@"))
+            .count();
+
+        lines.insert(at, format!("//@ exit-code: {code}"));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// Replaces the annotations of `source` with those of `reported`, each on the line it points at. Further ones on the same
+/// line go on lines of their own below it, pointing up
+pub fn annotate(source: &str, reported: &[Diagnostic]) -> String
+{
+    // Lines which only held annotations are gone, so the rest move up
+    let mut kept = Vec::new();
+    let mut moved = Vec::new();
+
+    for line in source.lines()
+    {
+        match line.split_once("//~")
+        {
+            Some((code, _)) if code.trim().is_empty() => moved.push(None),
+            Some((code, _)) =>
+            {
+                moved.push(Some(kept.len()));
+                kept.push(code.trim_end().to_owned());
+            }
+            None =>
+            {
+                moved.push(Some(kept.len()));
+                kept.push(line.to_owned());
+            }
+        }
+    }
+
+    let mut on = vec![Vec::new(); kept.len()];
+    let mut anywhere = Vec::new();
+
+    for diagnostic in reported
+    {
+        let line = diagnostic
+            .line
+            .and_then(|l| moved.get(l - 1).copied().flatten());
+
+        let annotation = format!("{} {}", diagnostic.kind, diagnostic.summary());
+
+        match line
+        {
+            Some(line) => on[line].push(annotation),
+            None => anywhere.push(annotation),
+        }
+    }
+
+    let mut out = String::new();
+
+    for (line, annotations) in kept.iter().zip(on)
+    {
+        out.push_str(line);
+
+        let indent = &line[..line.len() - line.trim_start().len()];
+
+        for (i, annotation) in annotations.iter().enumerate()
+        {
+            if i == 0
+            {
+                out.push_str(&format!(" //~ {annotation}"));
+            }
+            else
+            {
+                out.push_str(&format!("\n{indent}//~{} {annotation}", "^".repeat(i)));
+            }
+        }
+
+        out.push('\n');
+    }
+
+    for annotation in anywhere
+    {
+        out.push_str(&format!("//~? {annotation}\n"));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn diagnostic(kind: Kind, line: Option<usize>, messages: &[&str]) -> Diagnostic
+    {
+        Diagnostic {
+            kind,
+            line,
+            messages: messages.iter().map(|&m| m.into()).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_annotations_and_directives()
+    {
+        let (directives, expected) = parse(
+            "//@ exit-code: 101\n//@ ignore-interp\nfunc main() {\n    a = 1 //~ ERROR Can't assign\n    //~^ WARN unused\n}\n//~? ERROR failed\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            directives,
+            Directives {
+                check_pass: false,
+                exit_code: 101,
+                interp: false,
+            }
+        );
+        assert_eq!(
+            expected,
+            vec![
+                Expected {
+                    kind: Kind::Error,
+                    line: Some(4),
+                    message: "Can't assign".into(),
+                },
+                Expected {
+                    kind: Kind::Warn,
+                    line: Some(4),
+                    message: "unused".into(),
+                },
+                Expected {
+                    kind: Kind::Error,
+                    line: None,
+                    message: "failed".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_directives()
+    {
+        assert_eq!(
+            parse("//@ run-fail\n").map_err(|e| e.to_string()),
+            Err("1: unknown directive `run-fail`, expected `check-pass`, `exit-code: N` or `ignore-interp`".into())
+        );
+        assert_eq!(
+            parse("//~^ ERROR up\n").map_err(|e| e.to_string()),
+            Err("1: annotation points 1 lines up, before the start of the file".into())
+        );
+    }
+
+    #[test]
+    fn matches_located_annotations_first()
+    {
+        let expected = parse("a //~ ERROR x\n//~? ERROR x\n").unwrap().1;
+        let reported = [
+            diagnostic(Kind::Error, None, &["x"]),
+            diagnostic(Kind::Error, Some(1), &["failed", "x y"]),
+            diagnostic(Kind::Warn, Some(1), &["x"]),
+        ];
+
+        let (missing, unmatched) = matches(&expected, &reported);
+
+        assert_eq!(missing, Vec::<&Expected>::new());
+        assert_eq!(unmatched, vec![&reported[2]]);
+    }
+
+    #[test]
+    fn rewrites_annotations()
+    {
+        let source = "func main() {\n    //~? ERROR old\n    a = 1 //~ ERROR old\n}\n";
+
+        assert_eq!(
+            annotate(
+                source,
+                &[
+                    diagnostic(Kind::Error, Some(3), &["failed", "first"]),
+                    diagnostic(Kind::Warn, Some(3), &["second\nmore"]),
+                    diagnostic(Kind::Error, None, &["unlocated"]),
+                ]
+            ),
+            "func main() {\n    a = 1 //~ ERROR first\n    //~^ WARN second\n}\n//~? ERROR unlocated\n"
+        );
+        assert_eq!(
+            with_exit_code("//@ check-pass\n//@ exit-code: 1\nfunc main() {}\n", 101),
+            "//@ check-pass\n//@ exit-code: 101\nfunc main() {}\n"
+        );
+    }
+}
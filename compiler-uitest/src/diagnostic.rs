@@ -0,0 +1,159 @@
+//! Diagnostics the driver reported, read back from its `--error-format json` output
+
+use serde::Deserialize;
+
+use crate::annotation::Kind;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic
+{
+    pub kind: Kind,
+    /// 1-based line its first label points at in the test, `None` if it has none or points into another file
+    pub line: Option<usize>,
+    /// Its message, followed by those of what caused it, most specific last
+    pub messages: Vec<String>,
+}
+
+impl Diagnostic
+{
+    /// Whether any of its messages contains `text`
+    pub fn contains(&self, text: &str) -> bool
+    {
+        self.messages.iter().any(|m| m.contains(text))
+    }
+
+    /// First line of its most specific message, as annotations quote it
+    pub fn summary(&self) -> &str
+    {
+        self.messages
+            .last()
+            .and_then(|m| m.lines().next())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize)]
+struct Report
+{
+    message: String,
+    severity: String,
+    #[serde(default)]
+    causes: Vec<String>,
+    #[serde(default)]
+    filename: String,
+    #[serde(default)]
+    labels: Vec<Label>,
+}
+
+#[derive(Deserialize)]
+struct Label
+{
+    span: Offset,
+}
+
+#[derive(Deserialize)]
+struct Offset
+{
+    offset: usize,
+}
+
+/// Reads the diagnostics out of the stderr of the driver, where `file` named the test whose source is `source`. Lines which
+/// aren't JSON, as from a crash, make up one more error without a label
+pub fn parse(stderr: &str, file: &str, source: &str) -> Vec<Diagnostic>
+{
+    let mut diagnostics = Vec::new();
+    let mut other = Vec::new();
+
+    for line in stderr.lines().filter(|l| !l.trim().is_empty())
+    {
+        let Ok(report) = serde_json::from_str::<Report>(line)
+        else
+        {
+            other.push(line);
+            continue;
+        };
+
+        let kind = match report.severity.as_str()
+        {
+            "warning" | "advice" => Kind::Warn,
+            _ => Kind::Error,
+        };
+
+        let line = report
+            .labels
+            .first()
+            .filter(|_| report.filename == file)
+            .map(|l| line_of(source, l.span.offset));
+
+        diagnostics.push(Diagnostic {
+            kind,
+            line,
+            messages: [report.message].into_iter().chain(report.causes).collect(),
+        });
+    }
+
+    if !other.is_empty()
+    {
+        diagnostics.push(Diagnostic {
+            kind: Kind::Error,
+            line: None,
+            messages: vec![other.join("\n")],
+        });
+    }
+
+    diagnostics
+}
+
+/// 1-based line of the byte `offset` of `source`
+fn line_of(source: &str, offset: usize) -> usize
+{
+    source.as_bytes()[..offset.min(source.len())]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+        + 1
+}
+
+#[cfg(test)]
+mod tests
+{
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn reads_reports()
+    {
+        let stderr = concat!(
+            r#"{"message": "Cast from `i64` to `i32` may change the value","severity": "warning","causes": [],"filename": "a.su","labels": [{"label": "Not every `i64` fits in `i32`","span": {"offset": 14,"length": 6}}],"related": []}"#,
+            "\n",
+            r#"{"message": "Code generator failed","severity": "error","causes": ["Can't assign to immutable local `a`"],"filename": "b.su","labels": [{"span": {"offset": 0,"length": 1}}],"related": []}"#,
+            "\n",
+            "thread 'main' panicked\n",
+        );
+
+        assert_eq!(
+            parse(stderr, "a.su", "func main() {\n    a as i32\n}\n"),
+            vec![
+                Diagnostic {
+                    kind: Kind::Warn,
+                    line: Some(2),
+                    messages: vec!["Cast from `i64` to `i32` may change the value".into()],
+                },
+                Diagnostic {
+                    kind: Kind::Error,
+                    line: None,
+                    messages: vec![
+                        "Code generator failed".into(),
+                        "Can't assign to immutable local `a`".into()
+                    ],
+                },
+                Diagnostic {
+                    kind: Kind::Error,
+                    line: None,
+                    messages: vec!["thread 'main' panicked".into()],
+                },
+            ]
+        );
+    }
+}
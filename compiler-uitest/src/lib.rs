@@ -0,0 +1,387 @@
+//! UI test harness, in the style of rustc's compiletest. Every `.su` file under the tests directory is compiled with the driver
+//! and, unless it's expected to fail, run, both as a binary and interpreted. Then its diagnostics are checked against the
+//! `//~` annotations in it, and its output against golden files next to it: `<test>.stdout` and `<test>.stderr` for what the
+//! binary printed, either being left out when empty. See [`annotation`] for the annotations and the `//@` directives.
+//!
+//! Blessing rewrites the golden files, the annotations and the `exit-code` directive of failing tests to what they got
+//! instead. Directories named `auxiliary` hold modules the tests import rather than tests of their own.
+//!
+//! Programs link with plain `cc` against a small C runtime, [`RUNTIME`], rather than the Rust support library, so nothing
+//! needs building with cargo nor `mold`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    time::Instant,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::annotation::Kind;
+
+pub mod annotation;
+pub mod diagnostic;
+
+/// C source of `putd` and `prints`. `puts` is libc's own, which prints the same
+pub const RUNTIME: &str = include_str!("runtime.c");
+
+pub struct Config
+{
+    /// The driver binary
+    pub sundae: PathBuf,
+    /// Directory the tests are under
+    pub root: PathBuf,
+    /// Where binaries are built, cleared beforehand
+    pub build: PathBuf,
+    /// Only run the tests whose path relative to [`Config::root`] contains this
+    pub filter: Option<String>,
+    /// Rewrite the expectations of failing tests to what they got
+    pub bless: bool,
+}
+
+impl Config
+{
+    /// Takes `--bless` and a filter from the command line, ignoring the flags cargo passes to test harnesses. `BLESS=1` blesses
+    /// too
+    pub fn from_args(sundae: PathBuf, root: PathBuf, build: PathBuf) -> Self
+    {
+        let mut bless = std::env::var_os("BLESS").is_some_and(|b| b == "1");
+        let mut filter = None;
+
+        for arg in std::env::args().skip(1)
+        {
+            match arg.as_str()
+            {
+                "--bless" => bless = true,
+                _ if arg.starts_with('-') => (),
+                _ => filter = Some(arg),
+            }
+        }
+
+        Self {
+            sundae,
+            root,
+            build,
+            filter,
+            bless,
+        }
+    }
+}
+
+/// What a binary or the interpreter did
+struct Run
+{
+    code: i32,
+    stdout: String,
+    stderr: String,
+}
+
+fn run(command: &mut process::Command) -> Result<Run>
+{
+    let output = command
+        .output()
+        .with_context(|| format!("Couldn't run `{command:?}`"))?;
+
+    Ok(Run {
+        // Killed by a signal otherwise
+        code: output.status.code().unwrap_or(-1),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+/// Collects the tests under `directory` into `tests`, skipping `auxiliary` directories
+fn collect(directory: &Path, tests: &mut Vec<PathBuf>) -> Result<()>
+{
+    let mut entries = fs::read_dir(directory)
+        .with_context(|| format!("Couldn't read directory `{}`", directory.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    entries.sort();
+
+    for path in entries
+    {
+        if path.is_dir()
+        {
+            if path.file_name().is_none_or(|n| n != "auxiliary")
+            {
+                collect(&path, tests)?;
+            }
+        }
+        else if path.extension().is_some_and(|e| e == "su")
+        {
+            tests.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compiles [`RUNTIME`] into `libui.a` under `build`
+fn build_runtime(build: &Path) -> Result<()>
+{
+    let source = build.join("runtime.c");
+    let object = build.join("runtime.o");
+
+    fs::write(&source, RUNTIME)?;
+
+    let mut compile = process::Command::new("cc");
+    compile.arg("-c").arg(&source).arg("-o").arg(&object);
+
+    let mut archive = process::Command::new("ar");
+    archive.arg("rcs").arg(build.join("libui.a")).arg(&object);
+
+    for step in [&mut compile, &mut archive]
+    {
+        let Run { code, stderr, .. } = run(step)?;
+
+        if code != 0
+        {
+            bail!("Couldn't build the runtime:\n{stderr}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares `got` to the golden file at `path`, which is missing when empty. Blessing writes `got` to it instead
+fn golden(path: &Path, got: &str, bless: bool, failures: &mut Vec<String>) -> Result<()>
+{
+    let expected = fs::read_to_string(path).unwrap_or_default();
+
+    if got == expected
+    {
+        return Ok(());
+    }
+
+    if bless
+    {
+        if got.is_empty()
+        {
+            fs::remove_file(path)?;
+        }
+        else
+        {
+            fs::write(path, got)?;
+        }
+    }
+    else
+    {
+        failures.push(format!(
+            "`{}` differs\n--- expected\n{expected}--- got\n{got}",
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Runs the test at `relative` under [`Config::root`], returning why it failed if it did
+fn test(config: &Config, relative: &Path) -> Result<Vec<String>>
+{
+    let path = config.root.join(relative);
+    let source = fs::read_to_string(&path)?;
+    let (directives, expected) = annotation::parse(&source)?;
+
+    let binary = config.build.join(relative).with_extension("");
+
+    let compiled = run(process::Command::new(&config.sundae)
+        .current_dir(&config.root)
+        .arg(relative)
+        .args(["-O", "0", "--error-format", "json", "-C", "linker=default"])
+        .arg("-L")
+        .arg(&config.build)
+        .args(["-l", "ui", "-o"])
+        .arg(&binary))?;
+
+    let reported = diagnostic::parse(&compiled.stderr, &relative.display().to_string(), &source);
+
+    let mut failures = Vec::new();
+    let mut source = source;
+
+    let (missing, unmatched) = annotation::matches(&expected, &reported);
+
+    if !missing.is_empty() || !unmatched.is_empty()
+    {
+        if config.bless
+        {
+            source = annotation::annotate(&source, &reported);
+        }
+        else
+        {
+            failures.extend(missing.into_iter().map(|e| {
+                let at = e.line.map_or("anywhere".into(), |l| format!("on line {l}"));
+
+                format!(
+                    "expected {} {at} containing `{}`, got none",
+                    e.kind, e.message
+                )
+            }));
+            failures.extend(unmatched.into_iter().map(|d| {
+                let at = d
+                    .line
+                    .map_or("without a line".into(), |l| format!("on line {l}"));
+
+                format!("unexpected {} {at}: {}", d.kind, d.messages.join(": "))
+            }));
+        }
+    }
+
+    let should_compile = !expected.iter().any(|e| e.kind == Kind::Error);
+
+    if should_compile && compiled.code != 0 && !config.bless
+    {
+        failures.push(format!(
+            "expected to compile, exited with {}",
+            compiled.code
+        ));
+    }
+    else if !should_compile && compiled.code == 0 && !config.bless
+    {
+        failures.push("expected to fail to compile, but it compiled".into());
+    }
+
+    if compiled.code == 0 && !directives.check_pass
+    {
+        let got = run(&mut process::Command::new(&binary))?;
+
+        if got.code != directives.exit_code
+        {
+            if config.bless
+            {
+                source = annotation::with_exit_code(&source, got.code);
+            }
+            else
+            {
+                failures.push(format!(
+                    "expected to exit with {}, exited with {}",
+                    directives.exit_code, got.code
+                ));
+            }
+        }
+
+        golden(
+            &path.with_extension("stdout"),
+            &got.stdout,
+            config.bless,
+            &mut failures,
+        )?;
+        golden(
+            &path.with_extension("stderr"),
+            &got.stderr,
+            config.bless,
+            &mut failures,
+        )?;
+
+        // Panics report differently when interpreted, so only successful runs are compared
+        if directives.interp && got.code == 0
+        {
+            let interpreted = run(process::Command::new(&config.sundae)
+                .current_dir(&config.root)
+                .args(["run", "--interp"])
+                .arg(relative))?;
+
+            if interpreted.code != 0
+            {
+                failures.push(format!(
+                    "interpreter exited with {}\n{}",
+                    interpreted.code, interpreted.stderr
+                ));
+            }
+            else if interpreted.stdout != got.stdout
+            {
+                failures.push(format!(
+                    "interpreter printed otherwise than the binary\n--- binary\n{}--- interpreter\n{}",
+                    got.stdout, interpreted.stdout
+                ));
+            }
+        }
+    }
+
+    if config.bless
+    {
+        fs::write(&path, source)?;
+    }
+
+    Ok(failures)
+}
+
+/// Runs every test selected by `config`, printing how each went, and fails if any did
+pub fn run_tests(config: &Config) -> Result<ExitCode>
+{
+    let mut tests = Vec::new();
+    collect(&config.root, &mut tests)?;
+
+    let tests = tests
+        .iter()
+        .map(|t| t.strip_prefix(&config.root).unwrap_or(t))
+        .filter(|t| {
+            config
+                .filter
+                .as_ref()
+                .is_none_or(|f| t.to_string_lossy().contains(f.as_str()))
+        })
+        .collect::<Vec<_>>();
+
+    let _ = fs::remove_dir_all(&config.build);
+    fs::create_dir_all(&config.build)?;
+    build_runtime(&config.build)?;
+
+    println!("running {} ui tests", tests.len());
+
+    let start = Instant::now();
+    let mut failed = Vec::new();
+
+    for relative in tests.iter()
+    {
+        let test_start = Instant::now();
+        let failures =
+            test(config, relative).unwrap_or_else(|error| vec![format!("couldn't run: {error:#}")]);
+        let elapsed = test_start.elapsed();
+
+        if failures.is_empty()
+        {
+            println!("test {} ... ok ({elapsed:.2?})", relative.display());
+        }
+        else
+        {
+            println!("test {} ... FAILED ({elapsed:.2?})", relative.display());
+            failed.push((relative, failures));
+        }
+    }
+
+    for (relative, failures) in &failed
+    {
+        println!("\n---- {} ----", relative.display());
+
+        for failure in failures
+        {
+            println!("{failure}");
+        }
+    }
+
+    println!(
+        "\n{} passed, {} failed in {:.2?}",
+        tests.len() - failed.len(),
+        failed.len(),
+        start.elapsed()
+    );
+
+    if !failed.is_empty() && !config.bless
+    {
+        println!("\nrerun with `--bless` to take what they got as expected");
+    }
+
+    Ok(
+        if failed.is_empty()
+        {
+            ExitCode::SUCCESS
+        }
+        else
+        {
+            ExitCode::FAILURE
+        },
+    )
+}
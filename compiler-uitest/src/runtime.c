@@ -0,0 +1,20 @@
+// What the tests call out to, in place of the Rust support library in `library`
+#include <stdint.h>
+#include <stdio.h>
+
+void putd(int32_t d)
+{
+    printf("%d\n", d);
+}
+
+// A `[]T`, as it's passed to C
+struct slice
+{
+    const char *items;
+    uint64_t len;
+};
+
+void prints(struct slice s)
+{
+    printf("%.*s\n", (int)s.len, s.items);
+}
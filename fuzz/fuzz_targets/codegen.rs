@@ -0,0 +1,57 @@
+#![no_main]
+
+use compiler_codegen_llvm::Settings;
+use compiler_parser::Module;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    // Like the driver, only what lexed cleanly is parsed
+    let Ok(tokens) = compiler_parser::lex(source)
+    else
+    {
+        return;
+    };
+
+    let Ok(ast) = compiler_parser::parse(source, tokens.into_iter())
+    else
+    {
+        return;
+    };
+
+    // The root module, as the file the driver is given
+    let module = Module {
+        path: Default::default(),
+        file: "fuzz.su".into(),
+        source: source.into(),
+        ast,
+    };
+
+    // As in the driver, only programs whose names resolve and which type check get to the code generator
+    let Ok(hir) = compiler_hir::resolve(std::slice::from_ref(&module))
+    else
+    {
+        return;
+    };
+
+    let Ok(thir) = compiler_typeck::check(&hir)
+    else
+    {
+        return;
+    };
+
+    // Only generates the module, building it would be far too slow
+    let _ = compiler_codegen_llvm::check(
+        "fuzz",
+        &thir,
+        &Settings {
+            ir: false,
+            opt: 0,
+            output: None,
+            libraries: vec![],
+            library_paths: vec![],
+            test: false,
+            bounds_checks: true,
+            linker: None,
+        },
+    );
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    // Like the driver, only what lexed cleanly is parsed
+    let Ok(tokens) = compiler_parser::lex(source)
+    else
+    {
+        return;
+    };
+
+    let _ = compiler_parser::parse(source, tokens.into_iter());
+});
@@ -14,3 +14,49 @@ pub unsafe extern "C" fn puts(s: *const std::ffi::c_char)
 {
     println!("{}", unsafe { CStr::from_ptr(s) }.to_string_lossy());
 }
+
+/// A `[]T`, as it's passed to C
+#[repr(C)]
+pub struct Slice<T>
+{
+    items: *const T,
+    len: u64,
+}
+
+#[unsafe(no_mangle)]
+/// Prints the bytes of `s`, which needn't end with a nul byte
+///
+/// # Safety
+///
+/// This function is safe if `s` points to `s.len` valid bytes.
+pub unsafe extern "C" fn prints(s: Slice<u8>)
+{
+    let bytes = unsafe { std::slice::from_raw_parts(s.items, s.len as usize) };
+
+    println!("{}", String::from_utf8_lossy(bytes));
+}
+
+#[unsafe(no_mangle)]
+/// Calls `handler` with each number from `from` up to `to`, excluded
+pub extern "C" fn each(from: i32, to: i32, handler: extern "C" fn(i32))
+{
+    (from..to).for_each(|n| handler(n));
+}
+
+#[unsafe(no_mangle)]
+/// Sorts the `len` numbers at `values` in place, as `qsort` would. `compare` returns a negative number, zero or a positive one as
+/// its first argument goes before, along or after the second.
+///
+/// # Safety
+///
+/// This function is safe if `values` points to `len` valid, writable numbers.
+pub unsafe extern "C" fn sort(values: *mut i32, len: i32, compare: extern "C" fn(i32, i32) -> i32)
+{
+    let Ok(len) = usize::try_from(len)
+    else
+    {
+        return;
+    };
+
+    unsafe { std::slice::from_raw_parts_mut(values, len) }.sort_by(|a, b| compare(*a, *b).cmp(&0));
+}